@@ -0,0 +1,172 @@
+//! Serves the Alpaca API, with the driver's own handling in front of every request
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use ascom_alpaca::discovery::DiscoveryServer;
+use ascom_alpaca::Server;
+use axum::extract::State;
+use axum::http::{Method, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Json, Redirect, Response};
+use axum::routing::get;
+use hyper::Body;
+
+use crate::config::Config;
+use crate::telescope_control::StarAdventurer;
+use crate::util::parse_number;
+use crate::{client_traffic, custom_actions, dashboard};
+
+/// The Telescope PUT parameters that hold a coordinate or rate, in lower case
+const NUMBER_PARAMETERS: &[&str] = &[
+    "altitude",
+    "azimuth",
+    "declination",
+    "declinationrate",
+    "guideratedeclination",
+    "guideraterightascension",
+    "rate",
+    "rightascension",
+    "rightascensionrate",
+    "siteelevation",
+    "sitelatitude",
+    "sitelongitude",
+    "targetdeclination",
+    "targetrightascension",
+];
+
+/// Serves the Alpaca API and discovery until one of them fails
+pub async fn serve(
+    server: Server,
+    mounts: Vec<Arc<StarAdventurer>>,
+    config: &Config,
+) -> eyre::Result<Infallible> {
+    let listen_addr = server.listen_addr;
+    let mut discovery = DiscoveryServer::new(listen_addr.port());
+    discovery.listen_addr.set_ip(listen_addr.ip());
+
+    // Only the Alpaca API counts as client traffic
+    let app = client_traffic::wrap(server.into_router(), &config.client_traffic);
+    let mut app = app.route(
+        "/custom_capabilities",
+        get(|| async { Json(&custom_actions::CAPABILITIES) }),
+    );
+    if config.dashboard.enabled {
+        for (device_number, sa) in mounts.iter().enumerate() {
+            app = app.nest(
+                &format!("/dashboard/{}", device_number),
+                dashboard::router(sa.clone()),
+            );
+        }
+        app = app.route(
+            "/dashboard",
+            get(|| async { Redirect::temporary("/dashboard/0/") }),
+        );
+        tracing::info!("Serving the dashboard on http://{}/dashboard/", listen_addr);
+    }
+    let app =
+        app.layer(middleware::from_fn(normalize_numbers))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(mounts),
+                note_activity,
+            ));
+
+    tracing::info!("Serving Alpaca on http://{}/", listen_addr);
+    tokio::select! {
+        result = axum::Server::bind(&listen_addr).serve(app.into_make_service()) => {
+            result?;
+            Err(eyre::eyre!("The Alpaca server stopped"))
+        }
+        result = discovery.start_server() => result,
+    }
+}
+
+/// Any request, including for a dashboard, resets the idle-safety timeout of the mount it's for,
+/// or of every mount if it's for none
+async fn note_activity<B>(
+    State(mounts): State<Arc<Vec<Arc<StarAdventurer>>>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    match device_number(request.uri().path()).and_then(|n| mounts.get(n)) {
+        Some(sa) => sa.note_client_activity(),
+        None => mounts.iter().for_each(|sa| sa.note_client_activity()),
+    }
+    next.run(request).await
+}
+
+/// Rewrites coordinates and rates written with decimal commas or in sexagesimal as plain decimals,
+/// which is all the Alpaca parameter parsing understands
+async fn normalize_numbers(request: Request<Body>, next: Next<Body>) -> Response {
+    if request.method() != Method::PUT {
+        return next.run(request).await;
+    }
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let body = match normalize_form(&body) {
+        Some(form) => Body::from(form),
+        None => Body::from(body),
+    };
+    next.run(Request::from_parts(parts, body)).await
+}
+
+/// The form with its number parameters rewritten, or None if it has none to rewrite
+fn normalize_form(form: &[u8]) -> Option<String> {
+    let mut params = serde_urlencoded::from_bytes::<Vec<(String, String)>>(form).ok()?;
+    let mut changed = false;
+    for (name, value) in &mut params {
+        let name = name.to_lowercase();
+        if !NUMBER_PARAMETERS.contains(&name.as_str()) || value.parse::<f64>().is_ok() {
+            continue;
+        }
+        // Left as it is if it can't be read, for the Alpaca parsing to reject
+        if let Some(number) = parse_number(value) {
+            *value = number.to_string();
+            changed = true;
+        }
+    }
+    if !changed {
+        return None;
+    }
+    serde_urlencoded::to_string(&params).ok()
+}
+
+/// From "/api/v1/telescope/{device_number}/..." or "/dashboard/{device_number}/..."
+fn device_number(path: &str) -> Option<usize> {
+    let mut segments = path.trim_start_matches('/').split('/');
+    match segments.next()? {
+        "api" => segments.nth(2)?.parse().ok(),
+        "dashboard" => segments.next()?.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_number() {
+        assert_eq!(device_number("/api/v1/telescope/1/tracking"), Some(1));
+        assert_eq!(device_number("/api/v1/telescope/0/action"), Some(0));
+        assert_eq!(device_number("/management/v1/configureddevices"), None);
+        assert_eq!(device_number("/dashboard/1/status"), Some(1));
+        assert_eq!(device_number("/dashboard"), None);
+        assert_eq!(device_number("/setup"), None);
+    }
+
+    #[test]
+    fn test_normalize_form() {
+        assert_eq!(
+            normalize_form(b"RightAscension=12%2C5&Declination=-20%3A15%3A00&ClientID=3")
+                .as_deref(),
+            Some("RightAscension=12.5&Declination=-20.25&ClientID=3")
+        );
+        assert_eq!(normalize_form(b"SiteElevation=15&ClientID=3"), None);
+        // Not a coordinate or rate
+        assert_eq!(normalize_form(b"Action=hour_angle&Parameters=1%2C5"), None);
+        assert_eq!(normalize_form(b"Rate=fast"), None);
+    }
+}
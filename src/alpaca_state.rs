@@ -1,3 +1,5 @@
+use crate::custom_actions;
+use crate::macros::MacroRunner;
 use crate::telescope_control::StarAdventurer;
 use ascom_alpaca::api::{
     AlignmentMode, Axis, AxisRate, Device, DriveRate, EquatorialSystem, PutPulseGuideDirection,
@@ -5,16 +7,62 @@ use ascom_alpaca::api::{
 };
 use ascom_alpaca::{ASCOMError, ASCOMResult};
 
+use std::sync::Arc;
 use std::time::SystemTime;
 
+const SETUP_HTML: &str = include_str!("setup.html");
+
+/// How a mount shows up in Alpaca discovery
+#[derive(Debug, Clone)]
+pub struct DeviceIdentity {
+    pub name: String,
+    pub unique_id: String,
+}
+
+impl DeviceIdentity {
+    /// The first mount keeps the ID from before multiple mounts so clients don't lose it
+    pub fn for_mount(device_number: usize, name: Option<String>) -> Self {
+        const UNIQUE_ID: &str = "f2d8e3a1-6c52-4d34-b475-e88056182f2b";
+        match device_number {
+            0 => DeviceIdentity {
+                name: name.unwrap_or_else(|| "StarAdventurer".to_string()),
+                unique_id: UNIQUE_ID.to_string(),
+            },
+            n => DeviceIdentity {
+                name: name.unwrap_or_else(|| format!("StarAdventurer {}", n + 1)),
+                unique_id: format!("{}-{}", UNIQUE_ID, n),
+            },
+        }
+    }
+}
+
+impl Default for DeviceIdentity {
+    fn default() -> Self {
+        Self::for_mount(0, None)
+    }
+}
+
+/// The driver as registered with the Alpaca server. The telescope is shared with the INDI server.
+#[derive(Debug, Clone)]
+pub struct AlpacaDevice(
+    pub Arc<StarAdventurer>,
+    pub Arc<MacroRunner>,
+    pub Arc<DeviceIdentity>,
+);
+
 #[async_trait::async_trait]
-impl Device for StarAdventurer {
+impl Device for AlpacaDevice {
     fn static_name(&self) -> &str {
-        "StarAdventurer"
+        &self.2.name
     }
 
     fn unique_id(&self) -> &str {
-        "f2d8e3a1-6c52-4d34-b475-e88056182f2b"
+        &self.2.unique_id
+    }
+
+    /// Edits the config through the setup_config actions
+    async fn setup(&self) -> eyre::Result<String> {
+        Ok(SETUP_HTML.replace("{{name}}", &self.2.name))
     }
 
     /* Action */
@@ -23,54 +71,47 @@ impl Device for StarAdventurer {
         action: String,
         parameters: String,
     ) -> ascom_alpaca::ASCOMResult<String> {
-        match &*action {
-            "pending_declination_slew" => {
-                let change = self.get_pending_dec_change().await;
-                Ok(change.to_string())
-            }
-            "complete_declination_slew" => {
-                self.complete_dec_slew().await;
-                Ok("".to_string())
-            }
-            "set_pier_side_after_manual_move" => {
-                let pier_side = match &*parameters {
-                    "east" => SideOfPier::East,
-                    "west" => SideOfPier::West,
-                    _ => {
-                        return Err(ASCOMError::invalid_value(format_args!(
-                            "Unknown pier side: \"{}\"",
-                            parameters
-                        )))
-                    }
-                };
-                self.set_pier_side_after_manual_move(pier_side).await;
-                Ok("".to_string())
-            }
-            _ => Err(ASCOMError::ACTION_NOT_IMPLEMENTED),
+        if let Some(custom) = custom_actions::find(&action) {
+            return (custom.handler)(self, parameters).await;
         }
+        if self.1.has_macro(&action) {
+            return custom_actions::to_json(&self.1.start(self.clone(), &action)?);
+        }
+        Err(ASCOMError::ACTION_NOT_IMPLEMENTED)
+    }
+
+    async fn supported_actions(&self) -> ASCOMResult<Vec<String>> {
+        Ok(custom_actions::ACTIONS
+            .iter()
+            .map(|action| action.name.to_string())
+            .chain(self.1.get_macros().keys().cloned())
+            .collect())
     }
 
     /* Connected */
     async fn connected(&self) -> ASCOMResult<bool> {
-        Ok(self.is_connected().await)
+        Ok(self.0.is_connected().await)
     }
 
     async fn set_connected(&self, connected: bool) -> ASCOMResult<()> {
         if connected {
             tracing::warn!("Connecting");
-            self.connect().await
+            self.0.connect().await
         } else {
             tracing::warn!("Disconnecting");
-            self.disconnect().await
+            self.0.disconnect().await
         }
     }
 
     async fn description(&self) -> ASCOMResult<String> {
-        Ok("StarAdventurer".to_owned())
+        Ok(self.0.get_description().await)
     }
 
     async fn driver_info(&self) -> ASCOMResult<String> {
-        Ok("Rust ALPACA driver for Star Adventurer".to_owned())
+        Ok(format!(
+            "Rust ALPACA driver for Star Adventurer {}",
+            StarAdventurer::get_version_string()
+        ))
     }
 
     async fn driver_version(&self) -> ASCOMResult<String> {
@@ -79,205 +120,206 @@ impl Device for StarAdventurer {
 }
 
 #[async_trait::async_trait]
-impl Telescope for StarAdventurer {
+impl Telescope for AlpacaDevice {
     async fn alignment_mode(&self) -> ASCOMResult<AlignmentMode> {
-        self.get_alignment_mode().await
+        self.0.get_alignment_mode().await
     }
 
     async fn altitude(&self) -> ASCOMResult<f64> {
-        self.get_altitude().await
+        self.0.get_altitude().await
     }
 
     async fn aperture_area(&self) -> ASCOMResult<f64> {
-        self.get_aperture_area().await
+        self.0.get_aperture_area().await
     }
 
     async fn aperture_diameter(&self) -> ASCOMResult<f64> {
-        self.get_aperture().await
+        self.0.get_aperture().await
     }
 
     async fn at_home(&self) -> ASCOMResult<bool> {
-        self.is_home().await
+        self.0.is_home().await
     }
 
     async fn at_park(&self) -> ASCOMResult<bool> {
-        self.is_parked().await
+        self.0.is_parked().await
     }
 
     async fn azimuth(&self) -> ASCOMResult<f64> {
-        self.get_azimuth().await
+        self.0.get_azimuth().await
     }
 
     async fn can_find_home(&self) -> ASCOMResult<bool> {
-        self.can_find_home().await
+        self.0.can_find_home().await
     }
 
     async fn can_park(&self) -> ASCOMResult<bool> {
-        self.can_park().await
+        self.0.can_park().await
     }
 
     async fn can_pulse_guide(&self) -> ASCOMResult<bool> {
-        self.can_pulse_guide().await
+        self.0.can_pulse_guide().await
     }
 
     async fn can_set_declination_rate(&self) -> ASCOMResult<bool> {
-        self.can_set_declination_rate().await
+        self.0.can_set_declination_rate().await
     }
 
     async fn can_set_guide_rates(&self) -> ASCOMResult<bool> {
-        self.can_set_guide_rates().await
+        self.0.can_set_guide_rates().await
     }
 
     async fn can_set_park(&self) -> ASCOMResult<bool> {
-        self.can_set_park_pos().await
+        self.0.can_set_park_pos().await
     }
 
     async fn can_set_pier_side(&self) -> ASCOMResult<bool> {
-        self.can_set_side_of_pier().await
+        self.0.can_set_side_of_pier().await
     }
 
     async fn can_set_right_ascension_rate(&self) -> ASCOMResult<bool> {
-        self.can_set_ra_rate().await
+        self.0.can_set_ra_rate().await
     }
 
     async fn can_set_tracking(&self) -> ASCOMResult<bool> {
-        self.can_set_tracking().await
+        self.0.can_set_tracking().await
     }
 
     async fn can_slew(&self) -> ASCOMResult<bool> {
-        self.can_slew().await
+        self.0.can_slew().await
     }
 
     async fn can_slew_alt_az(&self) -> ASCOMResult<bool> {
-        self.can_slew_alt_az().await
+        self.0.can_slew_alt_az().await
     }
 
     async fn can_slew_alt_az_async(&self) -> ASCOMResult<bool> {
-        self.can_slew_alt_az_async().await
+        self.0.can_slew_alt_az_async().await
     }
 
     async fn can_slew_async(&self) -> ASCOMResult<bool> {
-        self.can_slew_async().await
+        self.0.can_slew_async().await
     }
 
     async fn can_sync(&self) -> ASCOMResult<bool> {
-        self.can_sync().await
+        self.0.can_sync().await
     }
 
     async fn can_sync_alt_az(&self) -> ASCOMResult<bool> {
-        self.can_sync_alt_az().await
+        self.0.can_sync_alt_az().await
     }
 
     async fn can_unpark(&self) -> ASCOMResult<bool> {
-        self.can_unpark().await
+        self.0.can_unpark().await
     }
 
     async fn declination(&self) -> ASCOMResult<f64> {
-        self.get_dec().await
+        self.0.get_dec().await
     }
 
     async fn declination_rate(&self) -> ASCOMResult<f64> {
-        self.get_declination_rate().await
+        self.0.get_declination_rate().await
     }
 
     async fn set_declination_rate(&self, declination_rate: f64) -> ASCOMResult<()> {
-        self.set_declination_rate(declination_rate).await
+        self.0.set_declination_rate(declination_rate).await
     }
 
     async fn does_refraction(&self) -> ASCOMResult<bool> {
-        self.does_refraction().await
+        self.0.does_refraction().await
     }
 
     async fn set_does_refraction(&self, does_refraction: bool) -> ASCOMResult<()> {
-        self.set_does_refraction(does_refraction).await
+        self.0.set_does_refraction(does_refraction).await
     }
 
     async fn equatorial_system(&self) -> ASCOMResult<EquatorialSystem> {
-        self.get_equatorial_system().await
+        self.0.get_equatorial_system().await
     }
 
     async fn focal_length(&self) -> ASCOMResult<f64> {
-        self.get_focal_length().await
+        self.0.get_focal_length().await
     }
 
     async fn guide_rate_declination(&self) -> ASCOMResult<f64> {
-        self.get_guide_rate_declination().await
+        self.0.get_guide_rate_declination().await
     }
 
     async fn set_guide_rate_declination(&self, guide_rate_declination: f64) -> ASCOMResult<()> {
-        self.set_guide_rate_declination(guide_rate_declination)
+        self.0
+            .set_guide_rate_declination(guide_rate_declination)
             .await
     }
 
     async fn guide_rate_right_ascension(&self) -> ASCOMResult<f64> {
-        self.get_guide_rate_ra().await
+        self.0.get_guide_rate_ra().await
     }
 
     async fn set_guide_rate_right_ascension(
         &self,
         guide_rate_right_ascension: f64,
     ) -> ASCOMResult<()> {
-        self.set_guide_rate_ra(guide_rate_right_ascension).await
+        self.0.set_guide_rate_ra(guide_rate_right_ascension).await
     }
 
     async fn is_pulse_guiding(&self) -> ASCOMResult<bool> {
-        self.is_pulse_guiding().await
+        self.0.is_pulse_guiding().await
     }
 
     async fn right_ascension(&self) -> ASCOMResult<f64> {
-        self.get_ra().await
+        self.0.get_ra().await
     }
 
     async fn right_ascension_rate(&self) -> ASCOMResult<f64> {
-        self.get_ra_rate().await
+        self.0.get_ra_rate().await
     }
 
     async fn set_right_ascension_rate(&self, right_ascension_rate: f64) -> ASCOMResult<()> {
-        self.set_ra_rate(right_ascension_rate).await
+        self.0.set_ra_rate(right_ascension_rate).await
     }
 
     async fn side_of_pier(&self) -> ASCOMResult<SideOfPier> {
-        self.get_side_of_pier().await
+        self.0.get_side_of_pier().await
     }
 
     async fn set_side_of_pier(&self, side_of_pier: SideOfPier) -> ASCOMResult<()> {
-        self.set_side_of_pier(side_of_pier).await
+        self.0.set_side_of_pier(side_of_pier).await
     }
 
     async fn sidereal_time(&self) -> ASCOMResult<f64> {
-        self.get_sidereal_time().await
+        self.0.get_sidereal_time().await
     }
 
     async fn site_elevation(&self) -> ASCOMResult<f64> {
-        self.get_elevation().await
+        self.0.get_elevation().await
     }
 
     async fn set_site_elevation(&self, site_elevation: f64) -> ASCOMResult<()> {
-        self.set_elevation(site_elevation).await
+        self.0.set_elevation(site_elevation).await
     }
 
     async fn site_latitude(&self) -> ASCOMResult<f64> {
-        self.get_latitude().await
+        self.0.get_latitude().await
     }
 
     async fn set_site_latitude(&self, site_latitude: f64) -> ASCOMResult<()> {
-        self.set_latitude(site_latitude).await
+        self.0.set_latitude(site_latitude).await
     }
 
     async fn site_longitude(&self) -> ASCOMResult<f64> {
-        self.get_longitude().await
+        self.0.get_longitude().await
     }
 
     async fn set_site_longitude(&self, site_longitude: f64) -> ASCOMResult<()> {
-        self.set_longitude(site_longitude).await
+        self.0.set_longitude(site_longitude).await
     }
 
     async fn slewing(&self) -> ASCOMResult<bool> {
-        self.is_slewing().await
+        self.0.is_slewing().await
     }
 
     async fn slew_settle_time(&self) -> ASCOMResult<i32> {
-        self.get_slew_settle_time().await.map(|x| x as i32)
+        self.0.get_slew_settle_time().await.map(|x| x as i32)
     }
 
     async fn set_slew_settle_time(&self, slew_settle_time: i32) -> ASCOMResult<()> {
@@ -286,63 +328,63 @@ impl Telescope for StarAdventurer {
                 "Slew settle time must be nonegative",
             ));
         }
-        self.set_slew_settle_time(slew_settle_time as u32).await
+        self.0.set_slew_settle_time(slew_settle_time as u32).await
     }
 
     async fn target_declination(&self) -> ASCOMResult<f64> {
-        self.get_target_declination().await
+        self.0.get_target_declination().await
     }
 
     async fn set_target_declination(&self, target_declination: f64) -> ASCOMResult<()> {
-        self.set_target_dec(target_declination).await
+        self.0.set_target_dec(target_declination).await
     }
 
     async fn target_right_ascension(&self) -> ASCOMResult<f64> {
-        self.get_target_ra().await
+        self.0.get_target_ra().await
     }
 
     async fn set_target_right_ascension(&self, target_right_ascension: f64) -> ASCOMResult<()> {
-        self.set_target_ra(target_right_ascension).await
+        self.0.set_target_ra(target_right_ascension).await
     }
 
     async fn tracking(&self) -> ASCOMResult<bool> {
-        self.is_tracking().await
+        self.0.is_tracking().await
     }
 
     async fn set_tracking(&self, tracking: bool) -> ASCOMResult<()> {
-        self.set_is_tracking(tracking).await
+        self.0.set_is_tracking(tracking).await
     }
 
     async fn tracking_rate(&self) -> ASCOMResult<DriveRate> {
-        self.get_tracking_rate().await
+        self.0.get_tracking_rate().await
     }
 
     async fn set_tracking_rate(&self, tracking_rate: DriveRate) -> ASCOMResult<()> {
-        self.set_tracking_rate(tracking_rate).await
+        self.0.set_tracking_rate(tracking_rate).await
     }
 
     async fn tracking_rates(&self) -> ASCOMResult<Vec<DriveRate>> {
-        self.get_tracking_rates().await
+        self.0.get_tracking_rates().await
     }
 
     async fn utc_date(&self) -> ASCOMResult<SystemTime> {
-        self.get_utc_date().await.map(SystemTime::from)
+        self.0.get_utc_date().await.map(SystemTime::from)
     }
 
     async fn set_utc_date(&self, utc_date: SystemTime) -> ASCOMResult<()> {
-        self.set_utc_date(utc_date.into()).await
+        self.0.set_utc_date(utc_date.into()).await
     }
 
     async fn abort_slew(&self) -> ASCOMResult<()> {
-        self.abort_slew().await
+        self.0.abort_slew().await
     }
 
     async fn axis_rates(&self, axis: Axis) -> ASCOMResult<Vec<AxisRate>> {
-        self.get_axis_rates(axis).await
+        self.0.get_axis_rates(axis).await
     }
 
     async fn can_move_axis(&self, axis: Axis) -> ASCOMResult<bool> {
-        self.can_move_axis(axis).await
+        self.0.can_move_axis(axis).await
     }
 
     async fn destination_side_of_pier(
@@ -350,20 +392,21 @@ impl Telescope for StarAdventurer {
         right_ascension: f64,
         declination: f64,
     ) -> ASCOMResult<SideOfPier> {
-        self.predict_destination_side_of_pier(right_ascension, declination)
+        self.0
+            .predict_destination_side_of_pier(right_ascension, declination)
             .await
     }
 
     async fn find_home(&self) -> ASCOMResult<()> {
-        self.find_home().await
+        self.0.find_home().await
     }
 
     async fn move_axis(&self, axis: Axis, rate: f64) -> ASCOMResult<()> {
-        self.move_axis(axis, rate).await
+        self.0.move_axis(axis, rate).await
     }
 
     async fn park(&self) -> ASCOMResult<()> {
-        self.park().await
+        self.0.park().await
     }
 
     async fn pulse_guide(
@@ -371,25 +414,26 @@ impl Telescope for StarAdventurer {
         direction: PutPulseGuideDirection,
         duration: i32,
     ) -> ASCOMResult<()> {
-        self.pulse_guide(direction, duration as u32).await
+        self.0.pulse_guide(direction, duration as u32).await
     }
 
     async fn set_park(&self) -> ASCOMResult<()> {
-        self.set_park_pos().await
+        self.0.set_park_pos().await
     }
 
     async fn slew_to_alt_az(&self, azimuth: f64, altitude: f64) -> ASCOMResult<()> {
-        self.slew_to_alt_az(altitude, azimuth).await?;
+        self.0.slew_to_alt_az(altitude, azimuth).await?;
         Ok(())
     }
 
     async fn slew_to_alt_az_async(&self, azimuth: f64, altitude: f64) -> ASCOMResult<()> {
-        let _finish = self.slew_to_alt_az_async(altitude, azimuth).await?;
+        let _finish = self.0.slew_to_alt_az_async(altitude, azimuth).await?;
         Ok(())
     }
 
     async fn slew_to_coordinates(&self, right_ascension: f64, declination: f64) -> ASCOMResult<()> {
-        self.slew_to_coordinates(right_ascension, declination)
+        self.0
+            .slew_to_coordinates(right_ascension, declination)
             .await?;
         Ok(())
     }
@@ -400,34 +444,830 @@ impl Telescope for StarAdventurer {
         declination: f64,
     ) -> ASCOMResult<()> {
         let _finish = self
+            .0
             .slew_to_coordinates_async(right_ascension, declination)
             .await?;
         Ok(())
     }
 
     async fn slew_to_target(&self) -> ASCOMResult<()> {
-        self.slew_to_target().await?;
+        self.0.slew_to_target().await?;
         Ok(())
     }
 
     async fn slew_to_target_async(&self) -> ASCOMResult<()> {
-        let _finish = self.slew_to_target_async().await?;
+        let _finish = self.0.slew_to_target_async().await?;
         Ok(())
     }
 
     async fn sync_to_alt_az(&self, azimuth: f64, altitude: f64) -> ASCOMResult<()> {
-        self.sync_to_alt_az(altitude, azimuth).await
+        self.0.sync_to_alt_az(altitude, azimuth).await
     }
 
     async fn sync_to_coordinates(&self, right_ascension: f64, declination: f64) -> ASCOMResult<()> {
-        self.sync_to_coordinates(right_ascension, declination).await
+        self.0
+            .sync_to_coordinates(right_ascension, declination)
+            .await
     }
 
     async fn sync_to_target(&self) -> ASCOMResult<()> {
-        self.sync_to_target().await
+        self.0.sync_to_target().await
     }
 
     async fn unpark(&self) -> ASCOMResult<()> {
-        self.unpark().await
+        self.0.unpark().await
+    }
+}
+
+/// End to end tests of the Alpaca glue over HTTP
+/// Like the other tests these run against the simulated mount in test_config.toml
+#[cfg(test)]
+mod tests {
+    use super::{AlpacaDevice, DeviceIdentity};
+    use crate::astro_math;
+    use crate::config::Config;
+    use crate::macros::MacroRunner;
+    use crate::telescope_control::test_util;
+    use crate::util::TrackingRateExt;
+    use ascom_alpaca::api::{CargoServerInfo, DriveRate};
+    use ascom_alpaca::Server;
+    use serde_json::Value;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    const NOT_IMPLEMENTED: i64 = 0x400;
+    const INVALID_VALUE: i64 = 0x401;
+    const VALUE_NOT_SET: i64 = 0x402;
+    const INVALID_WHILE_PARKED: i64 = 0x408;
+    const INVALID_OPERATION: i64 = 0x40B;
+    const ACTION_NOT_IMPLEMENTED: i64 = 0x40C;
+
+    struct Client {
+        addr: SocketAddr,
+        transaction_id: u32,
+    }
+
+    fn encode(s: &str) -> String {
+        s.bytes()
+            .map(|b| match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{:02X}", b),
+            })
+            .collect()
+    }
+
+    /// How far a is ahead of b, on a circle of the given period
+    fn off_by(a: f64, b: f64, period: f64) -> f64 {
+        astro_math::modulo(a - b + period / 2., period) - period / 2.
+    }
+
+    impl Client {
+        /// Starts a server on a free port with the test mount registered
+        async fn start() -> Self {
+            Self::start_with(confy::load_path("test_config.toml").unwrap()).await
+        }
+
+        /// Starts a server on a free port with a mount made from the config
+        async fn start_with(config: Config) -> Self {
+            let addr = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap();
+
+            let mut server = Server {
+                info: CargoServerInfo!(),
+                listen_addr: addr,
+                ..Default::default()
+            };
+            server.devices.register(AlpacaDevice(
+                Arc::new(test_util::create_sa(Some(config)).await),
+                Arc::new(MacroRunner::default()),
+                Arc::new(DeviceIdentity::default()),
+            ));
+            tokio::spawn(server.start());
+
+            for _ in 0..50 {
+                if TcpStream::connect(addr).await.is_ok() {
+                    return Self {
+                        addr,
+                        transaction_id: 0,
+                    };
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            panic!("Server didn't start listening on {}", addr);
+        }
+
+        /// Returns the HTTP status and the parsed body
+        async fn request(
+            &mut self,
+            method: &str,
+            endpoint: &str,
+            params: &[(&str, &str)],
+        ) -> (u16, Value) {
+            self.transaction_id += 1;
+            let transaction_id = self.transaction_id.to_string();
+            let params = params
+                .iter()
+                .chain(&[
+                    ("ClientID", "1"),
+                    ("ClientTransactionID", transaction_id.as_str()),
+                ])
+                .map(|(k, v)| format!("{}={}", k, encode(v)))
+                .collect::<Vec<_>>()
+                .join("&");
+
+            let path = format!("/api/v1/telescope/0/{}", endpoint);
+            let request = match method {
+                "GET" => format!(
+                    "GET {}?{} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+                    path, params, self.addr
+                ),
+                _ => format!(
+                    "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{}",
+                    method,
+                    path,
+                    self.addr,
+                    params.len(),
+                    params
+                ),
+            };
+
+            let mut stream = TcpStream::connect(self.addr).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+            let body = serde_json::from_str(body).unwrap_or(Value::Null);
+            (status, body)
+        }
+
+        /// Returns the Alpaca error number and the value
+        async fn call(
+            &mut self,
+            method: &str,
+            endpoint: &str,
+            params: &[(&str, &str)],
+        ) -> (i64, Value) {
+            let (status, body) = self.request(method, endpoint, params).await;
+            assert_eq!(status, 200, "{} {}: {}", method, endpoint, body);
+            let error = body["ErrorNumber"].as_i64().unwrap();
+            (error, body["Value"].clone())
+        }
+
+        async fn get(&mut self, endpoint: &str, params: &[(&str, &str)]) -> Value {
+            let (error, value) = self.call("GET", endpoint, params).await;
+            assert_eq!(error, 0, "GET {}", endpoint);
+            value
+        }
+
+        async fn put(&mut self, endpoint: &str, params: &[(&str, &str)]) -> i64 {
+            self.call("PUT", endpoint, params).await.0
+        }
+
+        async fn get_f64(&mut self, endpoint: &str) -> f64 {
+            self.get(endpoint, &[]).await.as_f64().unwrap()
+        }
+
+        /// The error the GET fails with
+        async fn get_error(&mut self, endpoint: &str, params: &[(&str, &str)]) -> i64 {
+            self.call("GET", endpoint, params).await.0
+        }
+
+        /// Returns the Alpaca error number and the action's result
+        async fn action(&mut self, action: &str, parameters: &str) -> (i64, String) {
+            let (error, value) = self
+                .call(
+                    "PUT",
+                    "action",
+                    &[("Action", action), ("Parameters", parameters)],
+                )
+                .await;
+            (error, value.as_str().unwrap_or_default().to_string())
+        }
+
+        /// Starts a server and connects to the mount
+        async fn connect() -> Self {
+            let mut client = Self::start().await;
+            assert_eq!(client.put("connected", &[("Connected", "true")]).await, 0);
+            client
+        }
+
+        /// Polls Slewing until the slew is over
+        async fn wait_for_slew(&mut self) {
+            for _ in 0..300 {
+                if self.get("slewing", &[]).await == Value::Bool(false) {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            panic!("Slew didn't finish");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slew_to_coordinates_async() {
+        let mut client = Client::connect().await;
+        assert_eq!(client.put("tracking", &[("Tracking", "true")]).await, 0);
+
+        // Near enough for the simulator to get there in a few seconds
+        let ra = (client.get_f64("rightascension").await + 23.98) % 24.;
+        let dec = client.get_f64("declination").await;
+        let dec = if dec < 80. { dec + 1. } else { dec - 1. };
+        let (ra_param, dec_param) = (ra.to_string(), dec.to_string());
+        assert_eq!(
+            client
+                .put(
+                    "slewtocoordinatesasync",
+                    &[("RightAscension", &ra_param), ("Declination", &dec_param)]
+                )
+                .await,
+            0
+        );
+        client.wait_for_slew().await;
+
+        assert!((client.get_f64("targetrightascension").await - ra).abs() < 1e-9);
+        let error = off_by(client.get_f64("rightascension").await, ra, 24.);
+        assert!(error.abs() < 0.002, "RA off by {}h", error);
+        assert!((client.get_f64("declination").await - dec).abs() < 0.01);
+    }
+
+    #[tokio::test]
+    async fn test_connection_and_driver() {
+        let mut client = Client::start().await;
+        assert_eq!(client.get("connected", &[]).await, Value::Bool(false));
+        assert_eq!(client.put("connected", &[("Connected", "true")]).await, 0);
+        assert_eq!(client.get("connected", &[]).await, Value::Bool(true));
+
+        assert_eq!(client.get("name", &[]).await, "StarAdventurer");
+        assert_eq!(
+            client.get("driverversion", &[]).await,
+            env!("CARGO_PKG_VERSION")
+        );
+        let driver_info = client.get("driverinfo", &[]).await;
+        assert!(driver_info
+            .as_str()
+            .unwrap()
+            .starts_with("Rust ALPACA driver for Star Adventurer"));
+        // The simulator's firmware
+        let description = client.get("description", &[]).await;
+        assert!(
+            description.as_str().unwrap().contains("mount code 0x0a"),
+            "{}",
+            description
+        );
+        assert!(3 <= client.get("interfaceversion", &[]).await.as_i64().unwrap());
+        let actions = client.get("supportedactions", &[]).await;
+        assert!(actions.as_array().unwrap().contains(&"snapshot".into()));
+
+        assert_eq!(client.put("connected", &[("Connected", "false")]).await, 0);
+        assert_eq!(client.get("connected", &[]).await, Value::Bool(false));
+    }
+
+    #[tokio::test]
+    async fn test_capabilities() {
+        let mut client = Client::connect().await;
+        for (property, expected) in [
+            ("canfindhome", false),
+            ("canpark", true),
+            ("canpulseguide", true),
+            ("cansetdeclinationrate", false),
+            ("cansetguiderates", true),
+            ("cansetpark", true),
+            ("cansetpierside", true),
+            ("cansetrightascensionrate", true),
+            ("cansettracking", true),
+            ("canslew", true),
+            ("canslewaltaz", true),
+            ("canslewaltazasync", true),
+            ("canslewasync", true),
+            ("cansync", true),
+            ("cansyncaltaz", true),
+            ("canunpark", true),
+            ("athome", false),
+            ("doesrefraction", false),
+        ] {
+            assert_eq!(
+                client.get(property, &[]).await,
+                Value::Bool(expected),
+                "{}",
+                property
+            );
+        }
+        assert_eq!(client.get("alignmentmode", &[]).await, 2); // German polar
+        assert_eq!(client.get("equatorialsystem", &[]).await, 1); // Topocentric
+
+        assert_eq!(client.put("findhome", &[]).await, NOT_IMPLEMENTED);
+        assert_eq!(
+            client
+                .put("doesrefraction", &[("DoesRefraction", "true")])
+                .await,
+            NOT_IMPLEMENTED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_optics_and_site() {
+        let mut client = Client::connect().await;
+        // From test_config.toml
+        assert_eq!(client.get_f64("aperturediameter").await, 0.1);
+        assert_eq!(client.get_f64("aperturearea").await, 0.785);
+        assert_eq!(client.get_f64("focallength").await, 0.5);
+        assert_eq!(client.get_f64("sitelatitude").await, 51.47);
+        assert_eq!(client.get_f64("sitelongitude").await, 0.);
+        assert_eq!(client.get_f64("siteelevation").await, 15.);
+
+        for (property, parameter, valid, invalid) in [
+            ("sitelatitude", "SiteLatitude", "-33.5", "91"),
+            ("sitelongitude", "SiteLongitude", "151.25", "-181"),
+            ("siteelevation", "SiteElevation", "1200", "10001"),
+        ] {
+            assert_eq!(client.put(property, &[(parameter, valid)]).await, 0);
+            assert_eq!(
+                client.get_f64(property).await,
+                valid.parse::<f64>().unwrap()
+            );
+            assert_eq!(
+                client.put(property, &[(parameter, invalid)]).await,
+                INVALID_VALUE,
+                "{} of {}",
+                property,
+                invalid
+            );
+            assert_eq!(
+                client.get_f64(property).await,
+                valid.parse::<f64>().unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clock_and_coordinates() {
+        let mut client = Client::connect().await;
+
+        let lst = client.get_f64("siderealtime").await;
+        let expected = astro_math::calculate_local_sidereal_time(chrono::Utc::now(), 0.);
+        assert!((lst - expected).abs() < 0.001, "{} vs {}", lst, expected);
+
+        let ra = client.get_f64("rightascension").await;
+        let dec = client.get_f64("declination").await;
+        let (error, hour_angle) = client.action("hour_angle", "").await;
+        assert_eq!(error, 0);
+        let hour_angle: f64 = hour_angle.parse().unwrap();
+        let ra_off_by = off_by(lst - hour_angle, ra, 24.);
+        assert!(
+            ra_off_by.abs() < 0.001,
+            "RA is {}h off the hour angle",
+            ra_off_by
+        );
+
+        let altitude = client.get_f64("altitude").await;
+        let azimuth = client.get_f64("azimuth").await;
+        let expected = astro_math::calculate_alt_from_ha_dec(hour_angle, dec, 51.47);
+        assert!((altitude - expected).abs() < 0.01);
+        let expected = astro_math::calculate_az_from_ha_dec(hour_angle, dec, 51.47);
+        assert!(off_by(azimuth, expected, 360.).abs() < 0.01);
+
+        let date = "2030-06-01T12:00:00.0000000Z";
+        assert_eq!(client.put("utcdate", &[("UTCDate", date)]).await, 0);
+        let now = client.get("utcdate", &[]).await;
+        assert!(
+            now.as_str().unwrap().starts_with("2030-06-01T12:00:0"),
+            "{}",
+            now
+        );
+        // Before the clock could have been set
+        assert_eq!(
+            client
+                .put("utcdate", &[("UTCDate", "2001-01-01T00:00:00.0000000Z")])
+                .await,
+            INVALID_VALUE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_target() {
+        let mut client = Client::connect().await;
+        assert_eq!(
+            client.get_error("targetrightascension", &[]).await,
+            VALUE_NOT_SET
+        );
+        assert_eq!(
+            client.get_error("targetdeclination", &[]).await,
+            VALUE_NOT_SET
+        );
+        assert_eq!(client.put("slewtotarget", &[]).await, VALUE_NOT_SET);
+
+        assert_eq!(
+            client
+                .put("targetrightascension", &[("TargetRightAscension", "5.5")])
+                .await,
+            0
+        );
+        assert_eq!(
+            client
+                .put("targetdeclination", &[("TargetDeclination", "-20.25")])
+                .await,
+            0
+        );
+        assert_eq!(client.get_f64("targetrightascension").await, 5.5);
+        assert_eq!(client.get_f64("targetdeclination").await, -20.25);
+
+        assert_eq!(
+            client
+                .put("targetrightascension", &[("TargetRightAscension", "24")])
+                .await,
+            INVALID_VALUE
+        );
+        assert_eq!(
+            client
+                .put("targetdeclination", &[("TargetDeclination", "100")])
+                .await,
+            INVALID_VALUE
+        );
+        assert_eq!(client.get_f64("targetrightascension").await, 5.5);
+        assert_eq!(client.get_f64("targetdeclination").await, -20.25);
+    }
+
+    #[tokio::test]
+    async fn test_sync() {
+        let mut client = Client::connect().await;
+        let ra = (client.get_f64("rightascension").await + 0.5) % 24.;
+        let ra_param = ra.to_string();
+        let coordinates = [("RightAscension", ra_param.as_str()), ("Declination", "20")];
+        assert_eq!(
+            client.put("synctocoordinates", &coordinates).await,
+            INVALID_OPERATION
+        );
+
+        assert_eq!(client.put("tracking", &[("Tracking", "true")]).await, 0);
+        assert_eq!(client.put("synctocoordinates", &coordinates).await, 0);
+        assert!((client.get_f64("rightascension").await - ra).abs() < 0.001);
+        assert!((client.get_f64("declination").await - 20.).abs() < 0.001);
+        assert_eq!(client.get_f64("targetrightascension").await, ra);
+        assert_eq!(client.get_f64("targetdeclination").await, 20.);
+        // Still on the same side of the pier
+        assert_eq!(client.get("sideofpier", &[]).await, 0);
+
+        let ra = (ra + 0.25) % 24.;
+        let ra_param = ra.to_string();
+        assert_eq!(
+            client
+                .put(
+                    "targetrightascension",
+                    &[("TargetRightAscension", &ra_param)]
+                )
+                .await,
+            0
+        );
+        assert_eq!(
+            client
+                .put("targetdeclination", &[("TargetDeclination", "25")])
+                .await,
+            0
+        );
+        assert_eq!(client.put("synctotarget", &[]).await, 0);
+        assert!((client.get_f64("rightascension").await - ra).abs() < 0.001);
+        assert!((client.get_f64("declination").await - 25.).abs() < 0.001);
+    }
+
+    #[tokio::test]
+    async fn test_slew_to_target() {
+        let mut client = Client::connect().await;
+        assert_eq!(client.put("tracking", &[("Tracking", "true")]).await, 0);
+        assert_eq!(
+            client
+                .put("slewsettletime", &[("SlewSettleTime", "-1")])
+                .await,
+            INVALID_VALUE
+        );
+        assert_eq!(
+            client
+                .put("slewsettletime", &[("SlewSettleTime", "1")])
+                .await,
+            0
+        );
+        assert_eq!(client.get("slewsettletime", &[]).await, 1);
+
+        // Near enough for the simulator to get there in a few seconds
+        let ra = (client.get_f64("rightascension").await + 0.02) % 24.;
+        let dec = client.get_f64("declination").await;
+        let dec = if dec < 80. { dec + 1. } else { dec - 1. };
+        let (ra_param, dec_param) = (ra.to_string(), dec.to_string());
+        assert_eq!(
+            client
+                .put(
+                    "targetrightascension",
+                    &[("TargetRightAscension", &ra_param)]
+                )
+                .await,
+            0
+        );
+        assert_eq!(
+            client
+                .put("targetdeclination", &[("TargetDeclination", &dec_param)])
+                .await,
+            0
+        );
+        assert_eq!(client.put("slewtotarget", &[]).await, 0);
+
+        // Synchronous, so it's over by the time it returns
+        assert_eq!(client.get("slewing", &[]).await, Value::Bool(false));
+        let error = off_by(client.get_f64("rightascension").await, ra, 24.);
+        assert!(error.abs() < 0.002, "RA off by {}h", error);
+        assert!((client.get_f64("declination").await - dec).abs() < 0.01);
+    }
+
+    #[tokio::test]
+    async fn test_tracking() {
+        let mut client = Client::connect().await;
+        assert_eq!(
+            client.get("trackingrates", &[]).await,
+            serde_json::json!([0, 1, 2, 3])
+        );
+        assert_eq!(client.get("trackingrate", &[]).await, 0); // Sidereal
+
+        assert_eq!(client.put("tracking", &[("Tracking", "true")]).await, 0);
+        assert_eq!(client.get("tracking", &[]).await, Value::Bool(true));
+        assert_eq!(
+            client.put("trackingrate", &[("TrackingRate", "1")]).await,
+            0
+        );
+        assert_eq!(client.get("trackingrate", &[]).await, 1); // Lunar
+
+        assert_eq!(client.get_f64("rightascensionrate").await, 0.);
+        assert_eq!(
+            client
+                .put("rightascensionrate", &[("RightAscensionRate", "0.5")])
+                .await,
+            0
+        );
+        assert_eq!(client.get_f64("rightascensionrate").await, 0.5);
+        assert_eq!(
+            client
+                .put("rightascensionrate", &[("RightAscensionRate", "10")])
+                .await,
+            INVALID_VALUE
+        );
+        assert_eq!(client.get_f64("rightascensionrate").await, 0.5);
+
+        assert_eq!(client.get_f64("declinationrate").await, 0.);
+        assert_eq!(
+            client
+                .put("declinationrate", &[("DeclinationRate", "1")])
+                .await,
+            NOT_IMPLEMENTED
+        );
+
+        assert_eq!(client.put("tracking", &[("Tracking", "false")]).await, 0);
+        assert_eq!(client.get("tracking", &[]).await, Value::Bool(false));
+    }
+
+    #[tokio::test]
+    async fn test_guiding() {
+        let mut client = Client::connect().await;
+        let sidereal = DriveRate::Sidereal.to_degrees();
+        let rate = (0.75 * sidereal).to_string();
+        assert_eq!(
+            client
+                .put(
+                    "guideraterightascension",
+                    &[("GuideRateRightAscension", &rate)]
+                )
+                .await,
+            0
+        );
+        assert!((client.get_f64("guideraterightascension").await - 0.75 * sidereal).abs() < 1e-9);
+        assert_eq!(
+            client
+                .put(
+                    "guideraterightascension",
+                    &[("GuideRateRightAscension", "1")]
+                )
+                .await,
+            INVALID_VALUE
+        );
+        // Accepted without a dec guide output, but there's nothing to guide with
+        assert_eq!(
+            client
+                .put("guideratedeclination", &[("GuideRateDeclination", &rate)])
+                .await,
+            0
+        );
+        assert_eq!(client.get_f64("guideratedeclination").await, 0.);
+
+        // Tracking holds RA still, so only the pulse moves it
+        assert_eq!(client.put("tracking", &[("Tracking", "true")]).await, 0);
+        let before = client.get_f64("rightascension").await;
+        // East
+        assert_eq!(
+            client
+                .put("pulseguide", &[("Direction", "2"), ("Duration", "2000")])
+                .await,
+            0
+        );
+        // Synchronous, so it's over by the time it returns
+        assert_eq!(client.get("ispulseguiding", &[]).await, Value::Bool(false));
+        // 2s at 0.75 sidereal is 1.5s of RA
+        let moved = off_by(client.get_f64("rightascension").await, before, 24.) * 3600.;
+        assert!((moved.abs() - 1.5).abs() < 0.5, "Moved {}s of RA", moved);
+        // North, without a dec guide output
+        assert_eq!(
+            client
+                .put("pulseguide", &[("Direction", "0"), ("Duration", "500")])
+                .await,
+            INVALID_VALUE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_move_axis() {
+        let mut client = Client::connect().await;
+        assert_eq!(
+            client.get("canmoveaxis", &[("Axis", "0")]).await,
+            Value::Bool(true)
+        );
+        // No dec motor
+        assert_eq!(
+            client.get("canmoveaxis", &[("Axis", "1")]).await,
+            Value::Bool(false)
+        );
+        let rates = client.get("axisrates", &[("Axis", "0")]).await;
+        assert_eq!(rates.as_array().unwrap().len(), 3);
+        assert_eq!(
+            client.get("axisrates", &[("Axis", "1")]).await,
+            serde_json::json!([{"Minimum": 0.0, "Maximum": 0.0}])
+        );
+
+        let (_, before) = client.action("motor_position", "").await;
+        assert_eq!(
+            client
+                .put("moveaxis", &[("Axis", "0"), ("Rate", "1")])
+                .await,
+            INVALID_VALUE
+        );
+        let maximum = rates[0]["Maximum"].as_f64().unwrap().to_string();
+        assert_eq!(
+            client
+                .put("moveaxis", &[("Axis", "0"), ("Rate", &maximum)])
+                .await,
+            0
+        );
+        assert_eq!(client.get("slewing", &[]).await, Value::Bool(true));
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(client.put("abortslew", &[]).await, 0);
+        assert_eq!(client.get("slewing", &[]).await, Value::Bool(false));
+        let (_, after) = client.action("motor_position", "").await;
+        assert_ne!(before, after);
+        assert_eq!(
+            client
+                .put("moveaxis", &[("Axis", "1"), ("Rate", &maximum)])
+                .await,
+            INVALID_VALUE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_park() {
+        let mut client = Client::connect().await;
+        assert_eq!(client.get("atpark", &[]).await, Value::Bool(false));
+
+        // Parking where it already is finishes at once
+        assert_eq!(client.put("setpark", &[]).await, 0);
+        let (_, park_position) = client.action("mechanical_hour_angle", "").await;
+        assert_eq!(client.put("park", &[]).await, 0);
+        assert_eq!(client.get("atpark", &[]).await, Value::Bool(true));
+        assert_eq!(client.get("slewing", &[]).await, Value::Bool(false));
+        let (_, parked_at) = client.action("mechanical_hour_angle", "").await;
+        let parked_off_by = off_by(
+            parked_at.parse().unwrap(),
+            park_position.parse().unwrap(),
+            24.,
+        );
+        assert!(
+            parked_off_by.abs() < 0.01,
+            "Parked {}h from the park position",
+            parked_off_by
+        );
+
+        for (endpoint, params) in [
+            ("tracking", &[("Tracking", "true")][..]),
+            ("moveaxis", &[("Axis", "0"), ("Rate", "0.1")][..]),
+            (
+                "slewtocoordinatesasync",
+                &[("RightAscension", "1"), ("Declination", "10")][..],
+            ),
+            ("pulseguide", &[("Direction", "2"), ("Duration", "100")][..]),
+            ("abortslew", &[][..]),
+        ] {
+            assert_eq!(
+                client.put(endpoint, params).await,
+                INVALID_WHILE_PARKED,
+                "PUT {} while parked",
+                endpoint
+            );
+        }
+        assert_eq!(client.put("unpark", &[]).await, 0);
+        assert_eq!(client.get("atpark", &[]).await, Value::Bool(false));
+        assert_eq!(client.put("tracking", &[("Tracking", "true")]).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_side_of_pier() {
+        // Without limits there's always room to flip
+        let mut config: Config = confy::load_path("test_config.toml").unwrap();
+        config.other.mount_limit_east = 0.;
+        config.other.mount_limit_west = 24.;
+        let mut client = Client::start_with(config).await;
+        assert_eq!(client.put("connected", &[("Connected", "true")]).await, 0);
+        // From test_config.toml
+        assert_eq!(client.get("sideofpier", &[]).await, 0); // East
+
+        let ra = client.get_f64("rightascension").await.to_string();
+        assert_eq!(
+            client
+                .get(
+                    "destinationsideofpier",
+                    &[("RightAscension", &ra), ("Declination", "10")]
+                )
+                .await,
+            0
+        );
+        // Already there
+        assert_eq!(client.put("sideofpier", &[("SideOfPier", "0")]).await, 0);
+        assert_eq!(client.get("slewing", &[]).await, Value::Bool(false));
+        assert_eq!(
+            client.put("sideofpier", &[("SideOfPier", "-1")]).await,
+            INVALID_VALUE
+        );
+
+        // The flip is a long slew, so it's only started
+        assert_eq!(client.put("sideofpier", &[("SideOfPier", "1")]).await, 0);
+        assert_eq!(client.get("slewing", &[]).await, Value::Bool(true));
+        assert_eq!(client.put("abortslew", &[]).await, 0);
+        assert_eq!(client.get("slewing", &[]).await, Value::Bool(false));
+    }
+
+    #[tokio::test]
+    async fn test_actions() {
+        let mut client = Client::connect().await;
+        let (error, snapshot) = client.action("snapshot", "").await;
+        assert_eq!(error, 0);
+        assert!(serde_json::from_str::<Value>(&snapshot).is_ok());
+        assert_eq!(
+            client.action("no_such_action", "").await.0,
+            ACTION_NOT_IMPLEMENTED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unschedule_target() {
+        let mut client = Client::connect().await;
+        let target = r#"{"name": "M31", "right_ascension": 0.71, "declination": 41.27, "earliest_start": "2099-01-01T00:00:00Z"}"#;
+        let (error, scheduled) = client.action("schedule_target", target).await;
+        assert_eq!(error, 0);
+        let id = serde_json::from_str::<Value>(&scheduled).unwrap()["id"]
+            .as_u64()
+            .unwrap();
+        for bad in [
+            format!("{}.5", id),
+            "-1".to_string(),
+            "4294967296".to_string(),
+        ] {
+            assert_eq!(
+                client.action("unschedule_target", &bad).await.0,
+                INVALID_VALUE
+            );
+        }
+        assert_eq!(
+            client.action("unschedule_target", &id.to_string()).await.0,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bad_requests() {
+        let mut client = Client::connect().await;
+        let (status, _) = client
+            .request("PUT", "tracking", &[("Tracking", "maybe")])
+            .await;
+        assert_eq!(status, 400);
+        assert_eq!(
+            client
+                .put(
+                    "slewtocoordinatesasync",
+                    &[("RightAscension", "25"), ("Declination", "10")]
+                )
+                .await,
+            INVALID_VALUE
+        );
+        assert_eq!(client.get("slewing", &[]).await, Value::Bool(false));
     }
 }
@@ -0,0 +1,167 @@
+//! TAI-UTC over time, for converting UTC to the Terrestrial Time that sidereal time is defined against.
+//! A table is bundled, and a newer IERS leap-seconds.list can be loaded in its place.
+
+use std::io;
+use std::sync::RwLock;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+/// Seconds between TAI and TT
+pub const TT_MINUS_TAI: f64 = 32.184;
+
+// Seconds since the Unix epoch that each offset starts at, and TAI-UTC from then
+const BUNDLED: [(i64, f64); 28] = [
+    (63072000, 10.),   // 1972-01-01
+    (78796800, 11.),   // 1972-07-01
+    (94694400, 12.),   // 1973-01-01
+    (126230400, 13.),  // 1974-01-01
+    (157766400, 14.),  // 1975-01-01
+    (189302400, 15.),  // 1976-01-01
+    (220924800, 16.),  // 1977-01-01
+    (252460800, 17.),  // 1978-01-01
+    (283996800, 18.),  // 1979-01-01
+    (315532800, 19.),  // 1980-01-01
+    (362793600, 20.),  // 1981-07-01
+    (394329600, 21.),  // 1982-07-01
+    (425865600, 22.),  // 1983-07-01
+    (489024000, 23.),  // 1985-07-01
+    (567993600, 24.),  // 1988-01-01
+    (631152000, 25.),  // 1990-01-01
+    (662688000, 26.),  // 1991-01-01
+    (709948800, 27.),  // 1992-07-01
+    (741484800, 28.),  // 1993-07-01
+    (773020800, 29.),  // 1994-07-01
+    (820454400, 30.),  // 1996-01-01
+    (867715200, 31.),  // 1997-07-01
+    (915148800, 32.),  // 1999-01-01
+    (1136073600, 33.), // 2006-01-01
+    (1230768000, 34.), // 2009-01-01
+    (1341100800, 35.), // 2012-07-01
+    (1435708800, 36.), // 2015-07-01
+    (1483228800, 37.), // 2017-01-01
+];
+
+// Seconds between the NTP epoch of leap-seconds.list and the Unix epoch
+const NTP_TO_UNIX: i64 = 2_208_988_800;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeapSecondTable {
+    entries: Vec<(i64, f64)>,
+    expires: Option<DateTime<Utc>>, // Past this a leap second may have been missed
+}
+
+// None for the bundled table
+static LOADED: RwLock<Option<LeapSecondTable>> = RwLock::new(None);
+
+impl LeapSecondTable {
+    /// Parses the IERS leap-seconds.list format
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut entries = Vec::new();
+        let mut expires = None;
+        for line in text.lines() {
+            if let Some(expiry) = line.strip_prefix("#@") {
+                let ntp: i64 = expiry.trim().parse().ok()?;
+                expires = Utc.timestamp_opt(ntp - NTP_TO_UNIX, 0).single();
+                continue;
+            }
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            if let (Some(ntp), Some(offset)) = (fields.next(), fields.next()) {
+                entries.push((ntp.parse::<i64>().ok()? - NTP_TO_UNIX, offset.parse().ok()?));
+            }
+        }
+        if entries.is_empty() {
+            return None;
+        }
+        entries.sort_by_key(|(start, _)| *start);
+        Some(Self { entries, expires })
+    }
+
+    pub fn tai_minus_utc(&self, time: DateTime<Utc>) -> f64 {
+        Self::lookup(&self.entries, time)
+    }
+
+    pub fn expires(&self) -> Option<DateTime<Utc>> {
+        self.expires
+    }
+
+    fn lookup(entries: &[(i64, f64)], time: DateTime<Utc>) -> f64 {
+        let secs = time.timestamp();
+        entries
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= secs)
+            .or(entries.first()) // Before 1972 UTC wasn't stepped, but this is close enough
+            .map_or(0., |(_, offset)| *offset)
+    }
+}
+
+/// Replaces the bundled table. Returns the number of leap second entries.
+pub fn load_file(path: &str) -> io::Result<usize> {
+    let text = std::fs::read_to_string(path)?;
+    let table = LeapSecondTable::parse(&text).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} isn't in the leap-seconds.list format", path),
+        )
+    })?;
+    let count = table.entries.len();
+    *LOADED.write().unwrap() = Some(table);
+    Ok(count)
+}
+
+/// TAI-UTC in seconds at the given time
+pub fn tai_minus_utc(time: DateTime<Utc>) -> f64 {
+    match &*LOADED.read().unwrap() {
+        Some(table) => table.tai_minus_utc(time),
+        None => LeapSecondTable::lookup(&BUNDLED, time),
+    }
+}
+
+/// None for the bundled table, which has no expiry
+pub fn expires() -> Option<DateTime<Utc>> {
+    LOADED
+        .read()
+        .unwrap()
+        .as_ref()
+        .and_then(|table| table.expires)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leap_seconds() {
+        assert_eq!(
+            tai_minus_utc(Utc.with_ymd_and_hms(1969, 1, 6, 1, 5, 0).unwrap()),
+            10.
+        );
+        assert_eq!(
+            tai_minus_utc(Utc.with_ymd_and_hms(2016, 12, 31, 23, 59, 59).unwrap()),
+            36.
+        );
+        assert_eq!(
+            tai_minus_utc(Utc.with_ymd_and_hms(2021, 1, 30, 21, 20, 0).unwrap()),
+            37.
+        );
+
+        let table = LeapSecondTable::parse(
+            "# Comment\n#@\t3960057600\n2272060800\t10\t# 1 Jan 1972\n3692217600\t37\t# 1 Jan 2017\n",
+        )
+        .unwrap();
+        assert_eq!(
+            table.tai_minus_utc(Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap()),
+            10.
+        );
+        assert_eq!(
+            table.tai_minus_utc(Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap()),
+            37.
+        );
+        assert_eq!(
+            table.expires(),
+            Some(Utc.with_ymd_and_hms(2025, 6, 28, 0, 0, 0).unwrap())
+        );
+        assert!(LeapSecondTable::parse("# Nothing\n").is_none());
+    }
+}
@@ -4,6 +4,8 @@ use chrono::{Datelike, Timelike};
 use polynomials::poly;
 use std::f64::consts::{PI, TAU};
 
+pub mod leap_seconds;
+
 pub type Hours = f64;
 pub type Degrees = f64;
 pub type Radians = f64;
@@ -63,15 +65,12 @@ fn calc_jd(time: chrono::DateTime<chrono::Utc>) -> Hours {
     jd += f64::floor(275. * m / 9.);
     jd += d;
     jd += 1721028.5;
-    jd + ms_to_dec(time.hour(), time.minute(), time.second() as f64) / 24.
+    let seconds = time.second() as f64 + time.nanosecond() as f64 / 1E9; // A second is 15" of sidereal time
+    jd + ms_to_dec(time.hour(), time.minute(), seconds) / 24.
 }
 
 // see https://thecynster.home.blog/2019/11/04/calculating-sidereal-time/
 pub fn calculate_greenwich_sidereal_time(time: chrono::DateTime<chrono::Utc>) -> Hours {
-    // The result will be off by the number of leap seconds different from this on the date given
-    // TODO use the total number of leap seconds at the time given
-    const LEAP_SECOND_TOTAL: u32 = 27;
-
     let jd_utc = calc_jd(time);
 
     let du = jd_utc - 2451545.0;
@@ -88,7 +87,8 @@ pub fn calculate_greenwich_sidereal_time(time: chrono::DateTime<chrono::Utc>) ->
         -0.000029956,
         -0.0000000368,
     ];
-    let jd_tt = jd_utc + ((LEAP_SECOND_TOTAL as f64 + 32.184) / 3600.) / 24.; // Hours
+    let tt_minus_utc = leap_seconds::tai_minus_utc(time) + leap_seconds::TT_MINUS_TAI;
+    let jd_tt = jd_utc + (tt_minus_utc / 3600.) / 24.;
     let t = (jd_tt - 2451545.0) / 36525.; // years
 
     let gmstp = deg_to_hours(modulo(poly.eval(t).unwrap() / 3600., 360.));
@@ -116,27 +116,32 @@ pub fn calculate_hour_angle(
     modulo(calculate_local_sidereal_time(time, longitude) - ra, 24.)
 }
 
+// asin and acos arguments are clamped since rounding can push them just past ±1, e.g. at the poles
 pub fn calculate_alt_from_ha_dec(ha: Hours, dec: Degrees, lat: Degrees) -> Degrees {
     let ha = hours_to_rad(ha);
     let dec = deg_to_rad(dec);
     let lat = deg_to_rad(lat);
-    rad_to_deg((dec.sin() * lat.sin() + dec.cos() * lat.cos() * ha.cos()).asin())
+    rad_to_deg(
+        (dec.sin() * lat.sin() + dec.cos() * lat.cos() * ha.cos())
+            .clamp(-1., 1.)
+            .asin(),
+    )
 }
 
+/// Azimuth from north through east.
+/// At the zenith and nadir any azimuth is right, so 90 or 270 is returned depending on the hour angle.
 pub fn calculate_az_from_ha_dec(ha: Hours, dec: Degrees, lat: Degrees) -> Degrees {
-    let alt = deg_to_rad(calculate_alt_from_ha_dec(ha, dec, lat));
     let ha = hours_to_rad(ha);
     let dec = deg_to_rad(dec);
     let lat = deg_to_rad(lat);
 
-    let a = rad_to_deg(((dec.sin() - alt.sin() * lat.sin()) / (alt.cos() * lat.cos())).acos())
-        as Degrees;
-
-    if 0. < ha.sin() {
-        360. - a
-    } else {
-        a
+    // atan2 rather than acos, which divides by cos(lat) and cos(alt) and so blows up near the poles
+    let y = -dec.cos() * ha.sin();
+    let x = dec.sin() * lat.cos() - dec.cos() * lat.sin() * ha.cos();
+    if y.hypot(x) < 1E-12 {
+        return if 0. < ha.sin() { 270. } else { 90. };
     }
+    modulo(rad_to_deg(y.atan2(x)), 360.)
 }
 
 pub fn calculate_ha_dec_from_alt_az(alt: Degrees, az: Degrees, lat: Degrees) -> (Hours, Degrees) {
@@ -149,19 +154,163 @@ pub fn calculate_ha_dec_from_alt_az(alt: Degrees, az: Degrees, lat: Degrees) ->
     let lat_rad = deg_to_rad(lat);
 
     let dec_rad = (lat_rad.sin() * alt_rad.sin() + lat_rad.cos() * alt_rad.cos() * az_rad.cos())
+        .clamp(-1., 1.)
         .asin() as Radians;
 
-    let ha_rad = (-az_rad.sin() * alt_rad.cos() / dec_rad.cos()).asin() as Radians;
+    // At the celestial pole both terms are 0 and any hour angle is right. atan2 gives 0.
+    let ha_rad = (-az_rad.sin() * alt_rad.cos())
+        .atan2(alt_rad.sin() * lat_rad.cos() - alt_rad.cos() * lat_rad.sin() * az_rad.cos())
+        as Radians;
+
+    (modulo(rad_to_hours(ha_rad), 24.), rad_to_deg(dec_rad))
+}
+
+/// Low precision (~0.01 degree) apparent position of the sun
+/// see https://aa.usno.navy.mil/faq/sun_approx
+pub fn calculate_sun_ra_dec(time: chrono::DateTime<chrono::Utc>) -> (Hours, Degrees) {
+    let n = calc_jd(time) - 2451545.0;
+    let mean_longitude = 280.459 + 0.98564736 * n;
+    let mean_anomaly = deg_to_rad(357.529 + 0.98560028 * n);
+    let ecliptic_longitude =
+        deg_to_rad(mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2. * mean_anomaly).sin());
+    let obliquity = deg_to_rad(23.439 - 0.00000036 * n);
+
+    let ra = (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let dec = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+    (modulo(rad_to_hours(ra), 24.), rad_to_deg(dec))
+}
+
+/// Low precision (~0.3 degree) geocentric position of the moon, with its distance in Earth radii
+/// see Meeus, Astronomical Algorithms, ch. 47, keeping only the largest terms
+fn calculate_moon_geocentric(time: chrono::DateTime<chrono::Utc>) -> (Hours, Degrees, f64) {
+    let n = calc_jd(time) - 2451545.0;
+    let mean_longitude = 218.316 + 13.176396 * n;
+    let mean_anomaly = deg_to_rad(134.963 + 13.064993 * n);
+    let sun_mean_anomaly = deg_to_rad(357.529 + 0.98560028 * n);
+    let elongation = deg_to_rad(297.850 + 12.190749 * n);
+    let latitude_argument = deg_to_rad(93.272 + 13.229350 * n);
+
+    let ecliptic_longitude = deg_to_rad(
+        mean_longitude
+            + 6.289 * mean_anomaly.sin()
+            + 1.274 * (2. * elongation - mean_anomaly).sin()
+            + 0.658 * (2. * elongation).sin()
+            + 0.214 * (2. * mean_anomaly).sin()
+            - 0.186 * sun_mean_anomaly.sin()
+            - 0.114 * (2. * latitude_argument).sin(),
+    );
+    let ecliptic_latitude = deg_to_rad(
+        5.128 * latitude_argument.sin()
+            + 0.280 * (mean_anomaly + latitude_argument).sin()
+            + 0.277 * (mean_anomaly - latitude_argument).sin()
+            + 0.173 * (2. * elongation - latitude_argument).sin(),
+    );
+    let distance = 60.36
+        - 3.27 * mean_anomaly.cos()
+        - 0.57 * (2. * elongation - mean_anomaly).cos()
+        - 0.45 * (2. * elongation).cos();
+    let obliquity = deg_to_rad(23.439 - 0.00000036 * n);
+
+    let ra = (ecliptic_longitude.sin() * obliquity.cos()
+        - ecliptic_latitude.tan() * obliquity.sin())
+    .atan2(ecliptic_longitude.cos());
+    let dec = (ecliptic_latitude.sin() * obliquity.cos()
+        + ecliptic_latitude.cos() * obliquity.sin() * ecliptic_longitude.sin())
+    .asin();
+    (modulo(rad_to_hours(ra), 24.), rad_to_deg(dec), distance)
+}
 
-    let ha_hours = rad_to_hours(ha_rad);
-    let polar_axis_alt = az.cos() * lat;
-    let ha_hours = if alt < polar_axis_alt {
-        12. - ha_hours
-    } else {
-        ha_hours
+/// Low precision position of the moon as seen from the site.
+/// Parallax shifts it by up to a degree from the geocentric position.
+/// see Meeus, Astronomical Algorithms, ch. 40, taking the Earth as a sphere
+pub fn calculate_moon_ra_dec(
+    time: chrono::DateTime<chrono::Utc>,
+    lat: Degrees,
+    longitude: Degrees,
+) -> (Hours, Degrees) {
+    let (ra, dec, distance) = calculate_moon_geocentric(time);
+    let ha = hours_to_rad(calculate_hour_angle(time, longitude, ra));
+    let (dec, lat) = (deg_to_rad(dec), deg_to_rad(lat));
+    let parallax = 1. / distance;
+
+    let x = dec.cos() - lat.cos() * parallax * ha.cos();
+    let ra_change = (-lat.cos() * parallax * ha.sin()).atan2(x);
+    let topocentric_dec = ((dec.sin() - lat.sin() * parallax) * ra_change.cos()).atan2(x);
+    (
+        modulo(ra + rad_to_hours(ra_change), 24.),
+        rad_to_deg(topocentric_dec),
+    )
+}
+
+/// How fast the moon moves in hour angle as seen from the site, in degrees per second.
+/// This varies by several percent over its orbit and through the night.
+pub fn calculate_moon_tracking_rate(
+    time: chrono::DateTime<chrono::Utc>,
+    lat: Degrees,
+    longitude: Degrees,
+) -> Degrees {
+    const STEP_SECS: i64 = 600;
+    let step = chrono::Duration::seconds(STEP_SECS);
+    let ha_at = |time| {
+        let (ra, _) = calculate_moon_ra_dec(time, lat, longitude);
+        calculate_hour_angle(time, longitude, ra)
     };
+    let change = ha_at(time + step / 2) - ha_at(time - step / 2);
+    hours_to_deg(modulo(change + 12., 24.) - 12.) / STEP_SECS as f64
+}
+
+/// Angle between two equatorial positions
+pub fn calculate_separation(ra1: Hours, dec1: Degrees, ra2: Hours, dec2: Degrees) -> Degrees {
+    let dec1 = deg_to_rad(dec1);
+    let dec2 = deg_to_rad(dec2);
+    let cos_sep = dec1.sin() * dec2.sin() + dec1.cos() * dec2.cos() * hours_to_rad(ra1 - ra2).cos();
+    rad_to_deg(cos_sep.clamp(-1., 1.).acos())
+}
+
+/// Atmospheric refraction at standard conditions from the true altitude (Bennett)
+pub fn calculate_refraction(alt: Degrees) -> Degrees {
+    if alt < -1. {
+        return 0.;
+    }
+    let arcminutes = 1.02 / deg_to_rad(alt + 10.3 / (alt + 5.11)).tan();
+    arcminutes / 60.
+}
 
-    (modulo(ha_hours, 24.), rad_to_deg(dec_rad))
+/// Relative air mass from the true altitude (Kasten and Young, 1989). None below the horizon.
+pub fn calculate_airmass(alt: Degrees) -> Option<f64> {
+    if alt < 0. {
+        return None;
+    }
+    Some(1. / (deg_to_rad(alt).sin() + 0.50572 * (alt + 6.07995).powf(-1.6364)))
+}
+
+/// Hour angle of the refracted position
+fn calculate_apparent_ha(ha: Hours, dec: Degrees, lat: Degrees) -> Hours {
+    let (ha, dec, lat) = (hours_to_rad(ha), deg_to_rad(dec), deg_to_rad(lat));
+
+    let alt = (dec.sin() * lat.sin() + dec.cos() * lat.cos() * ha.cos()).asin();
+    let az =
+        (-dec.cos() * ha.sin()).atan2(dec.sin() * lat.cos() - dec.cos() * lat.sin() * ha.cos());
+    let alt = alt + deg_to_rad(calculate_refraction(rad_to_deg(alt)));
+
+    let apparent_ha =
+        (-az.sin() * alt.cos()).atan2(alt.sin() * lat.cos() - alt.cos() * lat.sin() * az.cos());
+    rad_to_hours(apparent_ha)
+}
+
+/// How fast the refracted position moves in hour angle as a multiple of the sidereal rate
+/// Refraction lifts objects more the lower they are, which slows their apparent motion
+pub fn calculate_refraction_rate_factor(ha: Hours, dec: Degrees, lat: Degrees) -> f64 {
+    const STEP: Hours = 0.05;
+    let change = calculate_apparent_ha(ha + STEP / 2., dec, lat)
+        - calculate_apparent_ha(ha - STEP / 2., dec, lat);
+    let factor = (modulo(change + 12., 24.) - 12.) / STEP;
+
+    // Below the horizon or numerically unstable at the zenith
+    if !factor.is_finite() || calculate_alt_from_ha_dec(ha, dec, lat) < 0. {
+        return 1.;
+    }
+    factor.clamp(0.99, 1.01)
 }
 
 pub fn modulo(val: f64, base: f64) -> f64 {
@@ -367,6 +516,104 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_polar_latitudes() {
+        for lat in [89.9, -89.9, 90., -90.] {
+            for (ha, dec) in [(3., 45.), (20., -30.), (12., 0.), (0., lat)] {
+                let alt = calculate_alt_from_ha_dec(ha, dec, lat);
+                let az = calculate_az_from_ha_dec(ha, dec, lat);
+                assert!(
+                    alt.is_finite() && az.is_finite(),
+                    "lat {} ha {} dec {}",
+                    lat,
+                    ha,
+                    dec
+                );
+
+                let (ha2, dec2) = calculate_ha_dec_from_alt_az(alt, az, lat);
+                assert!(ha2.is_finite() && dec2.is_finite());
+                assert_float_absolute_eq!(dec2, dec, 1E-4);
+                // The hour angle of the celestial pole is undefined
+                if dec.abs() < 89. && lat.abs() < 90. {
+                    assert_float_absolute_eq!(modulo(ha2 - ha + 12., 24.) - 12., 0., 1E-4);
+                }
+            }
+        }
+
+        // Near the pole, altitude barely changes with hour angle
+        let alt = calculate_alt_from_ha_dec(6., 45., 89.9);
+        assert_float_absolute_eq!(alt, 45., 0.1);
+    }
+
+    #[test]
+    fn test_calculate_sun_ra_dec() {
+        let (ra, dec) = calculate_sun_ra_dec(Utc.with_ymd_and_hms(2000, 1, 1, 12, 0, 0).unwrap());
+        assert_float_absolute_eq!(ra, 18.752, 1E-2);
+        assert_float_absolute_eq!(dec, -23.03, 1E-1);
+
+        // March equinox
+        let (_ra, dec) =
+            calculate_sun_ra_dec(Utc.with_ymd_and_hms(2023, 3, 20, 21, 24, 0).unwrap());
+        assert_float_absolute_eq!(dec, 0., 1E-1);
+    }
+
+    #[test]
+    fn test_calculate_moon() {
+        // Meeus example 47.a, ignoring the minute between TT and UTC
+        let (ra, dec, distance) =
+            calculate_moon_geocentric(Utc.with_ymd_and_hms(1992, 4, 12, 0, 0, 0).unwrap());
+        assert_float_absolute_eq!(ra, 8.979, 1E-2);
+        assert_float_absolute_eq!(dec, 13.77, 0.5);
+        assert_float_absolute_eq!(distance, 368409.7 / 6378.14, 0.5);
+
+        // Within a few percent of the fixed lunar rate
+        let rate = calculate_moon_tracking_rate(
+            Utc.with_ymd_and_hms(2023, 3, 20, 21, 24, 0).unwrap(),
+            40.,
+            -105.,
+        );
+        assert_float_relative_eq!(rate, 0.004024138, 0.03);
+    }
+
+    #[test]
+    fn test_calculate_airmass() {
+        assert_float_absolute_eq!(calculate_airmass(90.).unwrap(), 1., 1E-3);
+        assert_float_absolute_eq!(calculate_airmass(30.).unwrap(), 1.995, 1E-2);
+        assert_float_absolute_eq!(calculate_airmass(0.).unwrap(), 38., 0.5);
+        assert_eq!(calculate_airmass(-1.), None);
+    }
+
+    #[test]
+    fn test_calculate_separation() {
+        assert_float_absolute_eq!(calculate_separation(0., 0., 6., 0.), 90.);
+        assert_float_absolute_eq!(calculate_separation(3., 90., 15., 45.), 45.);
+        assert_float_absolute_eq!(calculate_separation(23.5, 10., 0.5, 10.), 14.77, 1E-2);
+    }
+
+    #[test]
+    fn test_calculate_refraction() {
+        assert_float_absolute_eq!(calculate_refraction(90.), 0., 1E-4);
+        assert_float_absolute_eq!(calculate_refraction(45.), 0.0169, 1E-4);
+        assert_float_absolute_eq!(calculate_refraction(0.), 0.483, 1E-3);
+    }
+
+    #[test]
+    fn test_calculate_refraction_rate_factor() {
+        // Around the King rate high up, slower lower down
+        let high = calculate_refraction_rate_factor(0., 20., 60.);
+        assert_float_absolute_eq!(high, 0.9998, 1E-4);
+        let low = calculate_refraction_rate_factor(5., 0., 60.);
+        assert!(low < high);
+        assert_float_absolute_eq!(
+            calculate_refraction_rate_factor(4., 20., 60.),
+            calculate_refraction_rate_factor(-4., 20., 60.),
+            1E-9
+        );
+
+        // No adjustment below the horizon
+        assert_eq!(calculate_refraction_rate_factor(12., 0., 60.), 1.);
+    }
+
     #[test]
     fn test_modulo() {
         assert_eq!(modulo(std::f64::consts::TAU, std::f64::consts::PI), 0.);
@@ -0,0 +1,417 @@
+//! Commands for controlling the mount from a shell, either through a running server over Alpaca
+//! or straight over the serial port when no server is running.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use eyre::{bail, eyre};
+use hyper::{Body, Client, Method, Request};
+use serde_json::Value;
+
+use crate::config::{Config, ParkStore};
+use crate::telescope_control::{trace_replay, StarAdventurer};
+use crate::util::*;
+use crate::{CONFIG_PATH, STATE_PATH};
+
+const SLEW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const CLIENT_ID: &str = "1";
+
+#[derive(Debug, Parser)]
+#[command(version, about = "ASCOM Alpaca driver for the Star Adventurer")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Trace every motor command to this file
+    #[arg(long, global = true)]
+    pub trace_motor: Option<String>,
+    /// The running server to send commands to
+    #[arg(long, global = true, default_value = "127.0.0.1:8000")]
+    pub server: String,
+    /// The mount's device number
+    #[arg(long, global = true, default_value_t = 0)]
+    pub device: usize,
+    /// Open the serial port instead of going through a running server
+    #[arg(long, global = true)]
+    pub direct: bool,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Serve the mounts over Alpaca. The default.
+    Serve,
+    /// Print where the mount is pointing and what it's doing
+    Status,
+    Park,
+    Unpark,
+    /// Slew to coordinates, as decimal or sexagesimal
+    Goto {
+        ra: String, // Hours
+        #[arg(allow_hyphen_values = true)]
+        dec: String, // Degrees
+    },
+    Track {
+        state: OnOff,
+    },
+    /// Check the mount can be reached and print the driver info
+    TestConnection,
+    /// Feed a --trace-motor file into the simulated mount and print where its answers differ
+    Replay {
+        trace: String,
+        /// Whose commands to replay. The first port in the trace by default
+        #[arg(long)]
+        port: Option<String>,
+        /// The simulated mount's code, in hex as in sim://<code>
+        #[arg(long, value_parser = parse_mount_code)]
+        mount_code: Option<u8>,
+        /// How many times faster than recorded to send the commands. 0 to not wait between them
+        #[arg(long, default_value_t = 1.)]
+        speed: f64,
+    },
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub enum OnOff {
+    On,
+    Off,
+}
+
+#[derive(Debug)]
+struct Status {
+    ra: Hours,
+    dec: Degrees,
+    tracking: bool,
+    slewing: bool,
+    parked: bool,
+}
+
+/// Where the commands go
+enum Mount {
+    Remote(AlpacaClient),
+    Direct(Arc<StarAdventurer>),
+}
+
+/// Runs a command other than serve, printing its result
+pub async fn run(cli: &Cli, config: Config) -> eyre::Result<()> {
+    let command = cli.command.clone().unwrap_or(Command::Serve);
+    let mount = if cli.direct {
+        Mount::open_direct(config, cli.device).await?
+    } else {
+        Mount::Remote(AlpacaClient::new(&cli.server, cli.device))
+    };
+
+    match command {
+        Command::Serve => unreachable!("Serving isn't a client command"),
+        Command::Replay { .. } => unreachable!("Replaying doesn't need a mount"),
+        Command::Status => {
+            let status = mount.status().await?;
+            println!("RA {:.4}h  Dec {:.4}°", status.ra, status.dec);
+            println!(
+                "Tracking: {}  Slewing: {}  Parked: {}",
+                status.tracking, status.slewing, status.parked
+            );
+        }
+        Command::Park => {
+            mount.park().await?;
+            println!("Parked");
+        }
+        Command::Unpark => {
+            mount.unpark().await?;
+            println!("Unparked");
+        }
+        Command::Goto { ra, dec } => {
+            let ra = parse_number(&ra).ok_or_else(|| eyre!("Can't parse RA {:?}", ra))?;
+            let dec = parse_number(&dec).ok_or_else(|| eyre!("Can't parse Dec {:?}", dec))?;
+            mount.goto(ra, dec).await?;
+            println!("Slewed to RA {:.4}h Dec {:.4}°", ra, dec);
+        }
+        Command::Track { state } => {
+            let on = matches!(state, OnOff::On);
+            mount.set_tracking(on).await?;
+            println!("Tracking {}", if on { "on" } else { "off" });
+        }
+        Command::TestConnection => {
+            let info = mount.test_connection().await?;
+            println!("{}", serde_json::to_string_pretty(&info)?);
+        }
+    }
+    Ok(())
+}
+
+/// Replays a motor trace into the simulator, printing the commands answered differently
+pub async fn replay(
+    trace: String,
+    port: Option<String>,
+    mount_code: Option<u8>,
+    speed: f64,
+) -> eyre::Result<()> {
+    if speed < 0. {
+        bail!("Speed can't be negative");
+    }
+    // The commands are paced with blocking sleeps, like the serial thread's
+    let report = tokio::task::spawn_blocking(move || {
+        trace_replay::replay(&trace, port.as_deref(), mount_code, speed)
+    })
+    .await?
+    .map_err(|e| eyre!("Couldn't read the trace: {}", e))?;
+
+    for divergence in &report.divergences {
+        println!(
+            "Line {}: {}\n  recorded {}\n  replayed {}",
+            divergence.line, divergence.command, divergence.recorded, divergence.replayed
+        );
+    }
+    for (line, command) in &report.skipped {
+        println!("Line {}: skipped unknown command {}", line, command);
+    }
+    println!(
+        "Replayed {} commands to {}, {} answered differently",
+        report.replayed,
+        report.port,
+        report.divergences.len()
+    );
+    Ok(())
+}
+
+fn parse_mount_code(code: &str) -> Result<u8, String> {
+    u8::from_str_radix(code.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("{:?} isn't a hex byte", code))
+}
+
+impl Mount {
+    async fn open_direct(config: Config, device: usize) -> eyre::Result<Self> {
+        let (_, mut mount_config) = config
+            .mount_configs()
+            .into_iter()
+            .nth(device)
+            .ok_or_else(|| eyre!("No mount {} in the config", device))?;
+        let park_store = ParkStore {
+            config_path: CONFIG_PATH.to_string(),
+            state_path: STATE_PATH.to_string(),
+            mount: device,
+        };
+        park_store
+            .load_state()
+            .map_err(|e| eyre!("Couldn't load the saved state: {}", e))?
+            .apply(&mut mount_config);
+        let sa = StarAdventurer::new(&mount_config)
+            .await
+            .with_park_store(park_store);
+        // A running server holds the port
+        sa.connect()
+            .await
+            .map_err(|e| eyre!("Couldn't open the mount. Is a server running? {}", e))?;
+        Ok(Self::Direct(Arc::new(sa)))
+    }
+
+    async fn status(&self) -> eyre::Result<Status> {
+        match self {
+            Self::Remote(client) => {
+                if !client.get("connected").await?.as_bool().unwrap_or(false) {
+                    bail!("The server isn't connected to the mount");
+                }
+                Ok(Status {
+                    ra: client.get_f64("rightascension").await?,
+                    dec: client.get_f64("declination").await?,
+                    tracking: client.get_bool("tracking").await?,
+                    slewing: client.get_bool("slewing").await?,
+                    parked: client.get_bool("atpark").await?,
+                })
+            }
+            Self::Direct(sa) => Ok(Status {
+                ra: sa.get_ra().await?,
+                dec: sa.get_dec().await?,
+                tracking: sa.is_tracking().await?,
+                slewing: sa.is_slewing().await?,
+                parked: sa.is_parked().await?,
+            }),
+        }
+    }
+
+    async fn park(&self) -> eyre::Result<()> {
+        match self {
+            Self::Remote(client) => {
+                client.put("park", &[]).await?;
+                client.wait_for_slew().await
+            }
+            Self::Direct(sa) => Ok(sa.park().await?),
+        }
+    }
+
+    async fn unpark(&self) -> eyre::Result<()> {
+        match self {
+            Self::Remote(client) => client.put("unpark", &[]).await.map(|_| ()),
+            Self::Direct(sa) => Ok(sa.unpark().await?),
+        }
+    }
+
+    async fn goto(&self, ra: Hours, dec: Degrees) -> eyre::Result<()> {
+        match self {
+            Self::Remote(client) => {
+                let (ra, dec) = (ra.to_string(), dec.to_string());
+                client
+                    .put(
+                        "slewtocoordinatesasync",
+                        &[("RightAscension", &ra), ("Declination", &dec)],
+                    )
+                    .await?;
+                client.wait_for_slew().await
+            }
+            Self::Direct(sa) => Ok(sa.slew_to_coordinates(ra, dec).await?),
+        }
+    }
+
+    async fn set_tracking(&self, on: bool) -> eyre::Result<()> {
+        match self {
+            Self::Remote(client) => client
+                .put("tracking", &[("Tracking", &on.to_string())])
+                .await
+                .map(|_| ()),
+            Self::Direct(sa) => Ok(sa.set_is_tracking(on).await?),
+        }
+    }
+
+    async fn test_connection(&self) -> eyre::Result<Value> {
+        match self {
+            Self::Remote(client) => {
+                let info = client
+                    .put("action", &[("Action", "driver_info"), ("Parameters", "")])
+                    .await?;
+                let mut info: Value = serde_json::from_str(info.as_str().unwrap_or("null"))?;
+                info["connected"] = client.get("connected").await?;
+                Ok(info)
+            }
+            Self::Direct(sa) => Ok(serde_json::to_value(sa.get_driver_info().await)?),
+        }
+    }
+}
+
+/// Just enough of an Alpaca client for the commands
+struct AlpacaClient {
+    client: Client<hyper::client::HttpConnector>,
+    base_url: String,
+}
+
+impl AlpacaClient {
+    fn new(server: &str, device: usize) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: format!("http://{}/api/v1/telescope/{}", server, device),
+        }
+    }
+
+    async fn get(&self, endpoint: &str) -> eyre::Result<Value> {
+        let uri = format!(
+            "{}/{}?{}",
+            self.base_url,
+            endpoint,
+            Self::encode_params(&[])
+        );
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .body(Body::empty())?;
+        self.send(endpoint, request).await
+    }
+
+    async fn get_f64(&self, endpoint: &str) -> eyre::Result<f64> {
+        let value = self.get(endpoint).await?;
+        value
+            .as_f64()
+            .ok_or_else(|| eyre!("{} isn't a number: {}", endpoint, value))
+    }
+
+    async fn get_bool(&self, endpoint: &str) -> eyre::Result<bool> {
+        let value = self.get(endpoint).await?;
+        value
+            .as_bool()
+            .ok_or_else(|| eyre!("{} isn't a bool: {}", endpoint, value))
+    }
+
+    async fn put(&self, endpoint: &str, params: &[(&str, &str)]) -> eyre::Result<Value> {
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri(format!("{}/{}", self.base_url, endpoint))
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(Self::encode_params(params)))?;
+        self.send(endpoint, request).await
+    }
+
+    async fn wait_for_slew(&self) -> eyre::Result<()> {
+        while self.get_bool("slewing").await? {
+            tokio::time::sleep(SLEW_POLL_INTERVAL).await;
+        }
+        Ok(())
+    }
+
+    /// The value of the response, or its Alpaca error
+    async fn send(&self, endpoint: &str, request: Request<Body>) -> eyre::Result<Value> {
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(|e| eyre!("Couldn't reach the server. Is it running? {}", e))?;
+        if !response.status().is_success() {
+            bail!("{} returned {}", endpoint, response.status());
+        }
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let body: Value = serde_json::from_slice(&body)?;
+        match body["ErrorNumber"].as_i64() {
+            Some(0) | None => Ok(body["Value"].clone()),
+            Some(number) => bail!(
+                "{} failed ({:#X}): {}",
+                endpoint,
+                number,
+                body["ErrorMessage"].as_str().unwrap_or_default()
+            ),
+        }
+    }
+
+    fn encode_params(params: &[(&str, &str)]) -> String {
+        let encode = |s: &str| -> String {
+            s.bytes()
+                .map(|b| match b {
+                    b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' => {
+                        (b as char).to_string()
+                    }
+                    _ => format!("%{:02X}", b),
+                })
+                .collect()
+        };
+        params
+            .iter()
+            .chain(&[("ClientID", CLIENT_ID)])
+            .map(|(k, v)| format!("{}={}", k, encode(v)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let cli =
+            Cli::try_parse_from(["star_adventurer_alpaca", "goto", "5:35:17", "-5:23"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Goto { ref dec, .. }) if dec == "-5:23"));
+        let cli =
+            Cli::try_parse_from(["star_adventurer_alpaca", "--direct", "track", "off"]).unwrap();
+        assert!(cli.direct);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Track { state: OnOff::Off })
+        ));
+        assert!(Cli::try_parse_from(["star_adventurer_alpaca"])
+            .unwrap()
+            .command
+            .is_none());
+        assert!(Cli::try_parse_from(["star_adventurer_alpaca", "track", "maybe"]).is_err());
+
+        assert_eq!(
+            AlpacaClient::encode_params(&[("Declination", "-5.5")]),
+            "Declination=-5.5&ClientID=1"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Middleware in front of the Alpaca server that tags every request with its ClientID, keeps each client's recent history,
+//! and holds back clients polling faster than allowed so their reads don't crowd guide pulses off the serial link.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::{Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::{DateTime, Utc};
+use hyper::{Body, Method};
+use serde::Serialize;
+use tokio::time::Instant;
+
+use crate::config::ClientTrafficSettings;
+
+/// A request as the middleware passed it on
+#[derive(Debug, Clone, Serialize)]
+struct RequestRecord {
+    time: DateTime<Utc>,
+    method: String,
+    endpoint: String, // The last path segment, e.g. "rightascension"
+    status: u16,
+    delayed_millis: u64, // Held back by the poll limit
+}
+
+#[derive(Debug, Serialize)]
+struct ClientHistory {
+    requests: u64,
+    delayed: u64,
+    recent: VecDeque<RequestRecord>,
+    #[serde(skip)]
+    next_poll: Option<Instant>,
+    #[serde(skip)]
+    last_seen: Instant,
+}
+
+impl ClientHistory {
+    fn new(now: Instant) -> Self {
+        Self {
+            requests: 0,
+            delayed: 0,
+            recent: VecDeque::new(),
+            next_poll: None,
+            last_seen: now,
+        }
+    }
+}
+
+struct ClientTraffic {
+    history_length: usize,
+    poll_interval: Option<Duration>,
+    forget_after: Duration,
+    clients: Mutex<HashMap<u32, ClientHistory>>, // By ClientID. 0 for clients that don't send one
+}
+
+/// Puts the middleware in front of the Alpaca routes, and serves GET /clients, if enabled
+pub fn wrap(app: Router, settings: &ClientTrafficSettings) -> Router {
+    if !settings.enabled {
+        return app;
+    }
+    let traffic = Arc::new(ClientTraffic {
+        history_length: settings.history_length,
+        poll_interval: (0. < settings.max_polls_per_sec)
+            .then(|| Duration::from_secs_f64(1. / settings.max_polls_per_sec)),
+        forget_after: Duration::from_secs_f64(settings.forget_after_mins * 60.),
+        clients: Mutex::new(HashMap::new()),
+    });
+    app.layer(middleware::from_fn_with_state(traffic.clone(), track))
+        .route("/clients", get(clients).with_state(traffic))
+}
+
+async fn clients(State(traffic): State<Arc<ClientTraffic>>) -> impl IntoResponse {
+    let mut clients = traffic.clients.lock().unwrap();
+    evict_idle(&mut clients, Instant::now(), traffic.forget_after);
+    Json(serde_json::to_value(&*clients).unwrap())
+}
+
+async fn track(
+    State(traffic): State<Arc<ClientTraffic>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    // Alpaca sends parameters in the query for GETs and as a form for PUTs
+    let params = match parts.method {
+        Method::GET => parts.uri.query().unwrap_or_default(),
+        _ => std::str::from_utf8(&body).unwrap_or_default(),
+    };
+    let client_id = parse_client_id(params).unwrap_or(0);
+    let method = parts.method.clone();
+    let endpoint = parts
+        .uri
+        .path()
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let delay = match method {
+        Method::GET => traffic.reserve_poll(client_id),
+        _ => Duration::ZERO,
+    };
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    tracing::debug!(
+        client_id,
+        "{} {} -> {}",
+        method,
+        endpoint,
+        response.status()
+    );
+    traffic.record(
+        client_id,
+        RequestRecord {
+            time: Utc::now(),
+            method: method.to_string(),
+            endpoint,
+            status: response.status().as_u16(),
+            delayed_millis: delay.as_millis() as u64,
+        },
+    );
+    response
+}
+
+impl ClientTraffic {
+    /// Takes the client's next poll slot, returning how long to wait for it
+    fn reserve_poll(&self, client_id: u32) -> Duration {
+        let interval = match self.poll_interval {
+            Some(interval) => interval,
+            None => return Duration::ZERO,
+        };
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        let client = clients
+            .entry(client_id)
+            .or_insert_with(|| ClientHistory::new(now));
+        let (delay, next_poll) = schedule_poll(client.next_poll, now, interval);
+        client.next_poll = Some(next_poll);
+        delay
+    }
+
+    fn record(&self, client_id: u32, record: RequestRecord) {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        evict_idle(&mut clients, now, self.forget_after);
+        let client = clients
+            .entry(client_id)
+            .or_insert_with(|| ClientHistory::new(now));
+        client.last_seen = now;
+        client.requests += 1;
+        if 0 < record.delayed_millis {
+            client.delayed += 1;
+        }
+        client.recent.push_back(record);
+        while self.history_length < client.recent.len() {
+            client.recent.pop_front();
+        }
+    }
+}
+
+/// Drops clients that haven't been heard from for `forget_after`, so ClientIDs that come and go don't pile up
+fn evict_idle(clients: &mut HashMap<u32, ClientHistory>, now: Instant, forget_after: Duration) {
+    clients.retain(|_, client| now.saturating_duration_since(client.last_seen) < forget_after);
+}
+
+/// The wait before a poll at `now`, and when the one after may go
+fn schedule_poll(
+    next_poll: Option<Instant>,
+    now: Instant,
+    interval: Duration,
+) -> (Duration, Instant) {
+    let slot = next_poll.map_or(now, |next| next.max(now));
+    (slot - now, slot + interval)
+}
+
+/// Parameter names are case insensitive
+fn parse_client_id(params: &str) -> Option<u32> {
+    params
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.eq_ignore_ascii_case("ClientID"))
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_client_id() {
+        assert_eq!(parse_client_id("Tracking=true&ClientID=42"), Some(42));
+        assert_eq!(parse_client_id("clientid=7&ClientTransactionID=3"), Some(7));
+        assert_eq!(parse_client_id("ClientTransactionID=3"), None);
+    }
+
+    #[test]
+    fn test_evict_idle() {
+        let now = Instant::now();
+        let forget_after = Duration::from_secs(60);
+        let mut clients = HashMap::new();
+        clients.insert(1, ClientHistory::new(now));
+        clients.insert(2, ClientHistory::new(now + Duration::from_secs(50)));
+
+        evict_idle(&mut clients, now + Duration::from_secs(59), forget_after);
+        assert_eq!(clients.len(), 2);
+        evict_idle(&mut clients, now + Duration::from_secs(60), forget_after);
+        assert!(!clients.contains_key(&1));
+        assert!(clients.contains_key(&2));
+    }
+
+    #[test]
+    fn test_schedule_poll() {
+        let now = Instant::now();
+        let interval = Duration::from_millis(500);
+        assert_eq!(
+            schedule_poll(None, now, interval),
+            (Duration::ZERO, now + interval)
+        );
+
+        // A poll arriving too soon waits for its slot
+        let (delay, next) = schedule_poll(Some(now + interval), now, interval);
+        assert_eq!(delay, interval);
+        assert_eq!(next, now + interval * 2);
+
+        // A quiet client isn't held back for long past slots
+        let later = now + interval * 10;
+        assert_eq!(
+            schedule_poll(Some(now + interval), later, interval),
+            (Duration::ZERO, later + interval)
+        );
+    }
+}
@@ -3,6 +3,8 @@ use crate::rotation_direction::RotationDirectionKey;
 use crate::Hours;
 use ascom_alpaca::api::SideOfPier;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use synscan::AutoGuideSpeed;
 
 /* Config */
@@ -14,14 +16,309 @@ pub struct Config {
     pub telescope_details: TelescopeDetails,
     pub initialization: Initialization,
     pub other: OtherSettings,
+    #[serde(default)]
+    pub power: PowerSettings,
+    #[serde(default)]
+    pub declination_knob: DeclinationKnob,
+    #[serde(default)]
+    pub vibration_guard: VibrationGuardSettings,
+    #[serde(default)]
+    pub settle: SettleSettings,
+    #[serde(default)]
+    pub idle_safety: IdleSafetySettings,
+    #[serde(default)]
+    pub slew_safety: SlewSafety,
+    #[serde(default)]
+    pub session: SessionSettings,
+    #[serde(default)]
+    pub dec_guide_output: DecGuideOutputSettings,
+    #[serde(default)]
+    pub move_presets: MovePresets,
+    #[serde(default)]
+    pub centering: CenteringSettings,
+    #[serde(default)]
+    pub jog: JogSettings,
+    #[serde(default)]
+    pub pointing_model: PointingModelSettings,
+    #[serde(default)]
+    pub gps: GpsSettings,
+    #[serde(default)]
+    pub time: TimeSettings,
+    #[serde(default)]
+    pub ramp: RampSettings,
+    #[serde(default)]
+    pub guide_batch: GuideBatchSettings,
+    #[serde(default)]
+    pub events: EventSettings,
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+    #[serde(default)]
+    pub indi: IndiSettings,
+    #[serde(default)]
+    pub lx200: Lx200Settings,
+    #[serde(default)]
+    pub dashboard: DashboardSettings,
+    #[serde(default)]
+    pub client_traffic: ClientTrafficSettings,
+    // Run by name like any other action
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub macros: BTreeMap<String, Vec<MacroStep>>,
+    // Named tracking rates in degrees/sec, e.g. for a comet, activated in place of the standard rates
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom_tracking_rates: BTreeMap<String, Degrees>,
+    // One Alpaca device per entry, in device number order. Empty for just the mount configured above.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<MountSettings>,
+}
+
+impl Config {
+    /// Reads and checks the config file
+    pub fn load(path: &str) -> Result<Config, String> {
+        let mut config: Config = confy::load_path(path).map_err(|e| e.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Rejects values that would fail at runtime and puts lists in the order they're used in
+    pub fn validate(&mut self) -> Result<(), String> {
+        self.com.validate()?;
+        for com in self
+            .mounts
+            .iter_mut()
+            .filter_map(|mount| mount.com.as_mut())
+        {
+            com.validate()?;
+        }
+        self.slew_safety.validate()?;
+        self.idle_safety.validate()?;
+        self.client_traffic.validate()?;
+        let webhooks = self
+            .events
+            .webhooks
+            .iter()
+            .chain(&self.notifications.webhooks)
+            .chain(&self.settle.stability_webhook);
+        for url in webhooks {
+            crate::util::parse_webhook_uri(url)?;
+        }
+        for (name, steps) in &self.macros {
+            if crate::custom_actions::ACTIONS
+                .iter()
+                .any(|action| action.name == name)
+            {
+                return Err(format!(
+                    "macro \"{}\" has the name of a built-in action, which would hide it",
+                    name
+                ));
+            }
+            for step in steps {
+                step.validate()
+                    .map_err(|e| format!("macro \"{}\": {}", name, e))?;
+            }
+        }
+        for (name, rate) in &self.custom_tracking_rates {
+            if !(rate.is_finite() && 0. < *rate) {
+                return Err(format!(
+                    "custom tracking rate \"{}\" must be a positive number of degrees/sec, not {}",
+                    name, rate
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The config of each mount, with its overrides applied to everything else from this config
+    pub fn mount_configs(&self) -> Vec<(Option<String>, Config)> {
+        if self.mounts.is_empty() {
+            return vec![(None, self.clone())];
+        }
+        self.mounts
+            .iter()
+            .enumerate()
+            .map(|(device_number, mount)| {
+                let mut config = Config {
+                    mounts: Vec::new(),
+                    ..self.clone()
+                };
+                if let Some(com) = &mount.com {
+                    config.com = com.clone();
+                }
+                if let Some(observation_location) = mount.observation_location {
+                    config.observation_location = observation_location;
+                }
+                if let Some(park_hour_angle) = mount.park_hour_angle {
+                    config.other.park_hour_angle = park_hour_angle;
+                }
+                config.lx200 = match &mount.lx200 {
+                    Some(lx200) => lx200.clone(),
+                    None if device_number == 0 => self.lx200.clone(),
+                    None => Lx200Settings::default(),
+                };
+                (mount.name.clone(), config)
+            })
+            .collect()
+    }
+}
+
+/* Multiple Mounts */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MountSettings {
+    pub name: Option<String>, // Alpaca device name. None for "StarAdventurer" and a number
+    pub com: Option<ComSettings>,
+    pub observation_location: Option<ObservingLocation>,
+    pub park_hour_angle: Option<Hours>, // Mechanical. Overridden by SetPark
+    pub lx200: Option<Lx200Settings>,   // Only the first mount uses the top level LX200 port
+}
+
+/// Where SetPark, parking and the setup page save settings so they survive a restart.
+/// They go in a state file of their own, so config.toml is only ever written by the user.
+#[derive(Debug, Clone)]
+pub struct ParkStore {
+    pub config_path: String,
+    pub state_path: String,
+    pub mount: usize, // Device number
+}
+
+impl ParkStore {
+    pub fn store(&self, park_hour_angle: Hours) -> Result<(), confy::ConfyError> {
+        self.update_state(|state| state.park_hour_angle = Some(park_hour_angle))
+    }
+
+    /// The setup page's settings as this mount last saved them, or as the config file has them
+    pub fn load_setup(&self) -> Result<SetupConfig, String> {
+        let config = self.load_config()?;
+        Ok(SetupConfig {
+            mount_limit_east: config.other.mount_limit_east,
+            mount_limit_west: config.other.mount_limit_west,
+            flip_before_limit: config.other.flip_before_limit,
+            observation_location: config.observation_location,
+            telescope_details: config.telescope_details,
+        })
+    }
+
+    /// None once unparked
+    pub fn store_parked_motor_pos(&self, pos: Option<Degrees>) -> Result<(), confy::ConfyError> {
+        self.update_state(|state| state.parked_motor_pos = pos)
+    }
+
+    pub fn store_setup(&self, setup: &SetupConfig) -> Result<(), confy::ConfyError> {
+        self.update_state(|state| state.setup = Some(*setup))
+    }
+
+    /// This mount's config from the config file, with its saved state applied
+    pub fn load_config(&self) -> Result<Config, String> {
+        let (_, mut config) = Config::load(&self.config_path)?
+            .mount_configs()
+            .into_iter()
+            .nth(self.mount)
+            .ok_or_else(|| format!("Mount {} was removed", self.mount))?;
+        self.load_state()
+            .map_err(|e| e.to_string())?
+            .apply(&mut config);
+        Ok(config)
+    }
+
+    pub fn load_state(&self) -> Result<MountState, confy::ConfyError> {
+        let state: SavedState = confy::load_path(&self.state_path)?;
+        Ok(state.mounts.into_iter().nth(self.mount).unwrap_or_default())
+    }
+
+    fn update_state(&self, update: impl FnOnce(&mut MountState)) -> Result<(), confy::ConfyError> {
+        let mut state: SavedState = confy::load_path(&self.state_path)?;
+        if state.mounts.len() <= self.mount {
+            state.mounts.resize_with(self.mount + 1, Default::default);
+        }
+        update(&mut state.mounts[self.mount]);
+        confy::store_path(&self.state_path, state)
+    }
+}
+
+/// The state file. Written by the driver, not meant to be edited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SavedState {
+    pub mounts: Vec<MountState>, // By device number
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MountState {
+    pub park_hour_angle: Option<Hours>, // Mechanical. Set by SetPark
+    pub parked_motor_pos: Option<Degrees>, // Where the motor stopped, while parked
+    pub setup: Option<SetupConfig>,     // Saved from the setup page
+}
+
+impl MountState {
+    /// Saved settings take the place of those in the config file
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(park_hour_angle) = self.park_hour_angle {
+            config.other.park_hour_angle = park_hour_angle;
+        }
+        if let Some(setup) = self.setup {
+            config.observation_location = setup.observation_location;
+            config.telescope_details = setup.telescope_details;
+            config.other.mount_limit_east = setup.mount_limit_east;
+            config.other.mount_limit_west = setup.mount_limit_west;
+            config.other.flip_before_limit = setup.flip_before_limit;
+        }
+    }
+}
+
+/// The settings the Alpaca setup page edits
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SetupConfig {
+    // Values before tables, for TOML
+    pub mount_limit_east: Hours, // Mechanical
+    pub mount_limit_west: Hours, // Mechanical
+    pub flip_before_limit: Hours,
+    pub observation_location: ObservingLocation,
+    pub telescope_details: TelescopeDetails,
 }
 
 /* Serial Port Settings */
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct ComSettings {
-    pub path: Option<String>, // None for automatic
+    pub path: Option<String>, // None for automatic. "tcp://host:port" or "udp://host:port" for a WiFi adapter
     pub timeout_millis: u32,
+    // Appends every motor command and response to this file as JSON lines. Also set by --trace-motor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_file: Option<String>,
+    pub position_cache_millis: u64, // Position reads this close together share one inquiry. 0 to disable
+    pub tries: u32, // Sends of a failing command before giving up, including the first
+    pub retry_delay_millis: u64, // Before the first retry
+    pub retry_backoff: f64, // Each later retry waits this many times longer
+}
+
+impl ComSettings {
+    const MAX_TRIES: u32 = 20;
+    const MAX_RETRY_DELAY_MILLIS: u64 = 1000;
+
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=Self::MAX_TRIES).contains(&self.tries) {
+            return Err(format!(
+                "com.tries must be from 1 to {}, not {}",
+                Self::MAX_TRIES,
+                self.tries
+            ));
+        }
+        if Self::MAX_RETRY_DELAY_MILLIS < self.retry_delay_millis {
+            return Err(format!(
+                "com.retry-delay-millis must be at most {}, not {}",
+                Self::MAX_RETRY_DELAY_MILLIS,
+                self.retry_delay_millis
+            ));
+        }
+        if !(self.retry_backoff.is_finite() && 1. <= self.retry_backoff) {
+            return Err(format!(
+                "com.retry-backoff must be a number of at least 1, not {}",
+                self.retry_backoff
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl Default for ComSettings {
@@ -29,6 +326,11 @@ impl Default for ComSettings {
         Self {
             path: None,
             timeout_millis: 50,
+            trace_file: None,
+            position_cache_millis: 100,
+            tries: 3,
+            retry_delay_millis: 10,
+            retry_backoff: 10.,
         }
     }
 }
@@ -40,12 +342,33 @@ pub struct ObservingLocation {
     pub latitude: Degrees,
     pub longitude: Degrees,
     pub elevation: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hemisphere: Option<Hemisphere>, // None to determine from the latitude
+}
+
+/// Which way the mount's N/S switch is set
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hemisphere {
+    North,
+    South,
 }
 
 impl ObservingLocation {
-    // TODO use a config value rather that determining from the latitude
+    /// Every direction decision (tracking, guiding, dec knob) goes through this
+    /// The equator counts as north, which is the mount's default N/S switch position.
+    /// At the poles the RA axis points straight up and the sky turns clockwise (south) or counterclockwise (north)
+    /// seen from below, so the rotation direction stays set by the hemisphere right up to ±90°.
+    pub fn hemisphere(&self) -> Hemisphere {
+        self.hemisphere.unwrap_or(if 0. <= self.latitude {
+            Hemisphere::North
+        } else {
+            Hemisphere::South
+        })
+    }
+
     pub fn in_north(&self) -> bool {
-        0. < self.latitude
+        self.hemisphere() == Hemisphere::North
     }
 
     pub fn get_rotation_direction_key(&self) -> RotationDirectionKey {
@@ -59,6 +382,7 @@ impl Default for ObservingLocation {
             latitude: 51.47,
             longitude: 0.0,
             elevation: 15.0,
+            hemisphere: None,
         }
     }
 }
@@ -70,6 +394,28 @@ pub struct TelescopeDetails {
     pub aperture: Option<f64>,
     pub aperture_area: Option<f64>,
     pub focal_length: Option<f64>,
+    #[serde(default)]
+    pub model: MountModel,
+    // Slack in the RA gears, turned through before counting any move that reverses the motor. 0 to not compensate
+    #[serde(default)]
+    pub ra_backlash_arcsec: f64,
+}
+
+/// The hardware profile
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MountModel {
+    StarAdventurer, // Only RA is motorized. Dec is turned by hand.
+    Gti,            // Dec is motorized on the controller's second channel
+    AzGti,          // In equatorial mode. Has no autoguide port
+    EqM35,
+    Auto, // Picked from the mount code the firmware reports. Dec motors need the model set.
+}
+
+impl Default for MountModel {
+    fn default() -> Self {
+        Self::StarAdventurer
+    }
 }
 
 /* Initialization Settings */
@@ -88,6 +434,10 @@ fn is_false(b: &bool) -> bool {
     !*b
 }
 
+fn default_true() -> bool {
+    true
+}
+
 impl Default for Initialization {
     fn default() -> Self {
         Initialization {
@@ -145,6 +495,566 @@ mod pier_side {
     }
 }
 
+/* Manual Declination Knob */
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DeclinationKnob {
+    pub degrees_per_turn: Degrees,
+    // Looking at the knob with the mount on the east side of the pier,
+    // true if turning it clockwise moves the scope toward the celestial pole
+    pub clockwise_toward_pole: bool,
+}
+
+impl Default for DeclinationKnob {
+    fn default() -> Self {
+        Self {
+            degrees_per_turn: 2.957,
+            clockwise_toward_pole: true,
+        }
+    }
+}
+
+/* Vibration Guard */
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct VibrationGuardSettings {
+    pub enabled: bool,
+    // Time to wait for vibrations to die down after each kind of rate change
+    pub after_slew_millis: u64,
+    pub after_move_axis_millis: u64,
+    pub after_tracking_change_millis: u64,
+    pub after_park_millis: u64,
+}
+
+impl Default for VibrationGuardSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            after_slew_millis: 3000,
+            after_move_axis_millis: 2000,
+            after_tracking_change_millis: 1000,
+            after_park_millis: 0,
+        }
+    }
+}
+
+/* Settle */
+/// Gates the end of each settle on the mount actually being still, e.g. from an accelerometer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SettleSettings {
+    // After the settle time, keep reporting Slewing until stability is confirmed with the settled action or the webhook
+    pub wait_for_stability: bool,
+    // POSTed when the settle time runs out. A success response, which may be held until still, confirms stability.
+    // http:// only, like every webhook.
+    pub stability_webhook: Option<String>,
+    pub max_stability_wait_secs: u64, // Settled anyway if not confirmed by then
+}
+
+impl Default for SettleSettings {
+    fn default() -> Self {
+        Self {
+            wait_for_stability: false,
+            stability_webhook: None,
+            max_stability_wait_secs: 60,
+        }
+    }
+}
+
+/* Idle Safety */
+/// Makes the mount safe if the imaging PC goes away, before it tracks into the tripod
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct IdleSafetySettings {
+    pub after_mins: Option<f64>, // Without any Alpaca client request. None to disable
+    pub action: SafeAction,
+}
+
+impl IdleSafetySettings {
+    fn validate(&self) -> Result<(), String> {
+        match self.after_mins {
+            Some(mins) if !(mins.is_finite() && 0. < mins) => Err(format!(
+                "idle-safety after-mins must be a positive number of minutes, not {}",
+                mins
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for IdleSafetySettings {
+    fn default() -> Self {
+        Self {
+            after_mins: None,
+            action: SafeAction::Stop,
+        }
+    }
+}
+
+/* Slew Safety */
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SlewSafety {
+    // Slews to targets below this altitude are refused. None to allow any altitude.
+    pub min_altitude: Option<Degrees>,
+    // Slews closer than this to the sun are refused. None to disable.
+    pub min_sun_separation: Option<Degrees>,
+    // Trees, buildings etc. Interpolated between points, wrapping through north. Empty for none.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub horizon: Vec<HorizonPoint>,
+    // What to do when a tracked target sets below the horizon
+    pub below_horizon_action: TrackingLimitAction,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HorizonPoint {
+    pub azimuth: Degrees,
+    pub min_altitude: Degrees,
+}
+
+impl SlewSafety {
+    /// Checks every angle is a number and puts the horizon in azimuth order
+    pub fn validate(&mut self) -> Result<(), String> {
+        let angles = [self.min_altitude, self.min_sun_separation]
+            .into_iter()
+            .flatten()
+            .chain(
+                self.horizon
+                    .iter()
+                    .flat_map(|point| [point.azimuth, point.min_altitude]),
+            );
+        for angle in angles {
+            if !angle.is_finite() {
+                return Err(format!("slew-safety has an angle of {}", angle));
+            }
+        }
+        self.horizon.sort_by(|a, b| a.azimuth.total_cmp(&b.azimuth));
+        Ok(())
+    }
+
+    /// The lowest allowed altitude at an azimuth, from both min-altitude and the horizon profile
+    pub fn min_altitude_at(&self, azimuth: Degrees) -> Option<Degrees> {
+        let horizon = self.horizon_altitude_at(azimuth);
+        match (self.min_altitude, horizon) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// The horizon must be in azimuth order, as validate leaves it
+    fn horizon_altitude_at(&self, azimuth: Degrees) -> Option<Degrees> {
+        let points = &self.horizon;
+        let first = *points.first()?;
+        let last = *points.last()?;
+        let azimuth = crate::astro_math::modulo(azimuth, 360.);
+
+        // The points either side, wrapping around through north
+        let after = points
+            .iter()
+            .find(|p| azimuth <= p.azimuth)
+            .copied()
+            .unwrap_or(HorizonPoint {
+                azimuth: first.azimuth + 360.,
+                ..first
+            });
+        let before = points
+            .iter()
+            .rev()
+            .find(|p| p.azimuth <= azimuth)
+            .copied()
+            .unwrap_or(HorizonPoint {
+                azimuth: last.azimuth - 360.,
+                ..last
+            });
+
+        if after.azimuth == before.azimuth {
+            return Some(before.min_altitude);
+        }
+        let fraction = (azimuth - before.azimuth) / (after.azimuth - before.azimuth);
+        Some(before.min_altitude + fraction * (after.min_altitude - before.min_altitude))
+    }
+}
+
+/* Observing Sessions */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SessionSettings {
+    // Start a session on the first connection of each night
+    pub auto_start: bool,
+    pub summary_dir: String,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            auto_start: true,
+            summary_dir: "sessions".to_string(),
+        }
+    }
+}
+
+/* Mount Power Switch */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PowerSettings {
+    pub switch: Option<PowerSwitchKind>, // None if the mount power isn't switchable
+    pub power_off_on_park: bool,
+    pub power_on_delay_millis: u64, // Time for the motor controller to boot
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            switch: None,
+            power_off_on_park: false,
+            power_on_delay_millis: 2000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum PowerSwitchKind {
+    Gpio {
+        pin: u32,
+        #[serde(default, rename = "active-low")]
+        active_low: bool,
+    },
+    Command {
+        on: String,
+        off: String,
+    },
+}
+
+/* Declination Guide Output */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DecGuideOutputSettings {
+    pub output: Option<DecGuideOutputKind>, // None if dec can't be guided
+    pub guide_rate: Degrees, // Degrees/sec the dec motor moves at while its guide input is active
+}
+
+impl Default for DecGuideOutputSettings {
+    fn default() -> Self {
+        Self {
+            output: None,
+            guide_rate: 0.5 * 360. / 86164.0905, // Half sidereal, the usual ST-4 rate
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum DecGuideOutputKind {
+    /// Relays on an ST-4 style guide port, switched by Raspberry Pi GPIO pins
+    #[serde(alias = "gpio")]
+    GpioSt4 {
+        #[serde(rename = "north-pin")]
+        north_pin: u32,
+        #[serde(rename = "south-pin")]
+        south_pin: u32,
+        #[serde(default, rename = "active-low")]
+        active_low: bool,
+        // Shorter pulses are dropped, as the relays can't close for less
+        #[serde(default, rename = "min-pulse-millis")]
+        min_pulse_millis: u64,
+        // Added to every pulse to make up for the relay pulling in
+        #[serde(default, rename = "relay-delay-millis")]
+        relay_delay_millis: u64,
+    },
+}
+
+/* Events */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct EventSettings {
+    pub listen_addr: Option<SocketAddr>, // Serves GET /events as server-sent events. None to disable
+    pub webhooks: Vec<String>,           // http:// URLs every event is POSTed to as JSON
+}
+
+/* Notifications */
+/// Telling someone when the mount needs a hand, e.g. to turn the declination knob, or has run into trouble
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct NotificationSettings {
+    pub desktop: bool,         // With notify-send, or osascript on macOS
+    pub webhooks: Vec<String>, // POSTed {"kind", "title", "message"} as JSON. http:// only, there's no TLS
+    // Run with the shell, with NOTIFY_KIND, NOTIFY_TITLE and NOTIFY_MESSAGE set, e.g. "paplay /usr/share/sounds/bell.oga"
+    pub commands: Vec<String>,
+    pub mqtt: Option<MqttSettings>,
+    pub errors: bool, // Also notify when the driver had to stop the mount or lost it
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            desktop: false,
+            webhooks: Vec::new(),
+            commands: Vec::new(),
+            mqtt: None,
+            errors: true,
+        }
+    }
+}
+
+/// Notifications are published as JSON, at most once, without a retained message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MqttSettings {
+    pub broker: String, // host:port, e.g. "localhost:1883"
+    pub topic: String,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+fn default_mqtt_client_id() -> String {
+    "star-adventurer-alpaca".to_string()
+}
+
+/* Action Macros */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "kebab-case")]
+pub enum MacroStep {
+    Unpark,
+    Park,
+    Tracking {
+        enabled: bool,
+    },
+    SlewToTarget, // The last target set, waiting for the slew to finish
+    AbortSlew,
+    Wait {
+        seconds: f64,
+    },
+    Action {
+        action: String,
+        #[serde(default)]
+        parameters: String,
+    },
+}
+
+impl MacroStep {
+    /// Longest a wait step can be
+    pub const MAX_WAIT_SECS: f64 = 86_400.;
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            MacroStep::Wait { seconds } if !(0. <= *seconds && *seconds <= Self::MAX_WAIT_SECS) => {
+                Err(format!(
+                    "wait must be from 0 to {} seconds, not {}",
+                    Self::MAX_WAIT_SECS,
+                    seconds
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/* INDI Server */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct IndiSettings {
+    pub listen_addr: Option<SocketAddr>, // Usually port 7624. None to disable
+}
+
+/* LX200 */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Lx200Settings {
+    pub listen_addr: Option<SocketAddr>, // SkySafari defaults to port 4030. None to disable
+}
+
+/* Web Dashboard */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DashboardSettings {
+    pub enabled: bool, // Serves each mount's status page at /dashboard/{device_number}/ on the Alpaca port
+}
+
+/* Client Traffic */
+/// Logs each Alpaca client's requests and slows down clients polling too fast
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ClientTrafficSettings {
+    pub enabled: bool,
+    pub history_length: usize, // Requests kept per client, served at GET /clients
+    pub max_polls_per_sec: f64, // GETs per client. 0 for no limit. Commands are never held back
+    pub forget_after_mins: f64, // Clients not heard from for this long are dropped
+}
+
+impl ClientTrafficSettings {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.max_polls_per_sec.is_finite() && 0. <= self.max_polls_per_sec) {
+            return Err(format!(
+                "client-traffic max-polls-per-sec must be 0 or more, not {}",
+                self.max_polls_per_sec
+            ));
+        }
+        if !(self.forget_after_mins.is_finite() && 0. < self.forget_after_mins) {
+            return Err(format!(
+                "client-traffic forget-after-mins must be a positive number of minutes, not {}",
+                self.forget_after_mins
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ClientTrafficSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            history_length: 50,
+            max_polls_per_sec: 0.,
+            forget_after_mins: 60.,
+        }
+    }
+}
+
+/* Guide Pulse Batching */
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GuideBatchSettings {
+    pub min_pulse_interval_millis: u64, // 0 to command every pulse as it arrives
+}
+
+/* Move Speed Presets */
+/// Hand controller style MoveAxis speeds as multiples of sidereal
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MovePresets {
+    pub guide: f64,
+    pub center: f64,
+    pub find: f64,
+    pub slew: f64, // Clamped to the motor's max speed
+}
+
+impl Default for MovePresets {
+    fn default() -> Self {
+        Self {
+            guide: 0.5,
+            center: 8.,
+            find: 64.,
+            slew: 800.,
+        }
+    }
+}
+
+/* Jogging */
+/// Short relative moves for framing, e.g. from a phone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct JogSettings {
+    pub steps: Vec<f64>, // Arcminutes, offered as buttons
+    pub default_step: f64,
+    pub max_step: f64,
+}
+
+impl Default for JogSettings {
+    fn default() -> Self {
+        Self {
+            steps: vec![1., 5., 15., 60.],
+            default_step: 5.,
+            max_step: 120.,
+        }
+    }
+}
+
+/* GPS */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GpsSettings {
+    pub source: Option<GpsSource>, // None to only use the configured location
+    pub set_time: bool,            // Also set the mount's clock from the GPS
+    pub fix_timeout_secs: u64,
+}
+
+impl Default for GpsSettings {
+    fn default() -> Self {
+        Self {
+            source: None,
+            set_time: true,
+            fix_timeout_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum GpsSource {
+    // A USB receiver sending NMEA sentences
+    Nmea {
+        path: String,
+        baud_rate: Option<u32>, // None for the usual 4800
+    },
+    // e.g. "localhost:2947"
+    Gpsd {
+        address: String,
+    },
+}
+
+/* Rate Ramping */
+/// Rate changes from MoveAxis, tracking and the stops around gotos.
+/// Gotos themselves are accelerated by the motor controller.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RampSettings {
+    pub max_acceleration: Degrees, // deg/s². 0 to jump straight to the new rate
+}
+
+/* Time */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TimeSettings {
+    // An IERS leap-seconds.list newer than the bundled table, e.g. /usr/share/zoneinfo/leap-seconds.list
+    pub leap_second_file: Option<String>,
+}
+
+/* Pointing Model */
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PointingModelSettings {
+    // Syncs after the first add points to the model instead of moving the origin
+    pub enabled: bool,
+    pub max_points: usize, // The oldest point is dropped past this
+}
+
+impl Default for PointingModelSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_points: 20,
+        }
+    }
+}
+
+/* Plate-Solve Centering */
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CenteringSettings {
+    pub max_iterations: u32,
+    // RA residuals below this are nudged at the center move preset instead of with a goto.
+    // None to always use gotos.
+    pub fine_nudge_below: Option<Degrees>,
+    // Every goto waits for solves to center its target, as if started with center_on
+    pub closed_loop_gotos: bool,
+    pub tolerance_arcsec: f64, // For closed loop gotos
+}
+
+impl Default for CenteringSettings {
+    fn default() -> Self {
+        Self {
+            max_iterations: 5,
+            fine_nudge_below: None,
+            closed_loop_gotos: false,
+            tolerance_arcsec: 30.,
+        }
+    }
+}
+
 /* Other Settings */
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -154,9 +1064,117 @@ pub struct OtherSettings {
     pub instant_dec_slew: bool,
     #[serde(default = "auto_guide_speed::default", with = "auto_guide_speed")]
     pub auto_guide_speed: AutoGuideSpeed,
-    pub park_hour_angle: Hours,  // Mechanical
+    pub park_hour_angle: Hours, // Mechanical
+    // Park pointing here instead of at park-hour-angle. Only the RA axis is driven, so set dec to match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub park_alt_az: Option<ParkAltAz>,
     pub mount_limit_east: Hours, // Mechanical
     pub mount_limit_west: Hours, // Mechanical
+    // How long before tracking carries the mount into the west limit to act, e.g. to leave time to flip
+    #[serde(default)]
+    pub flip_before_limit: Hours,
+    #[serde(default)]
+    pub tracking_limit_action: TrackingLimitAction,
+    // If false, UTCDate is only reported back and sidereal time follows the system clock
+    #[serde(default = "default_true")]
+    pub sky_clock_follows_utc_date: bool,
+    // For clients that expect RA to stay put while the mount is stopped rather than drift with LST
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub freeze_coordinates_while_stopped: bool,
+    // RA, Alt and Az are dead reckoned from one motor position read for this long, so they move smoothly. 0 to read it every time
+    #[serde(default = "default_dead_reckoning_millis")]
+    pub dead_reckoning_millis: u64,
+    // How far the motor may be from where it was parked before a re-sync is required on unpark
+    #[serde(default = "default_unpark_tolerance")]
+    pub unpark_position_tolerance: Degrees,
+    // Adjust the sidereal rate for refraction at the current target, in place of a fixed King rate
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub smart_tracking: bool,
+    // Follow the Moon's actual motion at the lunar rate, recomputed every few minutes, in place of a fixed rate
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ephemeris_lunar_tracking: bool,
+    // At the King rate, compute the refraction-corrected rate for the latitude and current pointing, in place of the controller's fixed King period
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub refined_king_rate: bool,
+    // Panorama mode, with the RA axis upright turning in azimuth and the declination knob setting the altitude
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub alt_az: bool,
+    // Start tracking again on unpark, at the rate it was at, if the mount was tracking when parked
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub resume_tracking_on_unpark: bool,
+    // Always finish parking moving from this side so the backlash is the same every night
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub park_approach: Option<ParkApproach>,
+    // Degrees. Gotos that leave the mount tracking overshoot against tracking by this much, then come back with it,
+    // so the gear backlash is already taken up when tracking resumes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goto_approach: Option<Degrees>,
+    // What to leave the mount doing when the driver is stopped with Ctrl-C or SIGTERM. Slews are always aborted.
+    #[serde(default)]
+    pub on_shutdown: SafeAction,
+}
+
+/// What to do when tracking reaches the west limit, less `flip-before-limit`, or goes below the horizon
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrackingLimitAction {
+    Warn, // Only publish the event
+    StopTracking,
+}
+
+impl Default for TrackingLimitAction {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+
+/// How to leave the mount when nothing will be watching it
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SafeAction {
+    Nothing,
+    Stop, // Stop tracking
+    Park,
+}
+
+impl Default for SafeAction {
+    fn default() -> Self {
+        Self::Nothing
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ParkAltAz {
+    pub altitude: Degrees,
+    pub azimuth: Degrees,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParkApproachSide {
+    East,
+    West,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ParkApproach {
+    pub from: ParkApproachSide,
+    #[serde(default = "default_park_approach_distance")]
+    pub distance: Degrees, // Length of the final leg
+}
+
+fn default_park_approach_distance() -> Degrees {
+    2.
+}
+
+fn default_unpark_tolerance() -> Degrees {
+    0.5
+}
+
+fn default_dead_reckoning_millis() -> u64 {
+    1000
 }
 
 impl Default for OtherSettings {
@@ -166,13 +1184,29 @@ impl Default for OtherSettings {
             instant_dec_slew: true,
             auto_guide_speed: auto_guide_speed::default(),
             park_hour_angle: 0.,
+            park_alt_az: None,
             mount_limit_east: 18., // Horizontal on the east
             mount_limit_west: 6.,  // Horizontal on the west
+            flip_before_limit: 0.,
+            tracking_limit_action: TrackingLimitAction::default(),
+            sky_clock_follows_utc_date: true,
+            freeze_coordinates_while_stopped: false,
+            dead_reckoning_millis: default_dead_reckoning_millis(),
+            unpark_position_tolerance: default_unpark_tolerance(),
+            smart_tracking: false,
+            ephemeris_lunar_tracking: false,
+            refined_king_rate: false,
+            alt_az: false,
+            resume_tracking_on_unpark: false,
+            park_approach: None,
+            goto_approach: None,
+            on_shutdown: SafeAction::default(),
         }
     }
 }
 
 mod auto_guide_speed {
+    use crate::util::AutoGuideSpeedExt;
     use core::fmt::Formatter;
     use serde::de::{Error, Visitor};
     use serde::{Deserializer, Serializer};
@@ -183,21 +1217,54 @@ mod auto_guide_speed {
         type Value = AutoGuideSpeed;
 
         fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
-            formatter.write_str("1/8, 1/4, 1/2, 3/4, 1")
+            formatter.write_str("a fraction of the tracking rate from 0.125 to 1")
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            if !(AutoGuideSpeed::MIN_FRACTION..=AutoGuideSpeed::MAX_FRACTION).contains(&v) {
+                return Err(E::custom(format!("auto-guide speed {} out of range", v)));
+            }
+            let speed = AutoGuideSpeed::closest_to(v);
+            if speed.multiplier() != v {
+                tracing::warn!("Auto-guide speed {} snapped to {}", v, speed.multiplier());
+            }
+            Ok(speed)
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            self.visit_f64(v as f64)
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            self.visit_f64(v as f64)
         }
 
         fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
         where
             E: Error,
         {
+            // Older configs spell the speed as a ratio
             let lower = v.to_lowercase();
             Ok(match &*lower {
                 "1/8" => AutoGuideSpeed::Eighth,
                 "1/4" => AutoGuideSpeed::Quarter,
                 "1/2" => AutoGuideSpeed::Half,
                 "3/4" => AutoGuideSpeed::ThreeQuarters,
-                "1" => AutoGuideSpeed::One,
-                _ => return Err(E::custom(format!("unknown auto-guide speed: \"{}\"", v))),
+                _ => match lower.parse::<f64>() {
+                    Ok(fraction) => return self.visit_f64(fraction),
+                    Err(_) => {
+                        return Err(E::custom(format!("unknown auto-guide speed: \"{}\"", v)))
+                    }
+                },
             })
         }
     }
@@ -210,19 +1277,261 @@ mod auto_guide_speed {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_str(AutoGuideSpeedVisitor)
+        deserializer.deserialize_any(AutoGuideSpeedVisitor)
     }
 
     pub fn serialize<S>(s: &AutoGuideSpeed, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(match s {
-            AutoGuideSpeed::One => "1",
-            AutoGuideSpeed::ThreeQuarters => "3/4",
-            AutoGuideSpeed::Half => "1/2",
-            AutoGuideSpeed::Quarter => "1/4",
-            AutoGuideSpeed::Eighth => "1/8",
-        })
+        serializer.serialize_f64(s.multiplier())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotation_direction::RotationDirection;
+    use crate::telescope_control::dec_knob::DeclinationSlewPlan;
+    use crate::tracking_direction::TrackingDirection;
+    use ascom_alpaca::api::PutPulseGuideDirection;
+    use synscan::Direction;
+
+    fn location(latitude: Degrees, hemisphere: Option<Hemisphere>) -> ObservingLocation {
+        ObservingLocation {
+            latitude,
+            hemisphere,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hemisphere() {
+        assert_eq!(location(51.47, None).hemisphere(), Hemisphere::North);
+        assert_eq!(location(-33.9, None).hemisphere(), Hemisphere::South);
+        assert_eq!(location(0., None).hemisphere(), Hemisphere::North);
+        assert_eq!(location(-0., None).hemisphere(), Hemisphere::North);
+        assert_eq!(
+            location(0., Some(Hemisphere::South)).hemisphere(),
+            Hemisphere::South
+        );
+        assert_eq!(
+            location(10., Some(Hemisphere::South)).hemisphere(),
+            Hemisphere::South
+        );
+    }
+
+    #[test]
+    fn test_mount_configs() {
+        let config = Config::default();
+        assert_eq!(config.mount_configs().len(), 1);
+
+        let config = Config {
+            mounts: vec![
+                MountSettings {
+                    com: Some(ComSettings {
+                        path: Some("/dev/ttyUSB1".to_string()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                MountSettings {
+                    name: Some("Guide rig".to_string()),
+                    observation_location: Some(location(-33.9, None)),
+                    park_hour_angle: Some(18.),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mounts = config.mount_configs();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].0, None);
+        assert_eq!(mounts[0].1.com.path.as_deref(), Some("/dev/ttyUSB1"));
+        assert_eq!(mounts[0].1.observation_location.latitude, 51.47);
+        assert_eq!(mounts[1].0.as_deref(), Some("Guide rig"));
+        assert_eq!(mounts[1].1.com.path, None);
+        assert_eq!(mounts[1].1.observation_location.latitude, -33.9);
+        assert_eq!(mounts[0].1.other.park_hour_angle, 0.);
+        assert_eq!(mounts[1].1.other.park_hour_angle, 18.);
+        assert!(mounts.iter().all(|(_, config)| config.mounts.is_empty()));
+
+        // Two mounts can't share the LX200 port
+        let config = Config {
+            lx200: Lx200Settings {
+                listen_addr: Some("127.0.0.1:4030".parse().unwrap()),
+            },
+            ..config
+        };
+        let mounts = config.mount_configs();
+        assert!(mounts[0].1.lx200.listen_addr.is_some());
+        assert!(mounts[1].1.lx200.listen_addr.is_none());
+    }
+
+    #[test]
+    fn test_park_store() {
+        let state_path = std::env::temp_dir().join(format!("sa-state-{}.toml", std::process::id()));
+        let store = ParkStore {
+            config_path: "test_config.toml".to_string(),
+            state_path: state_path.to_string_lossy().into_owned(),
+            mount: 0,
+        };
+        let config_file = std::fs::read_to_string(&store.config_path).unwrap();
+
+        store.store(6.).unwrap();
+        let mut setup = store.load_setup().unwrap();
+        setup.mount_limit_east = 1.5;
+        setup.observation_location.latitude = -33.9;
+        store.store_setup(&setup).unwrap();
+
+        store.store_parked_motor_pos(Some(-12.5)).unwrap();
+
+        let state = store.load_state().unwrap();
+        assert_eq!(state.park_hour_angle, Some(6.));
+        assert_eq!(state.parked_motor_pos, Some(-12.5));
+        let config = store.load_config().unwrap();
+        assert_eq!(config.other.park_hour_angle, 6.);
+        assert_eq!(config.other.mount_limit_east, 1.5);
+        assert_eq!(config.observation_location.latitude, -33.9);
+        // Other mounts keep what the config file has
+        let other = ParkStore {
+            mount: 1,
+            ..store.clone()
+        };
+        let other_state = other.load_state().unwrap();
+        assert!(other_state.park_hour_angle.is_none() && other_state.setup.is_none());
+        other.store(18.).unwrap();
+        assert_eq!(store.load_state().unwrap().park_hour_angle, Some(6.));
+        store.store_parked_motor_pos(None).unwrap();
+        assert_eq!(store.load_state().unwrap().parked_motor_pos, None);
+        // The config file is never written
+        assert_eq!(
+            std::fs::read_to_string(&store.config_path).unwrap(),
+            config_file
+        );
+        std::fs::remove_file(state_path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_custom_tracking_rates() {
+        let mut config = Config::default();
+        config
+            .custom_tracking_rates
+            .insert("comet".to_string(), 0.0042);
+        assert!(config.validate().is_ok());
+        for bad in [f64::NAN, -0.0042, 0., f64::INFINITY] {
+            config
+                .custom_tracking_rates
+                .insert("comet".to_string(), bad);
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_validate_idle_safety() {
+        let mut config = Config::default();
+        config.idle_safety.after_mins = Some(30.);
+        assert!(config.validate().is_ok());
+        for after_mins in [0., -5., f64::NAN, f64::INFINITY] {
+            config.idle_safety.after_mins = Some(after_mins);
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_validate_webhooks() {
+        let mut config = Config::default();
+        config.notifications.webhooks = vec!["http://localhost:8080/notify".to_string()];
+        assert!(config.validate().is_ok());
+        config.settle.stability_webhook = Some("https://example.com/settled".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_macros() {
+        let mut config = Config::default();
+        config
+            .macros
+            .insert("dusk".to_string(), vec![MacroStep::Wait { seconds: 60. }]);
+        assert!(config.validate().is_ok());
+
+        config.macros.insert(
+            "dusk".to_string(),
+            vec![MacroStep::Wait { seconds: f64::NAN }],
+        );
+        assert!(config.validate().is_err());
+        config.macros.insert(
+            "dusk".to_string(),
+            vec![MacroStep::Wait {
+                seconds: f64::INFINITY,
+            }],
+        );
+        assert!(config.validate().is_err());
+
+        config.macros.clear();
+        config.macros.insert("snapshot".to_string(), Vec::new());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_horizon() {
+        let point = |azimuth, min_altitude| HorizonPoint {
+            azimuth,
+            min_altitude,
+        };
+        let mut safety = SlewSafety {
+            horizon: vec![point(90., 20.), point(270., 10.), point(0., 30.)],
+            ..Default::default()
+        };
+        safety.validate().unwrap();
+        assert_eq!(safety.min_altitude_at(0.), Some(30.));
+        assert_eq!(safety.min_altitude_at(45.), Some(25.));
+        assert_eq!(safety.min_altitude_at(180.), Some(15.));
+        assert_eq!(safety.min_altitude_at(315.), Some(20.));
+        assert_eq!(safety.min_altitude_at(-45.), Some(20.));
+
+        let safety = SlewSafety {
+            min_altitude: Some(18.),
+            ..safety
+        };
+        assert_eq!(safety.min_altitude_at(180.), Some(18.));
+        assert_eq!(safety.min_altitude_at(45.), Some(25.));
+        assert_eq!(SlewSafety::default().min_altitude_at(45.), None);
+
+        let mut safety = SlewSafety {
+            horizon: vec![point(f64::NAN, 20.)],
+            ..Default::default()
+        };
+        assert!(safety.validate().is_err());
+    }
+
+    /// Tracking, guiding and the dec knob must agree on the hemisphere, even on the equator
+    #[test]
+    fn test_hemisphere_consistent() {
+        let knob = DeclinationKnob::default();
+        for (loc, north) in [
+            (location(51.47, None), true),
+            (location(-33.9, None), false),
+            (location(0., None), true),
+            (location(0., Some(Hemisphere::South)), false),
+        ] {
+            let tracking: Direction = TrackingDirection::WithTracking
+                .using(loc.get_rotation_direction_key())
+                .into();
+            let expected = if north {
+                Direction::Clockwise
+            } else {
+                Direction::CounterClockwise
+            };
+            assert_eq!(tracking, expected);
+
+            let west: Direction = PutPulseGuideDirection::West
+                .using(loc.get_rotation_direction_key())
+                .into();
+            assert_eq!(west, tracking);
+
+            let plan = DeclinationSlewPlan::new(1., false, SideOfPier::East, loc.in_north(), &knob);
+            assert_eq!(plan.toward_pole, north);
+        }
     }
 }
@@ -0,0 +1,785 @@
+//! The custom Alpaca actions, described for client plugins that want to discover them.
+//! Each carries its handler, which `AlpacaDevice::action` dispatches to by name.
+
+use std::fmt;
+
+use ascom_alpaca::api::SideOfPier;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use crate::alpaca_state::AlpacaDevice;
+use crate::config::SetupConfig;
+use crate::telescope_control::scheduler::ScheduledTarget;
+use crate::util::{format_numbers, parse_numbers};
+
+/// Runs an action with its parameters
+pub type ActionHandler = for<'a> fn(&'a AlpacaDevice, String) -> BoxFuture<'a, ASCOMResult<String>>;
+
+#[derive(Serialize)]
+pub struct CustomAction {
+    pub name: &'static str,
+    pub parameters: &'static str, // Empty for none
+    pub returns: &'static str,    // Empty for nothing
+    pub description: &'static str,
+    #[serde(skip)]
+    pub handler: ActionHandler,
+}
+
+impl fmt::Debug for CustomAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomAction")
+            .field("name", &self.name)
+            .field("parameters", &self.parameters)
+            .field("returns", &self.returns)
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+/// A sequence of actions a client runs to get something done that ASCOM has no property for
+#[derive(Debug, Serialize)]
+pub struct Workflow {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub steps: &'static [&'static str],
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomCapabilities {
+    pub actions: &'static [CustomAction],
+    pub workflows: &'static [Workflow],
+}
+
+/// Configured macros are also actions, by name, but are listed by the macros action rather than here
+pub static CAPABILITIES: CustomCapabilities = CustomCapabilities {
+    actions: ACTIONS,
+    workflows: WORKFLOWS,
+};
+
+/// The handler body is an async block over the device and the parameters
+macro_rules! action {
+    (
+        $name:literal,
+        $parameters:literal,
+        $returns:literal,
+        $description:literal,
+        |$device:ident, $params:pat_param| $body:expr $(,)?
+    ) => {{
+        fn handler<'a>(
+            $device: &'a AlpacaDevice,
+            params: String,
+        ) -> BoxFuture<'a, ASCOMResult<String>> {
+            async fn run($device: &AlpacaDevice, $params: String) -> ASCOMResult<String> {
+                $body
+            }
+            Box::pin(run($device, params))
+        }
+        CustomAction {
+            name: $name,
+            parameters: $parameters,
+            returns: $returns,
+            description: $description,
+            handler,
+        }
+    }};
+}
+
+/// The action of that name. Macros aren't in here.
+pub fn find(name: &str) -> Option<&'static CustomAction> {
+    ACTIONS.iter().find(|action| action.name == name)
+}
+
+pub fn to_json<T: Serialize>(value: &T) -> ASCOMResult<String> {
+    serde_json::to_string(value).map_err(|e| {
+        ASCOMError::new(
+            ASCOMErrorCode::new_for_driver(4),
+            format_args!("Couldn't serialize response: {}", e),
+        )
+    })
+}
+
+/// Parses action parameters of `N` numbers, e.g. "ra,dec", in decimal or sexagesimal
+fn parse_parameters<const N: usize>(parameters: &str, expected: &str) -> ASCOMResult<[f64; N]> {
+    parse_numbers(parameters).ok_or_else(|| {
+        ASCOMError::invalid_value(format_args!(
+            "Expected \"{}\" but got \"{}\"",
+            expected, parameters
+        ))
+    })
+}
+
+pub const ACTIONS: &[CustomAction] = &[
+    action!(
+        "pending_declination_slew",
+        "",
+        "degrees",
+        "How far the declination knob still has to turn for the current slew, positive north. 0 when nothing is waiting on the knob",
+        |device, _| Ok(device.0.get_pending_dec_change().await.to_string())
+    ),
+    action!(
+        "declination_slew_plan",
+        "",
+        "JSON or null",
+        "How to turn the declination knob for the pending slew, including the turn direction and meridian flip",
+        |device, _| to_json(&device.0.get_pending_dec_slew_plan().await)
+    ),
+    action!(
+        "complete_declination_slew",
+        "",
+        "",
+        "Tells the driver the declination knob has been turned, finishing the slew",
+        |device, _| {
+            device.0.complete_dec_slew().await;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "report_declination_turns",
+        "turns",
+        "JSON or null",
+        "Tells the driver some of the knob turns are done, returning the plan for the rest. null once finished",
+        |device, parameters| {
+            let [turns] = parse_parameters(&parameters, "turns")?;
+            to_json(&device.0.report_dec_turns(turns).await?)
+        }
+    ),
+    action!(
+        "snapshot",
+        "",
+        "JSON",
+        "Position, state and tracking in one read",
+        |device, _| to_json(&device.0.get_snapshot().await)
+    ),
+    action!(
+        "hour_angle",
+        "",
+        "hours",
+        "The hour angle of the reported coordinates, 0..24",
+        |device, _| Ok(device.0.get_hour_angle().await?.to_string())
+    ),
+    action!(
+        "mechanical_hour_angle",
+        "",
+        "hours",
+        "Where the RA axis is turned to, 0..24, before the pier side and pointing model",
+        |device, _| Ok(device.0.get_mechanical_hour_angle().await?.to_string())
+    ),
+    action!(
+        "motor_position",
+        "",
+        "degrees",
+        "The raw RA motor position, from where it was at power on",
+        |device, _| Ok(device.0.get_motor_position().await?.to_string())
+    ),
+    action!(
+        "mech_ha_offset",
+        "",
+        "hours",
+        "The mechanical hour angle at motor position 0, moved by syncs and by restoring the park after a power cycle",
+        |device, _| Ok(device.0.get_mech_ha_offset().to_string())
+    ),
+    action!(
+        "worm_phase",
+        "",
+        "JSON",
+        "Where the RA worm is in its turn, for periodic error correction",
+        |device, _| to_json(&device.0.get_worm_phase().await?)
+    ),
+    action!(
+        "hardware_incidents",
+        "",
+        "JSON",
+        "Motor controller misbehaviour the driver has stopped",
+        |device, _| to_json(&device.0.get_hardware_incidents())
+    ),
+    action!(
+        "serial_health",
+        "",
+        "JSON",
+        "Command, retry and failure counts for the serial link",
+        |device, _| to_json(&device.0.get_serial_health())
+    ),
+    action!(
+        "capabilities",
+        "",
+        "JSON",
+        "What the connected mount can do",
+        |device, _| to_json(&device.0.get_capabilities().await?)
+    ),
+    action!(
+        "driver_info",
+        "",
+        "JSON",
+        "Driver version, enabled features and what the motor controller reports about itself",
+        |device, _| to_json(&device.0.get_driver_info().await)
+    ),
+    action!(
+        "guide_speed_fraction",
+        "",
+        "number",
+        "The RA guide rate as a fraction of the tracking rate",
+        |device, _| Ok(device.0.get_guide_speed_fraction().await?.to_string())
+    ),
+    action!(
+        "set_guide_speed_fraction",
+        "fraction",
+        "number",
+        "Sets the RA guide rate as a fraction of the tracking rate, returning the fraction the controller can do",
+        |device, parameters| {
+            let [fraction] = parse_parameters(&parameters, "fraction")?;
+            let snapped = device.0.set_guide_speed_fraction(fraction).await?;
+            Ok(snapped.to_string())
+        }
+    ),
+    action!(
+        "guide_stats",
+        "minutes",
+        "JSON",
+        "Guide pulse totals over the last minutes",
+        |device, parameters| {
+            let [minutes] = parse_parameters(&parameters, "minutes")?;
+            to_json(&device.0.get_guide_stats(minutes)?)
+        }
+    ),
+    action!(
+        "guide_latency",
+        "",
+        "JSON",
+        "How long guide pulses wait before the motor starts them",
+        |device, _| to_json(&device.0.get_guide_latency())
+    ),
+    action!(
+        "pulse_guide_axes",
+        "",
+        "JSON",
+        "What moves each axis for guide pulses",
+        |device, _| to_json(&device.0.get_pulse_guide_axes())
+    ),
+    action!(
+        "can_slew_to",
+        "ra,dec",
+        "JSON",
+        "Whether a slew to the coordinates would be allowed, and why not",
+        |device, parameters| {
+            let [ra, dec] = parse_parameters(&parameters, "ra,dec")?;
+            to_json(&device.0.can_slew_to(ra, dec).await?)
+        }
+    ),
+    action!(
+        "sync_and_track",
+        "ra,dec",
+        "ra,dec",
+        "Syncs and starts tracking as one operation",
+        |device, parameters| {
+            let [ra, dec] = parse_parameters(&parameters, "ra,dec")?;
+            device.0.sync_and_start_tracking(ra, dec).await?;
+            Ok(format_numbers(&[ra, dec]))
+        }
+    ),
+    action!(
+        "start_session",
+        "",
+        "JSON",
+        "Starts a session log",
+        |device, _| to_json(&device.0.start_session().await?)
+    ),
+    action!(
+        "end_session",
+        "",
+        "JSON",
+        "Ends the session log, returning its summary",
+        |device, _| to_json(&device.0.end_session().await?)
+    ),
+    action!(
+        "session",
+        "",
+        "JSON",
+        "The session so far",
+        |device, _| to_json(&device.0.get_session())
+    ),
+    action!(
+        "session_summary",
+        "",
+        "JSON",
+        "The last ended session",
+        |device, _| to_json(&device.0.get_last_session_summary())
+    ),
+    action!(
+        "export_session",
+        "json|csv",
+        "text",
+        "Exports the running or last session",
+        |device, parameters| device.0.export_session(&parameters)
+    ),
+    action!(
+        "panic_stop",
+        "",
+        "",
+        "Stops both motors at once, ahead of anything else queued",
+        |device, _| {
+            device.0.panic_stop().await?;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "set_pier_side_after_manual_move",
+        "east|west",
+        "",
+        "Records the pier side after the mount was moved by hand",
+        |device, parameters| {
+            let pier_side = match &*parameters {
+                "east" => SideOfPier::East,
+                "west" => SideOfPier::West,
+                _ => {
+                    return Err(ASCOMError::invalid_value(format_args!(
+                        "Unknown pier side: \"{}\"",
+                        parameters
+                    )))
+                }
+            };
+            device.0.set_pier_side_after_manual_move(pier_side).await;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "move_presets",
+        "",
+        "JSON",
+        "Named MoveAxis rates",
+        |device, _| to_json(&device.0.get_move_presets().await?)
+    ),
+    action!(
+        "move_preset",
+        "name or -name",
+        "",
+        "Moves the RA axis at a named rate, against tracking with a leading '-'",
+        |device, parameters| {
+            // e.g. "center" or "-center" to move against tracking
+            let (negative, name) = match parameters.trim().strip_prefix('-') {
+                Some(name) => (true, name),
+                None => (false, parameters.trim()),
+            };
+            device.0.move_at_preset(name.parse()?, negative).await?;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "jog_steps",
+        "",
+        "JSON",
+        "The default jog step in each direction",
+        |device, _| to_json(device.0.get_jog_steps())
+    ),
+    action!(
+        "jog",
+        "direction [arcminutes]",
+        "",
+        "Moves by a step, e.g. \"east 5\"",
+        |device, parameters| {
+            // e.g. "east 5" in arcminutes, or just "east" for the default step
+            let mut words = parameters.split_whitespace();
+            let direction = words.next().unwrap_or_default().parse()?;
+            let arcmin = words
+                .next()
+                .map(|arcmin| {
+                    arcmin.parse().map_err(|_| {
+                        ASCOMError::invalid_value(format_args!("Invalid jog step: \"{}\"", arcmin))
+                    })
+                })
+                .transpose()?;
+            device.0.jog(direction, arcmin).await?;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "position_problem",
+        "",
+        "JSON or null",
+        "Why the position can't be trusted, if it can't",
+        |device, _| to_json(&device.0.get_position_problem().await)
+    ),
+    action!(
+        "accept_position",
+        "",
+        "",
+        "Trusts the position again without a sync",
+        |device, _| {
+            device.0.accept_position().await;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "dither",
+        "max_arcsec",
+        "JSON",
+        "Moves by a random offset up to max_arcsec",
+        |device, parameters| {
+            let [max_arcsec] = parse_parameters(&parameters, "max_arcsec")?;
+            to_json(&device.0.dither(max_arcsec).await?)
+        }
+    ),
+    action!(
+        "settle_after_dither",
+        "",
+        "",
+        "Starts the settle timer after a dither",
+        |device, _| {
+            device.0.settle_after_dither().await;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "pause_settle",
+        "",
+        "",
+        "Pauses the settle timer",
+        |device, _| {
+            device.0.settle_timer().pause();
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "resume_settle",
+        "",
+        "",
+        "Resumes the settle timer",
+        |device, _| {
+            device.0.settle_timer().resume();
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "extend_settle",
+        "seconds",
+        "seconds",
+        "Adds time to the settle timer",
+        |device, parameters| {
+            let [secs] = parse_parameters(&parameters, "seconds")?;
+            if secs < 0. {
+                return Err(ASCOMError::invalid_value(format_args!(
+                    "Invalid settle extension: \"{}\"",
+                    parameters
+                )));
+            }
+            device
+                .0
+                .settle_timer()
+                .extend(std::time::Duration::from_secs_f64(secs));
+            Ok(format_numbers(&[secs]))
+        }
+    ),
+    action!(
+        "cancel_settle",
+        "",
+        "",
+        "Ends settling now",
+        |device, _| {
+            device.0.settle_timer().cancel();
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "settled",
+        "",
+        "",
+        "Confirms the mount is still, with wait-for-stability on. Settling ends once the settle time is also up",
+        |device, _| {
+            device.0.settle_timer().confirm_stable();
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "target_rates",
+        "",
+        "JSON or null",
+        "The rates the target moves at",
+        |device, _| to_json(&device.0.get_target_rates().await)
+    ),
+    action!(
+        "set_target_rates",
+        "ra_rate,dec_rate",
+        "ra_rate,dec_rate",
+        "Sets the target's rates in RA seconds per second and arcseconds per second",
+        |device, parameters| {
+            // "ra_rate,dec_rate" in RA seconds per second and arcseconds per second
+            let [ra_rate, dec_rate] = parse_parameters(&parameters, "ra_rate,dec_rate")?;
+            device.0.set_target_rates(ra_rate, dec_rate).await?;
+            Ok(format_numbers(&[ra_rate, dec_rate]))
+        }
+    ),
+    action!(
+        "center_on",
+        "ra,dec,tolerance_arcsec",
+        "JSON",
+        "Slews to the coordinates and refines with plate solves reported through report_solved_position",
+        |device, parameters| {
+            let [ra, dec, tolerance] = parse_parameters(&parameters, "ra,dec,tolerance_arcsec")?;
+            to_json(&device.0.center_on(ra, dec, tolerance).await?)
+        }
+    ),
+    action!(
+        "report_solved_position",
+        "ra,dec",
+        "JSON",
+        "Reports where a plate solve found the scope pointing",
+        |device, parameters| {
+            let [ra, dec] = parse_parameters(&parameters, "ra,dec")?;
+            to_json(&device.0.report_solved_position(ra, dec).await?)
+        }
+    ),
+    action!(
+        "centering",
+        "",
+        "JSON",
+        "Progress of center_on",
+        |device, _| to_json(&device.0.get_centering().await?)
+    ),
+    action!(
+        "cancel_centering",
+        "",
+        "",
+        "Stops center_on",
+        |device, _| {
+            device.0.cancel_centering().await;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "tracking_limit",
+        "",
+        "JSON",
+        "How long tracking can go on before the west limit",
+        |device, _| to_json(&device.0.get_tracking_limit().await?)
+    ),
+    action!(
+        "pointing_conditions",
+        "[ra,dec]",
+        "JSON",
+        "Altitude, airmass, transit and limit times for the pointing or the given target",
+        |device, parameters| {
+            // The current pointing, or the target given as "ra,dec"
+            let target = match parameters.trim() {
+                "" => None,
+                _ => Some(parse_parameters(&parameters, "ra,dec")?),
+            };
+            let target = target.map(|[ra, dec]| (ra, dec));
+            to_json(&device.0.get_pointing_conditions(target).await?)
+        }
+    ),
+    action!(
+        "slew_progress",
+        "",
+        "JSON or null",
+        "How far the slew has got",
+        |device, _| to_json(&device.0.get_slew_progress().await?)
+    ),
+    action!(
+        "interrupted_slew",
+        "",
+        "JSON or null",
+        "The last slew if it didn't reach its target",
+        |device, _| to_json(&device.0.get_interrupted_slew())
+    ),
+    action!(
+        "slew_to_mech_ha",
+        "hours",
+        "",
+        "Slews RA to a mechanical hour angle, 0..24, e.g. for a flat panel or balancing. Stops tracking. Only the mount limits are checked",
+        |device, parameters| {
+            let [mech_ha] = parse_parameters(&parameters, "hours")?;
+            let _finish = device.0.slew_to_mech_ha(mech_ha).await?;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "resume_slew",
+        "",
+        "",
+        "Re-plans and restarts the interrupted slew",
+        |device, _| {
+            let _finish = device.0.resume_slew().await?;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "custom_tracking_rates",
+        "",
+        "JSON",
+        "Named rates from the config",
+        |device, _| to_json(device.0.get_custom_tracking_rates())
+    ),
+    action!(
+        "custom_tracking_rate",
+        "",
+        "JSON or null",
+        "The active named rate",
+        |device, _| to_json(&device.0.get_custom_tracking_rate().await)
+    ),
+    action!(
+        "set_custom_tracking_rate",
+        "name",
+        "JSON",
+        "Tracks at a named rate until TrackingRate is set",
+        |device, parameters| to_json(&device.0.set_custom_tracking_rate(parameters.trim()).await?)
+    ),
+    action!(
+        "catch_up",
+        "",
+        "ra,dec",
+        "Slews back to what was tracked before tracking stopped and tracks it",
+        |device, _| {
+            let (ra, dec) = device.0.catch_up().await?;
+            Ok(format_numbers(&[ra, dec]))
+        }
+    ),
+    action!(
+        "macros",
+        "",
+        "JSON",
+        "The configured macros",
+        |device, _| to_json(device.1.get_macros())
+    ),
+    action!(
+        "run_macro",
+        "name",
+        "JSON",
+        "Starts a macro",
+        |device, parameters| to_json(&device.1.start(device.clone(), parameters.trim())?)
+    ),
+    action!(
+        "macro_status",
+        "",
+        "JSON or null",
+        "The running or last macro",
+        |device, _| to_json(&device.1.get_run())
+    ),
+    action!(
+        "cancel_macro",
+        "",
+        "",
+        "Stops the running macro",
+        |device, _| {
+            device.1.cancel();
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "gps_location",
+        "",
+        "JSON",
+        "Updates the site from the GPS",
+        |device, _| to_json(&device.0.update_location_from_gps().await?)
+    ),
+    action!(
+        "pointing_model",
+        "",
+        "JSON",
+        "The pointing model's sync points and terms",
+        |device, _| to_json(&device.0.get_pointing_model().await)
+    ),
+    action!(
+        "reset_pointing_model",
+        "",
+        "",
+        "Clears the pointing model",
+        |device, _| {
+            device.0.reset_pointing_model().await;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "setup_config",
+        "",
+        "JSON",
+        "The settings on the setup page",
+        |device, _| to_json(&device.0.get_setup_config().await?)
+    ),
+    action!(
+        "store_setup_config",
+        "JSON",
+        "",
+        "Saves the settings from the setup page",
+        |device, parameters| {
+            let setup: SetupConfig = serde_json::from_str(&parameters)
+                .map_err(|e| ASCOMError::invalid_value(format_args!("Invalid setup: {}", e)))?;
+            device.0.store_setup_config(setup).await?;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "schedule",
+        "",
+        "JSON",
+        "Queued scheduled targets and the current one",
+        |device, _| to_json(&device.0.get_schedule())
+    ),
+    action!(
+        "schedule_target",
+        "JSON",
+        "JSON",
+        "Queues a target with name, right_ascension, declination and optionally priority, earliest_start and latest_start",
+        |device, parameters| {
+            let target: ScheduledTarget = serde_json::from_str(&parameters)
+                .map_err(|e| ASCOMError::invalid_value(format_args!("Invalid target: {}", e)))?;
+            to_json(&device.0.schedule_target(target)?)
+        }
+    ),
+    action!(
+        "unschedule_target",
+        "id",
+        "",
+        "Removes a queued target",
+        |device, parameters| {
+            let [id] = parse_parameters(&parameters, "id")?;
+            if !(id.is_finite() && 0. <= id && id.fract() == 0. && id <= u32::MAX as f64) {
+                return Err(ASCOMError::invalid_value(format_args!(
+                    "Invalid target id {}",
+                    parameters
+                )));
+            }
+            device.0.unschedule_target(id as u32)?;
+            Ok("".to_string())
+        }
+    ),
+    action!(
+        "clear_schedule",
+        "",
+        "",
+        "Removes every queued target",
+        |device, _| {
+            device.0.clear_schedule();
+            Ok("".to_string())
+        }
+    ),
+];
+
+pub const WORKFLOWS: &[Workflow] = &[Workflow {
+    name: "declination_knob",
+    description: "The Star Adventurer has no declination motor, so slews only move RA until someone turns the knob. Slewing stays true until then.",
+    steps: &[
+        "Start a slew as usual",
+        "Poll pending_declination_slew. Non-zero means the knob has to turn that many degrees",
+        "Show the user declination_slew_plan, which says which way and how far to turn it",
+        "Optionally report turns as they're done with report_declination_turns, so turns in the plan counts down",
+        "Once turned, run complete_declination_slew. Slewing goes false once the mount has settled",
+    ],
+}];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_action_names_unique() {
+        let names: HashSet<_> = ACTIONS.iter().map(|action| action.name).collect();
+        assert_eq!(names.len(), ACTIONS.len());
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("snapshot").unwrap().name, "snapshot");
+        assert!(find("no_such_action").is_none());
+    }
+}
@@ -0,0 +1,67 @@
+//! A status page with the basic controls, for when no ASCOM client is at hand
+use std::sync::Arc;
+
+use ascom_alpaca::ASCOMResult;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Json};
+use axum::routing::{get, post};
+use axum::Router;
+
+use crate::telescope_control::StarAdventurer;
+
+const INDEX_HTML: &str = include_str!("index.html");
+
+type ActionResult = Result<(), (StatusCode, String)>;
+
+/// The dashboard of one mount, for nesting in the Alpaca server
+pub fn router(sa: Arc<StarAdventurer>) -> Router {
+    Router::new()
+        .route("/", get(|| async { Html(INDEX_HTML) }))
+        .route("/status", get(status))
+        .route("/serial_health", get(serial_health))
+        .route("/park", post(park))
+        .route("/unpark", post(unpark))
+        .route("/abort", post(abort))
+        .route("/complete_declination_slew", post(complete_dec_slew))
+        .route("/report_declination_turns", post(report_dec_turns))
+        .with_state(sa)
+}
+
+async fn status(State(sa): State<Arc<StarAdventurer>>) -> impl IntoResponse {
+    Json(sa.get_snapshot().await)
+}
+
+async fn serial_health(State(sa): State<Arc<StarAdventurer>>) -> impl IntoResponse {
+    Json(sa.get_serial_health())
+}
+
+async fn park(State(sa): State<Arc<StarAdventurer>>) -> ActionResult {
+    to_action_result(sa.park().await)
+}
+
+async fn unpark(State(sa): State<Arc<StarAdventurer>>) -> ActionResult {
+    to_action_result(sa.unpark().await)
+}
+
+async fn abort(State(sa): State<Arc<StarAdventurer>>) -> ActionResult {
+    to_action_result(sa.abort_slew().await)
+}
+
+async fn complete_dec_slew(State(sa): State<Arc<StarAdventurer>>) -> ActionResult {
+    sa.complete_dec_slew().await;
+    Ok(())
+}
+
+async fn report_dec_turns(State(sa): State<Arc<StarAdventurer>>, turns: String) -> ActionResult {
+    let turns = turns
+        .trim()
+        .parse()
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid turns: {}", turns)))?;
+    to_action_result(sa.report_dec_turns(turns).await.map(|_| ()))
+}
+
+/// The page shows the message of a failed command
+fn to_action_result(result: ASCOMResult<()>) -> ActionResult {
+    result.map_err(|e| (StatusCode::CONFLICT, e.message.to_string()))
+}
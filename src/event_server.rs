@@ -0,0 +1,95 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::get;
+use axum::Router;
+use futures::Stream;
+use hyper::{Client, Uri};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::EventSettings;
+use crate::telescope_control::events::{EventBus, EventMessage};
+use crate::util::{parse_webhook_uri, post_json};
+
+/// Serves the event stream and delivers events to the configured webhooks
+pub fn start(settings: &EventSettings, events: EventBus) {
+    for url in &settings.webhooks {
+        match parse_webhook_uri(url) {
+            Ok(uri) => {
+                tokio::spawn(deliver_webhook(url.clone(), uri, events.clone()));
+            }
+            Err(e) => tracing::error!("Not delivering events: {}", e),
+        }
+    }
+
+    if let Some(addr) = settings.listen_addr {
+        tokio::spawn(serve(addr, events));
+    }
+}
+
+async fn serve(addr: SocketAddr, events: EventBus) {
+    let app = Router::new().route("/events", get(move || sse(events.clone())));
+
+    tracing::info!("Serving events on http://{}/events", addr);
+    if let Err(e) = axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await
+    {
+        tracing::error!("Event server stopped: {}", e);
+    }
+}
+
+async fn sse(events: EventBus) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = futures::stream::unfold(events.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(message) => {
+                    let event = Event::default()
+                        .event(event_name(&message))
+                        .json_data(&message)
+                        .unwrap();
+                    return Some((Ok(event), receiver));
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!("Event stream client missed {} events", missed)
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// The tag of the event, used as the SSE event type so clients can listen selectively
+fn event_name(message: &EventMessage) -> String {
+    serde_json::to_value(message).unwrap()["event"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+async fn deliver_webhook(url: String, uri: Uri, events: EventBus) {
+    let client = Client::new();
+    let mut receiver = events.subscribe();
+
+    loop {
+        let message = match receiver.recv().await {
+            Ok(message) => message,
+            Err(RecvError::Lagged(missed)) => {
+                tracing::warn!("Webhook {} missed {} events", url, missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let json = serde_json::to_string(&message).unwrap();
+        match tokio::time::timeout(Duration::from_secs(5), post_json(&client, &uri, json)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!("Webhook {} failed: {}", url, e),
+            Err(_) => tracing::warn!("Webhook {} timed out", url),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! INDI frontend, so KStars/Ekos and PHD2 can use the mount alongside Alpaca clients
+mod protocol;
+mod telescope;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::config::IndiSettings;
+use crate::telescope_control::StarAdventurer;
+use protocol::{message_xml, ClientMessage, MessageReader, PropertyState, Vector};
+use telescope::IndiTelescope;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serves every mount over INDI, each as its own device, if a listen address is configured
+pub fn start(settings: &IndiSettings, mounts: &[Arc<StarAdventurer>]) {
+    if let Some(addr) = settings.listen_addr {
+        let telescopes = mounts
+            .iter()
+            .enumerate()
+            .map(|(device_number, sa)| Arc::new(IndiTelescope::new(sa.clone(), device_number)))
+            .collect();
+        tokio::spawn(serve(addr, Arc::new(telescopes)));
+    }
+}
+
+async fn serve(addr: SocketAddr, telescopes: Arc<Vec<Arc<IndiTelescope>>>) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Couldn't start INDI server on {}: {}", addr, e);
+            return;
+        }
+    };
+
+    tracing::info!("Serving INDI on {}", addr);
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                tracing::info!("INDI client {} connected", peer);
+                tokio::spawn(handle_client(stream, telescopes.clone()));
+            }
+            Err(e) => tracing::warn!("Couldn't accept INDI client: {}", e),
+        }
+    }
+}
+
+async fn handle_client(stream: TcpStream, telescopes: Arc<Vec<Arc<IndiTelescope>>>) {
+    let (read, mut write) = stream.into_split();
+    let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+
+    let writer = tokio::spawn(async move {
+        while let Some(xml) = receiver.recv().await {
+            if write.write_all(xml.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    });
+    let mut pollers: Vec<Option<JoinHandle<()>>> = telescopes.iter().map(|_| None).collect();
+
+    let mut reader = MessageReader::new(BufReader::new(read));
+    loop {
+        match reader.next().await {
+            Ok(Some(ClientMessage::GetProperties { device })) => {
+                for (telescope, poller) in telescopes.iter().zip(&mut pollers) {
+                    if device
+                        .as_deref()
+                        .map_or(false, |device| device != telescope.name)
+                    {
+                        continue;
+                    }
+                    telescope.note_client_activity();
+                    match poller {
+                        Some(_) => {
+                            for vector in telescope.get_properties().await {
+                                let _ = sender.send(vector.to_def_xml(&telescope.name));
+                            }
+                        }
+                        None => {
+                            *poller = Some(tokio::spawn(poll(telescope.clone(), sender.clone())));
+                        }
+                    }
+                }
+            }
+            Ok(Some(ClientMessage::New(new))) => {
+                let telescope = match telescopes.iter().find(|t| t.name == new.device) {
+                    Some(telescope) => telescope.clone(),
+                    None => continue, // For another driver's device
+                };
+                telescope.note_client_activity();
+                // Pulses and parks take a while, so don't hold up the client's other commands
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let result = telescope.handle(&new).await;
+                    let mut vector = match telescope
+                        .get_properties()
+                        .await
+                        .into_iter()
+                        .find(|vector| vector.name == new.name)
+                    {
+                        Some(vector) => vector,
+                        None => return,
+                    };
+                    if let Err(e) = result {
+                        tracing::warn!("INDI {} failed: {}", new.name, e.message);
+                        let _ = sender.send(message_xml(&telescope.name, &e.message));
+                        vector.state = PropertyState::Alert;
+                    }
+                    let _ = sender.send(vector.to_set_xml(&telescope.name));
+                });
+            }
+            Ok(None) => break,
+            Err(e) => {
+                tracing::warn!("Dropping INDI client after bad XML: {}", e);
+                break;
+            }
+        }
+    }
+
+    tracing::info!("INDI client disconnected");
+    for poller in pollers.into_iter().flatten() {
+        poller.abort();
+    }
+    writer.abort();
+}
+
+/// Defines every property, then sends the ones that change
+async fn poll(telescope: Arc<IndiTelescope>, sender: mpsc::UnboundedSender<String>) {
+    let mut sent: Vec<Vector> = telescope.get_properties().await;
+    for vector in &sent {
+        if sender.send(vector.to_def_xml(&telescope.name)).is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let current = telescope.get_properties().await;
+        for (vector, previous) in current.iter().zip(&sent) {
+            if vector != previous && sender.send(vector.to_set_xml(&telescope.name)).is_err() {
+                return;
+            }
+        }
+        sent = current;
+    }
+}
@@ -0,0 +1,358 @@
+use std::fmt::Write;
+
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use tokio::io::AsyncBufRead;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PropertyState {
+    Idle,
+    Ok,
+    Busy,
+    Alert,
+}
+
+impl PropertyState {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Idle => "Idle",
+            Self::Ok => "Ok",
+            Self::Busy => "Busy",
+            Self::Alert => "Alert",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Permission {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl Permission {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::ReadOnly => "ro",
+            Self::ReadWrite => "rw",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SwitchRule {
+    OneOfMany,
+    AtMostOne,
+}
+
+impl SwitchRule {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::OneOfMany => "OneOfMany",
+            Self::AtMostOne => "AtMostOne",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Number {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub format: &'static str, // printf style, %m for sexagesimal
+    pub min: f64,
+    pub max: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Members {
+    Number(Vec<Number>),
+    Switch(SwitchRule, Vec<(&'static str, &'static str, bool)>), // Name, label, on
+    Text(Vec<(&'static str, &'static str, String)>),             // Name, label, text
+}
+
+/// An INDI property with its current values
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub group: &'static str,
+    pub permission: Permission,
+    pub state: PropertyState,
+    pub members: Members,
+}
+
+impl Vector {
+    fn kind(&self) -> &'static str {
+        match self.members {
+            Members::Number(_) => "Number",
+            Members::Switch(..) => "Switch",
+            Members::Text(_) => "Text",
+        }
+    }
+
+    /// The defXXXVector message sent in reply to getProperties
+    pub fn to_def_xml(&self, device: &str) -> String {
+        let mut xml = format!(
+            "<def{}Vector device=\"{}\" name=\"{}\" label=\"{}\" group=\"{}\" state=\"{}\" perm=\"{}\" timeout=\"60\"",
+            self.kind(),
+            escape(device),
+            self.name,
+            self.label,
+            self.group,
+            self.state.as_str(),
+            self.permission.as_str(),
+        );
+        if let Members::Switch(rule, _) = &self.members {
+            write!(xml, " rule=\"{}\"", rule.as_str()).unwrap();
+        }
+        xml.push_str(">\n");
+
+        match &self.members {
+            Members::Number(numbers) => {
+                for n in numbers {
+                    writeln!(
+                        xml,
+                        "<defNumber name=\"{}\" label=\"{}\" format=\"{}\" min=\"{}\" max=\"{}\" step=\"0\">{}</defNumber>",
+                        n.name, n.label, n.format, n.min, n.max, n.value
+                    )
+                    .unwrap();
+                }
+            }
+            Members::Switch(_, switches) => {
+                for (name, label, on) in switches {
+                    writeln!(
+                        xml,
+                        "<defSwitch name=\"{}\" label=\"{}\">{}</defSwitch>",
+                        name,
+                        label,
+                        switch_str(*on)
+                    )
+                    .unwrap();
+                }
+            }
+            Members::Text(texts) => {
+                for (name, label, text) in texts {
+                    writeln!(
+                        xml,
+                        "<defText name=\"{}\" label=\"{}\">{}</defText>",
+                        name,
+                        label,
+                        escape(text)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        writeln!(xml, "</def{}Vector>", self.kind()).unwrap();
+        xml
+    }
+
+    /// The setXXXVector message sent when the values or state change
+    pub fn to_set_xml(&self, device: &str) -> String {
+        let mut xml = format!(
+            "<set{}Vector device=\"{}\" name=\"{}\" state=\"{}\">\n",
+            self.kind(),
+            escape(device),
+            self.name,
+            self.state.as_str(),
+        );
+
+        match &self.members {
+            Members::Number(numbers) => {
+                for n in numbers {
+                    writeln!(
+                        xml,
+                        "<oneNumber name=\"{}\">{}</oneNumber>",
+                        n.name, n.value
+                    )
+                    .unwrap();
+                }
+            }
+            Members::Switch(_, switches) => {
+                for (name, _, on) in switches {
+                    writeln!(
+                        xml,
+                        "<oneSwitch name=\"{}\">{}</oneSwitch>",
+                        name,
+                        switch_str(*on)
+                    )
+                    .unwrap();
+                }
+            }
+            Members::Text(texts) => {
+                for (name, _, text) in texts {
+                    writeln!(xml, "<oneText name=\"{}\">{}</oneText>", name, escape(text)).unwrap();
+                }
+            }
+        }
+
+        writeln!(xml, "</set{}Vector>", self.kind()).unwrap();
+        xml
+    }
+}
+
+fn switch_str(on: bool) -> &'static str {
+    if on {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+/// A message shown in the client's log
+pub fn message_xml(device: &str, message: &str) -> String {
+    format!(
+        "<message device=\"{}\" timestamp=\"{}\" message=\"{}\"/>\n",
+        escape(device),
+        chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S"),
+        escape(message)
+    )
+}
+
+/// A newXXXVector from a client, with the members it changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewVector {
+    pub device: String,
+    pub name: String,
+    pub members: Vec<(String, String)>, // Name, value
+}
+
+impl NewVector {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.members
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_number(&self, name: &str) -> Option<f64> {
+        self.get(name).and_then(crate::util::parse_number)
+    }
+
+    pub fn is_on(&self, name: &str) -> bool {
+        self.get(name).map_or(false, |value| value.trim() == "On")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    GetProperties { device: Option<String> },
+    New(NewVector),
+}
+
+/// Reads the stream of top level elements a client sends
+pub struct MessageReader<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+}
+
+impl<R: AsyncBufRead + Unpin> MessageReader<R> {
+    pub fn new(inner: R) -> Self {
+        let mut reader = Reader::from_reader(inner);
+        reader.trim_text(true);
+        Self {
+            reader,
+            buf: Vec::new(),
+        }
+    }
+
+    /// The next message this server handles. None at the end of the stream.
+    pub async fn next(&mut self) -> quick_xml::Result<Option<ClientMessage>> {
+        let mut vector: Option<NewVector> = None;
+        let mut member: Option<(String, String)> = None;
+
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into_async(&mut self.buf).await? {
+                Event::Eof => return Ok(None),
+                Event::Empty(e) if e.name().as_ref() == b"getProperties" => {
+                    return Ok(Some(ClientMessage::GetProperties {
+                        device: attribute(&e, "device")?,
+                    }))
+                }
+                Event::Start(e) => match e.name().as_ref() {
+                    b"getProperties" => {
+                        return Ok(Some(ClientMessage::GetProperties {
+                            device: attribute(&e, "device")?,
+                        }))
+                    }
+                    b"newNumberVector" | b"newSwitchVector" | b"newTextVector" => {
+                        vector = Some(NewVector {
+                            device: attribute(&e, "device")?.unwrap_or_default(),
+                            name: attribute(&e, "name")?.unwrap_or_default(),
+                            members: Vec::new(),
+                        });
+                    }
+                    b"oneNumber" | b"oneSwitch" | b"oneText" => {
+                        member = Some((attribute(&e, "name")?.unwrap_or_default(), String::new()));
+                    }
+                    _ => {}
+                },
+                Event::Text(e) => {
+                    if let Some((_, value)) = member.as_mut() {
+                        value.push_str(&e.unescape()?);
+                    }
+                }
+                Event::End(e) => match e.name().as_ref() {
+                    b"oneNumber" | b"oneSwitch" | b"oneText" => {
+                        if let (Some(vector), Some(member)) = (vector.as_mut(), member.take()) {
+                            vector.members.push(member);
+                        }
+                    }
+                    b"newNumberVector" | b"newSwitchVector" | b"newTextVector" => {
+                        if let Some(vector) = vector.take() {
+                            return Ok(Some(ClientMessage::New(vector)));
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+fn attribute(e: &BytesStart, name: &str) -> quick_xml::Result<Option<String>> {
+    Ok(match e.try_get_attribute(name)? {
+        Some(attr) => Some(attr.unescape_value()?.into_owned()),
+        None => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_client_messages() {
+        let input = br#"<getProperties version="1.7"/>
+<newNumberVector device="Star Adventurer" name="EQUATORIAL_EOD_COORD">
+  <oneNumber name="RA">5.5</oneNumber>
+  <oneNumber name="DEC">-20</oneNumber>
+</newNumberVector>
+<enableBLOB device="Star Adventurer">Never</enableBLOB>
+<newSwitchVector device="Star Adventurer" name="TELESCOPE_PARK"><oneSwitch name="PARK">On</oneSwitch></newSwitchVector>"#;
+        let mut reader = MessageReader::new(&input[..]);
+
+        assert_eq!(
+            reader.next().await.unwrap(),
+            Some(ClientMessage::GetProperties { device: None })
+        );
+
+        let coords = match reader.next().await.unwrap() {
+            Some(ClientMessage::New(vector)) => vector,
+            other => panic!("Expected a new vector, got {:?}", other),
+        };
+        assert_eq!(coords.name, "EQUATORIAL_EOD_COORD");
+        assert_eq!(coords.get_number("RA"), Some(5.5));
+        assert_eq!(coords.get_number("DEC"), Some(-20.));
+
+        match reader.next().await.unwrap() {
+            Some(ClientMessage::New(vector)) => assert!(vector.is_on("PARK")),
+            other => panic!("Expected a new vector, got {:?}", other),
+        }
+        assert_eq!(reader.next().await.unwrap(), None);
+    }
+}
@@ -0,0 +1,550 @@
+use std::sync::{Arc, Mutex};
+
+use ascom_alpaca::api::{Axis, DriveRate, PutPulseGuideDirection, SideOfPier};
+use ascom_alpaca::{ASCOMError, ASCOMResult};
+
+use super::protocol::{Members, NewVector, Number, Permission, PropertyState, SwitchRule, Vector};
+use crate::astro_math;
+use crate::telescope_control::StarAdventurer;
+
+pub const DEVICE: &str = "Star Adventurer";
+
+const TELESCOPE_INTERFACE: u32 = 1;
+const GUIDER_INTERFACE: u32 = 4;
+
+/// What setting EQUATORIAL_EOD_COORD does
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CoordSet {
+    Track,
+    Slew,
+    Sync,
+}
+
+/// INDI slew rate switches and the move presets they use
+const SLEW_RATES: [(&str, &str, &str); 4] = [
+    ("SLEW_GUIDE", "Guide", "guide"),
+    ("SLEW_CENTERING", "Centering", "center"),
+    ("SLEW_FIND", "Find", "find"),
+    ("SLEW_MAX", "Max", "slew"),
+];
+
+/// The standard INDI telescope properties on top of the shared StarAdventurer
+pub struct IndiTelescope {
+    pub name: String, // The INDI device name
+    sa: Arc<StarAdventurer>,
+    coord_set: Mutex<CoordSet>,
+    slew_rate: Mutex<&'static str>, // Name of the SLEW_RATES switch that's on
+}
+
+/// The first mount keeps the plain name so single mount setups look the same as before
+fn device_name(device_number: usize) -> String {
+    match device_number {
+        0 => DEVICE.to_string(),
+        n => format!("{} {}", DEVICE, n),
+    }
+}
+
+fn invalid_value(property: &str) -> ASCOMError {
+    ASCOMError::invalid_value(format_args!("Missing or invalid value for {}", property))
+}
+
+impl IndiTelescope {
+    pub fn new(sa: Arc<StarAdventurer>, device_number: usize) -> Self {
+        Self {
+            name: device_name(device_number),
+            sa,
+            coord_set: Mutex::new(CoordSet::Track),
+            slew_rate: Mutex::new(SLEW_RATES[1].0),
+        }
+    }
+
+    /// A message from an INDI client counts as activity for the idle safety
+    pub fn note_client_activity(&self) {
+        self.sa.note_client_activity();
+    }
+
+    /// Every property with its current values
+    pub async fn get_properties(&self) -> Vec<Vector> {
+        let sa = &self.sa;
+        let connected = sa.is_connected().await;
+        // Properties that need the mount are Idle while disconnected and Alert if it can't be read
+        let state = |ok: bool| match (connected, ok) {
+            (false, _) => PropertyState::Idle,
+            (true, true) => PropertyState::Ok,
+            (true, false) => PropertyState::Alert,
+        };
+
+        let coords = match (sa.get_ra().await, sa.get_dec().await) {
+            (Ok(ra), Ok(dec)) => Some((ra, dec)),
+            _ => None,
+        };
+        let slewing = sa.is_slewing().await.unwrap_or(false);
+        let tracking = sa.is_tracking().await;
+        let parked = sa.is_parked().await;
+        let guiding = sa.is_pulse_guiding().await.unwrap_or(false);
+        let pier_side = sa.get_side_of_pier().await.ok();
+        let location = (
+            sa.get_latitude().await.unwrap_or_default(),
+            sa.get_longitude().await.unwrap_or_default(),
+            sa.get_elevation().await.unwrap_or_default(),
+        );
+        let sidereal = DriveRate::Sidereal.to_degrees();
+        let guide_rates = (
+            sa.get_guide_speed_fraction().await.unwrap_or_default(),
+            sa.get_guide_rate_declination().await.unwrap_or_default() / sidereal,
+        );
+        let coord_set = *self.coord_set.lock().unwrap();
+        let slew_rate = *self.slew_rate.lock().unwrap();
+        let guide_state = if guiding {
+            PropertyState::Busy
+        } else {
+            state(true)
+        };
+
+        vec![
+            Vector {
+                name: "CONNECTION",
+                label: "Connection",
+                group: "Main Control",
+                permission: Permission::ReadWrite,
+                state: PropertyState::Ok,
+                members: Members::Switch(
+                    SwitchRule::OneOfMany,
+                    vec![
+                        ("CONNECT", "Connect", connected),
+                        ("DISCONNECT", "Disconnect", !connected),
+                    ],
+                ),
+            },
+            Vector {
+                name: "DRIVER_INFO",
+                label: "Driver Info",
+                group: "General Info",
+                permission: Permission::ReadOnly,
+                state: PropertyState::Idle,
+                members: Members::Text(vec![
+                    ("DRIVER_NAME", "Name", DEVICE.to_string()),
+                    ("DRIVER_EXEC", "Exec", env!("CARGO_PKG_NAME").to_string()),
+                    (
+                        "DRIVER_VERSION",
+                        "Version",
+                        StarAdventurer::get_version_string(),
+                    ),
+                    (
+                        "DRIVER_INTERFACE",
+                        "Interface",
+                        (TELESCOPE_INTERFACE | GUIDER_INTERFACE).to_string(),
+                    ),
+                ]),
+            },
+            Vector {
+                name: "EQUATORIAL_EOD_COORD",
+                label: "Eq. Coordinates",
+                group: "Main Control",
+                permission: Permission::ReadWrite,
+                state: if slewing {
+                    PropertyState::Busy
+                } else {
+                    state(coords.is_some())
+                },
+                members: Members::Number(vec![
+                    Number {
+                        name: "RA",
+                        label: "RA (hh:mm:ss)",
+                        format: "%010.6m",
+                        min: 0.,
+                        max: 24.,
+                        value: coords.map_or(0., |(ra, _)| ra),
+                    },
+                    Number {
+                        name: "DEC",
+                        label: "DEC (dd:mm:ss)",
+                        format: "%010.6m",
+                        min: -90.,
+                        max: 90.,
+                        value: coords.map_or(0., |(_, dec)| dec),
+                    },
+                ]),
+            },
+            Vector {
+                name: "ON_COORD_SET",
+                label: "On Set",
+                group: "Main Control",
+                permission: Permission::ReadWrite,
+                state: PropertyState::Ok,
+                members: Members::Switch(
+                    SwitchRule::OneOfMany,
+                    vec![
+                        ("TRACK", "Track", coord_set == CoordSet::Track),
+                        ("SLEW", "Slew", coord_set == CoordSet::Slew),
+                        ("SYNC", "Sync", coord_set == CoordSet::Sync),
+                    ],
+                ),
+            },
+            Vector {
+                name: "TELESCOPE_ABORT_MOTION",
+                label: "Abort Motion",
+                group: "Main Control",
+                permission: Permission::ReadWrite,
+                state: state(true),
+                members: Members::Switch(SwitchRule::AtMostOne, vec![("ABORT", "Abort", false)]),
+            },
+            Vector {
+                name: "TELESCOPE_TRACK_STATE",
+                label: "Tracking",
+                group: "Main Control",
+                permission: Permission::ReadWrite,
+                state: state(tracking.is_ok()),
+                members: Members::Switch(
+                    SwitchRule::OneOfMany,
+                    vec![
+                        ("TRACK_ON", "On", *tracking.as_ref().unwrap_or(&false)),
+                        ("TRACK_OFF", "Off", !*tracking.as_ref().unwrap_or(&false)),
+                    ],
+                ),
+            },
+            Vector {
+                name: "TELESCOPE_PARK",
+                label: "Parking",
+                group: "Main Control",
+                permission: Permission::ReadWrite,
+                state: state(parked.is_ok()),
+                members: Members::Switch(
+                    SwitchRule::OneOfMany,
+                    vec![
+                        ("PARK", "Park", *parked.as_ref().unwrap_or(&false)),
+                        ("UNPARK", "UnPark", !*parked.as_ref().unwrap_or(&false)),
+                    ],
+                ),
+            },
+            Vector {
+                name: "TELESCOPE_SLEW_RATE",
+                label: "Slew Rate",
+                group: "Motion Control",
+                permission: Permission::ReadWrite,
+                state: PropertyState::Ok,
+                members: Members::Switch(
+                    SwitchRule::OneOfMany,
+                    SLEW_RATES
+                        .iter()
+                        .map(|&(name, label, _)| (name, label, name == slew_rate))
+                        .collect(),
+                ),
+            },
+            Vector {
+                name: "TELESCOPE_MOTION_WE",
+                label: "Motion W/E",
+                group: "Motion Control",
+                permission: Permission::ReadWrite,
+                state: state(true),
+                members: Members::Switch(
+                    SwitchRule::AtMostOne,
+                    vec![
+                        ("MOTION_WEST", "West", false),
+                        ("MOTION_EAST", "East", false),
+                    ],
+                ),
+            },
+            Vector {
+                name: "TELESCOPE_TIMED_GUIDE_NS",
+                label: "Guide N/S",
+                group: "Guide",
+                permission: Permission::ReadWrite,
+                state: guide_state,
+                members: Members::Number(vec![
+                    guide_pulse_number("TIMED_GUIDE_N", "North (ms)"),
+                    guide_pulse_number("TIMED_GUIDE_S", "South (ms)"),
+                ]),
+            },
+            Vector {
+                name: "TELESCOPE_TIMED_GUIDE_WE",
+                label: "Guide W/E",
+                group: "Guide",
+                permission: Permission::ReadWrite,
+                state: guide_state,
+                members: Members::Number(vec![
+                    guide_pulse_number("TIMED_GUIDE_W", "West (ms)"),
+                    guide_pulse_number("TIMED_GUIDE_E", "East (ms)"),
+                ]),
+            },
+            Vector {
+                name: "GUIDE_RATE",
+                label: "Guiding Rate",
+                group: "Guide",
+                permission: Permission::ReadWrite,
+                state: PropertyState::Ok,
+                members: Members::Number(vec![
+                    Number {
+                        name: "GUIDE_RATE_WE",
+                        label: "W/E Rate",
+                        format: "%g",
+                        min: 0.125,
+                        max: 1.,
+                        value: guide_rates.0,
+                    },
+                    Number {
+                        name: "GUIDE_RATE_NS",
+                        label: "N/S Rate",
+                        format: "%g",
+                        min: 0.,
+                        max: 1.,
+                        value: guide_rates.1,
+                    },
+                ]),
+            },
+            Vector {
+                name: "TELESCOPE_PIER_SIDE",
+                label: "Pier Side",
+                group: "Main Control",
+                permission: Permission::ReadOnly,
+                state: state(pier_side.is_some()),
+                members: Members::Switch(
+                    SwitchRule::AtMostOne,
+                    vec![
+                        ("PIER_WEST", "West", pier_side == Some(SideOfPier::West)),
+                        ("PIER_EAST", "East", pier_side == Some(SideOfPier::East)),
+                    ],
+                ),
+            },
+            Vector {
+                name: "GEOGRAPHIC_COORD",
+                label: "Location",
+                group: "Site Management",
+                permission: Permission::ReadWrite,
+                state: PropertyState::Ok,
+                members: Members::Number(vec![
+                    Number {
+                        name: "LAT",
+                        label: "Lat (dd:mm:ss)",
+                        format: "%010.6m",
+                        min: -90.,
+                        max: 90.,
+                        value: location.0,
+                    },
+                    Number {
+                        name: "LONG",
+                        label: "Lon (dd:mm:ss)",
+                        format: "%010.6m",
+                        min: 0.,
+                        max: 360.,
+                        value: astro_math::modulo(location.1, 360.), // INDI longitudes are 0..360 east
+                    },
+                    Number {
+                        name: "ELEV",
+                        label: "Elevation (m)",
+                        format: "%g",
+                        min: -300.,
+                        max: 10000.,
+                        value: location.2,
+                    },
+                ]),
+            },
+        ]
+    }
+
+    /// Applies a client's newXXXVector
+    pub async fn handle(&self, new: &NewVector) -> ASCOMResult<()> {
+        let sa = &self.sa;
+        match new.name.as_str() {
+            "CONNECTION" => {
+                if new.is_on("CONNECT") {
+                    sa.connect().await
+                } else if new.is_on("DISCONNECT") {
+                    sa.disconnect().await
+                } else {
+                    Ok(())
+                }
+            }
+            "EQUATORIAL_EOD_COORD" => {
+                let ra = new.get_number("RA").ok_or_else(|| invalid_value("RA"))?;
+                let dec = new.get_number("DEC").ok_or_else(|| invalid_value("DEC"))?;
+                let coord_set = *self.coord_set.lock().unwrap();
+                match coord_set {
+                    // Slews keep the tracking state, so a stopped mount has to be started once there
+                    CoordSet::Track => {
+                        sa.slew_to_coordinates(ra, dec).await?;
+                        sa.set_is_tracking(true).await
+                    }
+                    CoordSet::Slew => {
+                        let _slew = sa.slew_to_coordinates_async(ra, dec).await?;
+                        Ok(())
+                    }
+                    CoordSet::Sync => sa.sync_to_coordinates(ra, dec).await,
+                }
+            }
+            "ON_COORD_SET" => {
+                let coord_set = if new.is_on("SYNC") {
+                    CoordSet::Sync
+                } else if new.is_on("SLEW") {
+                    CoordSet::Slew
+                } else {
+                    CoordSet::Track
+                };
+                *self.coord_set.lock().unwrap() = coord_set;
+                Ok(())
+            }
+            "TELESCOPE_ABORT_MOTION" => sa.abort_slew().await,
+            "TELESCOPE_TRACK_STATE" => sa.set_is_tracking(new.is_on("TRACK_ON")).await,
+            "TELESCOPE_PARK" => {
+                if new.is_on("PARK") {
+                    sa.park().await
+                } else if new.is_on("UNPARK") {
+                    sa.unpark().await
+                } else {
+                    Ok(())
+                }
+            }
+            "TELESCOPE_SLEW_RATE" => {
+                if let Some(&(name, _, _)) = SLEW_RATES.iter().find(|(name, ..)| new.is_on(name)) {
+                    *self.slew_rate.lock().unwrap() = name;
+                }
+                Ok(())
+            }
+            "TELESCOPE_MOTION_WE" => {
+                let slew_rate = *self.slew_rate.lock().unwrap();
+                let preset = SLEW_RATES
+                    .iter()
+                    .find(|(name, ..)| *name == slew_rate)
+                    .unwrap()
+                    .2;
+                // West is with tracking
+                if new.is_on("MOTION_WEST") {
+                    sa.move_at_preset(preset.parse()?, false).await
+                } else if new.is_on("MOTION_EAST") {
+                    sa.move_at_preset(preset.parse()?, true).await
+                } else {
+                    sa.move_axis(Axis::Primary, 0.).await
+                }
+            }
+            "TELESCOPE_TIMED_GUIDE_NS" => {
+                self.guide(new, "TIMED_GUIDE_N", PutPulseGuideDirection::North)
+                    .await?;
+                self.guide(new, "TIMED_GUIDE_S", PutPulseGuideDirection::South)
+                    .await
+            }
+            "TELESCOPE_TIMED_GUIDE_WE" => {
+                self.guide(new, "TIMED_GUIDE_W", PutPulseGuideDirection::West)
+                    .await?;
+                self.guide(new, "TIMED_GUIDE_E", PutPulseGuideDirection::East)
+                    .await
+            }
+            "GUIDE_RATE" => match new.get_number("GUIDE_RATE_WE") {
+                Some(fraction) => sa.set_guide_speed_fraction(fraction).await.map(|_| ()),
+                None => Ok(()),
+            },
+            "GEOGRAPHIC_COORD" => {
+                if let Some(latitude) = new.get_number("LAT") {
+                    sa.set_latitude(latitude).await?;
+                }
+                if let Some(longitude) = new.get_number("LONG") {
+                    let longitude = if 180. < longitude {
+                        longitude - 360.
+                    } else {
+                        longitude
+                    };
+                    sa.set_longitude(longitude).await?;
+                }
+                if let Some(elevation) = new.get_number("ELEV") {
+                    sa.set_elevation(elevation).await?;
+                }
+                Ok(())
+            }
+            name => Err(ASCOMError::invalid_value(format_args!(
+                "Unknown property {}",
+                name
+            ))),
+        }
+    }
+
+    /// Sends the pulse if the client asked for one in this direction
+    async fn guide(
+        &self,
+        new: &NewVector,
+        member: &str,
+        direction: PutPulseGuideDirection,
+    ) -> ASCOMResult<()> {
+        match new.get_number(member) {
+            Some(millis) if 0. < millis => self.sa.pulse_guide(direction, millis as u32).await,
+            Some(_) | None => Ok(()),
+        }
+    }
+}
+
+fn guide_pulse_number(name: &'static str, label: &'static str) -> Number {
+    Number {
+        name,
+        label,
+        format: "%.f",
+        min: 0.,
+        max: 60000.,
+        value: 0.,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_float_eq::*;
+
+    use super::*;
+    use crate::telescope_control::test_util;
+
+    fn new_vector(name: &str, members: &[(&str, &str)]) -> NewVector {
+        NewVector {
+            device: DEVICE.to_string(),
+            name: name.to_string(),
+            members: members
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    async fn set_coords(telescope: &IndiTelescope, coord_set: &str, ra: f64, dec: f64) {
+        telescope
+            .handle(&new_vector("ON_COORD_SET", &[(coord_set, "On")]))
+            .await
+            .unwrap();
+        telescope
+            .handle(&new_vector(
+                "EQUATORIAL_EOD_COORD",
+                &[("RA", &ra.to_string()), ("DEC", &dec.to_string())],
+            ))
+            .await
+            .unwrap();
+    }
+
+    async fn create_stopped_telescope() -> IndiTelescope {
+        let sa = test_util::create_sa(None).await;
+        sa.sync_to_coordinates(0., 30.).await.unwrap();
+        sa.set_is_tracking(false).await.unwrap();
+        IndiTelescope::new(Arc::new(sa), 0)
+    }
+
+    #[tokio::test]
+    async fn test_track_starts_tracking() {
+        let telescope = create_stopped_telescope().await;
+        set_coords(&telescope, "TRACK", 23., 14.).await;
+        assert!(!telescope.sa.is_slewing().await.unwrap());
+        assert!(telescope.sa.is_tracking().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_slew_keeps_tracking_off() {
+        let telescope = create_stopped_telescope().await;
+        set_coords(&telescope, "SLEW", 23., 14.).await;
+        while telescope.sa.is_slewing().await.unwrap() {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert!(!telescope.sa.is_tracking().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_sync_moves_nothing() {
+        let telescope = create_stopped_telescope().await;
+        telescope.sa.set_is_tracking(true).await.unwrap();
+        set_coords(&telescope, "SYNC", 2., 40.).await;
+        assert!(!telescope.sa.is_slewing().await.unwrap());
+        assert_float_absolute_eq!(telescope.sa.get_ra().await.unwrap(), 2., 1E-4);
+        assert_float_absolute_eq!(telescope.sa.get_dec().await.unwrap(), 40., 1E-4);
+    }
+}
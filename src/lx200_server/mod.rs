@@ -0,0 +1,124 @@
+//! LX200 frontend, so SkySafari and other planetarium apps can point the mount without an Alpaca bridge
+mod protocol;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::config::Lx200Settings;
+use crate::telescope_control::StarAdventurer;
+use protocol::Command;
+
+const ACK: u8 = 0x06;
+/// Longer than any LX200 command, so a client that never sends '#' can't grow the buffer
+const MAX_COMMAND_LEN: usize = 32;
+
+/// Serves the telescope over LX200 if a listen address is configured
+pub fn start(settings: &Lx200Settings, sa: Arc<StarAdventurer>) {
+    if let Some(addr) = settings.listen_addr {
+        tokio::spawn(serve(addr, sa));
+    }
+}
+
+async fn serve(addr: SocketAddr, sa: Arc<StarAdventurer>) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Couldn't start LX200 server on {}: {}", addr, e);
+            return;
+        }
+    };
+
+    tracing::info!("Serving LX200 on {}", addr);
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                tracing::info!("LX200 client {} connected", peer);
+                tokio::spawn(handle_client(stream, sa.clone()));
+            }
+            Err(e) => tracing::warn!("Couldn't accept LX200 client: {}", e),
+        }
+    }
+}
+
+async fn handle_client(stream: TcpStream, sa: Arc<StarAdventurer>) {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+    let mut command: Option<String> = None; // Since the last ':'
+    while let Ok(byte) = reader.read_u8().await {
+        let reply = match byte {
+            // Asks for the alignment. Always polar.
+            ACK if command.is_none() => Some("P".to_string()),
+            b':' => {
+                command = Some(String::new());
+                None
+            }
+            b'#' => match command.take() {
+                Some(command) => {
+                    sa.note_client_activity();
+                    handle(&sa, Command::parse(&command)).await
+                }
+                None => None,
+            },
+            byte => {
+                // Anything outside a command is ignored
+                if let Some(pending) = &mut command {
+                    if pending.len() < MAX_COMMAND_LEN {
+                        pending.push(byte as char);
+                    } else {
+                        tracing::debug!("Dropped an overlong LX200 command");
+                        command = None;
+                    }
+                }
+                None
+            }
+        };
+        if let Some(reply) = reply {
+            if write.write_all(reply.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+    tracing::info!("LX200 client disconnected");
+}
+
+/// The reply, if the command has one
+async fn handle(sa: &StarAdventurer, command: Command) -> Option<String> {
+    let accepted = |result: ascom_alpaca::ASCOMResult<()>| {
+        Some(if result.is_ok() { "1" } else { "0" }.to_string())
+    };
+    match command {
+        // An empty reply would leave the app waiting, so report zeroes while disconnected
+        Command::GetRa => Some(protocol::format_ra(sa.get_ra().await.unwrap_or(0.))),
+        Command::GetDec => Some(protocol::format_dec(sa.get_dec().await.unwrap_or(0.))),
+        Command::SetTargetRa(ra) => accepted(sa.set_target_ra(ra).await),
+        Command::SetTargetDec(dec) => accepted(sa.set_target_dec(dec).await),
+        Command::SlewToTarget => Some(match sa.slew_to_target_async().await {
+            Ok(_finish) => "0".to_string(),
+            Err(e) => {
+                tracing::warn!("LX200 slew failed: {}", e.message);
+                format!("1{}#", e.message)
+            }
+        }),
+        Command::SyncToTarget => Some(match sa.sync_to_target().await {
+            Ok(()) => "Synced#".to_string(),
+            Err(e) => {
+                tracing::warn!("LX200 sync failed: {}", e.message);
+                format!("{}#", e.message)
+            }
+        }),
+        Command::Abort => {
+            if let Err(e) = sa.abort_slew().await {
+                tracing::warn!("LX200 abort failed: {}", e.message);
+            }
+            None
+        }
+        Command::Invalid(command) => {
+            tracing::debug!("Ignoring LX200 command :{}#", command);
+            // Sets expect a 0 for a bad value
+            command.starts_with('S').then(|| "0".to_string())
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::astro_math;
+use crate::util::*;
+
+/// The part of the LX200 command set planetarium apps need to point the mount
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    GetRa,
+    GetDec,
+    SetTargetRa(Hours),
+    SetTargetDec(Degrees),
+    SlewToTarget,
+    SyncToTarget,
+    Abort,
+    Invalid(String), // Unsupported, or a set with an unreadable value
+}
+
+impl Command {
+    /// From a command with the leading ':' and trailing '#' removed
+    pub fn parse(command: &str) -> Self {
+        let invalid = || Self::Invalid(command.to_string());
+        match command.get(..2).unwrap_or(command) {
+            "GR" => Self::GetRa,
+            "GD" => Self::GetDec,
+            "Sr" => parse_sexagesimal(&command[2..])
+                .filter(|ra| (0. ..24.).contains(ra))
+                .map_or_else(invalid, Self::SetTargetRa),
+            "Sd" => parse_sexagesimal(&command[2..])
+                .filter(|dec| (-90. ..=90.).contains(dec))
+                .map_or_else(invalid, Self::SetTargetDec),
+            "MS" => Self::SlewToTarget,
+            "CM" => Self::SyncToTarget,
+            "Q" | "Qe" | "Qw" | "Qn" | "Qs" => Self::Abort,
+            _ => invalid(),
+        }
+    }
+}
+
+/// "HH:MM:SS", "HH:MM.T", "sDD*MM:SS" or "sDD*MM", with any of the separators apps use
+fn parse_sexagesimal(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1., value),
+        None => (1., value.strip_prefix('+').unwrap_or(value)),
+    };
+    let parts = value
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let (degrees, minutes, seconds) = match parts[..] {
+        [degrees, minutes] => (degrees, minutes, 0.),
+        [degrees, minutes, seconds] => (degrees, minutes, seconds),
+        _ => return None,
+    };
+    if 60. <= minutes || 60. <= seconds {
+        return None;
+    }
+    Some(sign * (degrees + minutes / 60. + seconds / 3600.))
+}
+
+/// Whole seconds, carrying into the minutes and hours/degrees
+fn split_sexagesimal(value: f64) -> (u32, u32, u32) {
+    let total = (value.abs() * 3600.).round() as u32;
+    (total / 3600, total / 60 % 60, total % 60)
+}
+
+pub fn format_ra(ra: Hours) -> String {
+    let (h, m, s) = split_sexagesimal(astro_math::modulo(ra, 24.));
+    format!("{:02}:{:02}:{:02}#", h % 24, m, s)
+}
+
+pub fn format_dec(dec: Degrees) -> String {
+    let (d, m, s) = split_sexagesimal(dec);
+    let sign = if dec < 0. { '-' } else { '+' };
+    format!("{}{:02}*{:02}:{:02}#", sign, d, m, s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Command::parse("GR"), Command::GetRa);
+        assert_eq!(Command::parse("Q"), Command::Abort);
+        match Command::parse("Sr 12:30:36") {
+            Command::SetTargetRa(ra) => assert_float_absolute_eq!(ra, 12.51),
+            command => panic!("{:?}", command),
+        }
+        match Command::parse("Sr12:30.6") {
+            Command::SetTargetRa(ra) => assert_float_absolute_eq!(ra, 12.51),
+            command => panic!("{:?}", command),
+        }
+        match Command::parse("Sd -05*30:00") {
+            Command::SetTargetDec(dec) => assert_float_absolute_eq!(dec, -5.5),
+            command => panic!("{:?}", command),
+        }
+        assert!(matches!(Command::parse("Sd+95*00"), Command::Invalid(_)));
+        assert!(matches!(Command::parse("GVP"), Command::Invalid(_)));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_ra(12.51), "12:30:36#");
+        assert_eq!(format_ra(23.99999), "00:00:00#");
+        assert_eq!(format_dec(-5.5), "-05*30:00#");
+        assert_eq!(format_dec(89.99999), "+90*00:00#");
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ascom_alpaca::api::Device;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use crate::alpaca_state::AlpacaDevice;
+use crate::config::MacroStep;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum MacroState {
+    Running,
+    Completed,
+    Failed { error: String },
+    Cancelled,
+}
+
+/// Progress of the running or last macro
+#[derive(Debug, Clone, Serialize)]
+pub struct MacroRun {
+    pub name: String,
+    pub started: DateTime<Utc>,
+    pub step: usize, // Index of the running step, or the failed one
+    pub steps: usize,
+    pub current_step: Option<MacroStep>,
+    #[serde(flatten)]
+    pub state: MacroState,
+}
+
+/// Runs the macros from the config server-side, one at a time
+#[derive(Debug, Default)]
+pub struct MacroRunner {
+    macros: BTreeMap<String, Vec<MacroStep>>,
+    run: Arc<Mutex<Option<MacroRun>>>, // Shared with the running macro
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl MacroRunner {
+    pub fn new(macros: BTreeMap<String, Vec<MacroStep>>) -> Self {
+        Self {
+            macros,
+            ..Default::default()
+        }
+    }
+
+    pub fn has_macro(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+    }
+
+    pub fn get_macros(&self) -> &BTreeMap<String, Vec<MacroStep>> {
+        &self.macros
+    }
+
+    pub fn get_run(&self) -> Option<MacroRun> {
+        self.run.lock().unwrap().clone()
+    }
+
+    /// Starts the macro in the background. Its steps go through the device like client requests.
+    pub fn start(&self, device: AlpacaDevice, name: &str) -> ASCOMResult<MacroRun> {
+        let steps = self.macros.get(name).cloned().ok_or_else(|| {
+            ASCOMError::invalid_value(format_args!("Unknown macro: \"{}\"", name))
+        })?;
+
+        let mut run_lock = self.run.lock().unwrap();
+        if let Some(run) = run_lock
+            .as_ref()
+            .filter(|run| run.state == MacroState::Running)
+        {
+            return Err(ASCOMError::new(
+                ASCOMErrorCode::new_for_driver(7),
+                format_args!("Macro \"{}\" is already running", run.name),
+            ));
+        }
+
+        let run = MacroRun {
+            name: name.to_string(),
+            started: Utc::now(),
+            step: 0,
+            steps: steps.len(),
+            current_step: steps.first().cloned(),
+            state: MacroState::Running,
+        };
+        *run_lock = Some(run.clone());
+        drop(run_lock);
+
+        tracing::info!("Running macro \"{}\"", name);
+        let shared_run = self.run.clone();
+        let task = tokio::spawn(async move {
+            for (i, step) in steps.iter().enumerate() {
+                if let Some(run) = shared_run.lock().unwrap().as_mut() {
+                    run.step = i;
+                    run.current_step = Some(step.clone());
+                }
+                if let Err(e) = run_step(&device, step).await {
+                    tracing::warn!("Macro step {:?} failed: {}", step, e.message);
+                    if let Some(run) = shared_run.lock().unwrap().as_mut() {
+                        run.state = MacroState::Failed {
+                            error: e.message.to_string(),
+                        };
+                    }
+                    return;
+                }
+            }
+            if let Some(run) = shared_run.lock().unwrap().as_mut() {
+                run.current_step = None;
+                run.state = MacroState::Completed;
+            }
+        });
+        *self.task.lock().unwrap() = Some(task);
+
+        Ok(run)
+    }
+
+    /// Stops the macro between steps. A slew or park it started keeps going.
+    pub fn cancel(&self) {
+        if let Some(task) = self.task.lock().unwrap().take() {
+            task.abort();
+        }
+        if let Some(run) = self
+            .run
+            .lock()
+            .unwrap()
+            .as_mut()
+            .filter(|run| run.state == MacroState::Running)
+        {
+            run.state = MacroState::Cancelled;
+        }
+    }
+}
+
+async fn run_step(device: &AlpacaDevice, step: &MacroStep) -> ASCOMResult<()> {
+    let sa = &device.0;
+    match step {
+        MacroStep::Unpark => sa.unpark().await,
+        MacroStep::Park => sa.park().await,
+        MacroStep::Tracking { enabled } => sa.set_is_tracking(*enabled).await,
+        MacroStep::SlewToTarget => sa.slew_to_target().await,
+        MacroStep::AbortSlew => sa.abort_slew().await,
+        MacroStep::Wait { seconds } => {
+            // Checked when the config loads, but a bad wait mustn't leave the macro running forever
+            let duration = Duration::try_from_secs_f64(*seconds).map_err(|_| {
+                ASCOMError::invalid_value(format_args!("Can't wait {} seconds", seconds))
+            })?;
+            tokio::time::sleep(duration).await;
+            Ok(())
+        }
+        MacroStep::Action { action, parameters } => device
+            .action(action.clone(), parameters.clone())
+            .await
+            .map(|_| ()),
+    }
+}
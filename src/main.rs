@@ -1,29 +1,162 @@
+mod alpaca_server;
 mod alpaca_state;
 mod astro_math;
+mod cli;
+mod client_traffic;
 pub mod config;
+mod custom_actions;
+mod dashboard;
+mod event_server;
+mod indi_server;
+mod lx200_server;
+mod macros;
+mod notifications;
 mod telescope_control;
 mod util;
 
+use alpaca_state::{AlpacaDevice, DeviceIdentity};
 use ascom_alpaca::api::CargoServerInfo;
 use ascom_alpaca::Server;
-use config::Config;
+use clap::Parser;
+use cli::{Cli, Command};
+use config::{Config, ParkStore};
+use macros::MacroRunner;
 use net_literals::addr;
+use std::sync::Arc;
+use telescope_control::events::EventBus;
 use telescope_control::StarAdventurer;
 use util::*;
 
+pub const CONFIG_PATH: &str = "config.toml";
+pub const STATE_PATH: &str = "state.toml"; // What the driver saves, such as the park position
+
 #[tokio::main]
-async fn main() -> eyre::Result<std::convert::Infallible> {
+async fn main() -> eyre::Result<()> {
     tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+    if let Some(Command::Replay {
+        trace,
+        port,
+        mount_code,
+        speed,
+    }) = cli.command
+    {
+        return cli::replay(trace, port, mount_code, speed).await;
+    }
+
+    let mut config = Config::load(CONFIG_PATH)
+        .map_err(|e| eyre::eyre!("Couldn't load the configuration: {}", e))?;
+    if let Some(trace_file) = &cli.trace_motor {
+        tracing::info!("Tracing motor commands to {}", trace_file);
+        config.com.trace_file = Some(trace_file.clone());
+        for mount in &mut config.mounts {
+            if let Some(com) = &mut mount.com {
+                com.trace_file = Some(trace_file.clone());
+            }
+        }
+    }
+
+    match cli.command {
+        None | Some(Command::Serve) => serve(config).await,
+        Some(_) => cli::run(&cli, config).await,
+    }
+}
 
-    let config = confy::load_path("config.toml").expect("Couldn't parse configuration");
-    let sa = StarAdventurer::new(&config).await;
+async fn serve(config: Config) -> eyre::Result<()> {
+    if let Some(path) = &config.time.leap_second_file {
+        match astro_math::leap_seconds::load_file(path) {
+            Ok(count) => tracing::info!("Loaded {} leap seconds from {}", count, path),
+            Err(e) => tracing::warn!("Using the bundled leap seconds: {}", e),
+        }
+    }
+    telescope_control::clock_monitor::check_system_clock();
 
     let mut server = Server {
         info: CargoServerInfo!(),
         listen_addr: addr!("127.0.0.1:8000"),
         ..Default::default()
     };
-    server.devices.register(sa);
 
-    server.start().await
+    let mount_configs = config.mount_configs();
+    let mut mounts = Vec::new();
+    if 1 < mount_configs
+        .iter()
+        .filter(|(_, c)| c.com.path.is_none())
+        .count()
+    {
+        tracing::warn!(
+            "More than one mount finds its serial port automatically. They may pick the same one."
+        );
+    }
+    for (device_number, (name, mut mount_config)) in mount_configs.into_iter().enumerate() {
+        let park_store = ParkStore {
+            config_path: CONFIG_PATH.to_string(),
+            state_path: STATE_PATH.to_string(),
+            mount: device_number,
+        };
+        park_store
+            .load_state()
+            .map_err(|e| eyre::eyre!("Couldn't load the saved state: {}", e))?
+            .apply(&mut mount_config);
+        let sa = Arc::new(
+            StarAdventurer::new(&mount_config)
+                .await
+                .with_park_store(park_store.clone()),
+        );
+        telescope_control::limit_monitor::start(sa.clone());
+        telescope_control::motion_limit_monitor::start(sa.clone());
+        telescope_control::horizon_monitor::start(sa.clone());
+        telescope_control::tracking_rate_monitor::start(sa.clone());
+        telescope_control::scheduler::start(sa.clone());
+        telescope_control::gps::start(sa.clone());
+        telescope_control::idle_watchdog::start(sa.clone());
+        telescope_control::stability_webhook::start(&mount_config.settle, sa.clone());
+        notifications::start(&mount_config.notifications, sa.events());
+        telescope_control::config_watcher::start(sa.clone(), park_store);
+        // LX200 has no device numbers, so each mount has its own port
+        lx200_server::start(&mount_config.lx200, sa.clone());
+
+        let macros = Arc::new(MacroRunner::new(config.macros.clone()));
+        let identity = Arc::new(DeviceIdentity::for_mount(device_number, name));
+        tracing::info!("Registering {} as device {}", identity.name, device_number);
+        mounts.push(sa.clone());
+        server.devices.register(AlpacaDevice(sa, macros, identity));
+    }
+
+    event_server::start(
+        &config.events,
+        EventBus::fan_in(mounts.iter().map(|sa| sa.events()).collect()),
+    );
+    indi_server::start(&config.indi, &mounts);
+
+    tokio::select! {
+        result = alpaca_server::serve(server, mounts.clone(), &config) => match result? {},
+        () = shutdown_signal() => {}
+    }
+    tracing::info!("Shutting down");
+    futures::future::join_all(mounts.iter().map(|sa| sa.shutdown())).await;
+    Ok(())
+}
+
+/// Ctrl-C, or SIGTERM on unix
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Can't handle SIGTERM: {}", e);
+                std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        () = terminate => {}
+    }
 }
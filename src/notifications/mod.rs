@@ -0,0 +1,266 @@
+//! Tells someone when the mount needs a hand or has run into trouble, for when nobody is watching the log
+
+mod mqtt;
+
+use std::time::Duration;
+
+use hyper::Client;
+use serde::Serialize;
+use tokio::process::Command;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::NotificationSettings;
+use crate::telescope_control::events::{Event, EventBus};
+use crate::util::{parse_webhook_uri, post_json};
+
+const SINK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    ActionRequired, // Someone has to do something at the mount
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub title: String,
+    pub message: String,
+}
+
+impl Notification {
+    fn action_required(title: &str, message: String) -> Self {
+        Self {
+            kind: NotificationKind::ActionRequired,
+            title: title.to_string(),
+            message,
+        }
+    }
+
+    fn error(title: &str, message: String) -> Self {
+        Self {
+            kind: NotificationKind::Error,
+            title: title.to_string(),
+            message,
+        }
+    }
+
+    /// None for events that don't need anyone
+    pub fn for_event(event: &Event) -> Option<Self> {
+        let tracking_note = |stopped| if stopped { " Tracking stopped." } else { "" };
+        Some(match event {
+            Event::DeclinationSlewRequired { plan } => {
+                Self::action_required("Turn the declination knob", plan.to_string())
+            }
+            Event::TrackingLimitReached {
+                hour_angle_past,
+                stopped_tracking,
+            } => Self::action_required(
+                "Meridian flip needed",
+                format!(
+                    "Tracking is {:.0} minutes past the flip point.{}",
+                    hour_angle_past * 60.,
+                    tracking_note(*stopped_tracking)
+                ),
+            ),
+            Event::ConnectionLost { reason } => {
+                Self::error("Lost the mount", format!("Disconnected: {}", reason))
+            }
+            Event::UnexpectedGotoStopped => Self::error(
+                "Unexpected goto stopped",
+                "Something else started a goto on the controller. Tracking is off.".to_string(),
+            ),
+            Event::RunawayMotionStopped {
+                commanded_rate,
+                reported_rate,
+            } => Self::error(
+                "Runaway motor stopped",
+                format!(
+                    "The motor ran at {:.5}°/s instead of {:.5}°/s. Tracking is off.",
+                    reported_rate, commanded_rate
+                ),
+            ),
+            Event::MountLimitReached {
+                mech_hour_angle,
+                stopped,
+            } => Self::error(
+                "Mount limit reached",
+                format!(
+                    "Stopped {} at mechanical hour angle {:.2}h",
+                    stopped.replace('_', " "),
+                    mech_hour_angle
+                ),
+            ),
+            Event::BelowHorizon {
+                altitude,
+                azimuth,
+                stopped_tracking,
+                ..
+            } => Self::error(
+                "Below the horizon",
+                format!(
+                    "Pointing at altitude {:.1}°, azimuth {:.1}°.{}",
+                    altitude,
+                    azimuth,
+                    tracking_note(*stopped_tracking)
+                ),
+            ),
+            Event::ScheduledTargetFailed { name, reason } => {
+                Self::error("Scheduled target failed", format!("{}: {}", name, reason))
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// Sends notifications for the mount's events to every configured sink
+pub fn start(settings: &NotificationSettings, events: EventBus) {
+    if !settings.desktop
+        && settings.webhooks.is_empty()
+        && settings.commands.is_empty()
+        && settings.mqtt.is_none()
+    {
+        return;
+    }
+    let settings = settings.clone();
+    let mut receiver = events.subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            let message = match receiver.recv().await {
+                Ok(message) => message,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!("Notifications missed {} events", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            let notification = match Notification::for_event(&message.event) {
+                Some(n) if n.kind == NotificationKind::Error && !settings.errors => continue,
+                Some(n) => n,
+                None => continue,
+            };
+            // Each on its own, so a slow sink can't hold up the next notification
+            let settings = settings.clone();
+            tokio::spawn(async move { notify(&settings, &notification).await });
+        }
+    });
+}
+
+async fn notify(settings: &NotificationSettings, notification: &Notification) {
+    let json = serde_json::to_string(notification).unwrap();
+    let mut sinks: Vec<(String, SinkFuture)> = Vec::new();
+    if settings.desktop {
+        sinks.push(("desktop".to_string(), run(desktop_command(notification))));
+    }
+    for command in &settings.commands {
+        sinks.push((command.clone(), run(shell_command(command, notification))));
+    }
+    for url in &settings.webhooks {
+        sinks.push((url.clone(), Box::pin(post(url.clone(), json.clone()))));
+    }
+    if let Some(mqtt) = &settings.mqtt {
+        sinks.push((
+            format!("mqtt {}", mqtt.topic),
+            Box::pin(mqtt::publish(mqtt.clone(), json.clone().into_bytes())),
+        ));
+    }
+
+    let results = futures::future::join_all(sinks.into_iter().map(|(name, sink)| async move {
+        match tokio::time::timeout(SINK_TIMEOUT, sink).await {
+            Ok(result) => (name, result),
+            Err(_) => (name, Err("timed out".to_string())),
+        }
+    }))
+    .await;
+    for (name, result) in results {
+        if let Err(e) = result {
+            tracing::warn!("Couldn't notify with {}: {}", name, e);
+        }
+    }
+}
+
+type SinkFuture = std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>>;
+
+fn run(mut command: Command) -> SinkFuture {
+    // Killed if it outlasts the sink timeout, rather than left running
+    command.kill_on_drop(true);
+    Box::pin(async move {
+        let status = command.status().await.map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("exited with {}", status));
+        }
+        Ok(())
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn desktop_command(notification: &Notification) -> Command {
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!(
+        "display notification {:?} with title {:?}",
+        notification.message, notification.title
+    ));
+    command
+}
+
+#[cfg(not(target_os = "macos"))]
+fn desktop_command(notification: &Notification) -> Command {
+    let urgency = match notification.kind {
+        NotificationKind::ActionRequired => "critical",
+        NotificationKind::Error => "normal",
+    };
+    let mut command = Command::new("notify-send");
+    command
+        .args(["-u", urgency])
+        .arg(&notification.title)
+        .arg(&notification.message);
+    command
+}
+
+fn shell_command(line: &str, notification: &Notification) -> Command {
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(line);
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(line);
+        command
+    };
+    command
+        .env(
+            "NOTIFY_KIND",
+            serde_json::to_value(notification.kind)
+                .unwrap()
+                .as_str()
+                .unwrap(),
+        )
+        .env("NOTIFY_TITLE", &notification.title)
+        .env("NOTIFY_MESSAGE", &notification.message);
+    command
+}
+
+async fn post(url: String, json: String) -> Result<(), String> {
+    post_json(&Client::new(), &parse_webhook_uri(&url)?, json).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_event() {
+        let notification = Notification::for_event(&Event::ConnectionLost {
+            reason: "Serial timeout".to_string(),
+        })
+        .unwrap();
+        assert_eq!(notification.kind, NotificationKind::Error);
+        assert!(notification.message.contains("Serial timeout"));
+        assert!(Notification::for_event(&Event::ParkCompleted).is_none());
+    }
+}
@@ -0,0 +1,106 @@
+//! Just enough MQTT 3.1.1 to publish a message at QoS 0. A connection is made for each, since they're rare.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config::MqttSettings;
+
+const KEEP_ALIVE_SECS: u16 = 60;
+
+pub async fn publish(settings: MqttSettings, payload: Vec<u8>) -> Result<(), String> {
+    let mut stream = TcpStream::connect(&settings.broker)
+        .await
+        .map_err(|e| e.to_string())?;
+    let io = |e: std::io::Error| e.to_string();
+
+    stream
+        .write_all(&connect_packet(&settings))
+        .await
+        .map_err(io)?;
+    let mut connack = [0; 4];
+    stream.read_exact(&mut connack).await.map_err(io)?;
+    if connack[0] != 0x20 {
+        return Err("broker didn't acknowledge the connection".to_string());
+    }
+    if connack[3] != 0 {
+        return Err(format!(
+            "broker refused the connection with code {}",
+            connack[3]
+        ));
+    }
+
+    stream
+        .write_all(&publish_packet(&settings.topic, &payload))
+        .await
+        .map_err(io)?;
+    stream.write_all(&[0xE0, 0]).await.map_err(io)?; // DISCONNECT
+    Ok(())
+}
+
+fn connect_packet(settings: &MqttSettings) -> Vec<u8> {
+    let mut flags = 0x02; // Clean session
+    let mut body = Vec::new();
+    put_string(&mut body, "MQTT");
+    body.push(4); // Protocol level 3.1.1
+    let flags_at = body.len();
+    body.push(0);
+    body.extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes());
+    put_string(&mut body, &settings.client_id);
+    if let Some(username) = &settings.username {
+        flags |= 0x80;
+        put_string(&mut body, username);
+    }
+    if let Some(password) = &settings.password {
+        flags |= 0x40;
+        put_string(&mut body, password);
+    }
+    body[flags_at] = flags;
+    packet(0x10, body)
+}
+
+fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_string(&mut body, topic);
+    body.extend_from_slice(payload);
+    packet(0x30, body)
+}
+
+fn packet(header: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut remaining = body.len();
+    loop {
+        let mut byte = (remaining % 128) as u8;
+        remaining /= 128;
+        if 0 < remaining {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if remaining == 0 {
+            break;
+        }
+    }
+    packet.extend(body);
+    packet
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packets() {
+        assert_eq!(
+            publish_packet("a/b", b"hi"),
+            [0x30, 7, 0, 3, b'a', b'/', b'b', b'h', b'i']
+        );
+        // Remaining lengths past 127 take another byte
+        let long = publish_packet("t", &[0; 200]);
+        assert_eq!(&long[..3], [0x30, 0xCB, 0x01]);
+        assert_eq!(long.len(), 3 + 203);
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::astro_math::leap_seconds;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const WARNING_INTERVAL: Duration = Duration::from_secs(60);
+const JUMP_THRESHOLD_MILLIS: i64 = 1000;
+// A Pi without an RTC boots in 1970 until NTP syncs
+const EARLIEST_PLAUSIBLE_YEAR: i32 = 2023;
+
+/// A step in the system clock, e.g. from NTP
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct ClockJump {
+    pub detected_at: DateTime<Utc>,
+    pub jump_millis: i64, // Positive if the clock jumped forward
+}
+
+/// Watches the system clock for steps and absorbs them into the date offset.
+/// The pointing model was synced against the old clock, so the sky time has to stay continuous
+/// or every reported coordinate shifts by the size of the step.
+#[derive(Clone)]
+pub struct ClockMonitor {
+    last_jump: Arc<Mutex<Option<ClockJump>>>,
+}
+
+impl ClockMonitor {
+    /// Stops by itself once the date offset is dropped
+    pub fn start(date_offset: &Arc<RwLock<chrono::Duration>>) -> Self {
+        let monitor = Self {
+            last_jump: Arc::new(Mutex::new(None)),
+        };
+        tokio::spawn(monitor.clone().run(Arc::downgrade(date_offset)));
+        monitor
+    }
+
+    pub fn last_jump(&self) -> Option<ClockJump> {
+        *self.last_jump.lock().unwrap()
+    }
+
+    async fn run(self, date_offset: Weak<RwLock<chrono::Duration>>) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let start = Instant::now();
+        let mut last_boot = boot_clock(start);
+        let mut last_utc = Utc::now();
+        let mut last_warning: Option<Instant> = None;
+        let mut unreported_jumps = 0;
+
+        loop {
+            interval.tick().await;
+            let date_offset = match date_offset.upgrade() {
+                Some(date_offset) => date_offset,
+                None => return,
+            };
+
+            let now_instant = Instant::now();
+            let now_boot = boot_clock(start);
+            let now_utc = Utc::now();
+            let elapsed = chrono::Duration::from_std(now_boot.saturating_sub(last_boot)).unwrap();
+            let jump = (now_utc - last_utc) - elapsed;
+            last_boot = now_boot;
+            last_utc = now_utc;
+
+            if jump.num_milliseconds().abs() < JUMP_THRESHOLD_MILLIS {
+                continue;
+            }
+
+            {
+                let mut lock = date_offset.write().await;
+                *lock = *lock - jump;
+            }
+
+            *self.last_jump.lock().unwrap() = Some(ClockJump {
+                detected_at: now_utc,
+                jump_millis: jump.num_milliseconds(),
+            });
+
+            // NTP can step repeatedly while it settles so don't flood the log
+            unreported_jumps += 1;
+            if last_warning.map_or(true, |t| WARNING_INTERVAL <= now_instant - t) {
+                tracing::warn!(
+                    "System clock jumped by {}ms ({} jump(s) since the last warning). Compensated in the date offset.",
+                    jump.num_milliseconds(),
+                    unreported_jumps
+                );
+                last_warning = Some(now_instant);
+                unreported_jumps = 0;
+            }
+        }
+    }
+}
+
+/// Time since boot including any time suspended, so a resume isn't taken for a clock step.
+/// Instant stops while suspended. It's only used where there's no /proc/uptime.
+fn boot_clock(start: Instant) -> Duration {
+    std::fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|uptime| uptime.split_whitespace().next()?.parse().ok())
+        .map(Duration::from_secs_f64)
+        .unwrap_or_else(|| start.elapsed())
+}
+
+/// Rejects dates from a clock that was never set
+pub fn check_plausible_date(time: DateTime<Utc>) -> Result<(), String> {
+    let earliest = Utc
+        .with_ymd_and_hms(EARLIEST_PLAUSIBLE_YEAR, 1, 1, 0, 0, 0)
+        .unwrap();
+    if time < earliest {
+        return Err(format!(
+            "{} is before {}. The clock is probably unset.",
+            time.to_rfc3339(),
+            EARLIEST_PLAUSIBLE_YEAR
+        ));
+    }
+    Ok(())
+}
+
+/// Warns at startup about a system clock or leap second table that can't be trusted
+pub fn check_system_clock() {
+    let now = Utc::now();
+    if let Err(e) = check_plausible_date(now) {
+        tracing::warn!("System clock: {} Set the date before syncing.", e);
+    }
+    if let Some(expires) = leap_seconds::expires().filter(|expires| *expires < now) {
+        tracing::warn!(
+            "The leap second table expired on {}. Sidereal time may be off by a second.",
+            expires.date_naive()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plausible_date() {
+        assert!(check_plausible_date(Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 12).unwrap()).is_err());
+        assert!(check_plausible_date(Utc.with_ymd_and_hms(2024, 5, 10, 22, 0, 0).unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,112 @@
+use ascom_alpaca::api::Axis;
+use ascom_alpaca::ASCOMResult;
+use serde::Serialize;
+
+use crate::telescope_control::StarAdventurer;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Support {
+    Supported,
+    Unsupported,
+    Conditional, // Depends on the config; `available` says how it is now
+    Emulated,    // Works, but not the way the ASCOM member implies
+}
+
+/// One ASCOM member and how this driver supports it
+#[derive(Debug, Clone, Serialize)]
+pub struct Capability {
+    pub member: &'static str,
+    pub support: Support,
+    pub available: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<&'static str>,
+}
+
+impl Capability {
+    /// Straight from the Can-property, so the two can't disagree
+    fn from_can(member: &'static str, can: bool) -> Self {
+        Capability {
+            member,
+            support: if can {
+                Support::Supported
+            } else {
+                Support::Unsupported
+            },
+            available: can,
+            note: None,
+        }
+    }
+
+    fn with(mut self, support: Support, note: &'static str) -> Self {
+        if self.available {
+            self.support = support;
+        }
+        self.note = Some(note);
+        self
+    }
+
+    /// Only noted on mounts where dec is turned by hand
+    fn with_manual_dec(self, manual_dec: bool, support: Support) -> Self {
+        if manual_dec {
+            self.with(support, MANUAL_DECLINATION)
+        } else {
+            self
+        }
+    }
+}
+
+const MANUAL_DECLINATION: &str =
+    "Only right ascension is motorized. Declination is turned by hand, see the declination_slew_plan action";
+
+impl StarAdventurer {
+    /// Which ASCOM telescope members work, for client integrators
+    pub async fn get_capabilities(&self) -> ASCOMResult<Vec<Capability>> {
+        let pulse_guide_axes = self.get_pulse_guide_axes();
+        let manual_dec = !self.has_dec_motor();
+        Ok(vec![
+            Capability::from_can("CanFindHome", self.can_find_home().await?),
+            Capability::from_can(
+                "CanMoveAxis(Primary)",
+                self.can_move_axis(Axis::Primary).await?,
+            ),
+            Capability::from_can(
+                "CanMoveAxis(Secondary)",
+                self.can_move_axis(Axis::Secondary).await?,
+            )
+            .with_manual_dec(manual_dec, Support::Unsupported),
+            Capability::from_can(
+                "CanMoveAxis(Tertiary)",
+                self.can_move_axis(Axis::Tertiary).await?,
+            ),
+            Capability::from_can("CanPark", self.can_park().await?),
+            Capability::from_can("CanUnpark", self.can_unpark().await?),
+            Capability::from_can("CanSetPark", self.can_set_park_pos().await?),
+            Capability::from_can("CanPulseGuide", self.can_pulse_guide().await?),
+            Capability {
+                member: "PulseGuide(North/South)",
+                support: Support::Conditional,
+                available: pulse_guide_axes.declination,
+                note: Some("Needs a gpio-st4 dec-guide-output or a mount with a dec motor"),
+            },
+            Capability::from_can("CanSetGuideRates", self.can_set_guide_rates().await?),
+            Capability::from_can(
+                "CanSetDeclinationRate",
+                self.can_set_declination_rate().await?,
+            ),
+            Capability::from_can("CanSetRightAscensionRate", self.can_set_ra_rate().await?),
+            Capability::from_can("CanSetPierSide", self.can_set_side_of_pier().await?),
+            Capability::from_can("CanSetTracking", self.can_set_tracking().await?),
+            Capability::from_can("CanSlew", self.can_slew().await?)
+                .with_manual_dec(manual_dec, Support::Emulated),
+            Capability::from_can("CanSlewAsync", self.can_slew_async().await?)
+                .with_manual_dec(manual_dec, Support::Emulated),
+            Capability::from_can("CanSlewAltAz", self.can_slew_alt_az().await?)
+                .with_manual_dec(manual_dec, Support::Emulated),
+            Capability::from_can("CanSlewAltAzAsync", self.can_slew_alt_az_async().await?)
+                .with_manual_dec(manual_dec, Support::Emulated),
+            Capability::from_can("CanSync", self.can_sync().await?),
+            Capability::from_can("CanSyncAltAz", self.can_sync_alt_az().await?),
+        ])
+    }
+}
@@ -0,0 +1,238 @@
+use serde::Serialize;
+use tokio::time::Duration;
+
+use super::move_presets::MovePreset;
+use crate::astro_math;
+use crate::rotation_direction::RotationDirection;
+use crate::telescope_control::StarAdventurer;
+use crate::tracking_direction::TrackingDirection;
+use crate::util::*;
+use ascom_alpaca::{ASCOMError, ASCOMResult};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CenteringState {
+    Moving,
+    AwaitingSolve,
+    Centered,
+    GaveUp,
+}
+
+/// How the last correction was made
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Nudge {
+    Goto,
+    Fine, // RA only, at the center move preset
+}
+
+/// A slew, solve and nudge loop. The client solves the images and reports back.
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct CenteringRun {
+    pub right_ascension: Hours,
+    pub declination: Degrees,
+    pub tolerance_arcsec: f64,
+    pub state: CenteringState,
+    pub nudges: u32,
+    pub residual_arcsec: Option<f64>, // From the latest solve
+    pub last_nudge: Option<Nudge>,
+    #[serde(skip)]
+    aim: (Hours, Degrees), // Where the mount was last sent
+}
+
+impl StarAdventurer {
+    /// The RA and Dec to add to the aim point so the solved position lands on the target
+    fn calc_centering_correction(
+        target: (Hours, Degrees),
+        solved: (Hours, Degrees),
+    ) -> (Hours, Degrees) {
+        (
+            astro_math::modulo(target.0 - solved.0 + 12., 24.) - 12.,
+            target.1 - solved.1,
+        )
+    }
+
+    async fn is_centering_move_running(&self) -> ASCOMResult<bool> {
+        Ok(self.is_slewing().await? || self.connection.is_guiding().await?)
+    }
+
+    /// Slews to the coordinates and starts a centering run that waits for solved positions
+    pub async fn center_on(
+        &self,
+        ra: Hours,
+        dec: Degrees,
+        tolerance_arcsec: f64,
+    ) -> ASCOMResult<CenteringRun> {
+        if !(tolerance_arcsec.is_finite() && 0. < tolerance_arcsec) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Invalid centering tolerance: {}",
+                tolerance_arcsec
+            )));
+        }
+
+        let _slew = self.slew_to_coordinates_async(ra, dec).await?;
+        Ok(self.begin_centering(ra, dec, tolerance_arcsec).await)
+    }
+
+    /// Starts a closed loop goto's centering run if they're enabled
+    pub(in crate::telescope_control) async fn begin_closed_loop_goto(
+        &self,
+        ra: Hours,
+        dec: Degrees,
+    ) {
+        let centering = self.settings.centering;
+        if centering.closed_loop_gotos {
+            self.begin_centering(ra, dec, centering.tolerance_arcsec)
+                .await;
+        }
+    }
+
+    /// Waits for solves once the goto to the target that's just been started is done
+    async fn begin_centering(
+        &self,
+        ra: Hours,
+        dec: Degrees,
+        tolerance_arcsec: f64,
+    ) -> CenteringRun {
+        let run = CenteringRun {
+            right_ascension: ra,
+            declination: dec,
+            tolerance_arcsec,
+            state: CenteringState::Moving,
+            nudges: 0,
+            residual_arcsec: None,
+            last_nudge: None,
+            aim: (ra, dec),
+        };
+        *self.settings.centering_run.write().await = Some(run);
+        run
+    }
+
+    /// The current or last centering run
+    pub async fn get_centering(&self) -> ASCOMResult<Option<CenteringRun>> {
+        let run = *self.settings.centering_run.read().await;
+        Ok(match run {
+            Some(mut run) if run.state == CenteringState::Moving => {
+                if !self.is_centering_move_running().await? {
+                    run.state = CenteringState::AwaitingSolve;
+                }
+                Some(run)
+            }
+            run => run,
+        })
+    }
+
+    /// Takes the solved position of an image taken after the last move and nudges toward the target if needed
+    pub async fn report_solved_position(
+        &self,
+        solved_ra: Hours,
+        solved_dec: Degrees,
+    ) -> ASCOMResult<CenteringRun> {
+        check_ra(solved_ra)?;
+        check_dec(solved_dec)?;
+
+        let mut lock = self.settings.centering_run.write().await;
+        let run = lock
+            .as_mut()
+            .filter(|run| run.state == CenteringState::Moving)
+            .ok_or_else(|| ASCOMError::invalid_operation("No centering in progress"))?;
+        if self.is_centering_move_running().await? {
+            return Err(ASCOMError::invalid_operation(
+                "Still moving. Solve an image taken after the mount has settled",
+            ));
+        }
+
+        let target = (run.right_ascension, run.declination);
+        let residual_arcsec =
+            astro_math::calculate_separation(target.0, target.1, solved_ra, solved_dec) * 3600.;
+        run.residual_arcsec = Some(residual_arcsec);
+        if residual_arcsec <= run.tolerance_arcsec {
+            tracing::info!("Centered within {:.1}\"", residual_arcsec);
+            run.state = CenteringState::Centered;
+            return Ok(*run);
+        }
+        if self.settings.centering.max_iterations <= run.nudges {
+            tracing::warn!("Gave up centering {:.1}\" from the target", residual_arcsec);
+            run.state = CenteringState::GaveUp;
+            return Ok(*run);
+        }
+
+        let (ra_change, dec_change) =
+            Self::calc_centering_correction(target, (solved_ra, solved_dec));
+        let fine = self
+            .settings
+            .centering
+            .fine_nudge_below
+            .map_or(false, |limit| {
+                astro_math::hours_to_deg(ra_change.abs()) < limit
+                    && (dec_change * 3600.).abs() <= run.tolerance_arcsec
+            });
+
+        if fine {
+            self.nudge_ra(ra_change).await?;
+        } else {
+            let aim = (
+                astro_math::modulo(run.aim.0 + ra_change, 24.),
+                (run.aim.1 + dec_change).clamp(-90., 90.),
+            );
+            let _slew = self.slew_to_ra(aim.0, aim.1).await?;
+            run.aim = aim;
+        }
+
+        run.nudges += 1;
+        run.last_nudge = Some(if fine { Nudge::Fine } else { Nudge::Goto });
+        Ok(*run)
+    }
+
+    /// Moves RA by the given amount at the center move preset, on top of tracking
+    pub(in crate::telescope_control) async fn nudge_ra(&self, ra_change: Hours) -> ASCOMResult<()> {
+        let speed = self
+            .get_move_presets()
+            .await?
+            .into_iter()
+            .find(|r| r.preset == MovePreset::Center)
+            .unwrap()
+            .rate;
+        // RA increases against tracking
+        let direction = if 0. < ra_change {
+            TrackingDirection::AgainstTracking
+        } else {
+            TrackingDirection::WithTracking
+        };
+        let rate = MotionRate::new(
+            speed,
+            direction
+                .using(
+                    self.settings
+                        .observation_location
+                        .get()
+                        .get_rotation_direction_key(),
+                )
+                .into(),
+        );
+        let duration = Duration::from_secs_f64(astro_math::hours_to_deg(ra_change.abs()) / speed);
+
+        let _nudge = self.connection.pulse_guide(rate, duration, None).await?;
+        Ok(())
+    }
+
+    /// Stops waiting for solves. Any move in progress continues.
+    pub async fn cancel_centering(&self) {
+        *self.settings.centering_run.write().await = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn correction_wraps_ra() {
+        let (ra, dec) = StarAdventurer::calc_centering_correction((23.99, 10.), (0.01, 10.5));
+        assert!((ra - -0.02).abs() < 1e-9);
+        assert!((dec - -0.5).abs() < 1e-9);
+
+        let (ra, _) = StarAdventurer::calc_centering_correction((0.01, 0.), (23.99, 0.));
+        assert!((ra - 0.02).abs() < 1e-9);
+    }
+}
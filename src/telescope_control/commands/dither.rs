@@ -0,0 +1,122 @@
+use std::f64::consts::TAU;
+
+use rand::Rng;
+use serde::Serialize;
+use tokio::join;
+use tokio::time::{self, Duration};
+
+use crate::astro_math;
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+use ascom_alpaca::api::PutPulseGuideDirection;
+use ascom_alpaca::{ASCOMError, ASCOMResult};
+
+// Past this a slew gets there quicker than guiding
+const MAX_DITHER_ARCSEC: f64 = 300.;
+const PULSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The offset a dither moved by, on sky
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct Dither {
+    pub ra_arcsec: f64,  // Positive to the east
+    pub dec_arcsec: f64, // Positive to the north. Always 0 without a dec guide output
+}
+
+impl StarAdventurer {
+    /// Moves a random distance of up to max_arcsec at the guide rates, then waits out the settle time.
+    /// Dec is only dithered through a dec guide output.
+    pub async fn dither(&self, max_arcsec: f64) -> ASCOMResult<Dither> {
+        if !(max_arcsec.is_finite() && 0. < max_arcsec && max_arcsec <= MAX_DITHER_ARCSEC) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Dither of {}\" isn't between 0 and {}\"",
+                max_arcsec, MAX_DITHER_ARCSEC
+            )));
+        }
+
+        let offset = Self::random_dither(max_arcsec, self.secondary_axis().is_some());
+        let dec = self.get_dec().await?;
+        // An arcsecond of RA on sky is more of the axis away from the equator
+        let ra_axis_arcsec = offset.ra_arcsec / astro_math::deg_to_rad(dec).cos().max(0.1);
+
+        let ra_pulse = async {
+            let rate = self.get_guide_rate_ra().await?;
+            let direction = if 0. < offset.ra_arcsec {
+                PutPulseGuideDirection::East
+            } else {
+                PutPulseGuideDirection::West
+            };
+            self.pulse_for_dither(direction, ra_axis_arcsec, rate).await
+        };
+        let dec_pulse = async {
+            let rate = self.get_guide_rate_declination().await?;
+            let direction = if 0. < offset.dec_arcsec {
+                PutPulseGuideDirection::North
+            } else {
+                PutPulseGuideDirection::South
+            };
+            self.pulse_for_dither(direction, offset.dec_arcsec, rate)
+                .await
+        };
+        let (ra_result, dec_result) = join!(ra_pulse, dec_pulse);
+        ra_result?;
+        dec_result?;
+
+        // Batched pulses may still be running
+        while self.is_pulse_guiding().await? {
+            time::sleep(PULSE_POLL_INTERVAL).await;
+        }
+        self.settle_after_dither().await;
+        self.settle_timer.wait().await;
+        Ok(offset)
+    }
+
+    async fn pulse_for_dither(
+        &self,
+        direction: PutPulseGuideDirection,
+        arcsec: f64,
+        rate: Degrees,
+    ) -> ASCOMResult<()> {
+        if rate <= 0. {
+            return Ok(());
+        }
+        let millis = (arcsec.abs() / 3600. / rate * 1000.).round() as u32;
+        if millis == 0 {
+            return Ok(());
+        }
+        self.pulse_guide(direction, millis).await
+    }
+
+    /// Uniform over the disc of max_arcsec, or along RA only
+    fn random_dither(max_arcsec: f64, with_dec: bool) -> Dither {
+        let mut rng = rand::thread_rng();
+        if with_dec {
+            let radius = max_arcsec * rng.gen::<f64>().sqrt();
+            let angle = rng.gen_range(0. ..TAU);
+            Dither {
+                ra_arcsec: radius * angle.cos(),
+                dec_arcsec: radius * angle.sin(),
+            }
+        } else {
+            Dither {
+                ra_arcsec: rng.gen_range(-max_arcsec..=max_arcsec),
+                dec_arcsec: 0.,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_dither() {
+        for _ in 0..100 {
+            let dither = StarAdventurer::random_dither(5., true);
+            assert!(dither.ra_arcsec.hypot(dither.dec_arcsec) <= 5.);
+            let dither = StarAdventurer::random_dither(5., false);
+            assert!(dither.ra_arcsec.abs() <= 5.);
+            assert_eq!(dither.dec_arcsec, 0.);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use serde::Serialize;
+
+use crate::config::MountModel;
+use crate::telescope_control::connection::{FirmwareInfo, SpeedCapabilities};
+use crate::telescope_control::mount_profile::MountProfile;
+use crate::telescope_control::secondary_axis::SecondaryAxis;
+use crate::telescope_control::StarAdventurer;
+
+/// Everything needed to reproduce a bug report
+#[derive(Debug, Clone, Serialize)]
+pub struct DriverInfo {
+    pub version: &'static str,
+    pub git_hash: Option<&'static str>,
+    pub enabled_features: Vec<&'static str>, // Optional subsystems turned on in the config
+    pub serial_port: Option<String>,         // None while disconnected
+    pub speed_capabilities: Option<SpeedCapabilities>, // Reported by the motor controller
+    pub firmware: Option<FirmwareInfo>,      // Reported by the motor controller
+    pub profile: &'static MountProfile,
+    pub config_path: Option<String>,
+}
+
+impl StarAdventurer {
+    pub fn get_version_string() -> String {
+        match option_env!("GIT_HASH") {
+            Some(hash) => format!("{} ({})", env!("CARGO_PKG_VERSION"), hash),
+            None => env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Names the mount and the motor controller's firmware once connected
+    pub async fn get_description(&self) -> String {
+        let name = self.settings.profile.get().name;
+        match self.connection.get_firmware().await {
+            Ok(Some(firmware)) => format!(
+                "{}, motor controller firmware {} (mount code {:#04x})",
+                name, firmware.version, firmware.mount_code
+            ),
+            _ => name.to_string(),
+        }
+    }
+
+    /// On connecting, picks the profile for model = "auto" from the reported mount code,
+    /// and warns if the controller doesn't look like the profile.
+    /// Whether dec is driven follows the profile picked.
+    pub(in crate::telescope_control) async fn resolve_profile(&self) -> &'static MountProfile {
+        let model = self.settings.telescope_details.model;
+        let mut profile = MountProfile::for_model(model);
+        let firmware = self.connection.get_firmware().await.ok().flatten();
+        if model == MountModel::Auto {
+            match firmware.and_then(|firmware| MountProfile::detect(firmware.mount_code)) {
+                Some(detected) => {
+                    tracing::info!("Detected the mount as a {}", detected.name);
+                    profile = detected;
+                }
+                None => tracing::info!("Mount not recognized, running as a {}", profile.name),
+            }
+        }
+
+        let parameters = self
+            .connection
+            .get_speed_capabilities()
+            .await
+            .ok()
+            .and_then(|speed| speed.parameters);
+        if let (Some(expected), Some(parameters)) = (profile.counts_per_revolution, parameters) {
+            if parameters.counts_per_revolution != expected {
+                tracing::warn!(
+                    "The controller has {} counts per revolution, not the {} of a {}. Is the model right?",
+                    parameters.counts_per_revolution,
+                    expected,
+                    profile.name
+                );
+            }
+        }
+        *self.settings.profile.write().await = profile;
+        profile
+    }
+
+    fn get_enabled_features(&self) -> Vec<&'static str> {
+        let settings = &self.settings;
+        [
+            (self.power_switch.is_some(), "power_switch"),
+            (
+                matches!(self.secondary_axis(), Some(SecondaryAxis::GpioSt4(_))),
+                "gpio_st4",
+            ),
+            (self.has_dec_motor(), "dec_motor"),
+            (settings.smart_tracking, "smart_tracking"),
+            (
+                settings.ephemeris_lunar_tracking,
+                "ephemeris_lunar_tracking",
+            ),
+            (settings.refined_king_rate, "refined_king_rate"),
+            (settings.alt_az, "alt_az"),
+            (
+                settings.freeze_coordinates_while_stopped,
+                "freeze_coordinates_while_stopped",
+            ),
+            (settings.park_approach.is_some(), "park_approach"),
+            (settings.goto_approach.is_some(), "goto_approach"),
+            (self.idle_watchdog.is_enabled(), "idle_safety"),
+            (
+                self.settle_timer.waits_for_stability(),
+                "wait_for_stability",
+            ),
+            (
+                settings.resume_tracking_on_unpark,
+                "resume_tracking_on_unpark",
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, name)| enabled.then_some(name))
+        .collect()
+    }
+
+    pub async fn get_driver_info(&self) -> DriverInfo {
+        DriverInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: option_env!("GIT_HASH"),
+            enabled_features: self.get_enabled_features(),
+            serial_port: self.connection.get_port().await.ok(),
+            speed_capabilities: self.connection.get_speed_capabilities().await.ok(),
+            firmware: self.connection.get_firmware().await.ok().flatten(),
+            profile: self.settings.profile.get(),
+            config_path: std::fs::canonicalize(crate::CONFIG_PATH)
+                .ok()
+                .map(|path| path.display().to_string()),
+        }
+    }
+}
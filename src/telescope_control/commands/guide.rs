@@ -1,11 +1,156 @@
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+
+use serde::Serialize;
 use synscan::AutoGuideSpeed;
-use tokio::time::Duration;
+use tokio::time::{Duration, Instant};
 
 use crate::rotation_direction::RotationDirection;
+use crate::telescope_control::dec_knob::dec_motor_change;
+use crate::telescope_control::events::Event;
+use crate::telescope_control::guide_latency::StageLatency;
+use crate::telescope_control::secondary_axis::SecondaryAxis;
 use crate::telescope_control::star_adventurer::StarAdventurer;
 use crate::util::*;
 use ascom_alpaca::api::PutPulseGuideDirection;
-use ascom_alpaca::{ASCOMError, ASCOMResult};
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct PulseGuideAxes {
+    pub right_ascension: bool,
+    pub declination: bool,
+}
+
+const GUIDE_HISTORY_KEPT: Duration = Duration::from_secs(2 * 60 * 60);
+
+#[derive(Debug, Copy, Clone)]
+struct GuidePulseRecord {
+    time: Instant,
+    direction: PutPulseGuideDirection,
+    duration_millis: u32,
+    correction_arcsec: f64, // On the axis, at the guide rate
+}
+
+/// Successful pulses over the last couple of hours
+#[derive(Debug)]
+pub struct GuideHistory {
+    started: Instant,
+    pulses: VecDeque<GuidePulseRecord>,
+}
+
+impl Default for GuideHistory {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            pulses: VecDeque::new(),
+        }
+    }
+}
+
+impl GuideHistory {
+    fn record(&mut self, pulse: GuidePulseRecord) {
+        while let Some(oldest) = self.pulses.front() {
+            if pulse.time.saturating_duration_since(oldest.time) <= GUIDE_HISTORY_KEPT {
+                break;
+            }
+            self.pulses.pop_front();
+        }
+        self.pulses.push_back(pulse);
+    }
+
+    fn stats(&self, window: Duration, now: Instant) -> GuideStats {
+        // Don't spread the pulses over time before there was any history
+        let span = window.min(now.saturating_duration_since(self.started));
+        let recent: Vec<_> = self
+            .pulses
+            .iter()
+            .filter(|p| now.saturating_duration_since(p.time) <= span)
+            .collect();
+        let minutes = span.as_secs_f64() / 60.;
+        let net = |plus, minus| -> f64 {
+            recent
+                .iter()
+                .map(|p| {
+                    if p.direction == plus {
+                        p.correction_arcsec
+                    } else if p.direction == minus {
+                        -p.correction_arcsec
+                    } else {
+                        0.
+                    }
+                })
+                .sum()
+        };
+        let ra_correction_arcsec = net(PutPulseGuideDirection::East, PutPulseGuideDirection::West);
+        let dec_correction_arcsec =
+            net(PutPulseGuideDirection::North, PutPulseGuideDirection::South);
+        let per_minute = |value: f64| if 0. < minutes { value / minutes } else { 0. };
+        let stats_for = |direction| {
+            DirectionGuideStats::from_durations(
+                recent
+                    .iter()
+                    .filter(|p| p.direction == direction)
+                    .map(|p| p.duration_millis as f64)
+                    .collect(),
+            )
+        };
+
+        GuideStats {
+            minutes,
+            pulses: recent.len(),
+            pulses_per_minute: per_minute(recent.len() as f64),
+            north: stats_for(PutPulseGuideDirection::North),
+            south: stats_for(PutPulseGuideDirection::South),
+            east: stats_for(PutPulseGuideDirection::East),
+            west: stats_for(PutPulseGuideDirection::West),
+            ra_correction_arcsec,
+            dec_correction_arcsec,
+            dec_drift_arcsec_per_minute: per_minute(dec_correction_arcsec),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct DirectionGuideStats {
+    pub pulses: usize,
+    pub mean_millis: f64,
+    pub p50_millis: f64,
+    pub p90_millis: f64,
+    pub max_millis: f64,
+}
+
+impl DirectionGuideStats {
+    fn from_durations(mut durations: Vec<f64>) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort_by(f64::total_cmp);
+        let percentile = |p: f64| durations[((durations.len() - 1) as f64 * p).round() as usize];
+        Some(Self {
+            pulses: durations.len(),
+            mean_millis: durations.iter().sum::<f64>() / durations.len() as f64,
+            p50_millis: percentile(0.5),
+            p90_millis: percentile(0.9),
+            max_millis: durations[durations.len() - 1],
+        })
+    }
+}
+
+/// Guiding over a recent window. Corrections are net, positive east and north.
+/// A steady dec drift is the polar alignment error showing through.
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct GuideStats {
+    pub minutes: f64, // Less than asked for if the history doesn't go back that far
+    pub pulses: usize,
+    pub pulses_per_minute: f64,
+    pub north: Option<DirectionGuideStats>, // None without pulses that way
+    pub south: Option<DirectionGuideStats>,
+    pub east: Option<DirectionGuideStats>,
+    pub west: Option<DirectionGuideStats>,
+    pub ra_correction_arcsec: f64,
+    pub dec_correction_arcsec: f64,
+    pub dec_drift_arcsec_per_minute: f64,
+}
 
 impl StarAdventurer {
     /// True if the guide rate properties used for PulseGuide(GuideDirections, Int32) can ba adjusted.
@@ -15,13 +160,26 @@ impl StarAdventurer {
 
     /// The current Declination movement rate offset for telescope guiding (degrees/sec)
     pub async fn get_guide_rate_declination(&self) -> ASCOMResult<Degrees> {
-        Ok(0.)
+        match self.secondary_axis() {
+            Some(SecondaryAxis::GpioSt4(output)) => Ok(output.guide_rate()),
+            // The dec motor guides at the RA rate
+            Some(SecondaryAxis::Motor) => self.get_guide_rate_ra().await,
+            None => Ok(0.),
+        }
     }
 
     /// Sets the current Declination movement rate offset for telescope guiding (degrees/sec).
-    pub async fn set_guide_rate_declination(&self, _rate: Degrees) -> ASCOMResult<()> {
-        // This must "function" per ASCOM specs
-        Ok(())
+    pub async fn set_guide_rate_declination(&self, rate: Degrees) -> ASCOMResult<()> {
+        // Without a guide output this must still "function" per ASCOM specs. A dec motor follows the RA rate.
+        let output = match self.secondary_axis() {
+            Some(SecondaryAxis::GpioSt4(output)) => output,
+            _ => return Ok(()),
+        };
+        let previous = output.guide_rate();
+        if output.set_guide_rate(rate)? == previous {
+            return Ok(());
+        }
+        self.publish_guide_rates().await
     }
 
     /// The current RightAscension movement rate offset for telescope guiding (degrees/sec)
@@ -35,39 +193,60 @@ impl StarAdventurer {
         let tracking_rate_deg = (*self.settings.tracking_rate.read().await).to_degrees();
         let lowest_guide_rate = AutoGuideSpeed::Eighth.multiplier() * tracking_rate_deg;
         let highest_guide_rate = AutoGuideSpeed::One.multiplier() * tracking_rate_deg;
-        if rate < lowest_guide_rate * 0.9 || highest_guide_rate * 1.1 < rate {
+        if !(lowest_guide_rate * 0.9 <= rate && rate <= highest_guide_rate * 1.1) {
             return Err(ASCOMError::invalid_value(format_args!(
                 "Guide rate must be between {} and {}",
                 lowest_guide_rate, highest_guide_rate
             )));
         }
 
-        let (best_speed, _distance) = [
-            AutoGuideSpeed::Eighth,
-            AutoGuideSpeed::Quarter,
-            AutoGuideSpeed::Half,
-            AutoGuideSpeed::ThreeQuarters,
-            AutoGuideSpeed::One,
-        ]
-        .into_iter()
-        .fold(
-            (AutoGuideSpeed::Eighth, 99999.),
-            |(closest, distance), try_speed| {
-                let try_distance = (try_speed.multiplier() * tracking_rate_deg - rate).abs();
-                if try_distance < distance {
-                    (try_speed, try_distance)
-                } else {
-                    (closest, distance)
-                }
-            },
-        );
+        self.apply_autoguide_speed(AutoGuideSpeed::closest_to(rate / tracking_rate_deg))
+            .await
+    }
+
+    /// The RightAscension guide speed as a fraction of the tracking rate
+    pub async fn get_guide_speed_fraction(&self) -> ASCOMResult<f64> {
+        Ok(self.settings.autoguide_speed.read().await.multiplier())
+    }
+
+    /// Sets the RightAscension guide speed as a fraction of the tracking rate.
+    /// The fraction is snapped to the closest speed the hardware supports, which is returned.
+    pub async fn set_guide_speed_fraction(&self, fraction: f64) -> ASCOMResult<f64> {
+        if !(AutoGuideSpeed::MIN_FRACTION..=AutoGuideSpeed::MAX_FRACTION).contains(&fraction) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Guide speed must be between {} and {} of the tracking rate",
+                AutoGuideSpeed::MIN_FRACTION,
+                AutoGuideSpeed::MAX_FRACTION
+            )));
+        }
 
-        if *self.settings.autoguide_speed.read().await == best_speed {
+        let speed = AutoGuideSpeed::closest_to(fraction);
+        self.apply_autoguide_speed(speed).await?;
+        Ok(speed.multiplier())
+    }
+
+    pub(in crate::telescope_control) async fn apply_autoguide_speed(
+        &self,
+        speed: AutoGuideSpeed,
+    ) -> ASCOMResult<()> {
+        if *self.settings.autoguide_speed.read().await == speed {
             return Ok(());
         }
 
-        self.connection.set_autoguide_speed(best_speed).await?;
-        *self.settings.autoguide_speed.write().await = best_speed;
+        if self.settings.profile.get().st4_port {
+            self.connection.set_autoguide_speed(speed).await?;
+        }
+        *self.settings.autoguide_speed.write().await = speed;
+        self.publish_guide_rates().await
+    }
+
+    /// Also needed when the tracking rate changes, since the guide rate is a fraction of it
+    pub(in crate::telescope_control) async fn publish_guide_rates(&self) -> ASCOMResult<()> {
+        self.events.publish(Event::GuideRateChanged {
+            guide_speed_fraction: self.get_guide_speed_fraction().await?,
+            guide_rate_right_ascension: self.get_guide_rate_ra().await?,
+            guide_rate_declination: self.get_guide_rate_declination().await?,
+        });
         Ok(())
     }
 
@@ -76,19 +255,66 @@ impl StarAdventurer {
         Ok(true)
     }
 
+    /// Which axes accept pulses. Declination needs a secondary axis.
+    pub fn get_pulse_guide_axes(&self) -> PulseGuideAxes {
+        PulseGuideAxes {
+            right_ascension: true,
+            declination: self.secondary_axis().is_some(),
+        }
+    }
+
     /// Moves the scope in the given direction for the given interval or time at the rate given by the corresponding guide rate property
-    /// Synchronous b/c only one axis is guideable
+    /// Synchronous. North/South only work with a dec guide output configured or a motorized dec axis
+    #[tracing::instrument(level = "debug", skip(self))]
     pub async fn pulse_guide(
         &self,
         guide_direction: PutPulseGuideDirection,
         duration: u32,
     ) -> ASCOMResult<()> {
+        let direction = format!("{:?}", guide_direction).to_lowercase();
+        self.events.publish(Event::GuidePulseStarted {
+            direction: direction.clone(),
+            duration_millis: duration,
+        });
+        let result = self.pulse_guide_axis(guide_direction, duration).await;
+        if result.is_ok() {
+            let rate = match guide_direction {
+                PutPulseGuideDirection::North | PutPulseGuideDirection::South => {
+                    self.get_guide_rate_declination().await
+                }
+                _ => self.get_guide_rate_ra().await,
+            };
+            let rate = rate.unwrap_or(0.);
+            self.session
+                .record_pulse_guide(guide_direction, duration, rate);
+            self.settings
+                .guide_history
+                .lock()
+                .unwrap()
+                .record(GuidePulseRecord {
+                    time: Instant::now(),
+                    direction: guide_direction,
+                    duration_millis: duration,
+                    correction_arcsec: rate * 3.6 * duration as f64,
+                });
+        }
+        self.events.publish(Event::GuidePulseEnded {
+            direction,
+            succeeded: result.is_ok(),
+        });
+        result
+    }
+
+    async fn pulse_guide_axis(
+        &self,
+        guide_direction: PutPulseGuideDirection,
+        duration: u32,
+    ) -> ASCOMResult<()> {
+        let timer = self.guide_latency.start();
         if guide_direction == PutPulseGuideDirection::North
             || guide_direction == PutPulseGuideDirection::South
         {
-            return Err(ASCOMError::invalid_value(
-                "Can't guide in declination".to_string(),
-            ));
+            return self.pulse_guide_dec(guide_direction, duration).await;
         }
 
         let guide_speed = self.settings.autoguide_speed.read().await.multiplier()
@@ -97,23 +323,177 @@ impl StarAdventurer {
             .using(
                 self.settings
                     .observation_location
-                    .read()
-                    .await
+                    .get()
                     .get_rotation_direction_key(),
             )
             .into();
         let guide_rate = MotionRate::new(guide_speed, guide_direction);
+        let duration = Duration::from_millis(duration as u64);
+
+        if self
+            .guide_batcher
+            .submit(guide_rate, duration, &self.connection)
+        {
+            return Ok(());
+        }
+
+        self.settings
+            .stopped_at_limit
+            .store(false, Ordering::SeqCst);
+        let result = async {
+            self.connection
+                .pulse_guide(guide_rate, duration, Some(timer))
+                .await?
+                .await
+                .unwrap()
+        }
+        .await;
+        self.guide_batcher.pulse_ended();
+        if self.settings.stopped_at_limit.swap(false, Ordering::SeqCst) {
+            return Err(Self::mount_limit_error());
+        }
+        result
+    }
+
+    /// Dec pulses go through the secondary axis, independently of RA
+    async fn pulse_guide_dec(
+        &self,
+        guide_direction: PutPulseGuideDirection,
+        duration: u32,
+    ) -> ASCOMResult<()> {
+        let output = match self.secondary_axis() {
+            Some(SecondaryAxis::GpioSt4(output)) => output,
+            Some(SecondaryAxis::Motor) => {
+                return self.pulse_guide_dec_motor(guide_direction, duration).await
+            }
+            None => {
+                return Err(ASCOMError::invalid_value(
+                    "Can't guide in declination without a dec guide output or dec motor"
+                        .to_string(),
+                ))
+            }
+        };
+
+        if self.connection.is_parked().await? {
+            return Err(ASCOMError::new(
+                ASCOMErrorCode::INVALID_WHILE_PARKED,
+                "Can't pulse guide while parked".to_string(),
+            ));
+        }
+
+        output
+            .pulse(
+                guide_direction == PutPulseGuideDirection::North,
+                Duration::from_millis(duration as u64),
+            )
+            .await
+    }
+
+    pub(in crate::telescope_control) async fn pulse_guide_dec_motor(
+        &self,
+        guide_direction: PutPulseGuideDirection,
+        duration: u32,
+    ) -> ASCOMResult<()> {
+        if self.connection.is_parked().await? {
+            return Err(ASCOMError::new(
+                ASCOMErrorCode::INVALID_WHILE_PARKED,
+                "Can't pulse guide while parked".to_string(),
+            ));
+        }
+
+        let north = if guide_direction == PutPulseGuideDirection::North {
+            1.
+        } else {
+            -1.
+        };
+        let sense = dec_motor_change(
+            north,
+            self.settings.pier_side.get(),
+            self.settings.observation_location.get().in_north(),
+        )
+        .ok_or_else(|| {
+            ASCOMError::invalid_operation("Can't guide in declination with the pier side unknown")
+        })?;
+        let rate =
+            MotionRate::from_clockwise_rate(sense * self.get_guide_rate_declination().await?);
 
         self.connection
-            .pulse_guide(guide_rate, Duration::from_millis(duration as u64))
-            .await?
+            .pulse_dec_motor(rate, Duration::from_millis(duration as u64))
             .await
-            .unwrap()?;
-        Ok(())
+    }
+
+    /// Guiding over the last `minutes`, for estimating drift without the guider's logs
+    pub fn get_guide_stats(&self, minutes: f64) -> ASCOMResult<GuideStats> {
+        if !(0. < minutes && minutes.is_finite()) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Window of {} minutes must be positive",
+                minutes
+            )));
+        }
+        let window = Duration::from_secs_f64(minutes * 60.).min(GUIDE_HISTORY_KEPT);
+        Ok(self
+            .settings
+            .guide_history
+            .lock()
+            .unwrap()
+            .stats(window, Instant::now()))
+    }
+
+    /// Latency percentiles of each stage of recent RA guide pulses
+    pub fn get_guide_latency(&self) -> Vec<StageLatency> {
+        self.guide_latency.get_stats()
     }
 
     /// True if a PulseGuide(GuideDirections, Int32) command is in progress, False otherwise
     pub async fn is_pulse_guiding(&self) -> ASCOMResult<bool> {
-        Ok(self.connection.is_guiding().await?)
+        let dec_guiding = match self.secondary_axis() {
+            Some(SecondaryAxis::GpioSt4(output)) => output.is_guiding(),
+            _ => false,
+        };
+        Ok(dec_guiding
+            || self.guide_batcher.is_busy()
+            || self.connection.is_guiding().await?
+            || self.connection.is_dec_guiding().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guide_stats() {
+        let start = Instant::now();
+        let mut history = GuideHistory {
+            started: start,
+            pulses: VecDeque::new(),
+        };
+        let pulse = |secs, direction, duration_millis| GuidePulseRecord {
+            time: start + Duration::from_secs(secs),
+            direction,
+            duration_millis,
+            correction_arcsec: duration_millis as f64 / 100.,
+        };
+        history.record(pulse(10, PutPulseGuideDirection::East, 200));
+        history.record(pulse(70, PutPulseGuideDirection::West, 100));
+        history.record(pulse(90, PutPulseGuideDirection::North, 300));
+        history.record(pulse(110, PutPulseGuideDirection::North, 500));
+
+        let now = start + Duration::from_secs(120);
+        let stats = history.stats(Duration::from_secs(600), now);
+        assert_eq!(stats.minutes, 2.);
+        assert_eq!(stats.pulses, 4);
+        assert_eq!(stats.pulses_per_minute, 2.);
+        assert!((stats.ra_correction_arcsec - 1.).abs() < 1e-9);
+        assert!((stats.dec_drift_arcsec_per_minute - 4.).abs() < 1e-9);
+        let north = stats.north.unwrap();
+        assert_eq!(north.pulses, 2);
+        assert_eq!(north.mean_millis, 400.);
+        assert!(stats.south.is_none());
+
+        // Only the last minute
+        let stats = history.stats(Duration::from_secs(60), now);
+        assert_eq!(stats.pulses, 3);
+        assert!(stats.east.is_none());
     }
 }
@@ -0,0 +1,94 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use ascom_alpaca::api::PutPulseGuideDirection;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+
+use crate::astro_math;
+use crate::telescope_control::secondary_axis::SecondaryAxis;
+use crate::telescope_control::StarAdventurer;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JogDirection {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl FromStr for JogDirection {
+    type Err = ASCOMError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "north" => Ok(Self::North),
+            "south" => Ok(Self::South),
+            "east" => Ok(Self::East),
+            "west" => Ok(Self::West),
+            _ => Err(ASCOMError::invalid_value(format_args!(
+                "Unknown jog direction: \"{}\"",
+                s
+            ))),
+        }
+    }
+}
+
+impl StarAdventurer {
+    /// Arcminutes
+    pub fn get_jog_steps(&self) -> &[f64] {
+        &self.settings.jog.steps
+    }
+
+    /// Moves a short way relative to the current position, on top of tracking.
+    /// North and south need a secondary axis.
+    pub async fn jog(&self, direction: JogDirection, arcmin: Option<f64>) -> ASCOMResult<()> {
+        let arcmin = arcmin.unwrap_or(self.settings.jog.default_step);
+        if !(0. < arcmin && arcmin <= self.settings.jog.max_step) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Jog step of {}' isn't between 0' and {}'",
+                arcmin, self.settings.jog.max_step
+            )));
+        }
+        let degrees = arcmin / 60.;
+
+        match direction {
+            // RA increases to the east
+            JogDirection::East => self.nudge_ra(astro_math::deg_to_hours(degrees)).await,
+            JogDirection::West => self.nudge_ra(-astro_math::deg_to_hours(degrees)).await,
+            JogDirection::North | JogDirection::South
+                if matches!(self.secondary_axis(), Some(SecondaryAxis::Motor)) =>
+            {
+                let direction = if direction == JogDirection::North {
+                    PutPulseGuideDirection::North
+                } else {
+                    PutPulseGuideDirection::South
+                };
+                let rate = self.get_guide_rate_declination().await?;
+                self.pulse_guide_dec_motor(direction, (degrees / rate * 1000.).round() as u32)
+                    .await
+            }
+            JogDirection::North | JogDirection::South => {
+                let output = match self.secondary_axis() {
+                    Some(SecondaryAxis::GpioSt4(output)) => output,
+                    _ => {
+                        return Err(ASCOMError::invalid_operation(
+                            "Can't jog in declination without a dec guide output or dec motor",
+                        ))
+                    }
+                };
+                if self.connection.is_parked().await? {
+                    return Err(ASCOMError::new(
+                        ASCOMErrorCode::INVALID_WHILE_PARKED,
+                        "Can't jog while parked".to_string(),
+                    ));
+                }
+                output
+                    .pulse(
+                        direction == JogDirection::North,
+                        Duration::from_secs_f64(degrees / output.guide_rate()),
+                    )
+                    .await
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::str::FromStr;
+
+use ascom_alpaca::api::{Axis, DriveRate};
+use serde::Serialize;
+
+use crate::config::MovePresets;
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+use ascom_alpaca::{ASCOMError, ASCOMResult};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MovePreset {
+    Guide,
+    Center,
+    Find,
+    Slew,
+}
+
+impl MovePreset {
+    pub const ALL: [MovePreset; 4] = [Self::Guide, Self::Center, Self::Find, Self::Slew];
+
+    fn sidereal_multiple(self, presets: &MovePresets) -> f64 {
+        match self {
+            Self::Guide => presets.guide,
+            Self::Center => presets.center,
+            Self::Find => presets.find,
+            Self::Slew => presets.slew,
+        }
+    }
+}
+
+impl FromStr for MovePreset {
+    type Err = ASCOMError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "guide" => Ok(Self::Guide),
+            "center" => Ok(Self::Center),
+            "find" => Ok(Self::Find),
+            "slew" => Ok(Self::Slew),
+            _ => Err(ASCOMError::invalid_value(format_args!(
+                "Unknown move preset: \"{}\"",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct MovePresetRate {
+    pub preset: MovePreset,
+    pub sidereal_multiple: f64,
+    pub rate: Degrees, // Degrees/sec, as passed to MoveAxis
+}
+
+impl StarAdventurer {
+    /// The rates of the move presets, moved to the nearest rate the motor can do
+    pub async fn get_move_presets(&self) -> ASCOMResult<Vec<MovePresetRate>> {
+        let ranges = self.get_axis_rate_ranges().await;
+        Ok(MovePreset::ALL
+            .iter()
+            .map(|&preset| {
+                let rate = Self::nearest_axis_rate(
+                    &ranges,
+                    preset.sidereal_multiple(&self.settings.move_presets)
+                        * DriveRate::Sidereal.to_degrees(),
+                );
+                MovePresetRate {
+                    preset,
+                    sidereal_multiple: rate / DriveRate::Sidereal.to_degrees(),
+                    rate,
+                }
+            })
+            .collect())
+    }
+
+    /// Moves the RA axis at a preset speed. Negative moves against tracking like MoveAxis.
+    pub async fn move_at_preset(&self, preset: MovePreset, negative: bool) -> ASCOMResult<()> {
+        let rate = self
+            .get_move_presets()
+            .await?
+            .into_iter()
+            .find(|r| r.preset == preset)
+            .unwrap()
+            .rate;
+        self.move_axis(Axis::Primary, if negative { -rate } else { rate })
+            .await
+    }
+}
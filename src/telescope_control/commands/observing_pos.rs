@@ -2,10 +2,14 @@ use chrono::{DateTime, Utc};
 use tokio::join;
 
 use crate::astro_math;
+use crate::telescope_control::clock_monitor;
 use crate::telescope_control::star_adventurer::StarAdventurer;
 use crate::util::*;
 use ascom_alpaca::{ASCOMError, ASCOMResult};
 
+// Like NTP's panic threshold, an offset this big is more likely a bad clock than a real correction
+const DATE_OFFSET_WARNING_SECS: i64 = 1000;
+
 impl StarAdventurer {
     /*** Date ***/
 
@@ -13,6 +17,15 @@ impl StarAdventurer {
         Utc::now() + date_offset
     }
 
+    /// The offset of the clock that sidereal time is calculated from
+    pub(in crate::telescope_control) async fn get_sky_date_offset(&self) -> chrono::Duration {
+        if self.settings.sky_clock_follows_utc_date {
+            *self.settings.date_offset.read().await
+        } else {
+            chrono::Duration::zero()
+        }
+    }
+
     /// The UTC date/time of the telescope's internal clock in ISO 8601 format including fractional seconds.
     /// The general format (in Microsoft custom date format style) is yyyy-MM-ddTHH:mm:ss.fffffffZ E.g. 2016-03-04T17:45:31.1234567Z or 2016-11-14T07:03:08.1234567Z
     /// Please note the compulsory trailing Z indicating the 'Zulu', UTC time zone.
@@ -24,7 +37,15 @@ impl StarAdventurer {
 
     /// The UTC date/time of the telescope's internal clock in ISO 8601 format including fractional seconds. The general format (in Microsoft custom date format style) is yyyy-MM-ddTHH:mm:ss.fffffffZ E.g. 2016-03-04T17:45:31.1234567Z or 2016-11-14T07:03:08.1234567Z Please note the compulsary trailing Z indicating the 'Zulu', UTC time zone.
     pub async fn set_utc_date(&self, time: DateTime<Utc>) -> ASCOMResult<()> {
-        *self.settings.date_offset.write().await = time - Utc::now();
+        clock_monitor::check_plausible_date(time).map_err(ASCOMError::invalid_value)?;
+        let offset = time - Utc::now();
+        if DATE_OFFSET_WARNING_SECS < offset.num_seconds().abs() {
+            tracing::warn!(
+                "UTC date is {}s from the system clock. Check the system clock is set.",
+                offset.num_seconds()
+            );
+        }
+        *self.settings.date_offset.write().await = offset;
         Ok(())
     }
 
@@ -32,7 +53,7 @@ impl StarAdventurer {
 
     /// The geodetic(map) latitude (degrees, positive North, WGS84) of the site at which the telescope is located.
     pub async fn get_latitude(&self) -> ASCOMResult<Degrees> {
-        Ok(self.settings.observation_location.read().await.latitude)
+        Ok(self.settings.observation_location.get().latitude)
     }
 
     /// Sets the observing site's latitude (degrees).
@@ -43,7 +64,16 @@ impl StarAdventurer {
                 latitude
             )));
         }
-        self.settings.observation_location.write().await.latitude = latitude;
+        let mut location = self.settings.observation_location.write().await;
+        let old_hemisphere = location.hemisphere();
+        location.latitude = latitude;
+        if location.hemisphere() != old_hemisphere {
+            tracing::warn!(
+                "Latitude {} changes the hemisphere to {:?}. Make sure the mount's N/S switch matches.",
+                latitude,
+                location.hemisphere()
+            );
+        }
         Ok(())
     }
 
@@ -51,7 +81,7 @@ impl StarAdventurer {
 
     /// The longitude (degrees, positive East, WGS84) of the site at which the telescope is located.
     pub async fn get_longitude(&self) -> ASCOMResult<Degrees> {
-        Ok(self.settings.observation_location.read().await.longitude)
+        Ok(self.settings.observation_location.get().longitude)
     }
 
     /// Sets the observing site's longitude (degrees, positive East, WGS84).
@@ -70,7 +100,7 @@ impl StarAdventurer {
 
     /// The elevation above mean sea level (meters) of the site at which the telescope is located
     pub async fn get_elevation(&self) -> ASCOMResult<f64> {
-        Ok(self.settings.observation_location.read().await.elevation)
+        Ok(self.settings.observation_location.get().elevation)
     }
 
     /// Sets the elevation above mean sea level (metres) of the site at which the telescope is located.
@@ -89,8 +119,11 @@ impl StarAdventurer {
 
     /// The local apparent sidereal time from the telescope's internal clock (hours, sidereal)
     pub async fn get_sidereal_time(&self) -> ASCOMResult<Hours> {
-        let (date, longitude) = join!(self.get_utc_date(), self.get_longitude());
-        Ok(astro_math::calculate_local_sidereal_time(date?, longitude?))
+        let (date_offset, longitude) = join!(self.get_sky_date_offset(), self.get_longitude());
+        Ok(astro_math::calculate_local_sidereal_time(
+            Self::calculate_utc_date(date_offset),
+            longitude?,
+        ))
     }
 }
 
@@ -98,8 +131,11 @@ impl StarAdventurer {
 mod tests {
     use std::time::Duration;
 
+    use assert_float_eq::*;
     use chrono::{TimeZone, Utc};
 
+    use crate::astro_math;
+    use crate::config::Config;
     use crate::telescope_control::test_util;
 
     #[tokio::test]
@@ -144,4 +180,46 @@ mod tests {
         assert_eq!(sa.get_latitude().await.unwrap(), test_lat1);
         assert_eq!(sa.get_elevation().await.unwrap(), test_elevation);
     }
+
+    #[tokio::test]
+    async fn test_large_date_offset() {
+        let sa = test_util::create_sa(None).await;
+        let longitude = sa.get_longitude().await.unwrap();
+
+        let offset_date = Utc::now() + chrono::Duration::days(100) + chrono::Duration::hours(7);
+        sa.set_utc_date(offset_date).await.unwrap();
+        assert_float_absolute_eq!(
+            sa.get_sidereal_time().await.unwrap(),
+            astro_math::calculate_local_sidereal_time(offset_date, longitude),
+            1E-3
+        );
+
+        // Sync, pointing and slew planning all have to agree on the clock
+        sa.sync_to_coordinates(5., 20.).await.unwrap();
+        assert_float_absolute_eq!(sa.get_ra().await.unwrap(), 5., 1E-4);
+        assert!(sa.can_slew_to(7., 10.).await.unwrap().is_ok());
+        sa.slew_to_coordinates(7., 10.).await.unwrap();
+        assert_float_absolute_eq!(sa.get_ra().await.unwrap(), 7., 1E-2);
+        assert_float_absolute_eq!(sa.get_dec().await.unwrap(), 10., 1E-4);
+    }
+
+    #[tokio::test]
+    async fn test_sky_clock_ignores_utc_date() {
+        let mut config: Config = confy::load_path("test_config.toml").unwrap();
+        config.other.sky_clock_follows_utc_date = false;
+        let sa = test_util::create_sa(Some(config)).await;
+        let longitude = sa.get_longitude().await.unwrap();
+
+        let offset_date = Utc::now() + chrono::Duration::days(100) + chrono::Duration::hours(7);
+        sa.set_utc_date(offset_date).await.unwrap();
+        assert!(sa.get_utc_date().await.unwrap() - offset_date < chrono::Duration::milliseconds(1));
+        assert_float_absolute_eq!(
+            sa.get_sidereal_time().await.unwrap(),
+            astro_math::calculate_local_sidereal_time(Utc::now(), longitude),
+            1E-3
+        );
+
+        sa.sync_to_coordinates(5., 20.).await.unwrap();
+        assert_float_absolute_eq!(sa.get_ra().await.unwrap(), 5., 1E-4);
+    }
 }
@@ -1,11 +1,53 @@
 use crate::astro_math;
-use crate::rotation_direction::RotationDirection;
+use crate::config::{Config, ParkAltAz, ParkApproach, ParkApproachSide};
+use crate::rotation_direction::{RotationDirection, RotationDirectionKey};
+use crate::telescope_control::events::Event;
+use crate::telescope_control::settle_timer::SettleSource;
 use crate::telescope_control::slew_def::Slew;
+use crate::telescope_control::vibration_guard::GuardedOperation;
 use crate::telescope_control::StarAdventurer;
+use crate::tracking_direction::TrackingDirection;
 use crate::util::*;
-use ascom_alpaca::ASCOMResult;
+use ascom_alpaca::api::SideOfPier;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
 use tokio::join;
 
+/// Why the mount's position can't be trusted after unparking
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+pub enum PositionProblem {
+    /// The motor was turned by something other than this driver while parked
+    MovedWhileParked {
+        parked_position: Degrees,
+        position: Degrees,
+    },
+    OutsideLimits {
+        mech_hour_angle: Hours,
+    },
+}
+
+impl Display for PositionProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MovedWhileParked {
+                parked_position,
+                position,
+            } => write!(
+                f,
+                "Mount moved by {:.2}° while parked",
+                position - parked_position
+            ),
+            Self::OutsideLimits { mech_hour_angle } => write!(
+                f,
+                "Mechanical hour angle {:.2}h is outside the mount limits",
+                mech_hour_angle
+            ),
+        }
+    }
+}
+
 impl StarAdventurer {
     /// True if this telescope is capable of programmed parking (Park() method)
     pub async fn can_park(&self) -> ASCOMResult<bool> {
@@ -25,31 +67,87 @@ impl StarAdventurer {
 
     /// True if this telescope is capable of programmed setting of its park position (SetPark() method)
     pub async fn can_set_park_pos(&self) -> ASCOMResult<bool> {
-        Ok(true)
+        Ok(!self.settings.park_fixed)
     }
 
     /// Sets the telescope's park position to be its current position.
     pub async fn set_park_pos(&self) -> ASCOMResult<()> {
-        *self.settings.park_ha.write().await = self.get_mech_ha().await?;
+        if self.settings.park_fixed {
+            return Err(ASCOMError::new(
+                ASCOMErrorCode::NOT_IMPLEMENTED,
+                "The park position is fixed by park-alt-az in the config".to_string(),
+            ));
+        }
+        self.check_position_trusted().await?;
+
+        let mech_ha = self.get_mech_ha().await?;
+        if !self.settings.mount_limits.read().await.is_valid_ha(mech_ha) {
+            return Err(ASCOMError::invalid_operation(format_args!(
+                "Mechanical hour angle {:.2}h is outside the mount limits",
+                mech_ha
+            )));
+        }
+        *self.settings.park_ha.write().await = mech_ha;
+        self.session.log(format!(
+            "Park position set to mechanical HA {:.2}h",
+            mech_ha
+        ));
+
+        if let Some(park_store) = self.settings.park_store.clone() {
+            let stored = tokio::task::spawn_blocking(move || park_store.store(mech_ha))
+                .await
+                .unwrap();
+            if let Err(e) = stored {
+                tracing::warn!("Couldn't save the park position: {}", e);
+            }
+        }
         Ok(())
     }
 
+    /// The mechanical hour angle to park at from the config
+    pub(in crate::telescope_control) fn calc_park_ha(config: &Config) -> Hours {
+        let alt_az_ha = config.other.park_alt_az.and_then(|alt_az| {
+            Self::calc_park_ha_from_alt_az(
+                alt_az,
+                config.observation_location.latitude,
+                config.initialization.pier_side,
+            )
+        });
+        astro_math::modulo(alt_az_ha.unwrap_or(config.other.park_hour_angle), 24.)
+    }
+
+    fn calc_park_ha_from_alt_az(
+        alt_az: ParkAltAz,
+        latitude: Degrees,
+        pier_side: SideOfPier,
+    ) -> Option<Hours> {
+        if pier_side == SideOfPier::Unknown {
+            tracing::warn!("Can't park at an alt/az without knowing the pier side");
+            return None;
+        }
+        let (ha, _dec) =
+            astro_math::calculate_ha_dec_from_alt_az(alt_az.altitude, alt_az.azimuth, latitude);
+        Some(Self::calc_mech_ha_from_ha(ha, pier_side))
+    }
+
     /// Move the telescope to its park position, stop all motion, and set AtPark to True.
     pub async fn park(&self) -> ASCOMResult<()> {
         let current_motor_pos = self.connection.get_pos().await?;
+        let tracking_rate = if self.connection.is_tracking().await? {
+            Some(*self.settings.tracking_rate.read().await)
+        } else {
+            None
+        };
 
-        let (park_ha, key, mech_ha_offset, mount_limits) = join!(
-            async { *self.settings.park_ha.read().await },
-            async {
-                self.settings
-                    .observation_location
-                    .read()
-                    .await
-                    .get_rotation_direction_key()
-            },
-            async { *self.settings.mech_ha_offset.read().await },
-            async { *self.settings.mount_limits.read().await },
-        );
+        let key = self
+            .settings
+            .observation_location
+            .get()
+            .get_rotation_direction_key();
+        let mech_ha_offset = self.settings.mech_ha_offset.get();
+        let (park_ha, mount_limits) = join!(async { *self.settings.park_ha.read().await }, async {
+            *self.settings.mount_limits.read().await
+        },);
 
         let current_mech_ha = Self::calc_mech_ha(current_motor_pos, mech_ha_offset, key);
 
@@ -58,13 +156,262 @@ impl StarAdventurer {
         let pos_change = astro_math::hours_to_deg(slew.distance()) * motor_direction.get_sign_f64();
         let dest_motor_pos = current_motor_pos + pos_change;
 
-        self.connection.park(dest_motor_pos).await?.await.unwrap()?;
+        let approach_pos = self.settings.park_approach.and_then(|approach| {
+            Self::calc_park_approach_pos(
+                approach,
+                current_motor_pos,
+                dest_motor_pos,
+                key,
+                mech_ha_offset,
+                mount_limits,
+            )
+        });
+
+        self.connection
+            .park(dest_motor_pos, approach_pos)
+            .await?
+            .await
+            .unwrap()?;
+        self.save_parked_motor_pos(Some(dest_motor_pos)).await;
+        *self.settings.tracking_before_park.write().await = tracking_rate;
+        self.vibration_guard
+            .note_rate_change(GuardedOperation::Park);
+        self.settle_timer
+            .start(SettleSource::Park, self.get_settle_duration().await);
+        self.settle_timer.wait().await;
+        self.session.record_park();
+        self.events.publish(Event::ParkCompleted);
+
+        if let Some(power_switch) = &self.power_switch {
+            if power_switch.powers_off_on_park() {
+                // Never cut the power while the motor is still turning
+                if !self.connection.is_parked().await? || self.connection.is_motor_running().await?
+                {
+                    tracing::warn!("Motor not stopped at park -- leaving the mount powered");
+                    return Ok(());
+                }
+                self.connection.disconnect().await;
+                power_switch.power_off(true).await?;
+            }
+        }
         Ok(())
     }
 
+    /// Where to go before the park position so the final leg moves away from the approach side.
+    /// None if the park slew already ends with a long enough move that way, or if going there would pass a mount limit.
+    fn calc_park_approach_pos(
+        approach: ParkApproach,
+        current_pos: Degrees,
+        park_pos: Degrees,
+        key: RotationDirectionKey,
+        mech_ha_offset: Hours,
+        mount_limits: MountLimits,
+    ) -> Option<Degrees> {
+        // Moving with tracking goes from east to west
+        let westward: MotorEncodingDirection = TrackingDirection::WithTracking.using(key).into();
+        let final_sign = match approach.from {
+            ParkApproachSide::East => westward.get_sign_f64(),
+            ParkApproachSide::West => -westward.get_sign_f64(),
+        };
+        if approach.distance <= (park_pos - current_pos) * final_sign {
+            return None;
+        }
+        let approach_pos = park_pos - final_sign * approach.distance;
+        if !mount_limits.is_valid_ha(Self::calc_mech_ha(approach_pos, mech_ha_offset, key)) {
+            tracing::warn!("Parking without the approach, which would pass a mount limit");
+            return None;
+        }
+        Some(approach_pos)
+    }
+
     /// Takes telescope out of the Parked state.
     pub async fn unpark(&self) -> ASCOMResult<()> {
+        if let Some(power_switch) = &self.power_switch {
+            if power_switch.is_off_at_park().await {
+                self.connect().await?;
+            }
+        }
+        let was_parked = self.connection.is_parked().await?;
         self.connection.unpark().await?;
+        self.session.log("Unparked");
+
+        if was_parked {
+            let problem = self.check_unpark_position().await?;
+            if let Some(problem) = problem {
+                tracing::warn!("{}. Sync the mount before tracking or slewing.", problem);
+                self.session.log(format!("Re-sync required: {}", problem));
+            }
+            *self.settings.position_problem.write().await = problem;
+            self.save_parked_motor_pos(None).await;
+            let tracking_rate = self.settings.tracking_before_park.write().await.take();
+            if let Some(tracking_rate) =
+                tracking_rate.filter(|_| self.settings.resume_tracking_on_unpark)
+            {
+                if problem.is_some() {
+                    tracing::warn!("Not resuming tracking until the mount is synced");
+                } else {
+                    *self.settings.tracking_rate.write().await = tracking_rate;
+                    self.set_is_tracking(true).await?;
+                    self.session
+                        .log(format!("Resumed tracking at the {:?} rate", tracking_rate));
+                }
+            }
+        }
         Ok(())
     }
+
+    async fn check_unpark_position(&self) -> ASCOMResult<Option<PositionProblem>> {
+        let position = self.connection.get_pos().await?;
+        if let Some(parked_position) = *self.settings.parked_motor_pos.read().await {
+            if self.settings.unpark_position_tolerance < (position - parked_position).abs() {
+                return Ok(Some(PositionProblem::MovedWhileParked {
+                    parked_position,
+                    position,
+                }));
+            }
+        }
+
+        let mech_hour_angle = self.get_mech_ha().await?;
+        if !self
+            .settings
+            .mount_limits
+            .read()
+            .await
+            .is_valid_ha(mech_hour_angle)
+        {
+            return Ok(Some(PositionProblem::OutsideLimits { mech_hour_angle }));
+        }
+        Ok(None)
+    }
+
+    /// The position problem found on unpark, if it hasn't been resolved
+    pub async fn get_position_problem(&self) -> Option<PositionProblem> {
+        *self.settings.position_problem.read().await
+    }
+
+    /// Trusts the current position without a sync
+    pub async fn accept_position(&self) {
+        if self
+            .settings
+            .position_problem
+            .write()
+            .await
+            .take()
+            .is_some()
+        {
+            self.session.log("Position accepted without re-sync");
+        }
+    }
+
+    /// Tracking and slews use the position, so they're refused until it's trusted again
+    pub(in crate::telescope_control) async fn check_position_trusted(&self) -> ASCOMResult<()> {
+        match *self.settings.position_problem.read().await {
+            None => Ok(()),
+            Some(problem) => Err(ASCOMError::invalid_operation(format_args!(
+                "{}. Sync the mount or accept the position first.",
+                problem
+            ))),
+        }
+    }
+
+    /// The motor controller's position counter restarts when it is powered up,
+    /// so re-anchor the mechanical hour angle to the park position and return to the parked state
+    pub(in crate::telescope_control) async fn restore_park_after_power_cycle(
+        &self,
+    ) -> ASCOMResult<()> {
+        let motor_pos = self.connection.get_pos().await?;
+        let park_ha = *self.settings.park_ha.read().await;
+        *self.settings.mech_ha_offset.write().await = Self::calc_mech_ha_offset(park_ha, motor_pos);
+        self.connection
+            .park(motor_pos, None)
+            .await?
+            .await
+            .unwrap()?;
+        self.save_parked_motor_pos(Some(motor_pos)).await;
+        Ok(())
+    }
+
+    /// The driver forgets a park when it restarts, so return to the parked state the last run saved.
+    /// The motor is left where it is, for unparking to check against where it was parked.
+    pub(in crate::telescope_control) async fn restore_saved_park(
+        &self,
+        parked_pos: Degrees,
+    ) -> ASCOMResult<()> {
+        if self.connection.is_parked().await? {
+            return Ok(());
+        }
+        if self.connection.is_motor_running().await? {
+            tracing::warn!("Motor found running, so it's no longer parked");
+            self.save_parked_motor_pos(None).await;
+            return Ok(());
+        }
+        let park_ha = *self.settings.park_ha.read().await;
+        *self.settings.mech_ha_offset.write().await =
+            Self::calc_mech_ha_offset(park_ha, parked_pos);
+        let motor_pos = self.connection.get_pos().await?;
+        self.connection
+            .park(motor_pos, None)
+            .await?
+            .await
+            .unwrap()?;
+        self.session.log("Parked, as when the driver last ran");
+        Ok(())
+    }
+
+    /// Saved with the park position, so unparking after a restart still checks for movement
+    async fn save_parked_motor_pos(&self, pos: Option<Degrees>) {
+        *self.settings.parked_motor_pos.write().await = pos;
+        if let Some(park_store) = self.settings.park_store.clone() {
+            let stored =
+                tokio::task::spawn_blocking(move || park_store.store_parked_motor_pos(pos))
+                    .await
+                    .unwrap();
+            if let Err(e) = stored {
+                tracing::warn!("Couldn't save the parked motor position: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_park_approach_pos() {
+        let key = RotationDirectionKey::from_hemisphere(true);
+        let west =
+            MotorEncodingDirection::from(TrackingDirection::WithTracking.using(key)).get_sign_f64();
+        let wide_limits = MountLimits::new(18., 12., 0.);
+        let from_east = ParkApproach {
+            from: ParkApproachSide::East,
+            distance: 2.,
+        };
+        let from_west = ParkApproach {
+            from: ParkApproachSide::West,
+            distance: 2.,
+        };
+        let approach = |approach, current: f64, park: f64, limits| {
+            StarAdventurer::calc_park_approach_pos(approach, current, park, key, 5., limits)
+        };
+
+        // Already ends with a long enough westward move
+        assert_eq!(approach(from_east, 0., 10. * west, wide_limits), None);
+        // Ends moving east, so stop short to the east first
+        assert_eq!(
+            approach(from_east, 10. * west, 0., wide_limits),
+            Some(-2. * west)
+        );
+        // Ends moving west, but not far enough
+        assert_eq!(approach(from_east, 0., west, wide_limits), Some(-west));
+        // Overshoots to the west, then comes back east
+        assert_eq!(
+            approach(from_west, 0., 10. * west, wide_limits),
+            Some(12. * west)
+        );
+
+        // The overshoot would end at mechanical hour angle 5.8, past a west limit of 5.7
+        let limits = MountLimits::new(18., 5.7, 0.);
+        assert_eq!(approach(from_west, 0., 10. * west, limits), None);
+    }
 }
@@ -0,0 +1,128 @@
+use ascom_alpaca::api::DriveRate;
+use ascom_alpaca::ASCOMResult;
+use serde::Serialize;
+
+use crate::astro_math;
+use crate::config::SlewSafety;
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+
+// Fine enough to catch a horizon notch, a minute of sidereal time
+const HORIZON_STEP: Hours = 1. / 60.;
+
+/// Observing data for the current pointing or a target, as things stand now
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct PointingConditions {
+    pub right_ascension: Hours,
+    pub declination: Degrees,
+    pub altitude: Degrees,
+    pub azimuth: Degrees,
+    pub airmass: Option<f64>, // None below the horizon
+    pub hour_angle: Hours,    // -12 to 12, negative east of the meridian
+    pub secs_to_transit: f64,
+    pub secs_to_mount_limit: Option<f64>, // Tracking on to the west limit, less the flip margin. None in alt-az mode
+    pub secs_to_horizon: Option<f64>, // Until it sets below the configured horizon. None if there is none or it doesn't set
+}
+
+impl StarAdventurer {
+    /// The conditions at the given ra and dec, or at the current pointing
+    pub async fn get_pointing_conditions(
+        &self,
+        target: Option<(Hours, Degrees)>,
+    ) -> ASCOMResult<PointingConditions> {
+        let location = self.settings.observation_location.get();
+        let (ra, dec, ha, mech_ha) = match target {
+            Some((ra, dec)) => {
+                check_ra(ra)?;
+                check_dec(dec)?;
+                let time = Self::calculate_utc_date(self.get_sky_date_offset().await);
+                let ha = astro_math::calculate_hour_angle(time, location.longitude, ra);
+                let mech_ha = if self.settings.alt_az {
+                    None
+                } else {
+                    let pier_side = self.predict_destination_side_of_pier(ra, dec).await?;
+                    Some(Self::calc_mech_ha_from_ha(ha, pier_side))
+                };
+                (ra, dec, ha, mech_ha)
+            }
+            None => {
+                let mech_ha = if self.settings.alt_az {
+                    None
+                } else {
+                    Some(self.get_mech_ha().await?)
+                };
+                (
+                    self.get_ra().await?,
+                    self.get_dec().await?,
+                    self.get_ha().await?,
+                    mech_ha,
+                )
+            }
+        };
+
+        let altitude = astro_math::calculate_alt_from_ha_dec(ha, dec, location.latitude);
+        let azimuth = astro_math::calculate_az_from_ha_dec(ha, dec, location.latitude);
+        let mount_limits = *self.settings.mount_limits.read().await;
+        Ok(PointingConditions {
+            right_ascension: ra,
+            declination: dec,
+            altitude,
+            azimuth,
+            airmass: astro_math::calculate_airmass(altitude),
+            hour_angle: astro_math::modulo(ha + 12., 24.) - 12.,
+            secs_to_transit: sidereal_hours_to_secs(astro_math::modulo(-ha, 24.)),
+            secs_to_mount_limit: mech_ha
+                .map(|mech_ha| sidereal_hours_to_secs(mount_limits.tracking_left(mech_ha))),
+            secs_to_horizon: hours_to_horizon(
+                ha,
+                dec,
+                location.latitude,
+                &*self.settings.slew_safety.read().await,
+            )
+            .map(sidereal_hours_to_secs),
+        })
+    }
+}
+
+/// Seconds for the sky to turn through the hour angle. Negative if already past.
+fn sidereal_hours_to_secs(hours: Hours) -> f64 {
+    hours * 15. / DriveRate::Sidereal.to_degrees()
+}
+
+/// Hours of hour angle until the position sets below the horizon, zero if it's already below
+fn hours_to_horizon(ha: Hours, dec: Degrees, lat: Degrees, safety: &SlewSafety) -> Option<Hours> {
+    (0..(24. / HORIZON_STEP) as u32)
+        .map(|step| step as f64 * HORIZON_STEP)
+        .find_map(|hours| {
+            let ha = ha + hours;
+            let altitude = astro_math::calculate_alt_from_ha_dec(ha, dec, lat);
+            let azimuth = astro_math::calculate_az_from_ha_dec(ha, dec, lat);
+            match safety.min_altitude_at(azimuth) {
+                Some(min_altitude) if altitude < min_altitude => Some(Some(hours)),
+                Some(_) => None,
+                None => Some(None), // No horizon configured
+            }
+        })
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn test_hours_to_horizon() {
+        let safety = SlewSafety {
+            min_altitude: Some(0.),
+            ..Default::default()
+        };
+        // On the equator, setting six hours after transit
+        let hours = hours_to_horizon(0., 0., 40., &safety).unwrap();
+        assert_float_absolute_eq!(hours, 6., 2. * HORIZON_STEP);
+        assert_eq!(hours_to_horizon(12., 0., 40., &safety), Some(0.));
+        // Circumpolar
+        assert_eq!(hours_to_horizon(0., 80., 40., &safety), None);
+        assert_eq!(hours_to_horizon(0., 0., 40., &SlewSafety::default()), None);
+    }
+}
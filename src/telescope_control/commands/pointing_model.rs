@@ -0,0 +1,62 @@
+use ascom_alpaca::ASCOMResult;
+use serde::Serialize;
+
+use crate::telescope_control::pointing_model::{ModelTerms, SyncPoint};
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PointingModelStatus {
+    pub enabled: bool,
+    pub points: Vec<SyncPoint>,
+    pub terms: ModelTerms,
+    pub rms_arcsec: f64,
+}
+
+impl StarAdventurer {
+    pub async fn get_pointing_model(&self) -> PointingModelStatus {
+        let model = self.settings.pointing_model.read().await;
+        PointingModelStatus {
+            enabled: self.settings.pointing_model_settings.enabled,
+            points: model.points().to_vec(),
+            terms: model.terms(),
+            rms_arcsec: model.rms() * 3600.,
+        }
+    }
+
+    /// The next sync starts a new model from scratch
+    pub async fn reset_pointing_model(&self) {
+        self.settings.pointing_model.write().await.reset();
+    }
+
+    /// Adds a sync to the model, or starts a new one if the origin was just moved to the synced position
+    pub(in crate::telescope_control) async fn record_sync_point(
+        &self,
+        origin_moved: bool,
+        true_ha: Hours,
+        true_dec: Degrees,
+    ) -> ASCOMResult<()> {
+        let mut model = self.settings.pointing_model.write().await;
+        if origin_moved {
+            model.reset();
+        }
+        if !self.settings.pointing_model_settings.enabled {
+            return Ok(());
+        }
+        let point = SyncPoint {
+            mount_ha: self.get_mount_ha().await?,
+            mount_dec: *self.settings.declination.read().await,
+            true_ha,
+            true_dec,
+        };
+        model.add_point(point, self.settings.pointing_model_settings.max_points);
+        Ok(())
+    }
+
+    /// Whether a sync should refine the model rather than move the origin
+    pub(in crate::telescope_control) async fn is_refining_pointing_model(&self) -> bool {
+        self.settings.pointing_model_settings.enabled
+            && !self.settings.pointing_model.read().await.is_empty()
+            && self.get_position_problem().await.is_none()
+    }
+}
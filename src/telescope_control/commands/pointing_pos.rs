@@ -1,4 +1,4 @@
-use tokio::join;
+use chrono::{DateTime, Utc};
 
 use crate::astro_math;
 use crate::rotation_direction::RotationDirectionKey;
@@ -7,6 +7,32 @@ use crate::util::*;
 use ascom_alpaca::api::SideOfPier;
 use ascom_alpaca::ASCOMResult;
 
+// Hour angles this close count as the same position, so rounding in the position doesn't unfreeze
+const FREEZE_HA_TOLERANCE: Hours = 1. / 54_000.; // One arcsecond
+
+/// The sky time the reported RA is pinned to while the mount is stopped
+#[derive(Debug, Copy, Clone)]
+pub struct FrozenCoordinates {
+    ha: Hours, // Any move or sync changes this, which unfreezes
+    sky_time: DateTime<Utc>,
+}
+
+impl FrozenCoordinates {
+    /// The pinned sky time, or now, pinned afresh, if the hour angle has changed since
+    fn sky_time_at(frozen: &mut Option<Self>, ha: Hours, now: DateTime<Utc>) -> DateTime<Utc> {
+        // Compared across 0h, and always to where it was frozen so a slow creep still unfreezes
+        let moved =
+            |f: &Self| FREEZE_HA_TOLERANCE < (astro_math::modulo(ha - f.ha + 12., 24.) - 12.).abs();
+        match *frozen {
+            Some(f) if !moved(&f) => f.sky_time,
+            _ => {
+                *frozen = Some(Self { ha, sky_time: now });
+                now
+            }
+        }
+    }
+}
+
 impl StarAdventurer {
     pub fn calc_mech_ha_from_ha(ha: Hours, pier_side: SideOfPier) -> Hours {
         astro_math::modulo(
@@ -30,12 +56,85 @@ impl StarAdventurer {
         )
     }
 
-    pub(in crate::telescope_control) async fn get_ha(&self) -> ASCOMResult<Hours> {
-        let mech_ha = self.get_mech_ha().await?;
+    /// In alt-az mode the RA axis turns in azimuth, with zero mechanical hour angle facing south
+    pub fn calc_az_from_mech_ha(mech_ha: Hours) -> Degrees {
+        astro_math::modulo(astro_math::hours_to_deg(mech_ha) + 180., 360.)
+    }
+
+    pub fn calc_mech_ha_from_az(az: Degrees) -> Hours {
+        astro_math::modulo(astro_math::deg_to_hours(az - 180.), 24.)
+    }
+
+    /// In alt-az mode, the altitude the declination knob is set to and the azimuth the RA axis has turned to
+    pub(in crate::telescope_control) async fn get_axis_alt_az(
+        &self,
+    ) -> ASCOMResult<(Degrees, Degrees)> {
+        let mech_ha = self.calc_mech_ha_at(self.get_reckoned_motor_pos().await?);
+        let alt = *self.settings.declination.read().await;
+        Ok((alt, Self::calc_az_from_mech_ha(mech_ha)))
+    }
+
+    /// Where the motor position puts the scope, before the pointing model
+    pub(in crate::telescope_control) async fn get_mount_ha(&self) -> ASCOMResult<Hours> {
+        let mech_ha = self.calc_mech_ha_at(self.get_reckoned_motor_pos().await?);
         let pier_side = self.get_side_of_pier().await?;
         Ok(Self::calc_ha_from_mech_ha(mech_ha, pier_side))
     }
 
+    /// Where the scope really points, through the pointing model
+    pub(in crate::telescope_control) async fn get_ha_dec(&self) -> ASCOMResult<(Hours, Degrees)> {
+        if self.settings.alt_az {
+            let (alt, az) = self.get_axis_alt_az().await?;
+            return Ok(astro_math::calculate_ha_dec_from_alt_az(
+                alt,
+                az,
+                self.settings.observation_location.get().latitude,
+            ));
+        }
+        let mount_ha = self.get_mount_ha().await?;
+        let mount_dec = *self.settings.declination.read().await;
+        Ok(self
+            .settings
+            .pointing_model
+            .read()
+            .await
+            .to_true(mount_ha, mount_dec))
+    }
+
+    /// The motor position extrapolated from the last fix, so readouts move smoothly between reads.
+    /// Parking and limits use get_mech_ha, which always reads the motor.
+    async fn get_reckoned_motor_pos(&self) -> ASCOMResult<Degrees> {
+        let rate = self.connection.get_known_rate().await?;
+        self.settings
+            .reckoned_pos
+            .get_or_inquire(rate, self.connection.get_pos())
+            .await
+    }
+
+    pub(in crate::telescope_control) async fn get_ha(&self) -> ASCOMResult<Hours> {
+        Ok(self.get_ha_dec().await?.0)
+    }
+
+    /// The hour angle of the reported coordinates, 0..24
+    pub async fn get_hour_angle(&self) -> ASCOMResult<Hours> {
+        Ok(astro_math::modulo(self.get_ha().await?, 24.))
+    }
+
+    /// 0..24, read straight from the motor. Doesn't depend on the pier side or pointing model.
+    pub async fn get_mechanical_hour_angle(&self) -> ASCOMResult<Hours> {
+        self.get_mech_ha().await
+    }
+
+    /// Degrees from where the motor was when the controller was powered on, positive clockwise
+    pub async fn get_motor_position(&self) -> ASCOMResult<Degrees> {
+        self.connection.get_pos().await
+    }
+
+    /// The mechanical hour angle at motor position 0, set by syncs and by restoring the park after a power cycle
+    pub fn get_mech_ha_offset(&self) -> Hours {
+        self.settings.mech_ha_offset.get()
+    }
+
     // With the telescope pointing at the meridian, this is zero
     pub fn calc_ha(
         motor_pos: Degrees,
@@ -60,10 +159,15 @@ impl StarAdventurer {
     /// in the coordinate system given by the EquatorialSystem property
     pub async fn get_ra(&self) -> ASCOMResult<Hours> {
         let ha = self.get_ha().await?;
-        let (observation_location, date_offset) = join!(
-            async { *self.settings.observation_location.read().await },
-            async { *self.settings.date_offset.read().await },
-        );
+        let observation_location = self.settings.observation_location.get();
+        let date_offset = self.get_sky_date_offset().await;
+
+        if self.settings.freeze_coordinates_while_stopped {
+            let sky_time = self.get_frozen_sky_time(ha, date_offset).await?;
+            let lst =
+                astro_math::calculate_local_sidereal_time(sky_time, observation_location.longitude);
+            return Ok(astro_math::modulo(lst - ha, 24.));
+        }
 
         Ok(Self::calc_ra(
             ha,
@@ -72,31 +176,102 @@ impl StarAdventurer {
         ))
     }
 
+    /// The sky time is pinned when the mount is first seen stopped at an hour angle,
+    /// and released as soon as it moves or is synced
+    async fn get_frozen_sky_time(
+        &self,
+        ha: Hours,
+        date_offset: chrono::Duration,
+    ) -> ASCOMResult<DateTime<Utc>> {
+        let now = Self::calculate_utc_date(date_offset);
+        let mut frozen = self.settings.frozen_coordinates.write().await;
+
+        if !self.connection.is_stationary().await? {
+            *frozen = None;
+            return Ok(now);
+        }
+
+        Ok(FrozenCoordinates::sky_time_at(&mut frozen, ha, now))
+    }
+
     /// The declination (degrees) of the mount's current equatorial coordinates, in the coordinate system given by the EquatorialSystem property.
     /// Reading the property will raise an error if the value is unavailable.
     pub async fn get_dec(&self) -> ASCOMResult<Degrees> {
-        Ok(*self.settings.declination.read().await)
+        if !self.settings.alt_az && self.settings.pointing_model.read().await.is_empty() {
+            // Without a model this doesn't need the motor
+            return Ok(*self.settings.declination.read().await);
+        }
+        Ok(self.get_ha_dec().await?.1)
     }
 
     /// The altitude above the local horizon of the mount's current position (degrees, positive up)
     pub async fn get_altitude(&self) -> ASCOMResult<Degrees> {
-        let hour_angle = self.get_ha().await?;
+        if self.settings.alt_az {
+            return Ok(self.get_axis_alt_az().await?.0);
+        }
+        let (hour_angle, declination) = self.get_ha_dec().await?;
 
         Ok(astro_math::calculate_alt_from_ha_dec(
             hour_angle,
-            *self.settings.declination.read().await,
-            self.settings.observation_location.read().await.latitude,
+            declination,
+            self.settings.observation_location.get().latitude,
         ))
     }
 
     /// The azimuth at the local horizon of the mount's current position (degrees, North-referenced, positive East/clockwise).
     pub async fn get_azimuth(&self) -> ASCOMResult<f64> {
-        let hour_angle = self.get_ha().await?;
+        if self.settings.alt_az {
+            return Ok(self.get_axis_alt_az().await?.1);
+        }
+        let (hour_angle, declination) = self.get_ha_dec().await?;
 
         Ok(astro_math::calculate_az_from_ha_dec(
             hour_angle,
-            *self.settings.declination.read().await,
-            self.settings.observation_location.read().await.latitude,
+            declination,
+            self.settings.observation_location.get().latitude,
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_frozen_sky_time() {
+        let start = Utc.with_ymd_and_hms(2023, 6, 21, 0, 0, 0).unwrap();
+        let later = start + chrono::Duration::seconds(30);
+        let mut frozen = None;
+
+        assert_eq!(
+            FrozenCoordinates::sky_time_at(&mut frozen, 3., start),
+            start
+        );
+        assert_eq!(
+            FrozenCoordinates::sky_time_at(&mut frozen, 3., later),
+            start
+        );
+        // Rounding in the position stays frozen
+        let rounded = 3. + FREEZE_HA_TOLERANCE / 2.;
+        assert_eq!(
+            FrozenCoordinates::sky_time_at(&mut frozen, rounded, later),
+            start
+        );
+        // A move unfreezes
+        let moved = 3. + FREEZE_HA_TOLERANCE * 2.;
+        assert_eq!(
+            FrozenCoordinates::sky_time_at(&mut frozen, moved, later),
+            later
+        );
+
+        // Across 0h
+        let mut frozen = None;
+        FrozenCoordinates::sky_time_at(&mut frozen, 24. - FREEZE_HA_TOLERANCE / 4., start);
+        let across = FREEZE_HA_TOLERANCE / 4.;
+        assert_eq!(
+            FrozenCoordinates::sky_time_at(&mut frozen, across, later),
+            start
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use ascom_alpaca::{ASCOMError, ASCOMResult};
+
+use crate::telescope_control::scheduler::{Schedule, ScheduledTarget};
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+
+impl StarAdventurer {
+    pub fn get_schedule(&self) -> Schedule {
+        self.scheduler.get()
+    }
+
+    /// Queues a target to be slewed to once its window opens. Returns it with its id.
+    pub fn schedule_target(&self, target: ScheduledTarget) -> ASCOMResult<ScheduledTarget> {
+        check_ra(target.right_ascension)?;
+        check_dec(target.declination)?;
+        if let (Some(earliest), Some(latest)) = (target.earliest_start, target.latest_start) {
+            if latest < earliest {
+                return Err(ASCOMError::invalid_value(
+                    "The latest start is before the earliest start",
+                ));
+            }
+        }
+        let target = self.scheduler.add(target);
+        self.session.log(format!(
+            "Scheduled target {} as #{}",
+            target.name, target.id
+        ));
+        Ok(target)
+    }
+
+    pub fn unschedule_target(&self, id: u32) -> ASCOMResult<()> {
+        if !self.scheduler.remove(id) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "No scheduled target #{}",
+                id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Drops the queued targets. One already started carries on.
+    pub fn clear_schedule(&self) {
+        self.scheduler.clear()
+    }
+}
@@ -0,0 +1,42 @@
+use super::super::session::Session;
+use super::super::star_adventurer::StarAdventurer;
+use ascom_alpaca::{ASCOMError, ASCOMResult};
+
+impl StarAdventurer {
+    /// Ends any running observing session and starts a new one
+    pub async fn start_session(&self) -> ASCOMResult<Session> {
+        let longitude = self.settings.observation_location.get().longitude;
+        self.session.start(longitude).await
+    }
+
+    /// Ends the running observing session, returning its summary
+    pub async fn end_session(&self) -> ASCOMResult<Option<Session>> {
+        self.session.end().await
+    }
+
+    pub fn get_session(&self) -> Option<Session> {
+        self.session.current()
+    }
+
+    /// The summary of the most recently ended session
+    pub fn get_last_session_summary(&self) -> Option<Session> {
+        self.session.last_summary()
+    }
+
+    /// The running session, or else the last one, as "json" or "csv"
+    pub fn export_session(&self, format: &str) -> ASCOMResult<String> {
+        let session = self
+            .session
+            .current()
+            .or_else(|| self.session.last_summary())
+            .ok_or_else(|| ASCOMError::invalid_operation("No session to export"))?;
+        match format {
+            "" | "json" => Ok(serde_json::to_string_pretty(&session).unwrap()),
+            "csv" => Ok(session.summary_csv()),
+            _ => Err(ASCOMError::invalid_value(format_args!(
+                "Unknown session export format \"{}\"",
+                format
+            ))),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+
+use crate::config::{ParkStore, SetupConfig};
+use crate::telescope_control::StarAdventurer;
+
+impl StarAdventurer {
+    /// The settings shown on the setup page, as last saved or else from the config
+    pub async fn get_setup_config(&self) -> ASCOMResult<SetupConfig> {
+        let store = self.config_store()?;
+        tokio::task::spawn_blocking(move || store.load_setup())
+            .await
+            .unwrap()
+            .map_err(Self::config_file_error)
+    }
+
+    /// Saves the settings from the setup page to the state file.
+    /// The config watcher applies those that can change while running.
+    pub async fn store_setup_config(&self, setup: SetupConfig) -> ASCOMResult<()> {
+        Self::check_setup_config(&setup)?;
+        let store = self.config_store()?;
+        tokio::task::spawn_blocking(move || store.store_setup(&setup))
+            .await
+            .unwrap()
+            .map_err(Self::config_file_error)?;
+        self.session.log("Setup saved");
+        Ok(())
+    }
+
+    fn config_store(&self) -> ASCOMResult<ParkStore> {
+        self.settings
+            .park_store
+            .clone()
+            .ok_or_else(|| ASCOMError::invalid_operation("Only the server has a config to set up"))
+    }
+
+    fn config_file_error(e: impl std::fmt::Display) -> ASCOMError {
+        ASCOMError::new(
+            ASCOMErrorCode::new_for_driver(10),
+            format_args!("Couldn't access the config: {}", e),
+        )
+    }
+
+    fn check_setup_config(setup: &SetupConfig) -> ASCOMResult<()> {
+        let location = &setup.observation_location;
+        if !(-90. ..=90.).contains(&location.latitude) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Latitude of {} is outside the valid range of -90 to 90",
+                location.latitude
+            )));
+        }
+        if !(-180. ..=180.).contains(&location.longitude) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Longitude of {} is outside the valid range of -180 to 180",
+                location.longitude
+            )));
+        }
+        let hours = [
+            setup.mount_limit_east,
+            setup.mount_limit_west,
+            setup.flip_before_limit,
+        ];
+        if !location.elevation.is_finite() || !hours.iter().all(|h| h.is_finite()) {
+            return Err(ASCOMError::invalid_value("Setup values must be numbers"));
+        }
+        let details = &setup.telescope_details;
+        if [
+            details.aperture,
+            details.aperture_area,
+            details.focal_length,
+        ]
+        .iter()
+        .flatten()
+        .any(|value| !(0. < *value))
+        {
+            return Err(ASCOMError::invalid_value(
+                "Telescope details must be positive",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_check_setup_config() {
+        let config: Config = confy::load_path("test_config.toml").unwrap();
+        let setup = SetupConfig {
+            mount_limit_east: config.other.mount_limit_east,
+            mount_limit_west: config.other.mount_limit_west,
+            flip_before_limit: config.other.flip_before_limit,
+            observation_location: config.observation_location,
+            telescope_details: config.telescope_details,
+        };
+        assert!(StarAdventurer::check_setup_config(&setup).is_ok());
+
+        let mut bad = setup;
+        bad.observation_location.longitude = 200.;
+        assert!(StarAdventurer::check_setup_config(&bad).is_err());
+        let mut bad = setup;
+        bad.telescope_details.focal_length = Some(0.);
+        assert!(StarAdventurer::check_setup_config(&bad).is_err());
+    }
+}
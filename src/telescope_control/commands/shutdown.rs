@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use ascom_alpaca::{ASCOMError, ASCOMResult};
+
+use crate::config::SafeAction;
+use crate::telescope_control::StarAdventurer;
+
+// A park can be a long slew, but the process shouldn't hang on a wedged mount
+const PARK_TIMEOUT: Duration = Duration::from_secs(180);
+
+impl StarAdventurer {
+    /// Leaves the mount safe before the driver exits: aborts any slew, applies `on-shutdown`
+    /// and ends the session so its summary is written
+    pub async fn shutdown(&self) {
+        self.scheduler.clear();
+        if self.is_connected().await && !self.is_parked().await.unwrap_or(true) {
+            if let Err(e) = self.abort_slew().await {
+                tracing::error!("Couldn't abort the slew on shutdown: {}", e);
+            }
+            if let Err(e) = self.apply_safe_action(self.settings.on_shutdown).await {
+                tracing::error!("Couldn't make the mount safe on shutdown: {}", e);
+            }
+        }
+        self.session.log("Driver shut down");
+        if let Err(e) = self.session.end().await {
+            tracing::error!("{}", e);
+        }
+    }
+
+    pub(in crate::telescope_control) async fn apply_safe_action(
+        &self,
+        action: SafeAction,
+    ) -> ASCOMResult<()> {
+        match action {
+            SafeAction::Nothing => Ok(()),
+            SafeAction::Stop => {
+                if self.is_tracking().await? {
+                    self.set_is_tracking(false).await?;
+                }
+                Ok(())
+            }
+            SafeAction::Park => tokio::time::timeout(PARK_TIMEOUT, self.park())
+                .await
+                .unwrap_or_else(|_| Err(ASCOMError::invalid_operation("Timed out parking"))),
+        }
+    }
+}
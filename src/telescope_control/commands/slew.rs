@@ -2,28 +2,83 @@ use std::future::Future;
 use std::mem;
 use std::time::Duration;
 
-use tokio::{join, task, time};
+use chrono::{DateTime, Utc};
+use futures::future::Either;
+use serde::Serialize;
+use tokio::{join, task};
 
 use crate::astro_math;
 use crate::rotation_direction::{RotationDirection, RotationDirectionKey};
 use crate::telescope_control::connection::consts;
+use crate::telescope_control::motion_limit_monitor;
+use crate::telescope_control::settle_timer::SettleSource;
 use crate::telescope_control::slew_def::Slew;
+use crate::telescope_control::vibration_guard::GuardedOperation;
 use crate::tracking_direction::TrackingDirection;
 use crate::util::*;
 
+use super::super::commands::slew_check::SlewVerdict;
+use super::super::commands::slew_progress::ActiveSlew;
 use super::super::commands::target::Target;
+use super::super::dec_knob::{dec_motor_change, DeclinationSlewPlan};
+use super::super::events::Event;
 use super::super::star_adventurer::{DeclinationSlew, StarAdventurer};
 use ascom_alpaca::api::{Axis, AxisRate, SideOfPier};
 use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
 
+// Reported knob turns leaving less than this finish the declination slew
+const DEC_TURN_EPSILON: Degrees = 1E-6;
+
+/// Where a slew was headed, so it can be re-planned if it's interrupted
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SlewTarget {
+    Equatorial {
+        right_ascension: Hours,
+        declination: Degrees,
+    },
+    HourAngle {
+        hour_angle: Hours,
+        declination: Degrees,
+    },
+    MechanicalHourAngle {
+        mech_hour_angle: Hours,
+    },
+}
+
+/// A slew that was aborted or failed before reaching its target
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct InterruptedSlew {
+    pub target: SlewTarget,
+    pub interrupted_at: DateTime<Utc>,
+    pub progress: Option<f64>, // Fraction of the RA motion completed. None if the position couldn't be read
+}
+
 impl StarAdventurer {
     /// True if telescope is currently moving in response to one of the Slew methods or the MoveAxis(TelescopeAxes, Double) method
-    /// False at all other times.
+    /// Stays true while settling afterwards and while waiting on the declination knob. False at all other times.
     pub async fn is_slewing(&self) -> ASCOMResult<bool> {
+        // The slew stays active from the motor stopping until settling starts, so there's no gap
         Ok(matches!(
             &*self.dec_slew.read().await,
             DeclinationSlew::Waiting { .. }
-        ) || self.connection.is_slewing().await?)
+        ) || self
+            .settle_timer
+            .source()
+            .map_or(false, |source| source != SettleSource::Vibration)
+            || self.settings.active_slew.lock().unwrap().is_some()
+            || self.connection.is_slewing().await?
+            || self.connection.is_dec_slewing().await?)
+    }
+
+    pub(in crate::telescope_control) async fn get_settle_duration(&self) -> Duration {
+        Duration::from_secs(*self.settings.post_slew_settle_time.read().await as u64)
+    }
+
+    /// Settles for the slew settle time after a dither, so it reports like any other motion
+    pub async fn settle_after_dither(&self) {
+        self.settle_timer
+            .start(SettleSource::Dither, self.get_settle_duration().await);
     }
 
     /// Returns the post-slew settling time (sec.)
@@ -45,6 +100,78 @@ impl StarAdventurer {
         }
     }
 
+    /// How to turn the declination knob to finish the pending slew, if there is one
+    pub async fn get_pending_dec_slew_plan(&self) -> Option<DeclinationSlewPlan> {
+        let (dec_change, turned, meridian_flip) = match &*self.dec_slew.read().await {
+            DeclinationSlew::Waiting {
+                dec_change,
+                turned,
+                meridian_flip,
+                ..
+            } => (*dec_change, *turned, *meridian_flip),
+            DeclinationSlew::Idle => return None,
+        };
+        let plan = self.plan_dec_slew(dec_change, meridian_flip).await;
+        Some(DeclinationSlewPlan {
+            turns_done: turned.abs() / self.settings.dec_knob.degrees_per_turn,
+            ..plan
+        })
+    }
+
+    /// Takes the given turns of the knob, in the planned direction, off the pending slew.
+    /// Finishes the slew once nothing is left. Returns what's left to do.
+    pub async fn report_dec_turns(&self, turns: f64) -> ASCOMResult<Option<DeclinationSlewPlan>> {
+        if !(0. < turns && turns.is_finite()) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Invalid number of turns: {}",
+                turns
+            )));
+        }
+        let mut dec_slew_lock = self.dec_slew.write().await;
+        let finished = match &mut *dec_slew_lock {
+            DeclinationSlew::Waiting {
+                dec_change, turned, ..
+            } => {
+                let change = (turns * self.settings.dec_knob.degrees_per_turn)
+                    .min(dec_change.abs())
+                    * dec_change.signum();
+                *dec_change -= change;
+                *turned += change;
+                *self.settings.declination.write().await += change;
+                dec_change.abs() < DEC_TURN_EPSILON
+            }
+            DeclinationSlew::Idle => {
+                return Err(ASCOMError::invalid_operation(
+                    "No declination slew is waiting on the knob",
+                ))
+            }
+        };
+        drop(dec_slew_lock);
+
+        if finished {
+            self.complete_dec_slew().await;
+            return Ok(None);
+        }
+        let plan = self.get_pending_dec_slew_plan().await;
+        if let Some(plan) = plan {
+            self.events.publish(Event::DeclinationSlewProgress { plan });
+        }
+        Ok(plan)
+    }
+
+    /// Must be called before the pier side is flipped for the slew
+    async fn plan_dec_slew(&self, dec_change: Degrees, meridian_flip: bool) -> DeclinationSlewPlan {
+        let pier_side = self.settings.pier_side.get();
+        let observation_location = self.settings.observation_location.get();
+        DeclinationSlewPlan::new(
+            dec_change,
+            meridian_flip,
+            pier_side,
+            observation_location.in_north(),
+            &self.settings.dec_knob,
+        )
+    }
+
     pub async fn complete_dec_slew(&self) {
         let mut dec_slew_lock = self.dec_slew.write().await;
         let dec_slew = mem::take(&mut *dec_slew_lock);
@@ -52,6 +179,7 @@ impl StarAdventurer {
             dec_change,
             meridian_flip,
             finisher,
+            ..
         } = dec_slew
         {
             let mut declination_lock = self.settings.declination.write().await;
@@ -59,10 +187,22 @@ impl StarAdventurer {
             if meridian_flip {
                 self.settings.pier_side.write().await.flip();
             }
+            // Turning the knob shakes the mount as much as the RA motion does
+            self.settle_timer
+                .start(SettleSource::Slew, self.get_settle_duration().await);
             finisher.finish(AbortResult::Completed(()))
         }
     }
 
+    /// Stops waiting on the user to finish a declination slew
+    pub(in crate::telescope_control) async fn abort_dec_slew(&self) {
+        let mut dec_slew_lock = self.dec_slew.write().await;
+        let dec_slew = mem::take(&mut *dec_slew_lock);
+        if let DeclinationSlew::Waiting { finisher, .. } = dec_slew {
+            finisher.finish(AbortResult::Aborted(()))
+        }
+    }
+
     /// Immediately Stops a slew in progress.
     pub async fn abort_slew(&self) -> ASCOMResult<()> {
         // Spec wants this for some reason
@@ -73,40 +213,108 @@ impl StarAdventurer {
             ));
         }
 
-        let mut dec_slew_lock = self.dec_slew.write().await;
-        let dec_slew = mem::take(&mut *dec_slew_lock);
-        if let DeclinationSlew::Waiting { finisher, .. } = dec_slew {
-            finisher.finish(AbortResult::Aborted(()))
+        self.abort_dec_slew().await;
+        if self.connection.is_dec_slewing().await? {
+            self.stop_dec_move().await?;
         }
 
+        let was_slewing = self.connection.is_slewing().await?;
         self.connection.abort_slew().await?;
+        // Don't leave Slewing true while the slew task catches up or settles
+        *self.settings.active_slew.lock().unwrap() = None;
+        self.settle_timer.cancel();
+        if was_slewing {
+            self.vibration_guard
+                .note_rate_change(GuardedOperation::Slew);
+        }
         Ok(())
     }
 
-    pub(in crate::telescope_control) fn get_axis_rate_range() -> AxisRate {
-        // experimentally, 1_103 to 16_000_000 for period
-        AxisRate {
-            // TODO are these accurate? testing needed
-            minimum: consts::MIN_SPEED,
-            maximum: consts::SLEW_SPEED_WITH_TRACKING.min(consts::SLEW_SPEED_AGAINST_TRACKING),
-        }
+    /// Stops the motors even if a task has wedged, bypassing the task queue. Tracking is off afterwards.
+    /// Doesn't wait on any lock a task could be holding.
+    pub async fn panic_stop(&self) -> ASCOMResult<()> {
+        tracing::warn!("Panic stop");
+        self.connection.panic_stop().await?;
+        *self.settings.active_slew.lock().unwrap() = None;
+        self.settle_timer.cancel();
+        self.session.log("Panic stop");
+        Ok(())
+    }
+
+    /// What the controller can actually hold, from the parameters it reported when connecting:
+    /// a continuous range of slow rates and each of the high speeds. Rates in between can't be honored.
+    /// Assumes a Star Adventurer while disconnected.
+    pub(in crate::telescope_control) async fn get_axis_rate_ranges(&self) -> Vec<AxisRate> {
+        self.connection
+            .get_speed_capabilities()
+            .await
+            .unwrap_or_default()
+            .rate_ranges()
+            .into_iter()
+            .map(|(minimum, maximum)| AxisRate { minimum, maximum })
+            .collect()
+    }
+
+    /// Maps a requested MoveAxis rate onto the reported ranges, or None if it's outside all of them.
+    /// Snaps onto the ends of ranges to absorb rounding by clients.
+    fn snap_axis_rate(ranges: &[AxisRate], rate: Degrees) -> Option<Degrees> {
+        const TOLERANCE: f64 = 1e-6;
+        ranges.iter().find_map(|range| {
+            if (range.minimum..=range.maximum).contains(&rate) {
+                Some(rate)
+            } else if (rate - range.minimum).abs() <= TOLERANCE * range.minimum {
+                Some(range.minimum)
+            } else if (rate - range.maximum).abs() <= TOLERANCE * range.maximum {
+                Some(range.maximum)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The closest rate to `rate` that's in one of the reported ranges
+    pub(in crate::telescope_control) fn nearest_axis_rate(
+        ranges: &[AxisRate],
+        rate: Degrees,
+    ) -> Degrees {
+        ranges
+            .iter()
+            .map(|range| rate.clamp(range.minimum, range.maximum))
+            .min_by(|a, b| (a - rate).abs().total_cmp(&(b - rate).abs()))
+            .unwrap()
+    }
+
+    /// The dec motor's ranges, which are reported like RA's
+    async fn get_dec_axis_rate_ranges(&self) -> Vec<AxisRate> {
+        self.connection
+            .get_dec_speed_capabilities()
+            .await
+            .unwrap_or_default()
+            .rate_ranges()
+            .into_iter()
+            .map(|(minimum, maximum)| AxisRate { minimum, maximum })
+            .collect()
     }
 
     /// The rates at which the telescope may be moved about the specified axis by the MoveAxis(TelescopeAxes, Double) method.
     pub async fn get_axis_rates(&self, axis: Axis) -> ASCOMResult<Vec<AxisRate>> {
-        Ok(if axis == Axis::Primary {
-            vec![Self::get_axis_rate_range()]
-        } else {
-            vec![AxisRate {
+        Ok(match axis {
+            Axis::Primary => self.get_axis_rate_ranges().await,
+            Axis::Secondary if self.has_dec_motor() => self.get_dec_axis_rate_ranges().await,
+            _ => vec![AxisRate {
                 minimum: 0.,
                 maximum: 0.,
-            }]
+            }],
         })
     }
 
-    /// True if this telescope can move the requested axis.
+    /// True if this telescope can move the requested axis. Dec needs a dec motor.
     pub async fn can_move_axis(&self, axis: Axis) -> ASCOMResult<bool> {
-        Ok(axis == Axis::Primary)
+        Ok(match axis {
+            Axis::Primary => true,
+            Axis::Secondary => self.has_dec_motor(),
+            _ => false,
+        })
     }
 
     /// True if this telescope is capable of programmed finding its home position (FindHome() method).
@@ -123,21 +331,35 @@ impl StarAdventurer {
     /// Rate in deg/sec
     /// TODO Does this stop other slewing? Returning an error for now
     pub async fn move_axis(&self, axis: Axis, rate: Degrees) -> ASCOMResult<()> {
-        if axis != Axis::Primary {
-            return Err(ASCOMError::invalid_value("Can only slew on primary axis"));
+        match axis {
+            Axis::Primary => {}
+            Axis::Secondary if self.has_dec_motor() => return self.move_dec_axis(rate).await,
+            _ => {
+                return Err(ASCOMError::invalid_value(
+                    "Can only slew on primary axis, or secondary with a dec motor",
+                ))
+            }
         }
 
         // rate of 0 is just an alias for killing slews (i think) so we can redirect there
         if rate == 0. {
             tracing::info!("Redirecting moveaxis to abort");
-            return self.abort_slew().await;
+            let was_moving = self.connection.is_slewing().await?;
+            self.abort_slew().await?;
+            if was_moving {
+                self.settle_timer
+                    .start(SettleSource::MoveAxis, self.get_settle_duration().await);
+            }
+            return Ok(());
         }
 
-        if !(self.connection.get_min_speed().await?..=self.connection.get_max_speed().await?)
-            .contains(&rate.abs())
-        {
-            return Err(ASCOMError::invalid_value("Rate is invalid"));
-        }
+        let ranges = self.get_axis_rate_ranges().await;
+        let speed = Self::snap_axis_rate(&ranges, rate.abs()).ok_or_else(|| {
+            ASCOMError::invalid_value(format_args!(
+                "Rate {} isn't in any of the supported axis rate ranges",
+                rate
+            ))
+        })?;
 
         let target_direction = if rate < 0. {
             TrackingDirection::AgainstTracking
@@ -146,22 +368,116 @@ impl StarAdventurer {
         };
 
         let target_rate = MotionRate::new(
-            rate.abs(),
+            speed,
             target_direction
                 .using(
                     self.settings
                         .observation_location
-                        .read()
-                        .await
+                        .get()
                         .get_rotation_direction_key(),
                 )
                 .into(),
         );
 
+        if self
+            .is_heading_past_limit(target_rate, motion_limit_monitor::LOOKAHEAD)
+            .await?
+        {
+            return Err(Self::mount_limit_error());
+        }
+
         self.connection.move_motor(target_rate).await?;
+        self.vibration_guard
+            .note_rate_change(GuardedOperation::MoveAxis);
         Ok(())
     }
 
+    /// MoveAxis(Secondary) on the dec motor. Positive moves north.
+    /// Dec is updated with how far it went once it's stopped.
+    async fn move_dec_axis(&self, rate: Degrees) -> ASCOMResult<()> {
+        if rate == 0. {
+            let was_moving = self.connection.is_dec_slewing().await?;
+            self.stop_dec_move().await?;
+            if was_moving {
+                self.settle_timer
+                    .start(SettleSource::MoveAxis, self.get_settle_duration().await);
+            }
+            return Ok(());
+        }
+
+        if self.connection.is_parked().await? {
+            return Err(ASCOMError::new(
+                ASCOMErrorCode::INVALID_WHILE_PARKED,
+                "Can't move axis while parked".to_string(),
+            ));
+        }
+
+        let ranges = self.get_dec_axis_rate_ranges().await;
+        let speed = Self::snap_axis_rate(&ranges, rate.abs()).ok_or_else(|| {
+            ASCOMError::invalid_value(format_args!(
+                "Rate {} isn't in any of the supported axis rate ranges",
+                rate
+            ))
+        })?;
+        let motor_rate = dec_motor_change(
+            speed.copysign(rate),
+            self.settings.pier_side.get(),
+            self.settings.observation_location.get().in_north(),
+        )
+        .ok_or_else(|| {
+            ASCOMError::invalid_operation("Can't move in declination with the pier side unknown")
+        })?;
+        let target_rate = MotionRate::from_clockwise_rate(motor_rate);
+
+        if self
+            .is_dec_heading_past_limit(target_rate, motion_limit_monitor::LOOKAHEAD)
+            .await?
+        {
+            return Err(Self::mount_limit_error());
+        }
+
+        {
+            let mut start = self.settings.dec_move_start.write().await;
+            if start.is_none() {
+                *start = Some(self.connection.get_dec_motor_pos().await?);
+            }
+        }
+        self.connection.move_dec_motor(target_rate).await?;
+        self.vibration_guard
+            .note_rate_change(GuardedOperation::MoveAxis);
+        Ok(())
+    }
+
+    /// Stops the dec motor. Dec is moved on by how far a MoveAxis(Secondary) took it.
+    pub(in crate::telescope_control) async fn stop_dec_move(&self) -> ASCOMResult<()> {
+        self.connection.stop_dec_motor().await?;
+        let mut start = self.settings.dec_move_start.write().await;
+        if let Some(start_pos) = *start {
+            let pos = self.connection.get_dec_motor_pos().await?;
+            let mut dec = self.settings.declination.write().await;
+            if let Some(moved) = self.dec_after_motor_change(*dec, pos - start_pos) {
+                *dec = moved.clamp(-90., 90.);
+            }
+            *start = None;
+        }
+        Ok(())
+    }
+
+    /// Dec after the dec motor turns by motor_change. None if the pier side is unknown.
+    pub(in crate::telescope_control) fn dec_after_motor_change(
+        &self,
+        dec: Degrees,
+        motor_change: Degrees,
+    ) -> Option<Degrees> {
+        // Mapping a motor change back to a dec change is the same as the other way
+        dec_motor_change(
+            motor_change,
+            self.settings.pier_side.get(),
+            self.settings.observation_location.get().in_north(),
+        )
+        .map(|change| dec + change)
+    }
+
     // // Positive if with tracking, negative if against
     // fn calculate_pos_change(ra_change: Hours, slew_speed: Degrees) -> (Hours, chrono::Duration) {
     //     const INSTANT_DISTANCE: Hours = 0.1;
@@ -336,29 +652,14 @@ impl StarAdventurer {
         }
     }
 
-    fn alert_user_to_change_declination(dec_change: Degrees) {
-        // Handle declination stuff
-        // FIXME Better notification
-        if dec_change != 0. {
-            let dec_change_turns = dec_change / 2.957;
-            // TODO Remove the turns after blocking app is implemented
-            println!(
-                "TURN DECLINATION KNOB {:.2} TURNS TO THE {}",
-                dec_change_turns.abs(),
-                if dec_change_turns < 0. {
-                    "SOUTH"
-                } else {
-                    "NORTH"
-                }
-            );
-        }
-    }
-
     async fn slew_dec(
         &self,
         target_dec: Degrees,
         meridian_flip: bool,
     ) -> WaitableTask<AbortResult<(), ()>> {
+        if self.has_dec_motor() {
+            return self.slew_dec_motor(target_dec, meridian_flip).await;
+        }
         if !*self.settings.instant_dec_slew.read().await {
             // Lock the slew bookkeeper
             let mut lock = self.dec_slew.write().await;
@@ -370,8 +671,11 @@ impl StarAdventurer {
                 *lock = DeclinationSlew::Waiting {
                     meridian_flip,
                     dec_change,
+                    turned: 0.,
                     finisher,
                 };
+                let plan = self.plan_dec_slew(dec_change, meridian_flip).await;
+                self.events.publish(Event::DeclinationSlewRequired { plan });
                 return slew_task;
             }
         } else {
@@ -380,7 +684,9 @@ impl StarAdventurer {
             let current_dec = *dec_lock;
             if target_dec != current_dec || meridian_flip {
                 let dec_change = Self::calculate_dec_change(current_dec, target_dec, meridian_flip);
-                Self::alert_user_to_change_declination(dec_change);
+                let plan = self.plan_dec_slew(dec_change, meridian_flip).await;
+                tracing::info!("{}", plan);
+                self.events.publish(Event::DeclinationSlewRequired { plan });
             }
             *dec_lock = target_dec;
             if meridian_flip {
@@ -390,37 +696,163 @@ impl StarAdventurer {
         WaitableTask::new_completed(AbortResult::Completed(()))
     }
 
+    /// The dec motor is started toward the target. As with instant dec slews, the coordinates are updated up front.
+    async fn slew_dec_motor(
+        &self,
+        target_dec: Degrees,
+        meridian_flip: bool,
+    ) -> WaitableTask<AbortResult<(), ()>> {
+        // A MoveAxis is stopped by the goto, so how far it went is counted first
+        if self.settings.dec_move_start.read().await.is_some() {
+            if let Err(e) = self.stop_dec_move().await {
+                tracing::warn!("Couldn't stop moving dec: {}", e);
+            }
+        }
+        let mut dec_lock = self.settings.declination.write().await;
+        let dec_change = Self::calculate_dec_change(*dec_lock, target_dec, meridian_flip);
+        // The change is in the frame of the starting pier side
+        let motor_change = dec_motor_change(
+            dec_change,
+            self.settings.pier_side.get(),
+            self.settings.observation_location.get().in_north(),
+        );
+        *dec_lock = target_dec;
+        if meridian_flip {
+            self.settings.pier_side.write().await.flip();
+        }
+        drop(dec_lock);
+
+        let motor_change = match motor_change {
+            Some(change) if change != 0. => change,
+            Some(_) => return WaitableTask::new_completed(AbortResult::Completed(())),
+            None => {
+                tracing::warn!("Not moving the dec motor with the pier side unknown");
+                return WaitableTask::new_completed(AbortResult::Aborted(()));
+            }
+        };
+        let (slew_task, finisher) = WaitableTask::new();
+        let connection = self.connection.clone();
+        task::spawn(async move {
+            match connection.move_dec_motor_by(motor_change).await {
+                Ok(()) => finisher.finish(AbortResult::Completed(())),
+                Err(e) => {
+                    tracing::warn!("Dec slew failed: {}", e);
+                    finisher.finish(AbortResult::Aborted(()))
+                }
+            }
+        });
+        slew_task
+    }
+
     async fn slew(
         &self,
         slew: Slew,
         dec: Degrees,
         current_pos: Degrees,
         key: RotationDirectionKey,
+        target_ha: Hours,
+        target: SlewTarget,
     ) -> ASCOMResult<impl Future<Output = ASCOMResult<()>>> {
+        // Smart tracking switches to the rate for the target once there
+        let tracking_rate = *self.settings.tracking_rate.read().await;
+        let arrival_tracking_rate = if self.rate_follows_pointing() {
+            Some(
+                self.calc_tracking_motion_rate(tracking_rate, Some((target_ha, dec)))
+                    .await,
+            )
+        } else {
+            None
+        };
+
         /* RA */
-        tracing::warn!(
-            "Starting slew estimated to take {}s",
-            slew.estimate_slew_time().as_secs()
+        let estimate = slew.estimate_slew_time(
+            &self
+                .connection
+                .get_speed_capabilities()
+                .await
+                .unwrap_or_default(),
         );
+        tracing::warn!("Starting slew estimated to take {}s", estimate.as_secs());
 
         let motor_direction = MotorEncodingDirection::from(slew.direction().using(key));
         let pos_change = astro_math::hours_to_deg(slew.distance()) * motor_direction.get_sign_f64();
         let dest_motor_pos = current_pos + pos_change;
 
-        let motor_slew_task = self.connection.slew_to(dest_motor_pos).await?;
+        let motor_slew_task = self
+            .connection
+            .slew_to(dest_motor_pos, self.settings.goto_approach)
+            .await?;
+        *self.settings.interrupted_slew.lock().unwrap() = None;
+        let this_slew = ActiveSlew {
+            start_pos: current_pos,
+            dest_pos: dest_motor_pos,
+            estimate,
+        };
+        *self.settings.active_slew.lock().unwrap() = Some(this_slew);
+        self.events.publish(Event::SlewStarted {
+            target,
+            estimated_secs: estimate.as_secs_f64(),
+        });
         let (ra_slew_task, finisher) = WaitableTask::new();
-        let settle_time = *self.settings.post_slew_settle_time.read().await;
+        let settle_duration = self.get_settle_duration().await;
+        let settle_timer = self.settle_timer.clone();
+        let vibration_guard = self.vibration_guard.clone();
+        let connection = self.connection.clone();
+        let interrupted_slew = self.settings.interrupted_slew.clone();
+        let active_slew = self.settings.active_slew.clone();
+        let events = self.events.clone();
         task::spawn(async move {
             let result = motor_slew_task.await;
-            if matches!(&result, AbortResult::Completed(Ok(_))) {
-                time::sleep(Duration::from_secs(settle_time as u64)).await;
+            events.publish(Event::SlewFinished {
+                completed: matches!(&result, AbortResult::Completed(Ok(_))),
+            });
+            if !matches!(&result, AbortResult::Completed(Ok(_))) {
+                let progress = connection
+                    .get_pos()
+                    .await
+                    .ok()
+                    .map(|pos| ((pos - current_pos) / pos_change).clamp(0., 1.));
+                *interrupted_slew.lock().unwrap() = Some(InterruptedSlew {
+                    target,
+                    interrupted_at: Utc::now(),
+                    progress,
+                });
+            }
+            if let (AbortResult::Completed(Ok(_)), Some(rate)) = (&result, arrival_tracking_rate) {
+                if let Err(e) = connection.update_tracking_rate(rate).await {
+                    tracing::warn!("Couldn't update the smart tracking rate: {}", e);
+                }
+            }
+            vibration_guard.note_rate_change(GuardedOperation::Slew);
+            let completed = matches!(&result, AbortResult::Completed(Ok(_)));
+            if completed {
+                settle_timer.start(SettleSource::Slew, settle_duration);
+            }
+            {
+                // Settling, if any, now reports the slewing. Leave a newer slew alone.
+                let mut active_slew = active_slew.lock().unwrap();
+                if *active_slew == Some(this_slew) {
+                    *active_slew = None;
+                }
+            }
+            if completed {
+                settle_timer.wait().await;
             }
             finisher.finish(result)
         });
 
         /* Dec */
 
-        let dec_slew_task = self.slew_dec(dec, slew.does_meridian_flip()).await;
+        let (_, mount_dec) = self
+            .settings
+            .pointing_model
+            .read()
+            .await
+            .to_mount(target_ha, dec);
+        let dec_slew_task = self.slew_dec(mount_dec, slew.does_meridian_flip()).await;
+        if slew.does_meridian_flip() {
+            self.session.record_meridian_flip();
+        }
 
         /* Join, discarding abort result because this isn't used by ASCOM */
 
@@ -430,51 +862,302 @@ impl StarAdventurer {
         })
     }
 
+    /// In alt-az mode the RA axis goes straight to the azimuth and the declination knob to the altitude
+    async fn slew_to_axis_angles(
+        &self,
+        alt: Degrees,
+        az: Degrees,
+        target: SlewTarget,
+    ) -> ASCOMResult<impl Future<Output = ASCOMResult<()>>> {
+        self.check_position_trusted().await?;
+
+        let target_mech_ha = Self::calc_mech_ha_from_az(az);
+        let mount_limits = *self.settings.mount_limits.read().await;
+        if !mount_limits.is_valid_ha(target_mech_ha) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Azimuth {:.1}° is outside the mount limits",
+                az
+            )));
+        }
+        let current_pos = self.connection.get_pos().await?;
+        let observation_location = self.settings.observation_location.get();
+        let key = observation_location.get_rotation_direction_key();
+        let slew = Slew::to_mech_ha(
+            self.calc_mech_ha_at(current_pos),
+            target_mech_ha,
+            mount_limits,
+        );
+
+        let (ha, dec) =
+            astro_math::calculate_ha_dec_from_alt_az(alt, az, observation_location.latitude);
+        let time = Self::calculate_utc_date(self.get_sky_date_offset().await);
+        let ra = astro_math::modulo(
+            astro_math::calculate_local_sidereal_time(time, observation_location.longitude) - ha,
+            24.,
+        );
+        SlewVerdict::evaluate(
+            Some(&slew),
+            ra,
+            dec,
+            time,
+            &observation_location,
+            &*self.settings.slew_safety.read().await,
+        )
+        .check()?;
+
+        let slew_task = self.slew(slew, alt, current_pos, key, ha, target).await?;
+        self.session.record_slew(ra, dec);
+        Ok(slew_task)
+    }
+
+    /// Slews RA to a mechanical hour angle whatever is in the sky there, e.g. to face a flat panel or to balance the mount.
+    /// Tracking is stopped so the mount stays put, and dec is left alone. Only the mount limits are checked, not the horizon.
+    pub async fn slew_to_mech_ha(
+        &self,
+        mech_ha: Hours,
+    ) -> ASCOMResult<impl Future<Output = ASCOMResult<()>>> {
+        if !(0. ..24.).contains(&mech_ha) {
+            return Err(ASCOMError::invalid_value(
+                "Mechanical hour angle must be in 0..24",
+            ));
+        }
+        self.check_position_trusted().await?;
+
+        let mount_limits = *self.settings.mount_limits.read().await;
+        if !mount_limits.is_valid_ha(mech_ha) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Mechanical hour angle {:.2}h is outside the mount limits",
+                mech_ha
+            )));
+        }
+        if self.connection.is_tracking().await? {
+            self.set_is_tracking(false).await?;
+        }
+
+        let current_pos = self.connection.get_pos().await?;
+        let key = self
+            .settings
+            .observation_location
+            .get()
+            .get_rotation_direction_key();
+        let slew = Slew::to_mech_ha(self.calc_mech_ha_at(current_pos), mech_ha, mount_limits);
+
+        // Where the mount will point, with dec as it is
+        let mount_dec = *self.settings.declination.read().await;
+        let (ha, dec) = match self.settings.pier_side.get() {
+            pier_side if !self.settings.alt_az && pier_side != SideOfPier::Unknown => self
+                .settings
+                .pointing_model
+                .read()
+                .await
+                .to_true(Self::calc_ha_from_mech_ha(mech_ha, pier_side), mount_dec),
+            _ => (mech_ha, mount_dec), // Only RA moves, so there's nothing for the model to do
+        };
+        let target = SlewTarget::MechanicalHourAngle {
+            mech_hour_angle: mech_ha,
+        };
+        self.slew(slew, dec, current_pos, key, ha, target).await
+    }
+
+    fn calc_alt_az_from_ha_dec(&self, ha: Hours, dec: Degrees) -> (Degrees, Degrees) {
+        let latitude = self.settings.observation_location.get().latitude;
+        (
+            astro_math::calculate_alt_from_ha_dec(ha, dec, latitude),
+            astro_math::calculate_az_from_ha_dec(ha, dec, latitude),
+        )
+    }
+
     async fn slew_to_ha(
         &self,
         ha: Hours,
         dec: Degrees,
     ) -> ASCOMResult<impl Future<Output = ASCOMResult<()>>> {
+        let target = SlewTarget::HourAngle {
+            hour_angle: ha,
+            declination: dec,
+        };
+        if self.settings.alt_az {
+            let (alt, az) = self.calc_alt_az_from_ha_dec(ha, dec);
+            return Ok(Either::Left(
+                self.slew_to_axis_angles(alt, az, target).await?,
+            ));
+        }
+        self.check_position_trusted().await?;
+
         /* RA */
         let current_pos = self.connection.get_pos().await?;
-        let (observation_location, mech_ha_offset, pier_side, mount_limits) = join!(
-            async { *self.settings.observation_location.read().await },
-            async { *self.settings.mech_ha_offset.read().await },
-            async { *self.settings.pier_side.read().await },
-            async { *self.settings.mount_limits.read().await },
-        );
+        let observation_location = self.settings.observation_location.get();
+        let mech_ha_offset = self.settings.mech_ha_offset.get();
+        let pier_side = self.settings.pier_side.get();
+        let (date_offset, mount_limits) = join!(self.get_sky_date_offset(), async {
+            *self.settings.mount_limits.read().await
+        });
 
         let key = observation_location.get_rotation_direction_key();
         let current_mech_ha = Self::calc_mech_ha(current_pos, mech_ha_offset, key);
 
-        let slew = Slew::to_ha(current_mech_ha, ha, pier_side, mount_limits);
+        let (mount_ha, _) = self.settings.pointing_model.read().await.to_mount(ha, dec);
+        let slew = Slew::to_ha(current_mech_ha, mount_ha, pier_side, mount_limits);
 
-        self.slew(slew, dec, current_pos, key).await
+        let time = Self::calculate_utc_date(date_offset);
+        let ra = astro_math::modulo(
+            astro_math::calculate_local_sidereal_time(time, observation_location.longitude) - ha,
+            24.,
+        );
+        SlewVerdict::evaluate(
+            slew.as_ref(),
+            ra,
+            dec,
+            time,
+            &observation_location,
+            &*self.settings.slew_safety.read().await,
+        )
+        .check()?;
+
+        let slew_task = self
+            .slew(slew.unwrap(), dec, current_pos, key, ha, target)
+            .await?;
+        self.session.record_slew(ra, dec);
+        Ok(Either::Right(slew_task))
     }
 
-    async fn slew_to_ra(
+    /// Turns RA half a turn and flips dec, pointing at the same place from the other side of the pier
+    pub(in crate::telescope_control) async fn flip_pier_side(
+        &self,
+    ) -> ASCOMResult<impl Future<Output = ASCOMResult<()>>> {
+        self.check_position_trusted().await?;
+
+        let current_pos = self.connection.get_pos().await?;
+        let observation_location = self.settings.observation_location.get();
+        let (date_offset, mount_limits) = join!(self.get_sky_date_offset(), async {
+            *self.settings.mount_limits.read().await
+        });
+        let key = observation_location.get_rotation_direction_key();
+        let slew =
+            Slew::flip(self.calc_mech_ha_at(current_pos), mount_limits).ok_or_else(|| {
+                ASCOMError::invalid_operation("The mount limits leave no room to flip")
+            })?;
+
+        let (ha, dec) = self.get_ha_dec().await?;
+        let ra = Self::calc_ra(ha, observation_location.longitude, date_offset);
+        let target = SlewTarget::Equatorial {
+            right_ascension: ra,
+            declination: dec,
+        };
+        self.slew(slew, dec, current_pos, key, ha, target).await
+    }
+
+    /// Plans the RA part of a slew and checks that the slew is allowed
+    pub(in crate::telescope_control) async fn plan_slew_to_ra(
         &self,
         ra: Hours,
         dec: Degrees,
-    ) -> ASCOMResult<impl Future<Output = ASCOMResult<()>>> {
-        /* RA */
+    ) -> ASCOMResult<(Option<Slew>, SlewVerdict, Degrees, RotationDirectionKey)> {
         let current_pos = self.connection.get_pos().await?;
-        let (observation_location, mech_ha_offset, date_offset, pier_side, mount_limits) = join!(
-            async { *self.settings.observation_location.read().await },
-            async { *self.settings.mech_ha_offset.read().await },
-            async { *self.settings.date_offset.read().await },
-            async { *self.settings.pier_side.read().await },
-            async { *self.settings.mount_limits.read().await },
-        );
+        let observation_location = self.settings.observation_location.get();
+        let mech_ha_offset = self.settings.mech_ha_offset.get();
+        let pier_side = self.settings.pier_side.get();
+        let (date_offset, mount_limits) = join!(self.get_sky_date_offset(), async {
+            *self.settings.mount_limits.read().await
+        });
 
         let key = observation_location.get_rotation_direction_key();
         let current_mech_ha = Self::calc_mech_ha(current_pos, mech_ha_offset, key);
         let current_ha = Self::calc_ha_from_mech_ha(current_mech_ha, pier_side);
         let current_ra = Self::calc_ra(current_ha, observation_location.longitude, date_offset);
 
-        let slew = Slew::change_ra(current_mech_ha, ra - current_ra, mount_limits);
+        // The model shifts where the motor has to go to reach the target
+        let target_ha = astro_math::calculate_hour_angle(
+            Self::calculate_utc_date(date_offset),
+            observation_location.longitude,
+            ra,
+        );
+        let (mount_ha, _) = self
+            .settings
+            .pointing_model
+            .read()
+            .await
+            .to_mount(target_ha, dec);
+        let mount_ra = ra + (target_ha - mount_ha);
+
+        let slew = Slew::change_ra(current_mech_ha, mount_ra - current_ra, mount_limits);
+        let verdict = SlewVerdict::evaluate(
+            slew.as_ref(),
+            ra,
+            dec,
+            Self::calculate_utc_date(date_offset),
+            &observation_location,
+            &*self.settings.slew_safety.read().await,
+        );
 
-        self.slew(slew, dec, current_pos, key).await
+        Ok((slew, verdict, current_pos, key))
+    }
+
+    pub(in crate::telescope_control) async fn slew_to_ra(
+        &self,
+        ra: Hours,
+        dec: Degrees,
+    ) -> ASCOMResult<impl Future<Output = ASCOMResult<()>>> {
+        let target = SlewTarget::Equatorial {
+            right_ascension: ra,
+            declination: dec,
+        };
+        if self.settings.alt_az {
+            let ha = astro_math::calculate_hour_angle(
+                Self::calculate_utc_date(self.get_sky_date_offset().await),
+                self.settings.observation_location.get().longitude,
+                ra,
+            );
+            let (alt, az) = self.calc_alt_az_from_ha_dec(ha, dec);
+            return Ok(Either::Left(
+                self.slew_to_axis_angles(alt, az, target).await?,
+            ));
+        }
+        self.check_position_trusted().await?;
+
+        /* RA */
+        let (slew, verdict, current_pos, key) = self.plan_slew_to_ra(ra, dec).await?;
+        verdict.check()?;
+
+        let ha = astro_math::calculate_hour_angle(
+            Self::calculate_utc_date(self.get_sky_date_offset().await),
+            self.settings.observation_location.get().longitude,
+            ra,
+        );
+        let slew_task = self
+            .slew(slew.unwrap(), dec, current_pos, key, ha, target)
+            .await?;
+        self.session.record_slew(ra, dec);
+        Ok(Either::Right(slew_task))
+    }
+
+    /// The last slew if it didn't reach its target
+    pub fn get_interrupted_slew(&self) -> Option<InterruptedSlew> {
+        *self.settings.interrupted_slew.lock().unwrap()
+    }
+
+    /// Re-plans the interrupted slew from the current position, return immediately after the slew starts.
+    /// The pier side may differ from the original plan.
+    pub async fn resume_slew(&self) -> ASCOMResult<impl Future<Output = ASCOMResult<()>>> {
+        let interrupted = self
+            .get_interrupted_slew()
+            .ok_or_else(|| ASCOMError::invalid_operation("No interrupted slew to resume"))?;
+
+        Ok(match interrupted.target {
+            SlewTarget::Equatorial {
+                right_ascension,
+                declination,
+            } => Either::Left(self.slew_to_ra(right_ascension, declination).await?),
+            SlewTarget::HourAngle {
+                hour_angle,
+                declination,
+            } => Either::Right(Either::Left(
+                self.slew_to_ha(hour_angle, declination).await?,
+            )),
+            SlewTarget::MechanicalHourAngle { mech_hour_angle } => {
+                Either::Right(Either::Right(self.slew_to_mech_ha(mech_hour_angle).await?))
+            }
+        })
     }
 
     /// Predicts the pointing state that a German equatorial mount will be in if it slews to the given coordinates
@@ -484,20 +1167,22 @@ impl StarAdventurer {
         _dec: Degrees,
     ) -> ASCOMResult<SideOfPier> {
         let current_pos = self.connection.get_pos().await?;
-        let (observation_location, mech_ha_offset, date_offset, pier_side, mount_limits) = join!(
-            async { *self.settings.observation_location.read().await },
-            async { *self.settings.mech_ha_offset.read().await },
-            async { *self.settings.date_offset.read().await },
-            async { *self.settings.pier_side.read().await },
-            async { *self.settings.mount_limits.read().await },
-        );
+        let observation_location = self.settings.observation_location.get();
+        let mech_ha_offset = self.settings.mech_ha_offset.get();
+        let pier_side = self.settings.pier_side.get();
+        let (date_offset, mount_limits) = join!(self.get_sky_date_offset(), async {
+            *self.settings.mount_limits.read().await
+        });
 
         let key = observation_location.get_rotation_direction_key();
         let current_mech_ha = Self::calc_mech_ha(current_pos, mech_ha_offset, key);
         let current_ha = Self::calc_ha_from_mech_ha(current_mech_ha, pier_side);
         let current_ra = Self::calc_ra(current_ha, observation_location.longitude, date_offset);
 
-        let slew = Slew::change_ra(current_mech_ha, ra - current_ra, mount_limits);
+        let slew =
+            Slew::change_ra(current_mech_ha, ra - current_ra, mount_limits).ok_or_else(|| {
+                ASCOMError::invalid_operation("Mount limits block every path to the target")
+            })?;
 
         Ok(if slew.does_meridian_flip() {
             pier_side.opposite()
@@ -516,7 +1201,9 @@ impl StarAdventurer {
         let ra = target.try_get_right_ascension()?;
         let dec = target.try_get_declination()?;
 
-        self.slew_to_ra(ra, dec).await
+        let slew_task = self.slew_to_ra(ra, dec).await?;
+        self.begin_closed_loop_goto(ra, dec).await;
+        Ok(slew_task)
     }
 
     /// Move the telescope to the TargetRightAscension and TargetDeclination equatorial coordinates, return when slew is complete
@@ -540,12 +1227,11 @@ impl StarAdventurer {
         check_ra(ra)?;
         check_dec(dec)?;
 
-        *self.settings.target.write().await = Target {
-            right_ascension: Some(ra),
-            declination: Some(dec),
-        };
+        *self.settings.target.write().await = Target::fixed(ra, dec);
 
-        self.slew_to_ra(ra, dec).await
+        let slew_task = self.slew_to_ra(ra, dec).await?;
+        self.begin_closed_loop_goto(ra, dec).await;
+        Ok(slew_task)
     }
 
     /// True if this telescope is capable of programmed slewing (synchronous or asynchronous) to equatorial coordinates
@@ -578,10 +1264,19 @@ impl StarAdventurer {
         let (ha, dec) = astro_math::calculate_ha_dec_from_alt_az(
             alt,
             az,
-            self.settings.observation_location.read().await.latitude,
+            self.settings.observation_location.get().latitude,
         );
 
-        self.slew_to_ha(ha, dec).await
+        if self.settings.alt_az {
+            let target = SlewTarget::HourAngle {
+                hour_angle: ha,
+                declination: dec,
+            };
+            return Ok(Either::Left(
+                self.slew_to_axis_angles(alt, az, target).await?,
+            ));
+        }
+        Ok(Either::Right(self.slew_to_ha(ha, dec).await?))
     }
 
     /// True if this telescope is capable of programmed slewing (synchronous or asynchronous) to local horizontal coordinates
@@ -597,7 +1292,16 @@ impl StarAdventurer {
 
 #[cfg(test)]
 mod tests {
-    use crate::telescope_control::test_util;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use ascom_alpaca::api::{Axis, AxisRate};
+    use assert_float_eq::*;
+    use tokio::time;
+
+    use crate::telescope_control::connection::consts;
+    use crate::telescope_control::test_util::{self, DecOperator, DecResponse};
+    use crate::util::*;
 
     #[tokio::test]
     async fn test_slew() {
@@ -605,4 +1309,171 @@ mod tests {
         sa.sync_to_coordinates(0., 30.).await.unwrap();
         sa.slew_to_coordinates(-1., 14.).await.unwrap();
     }
+
+    #[test]
+    fn test_snap_axis_rate() {
+        let fast_speed = 0.3;
+        let ranges = [
+            AxisRate {
+                minimum: 0.00003,
+                maximum: 0.13,
+            },
+            AxisRate {
+                minimum: fast_speed,
+                maximum: fast_speed,
+            },
+        ];
+        let snap = |rate| super::StarAdventurer::snap_axis_rate(&ranges, rate);
+        assert_eq!(snap(0.01), Some(0.01));
+        assert_eq!(snap(fast_speed), Some(fast_speed));
+        assert_eq!(snap(fast_speed * (1. + 1e-9)), Some(fast_speed));
+        assert_eq!(snap((ranges[0].maximum + fast_speed) / 2.), None);
+        assert_eq!(snap(fast_speed * 2.), None);
+        assert_eq!(snap(0.), None);
+
+        let nearest = |rate| super::StarAdventurer::nearest_axis_rate(&ranges, rate);
+        assert_eq!(nearest(0.01), 0.01);
+        assert_eq!(nearest(fast_speed * 0.99), fast_speed);
+        assert_eq!(nearest(ranges[0].maximum * 1.01), ranges[0].maximum);
+        assert_eq!(nearest(10.), fast_speed);
+    }
+
+    #[tokio::test]
+    async fn test_axis_rates_from_controller() {
+        let sa = test_util::create_sa(None).await;
+        let ranges = sa.get_axis_rates(Axis::Primary).await.unwrap();
+        // The slow rates and both goto speeds
+        assert_eq!(ranges.len(), 3);
+        assert!(ranges[0].maximum < consts::SLEW_SPEED_WITH_TRACKING);
+        for range in &ranges {
+            sa.move_axis(Axis::Primary, range.maximum).await.unwrap();
+            sa.abort_slew().await.unwrap();
+        }
+        // Between the slow rates and the goto speeds
+        let between = (ranges[0].maximum + ranges[1].minimum) / 2.;
+        assert!(sa.move_axis(Axis::Primary, between).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_secondary_axis_needs_dec_motor() {
+        let sa = test_util::create_sa(None).await;
+        assert!(!sa.has_dec_motor());
+        assert!(!sa.can_move_axis(Axis::Secondary).await.unwrap());
+        assert_eq!(
+            sa.get_axis_rates(Axis::Secondary).await.unwrap()[0].maximum,
+            0.
+        );
+        assert!(sa.move_axis(Axis::Secondary, 0.01).await.is_err());
+    }
+
+    async fn create_manual_dec_sa(dec: f64) -> Arc<super::StarAdventurer> {
+        let sa = test_util::create_sa(Some(test_util::manual_dec_config())).await;
+        *sa.settings.declination.write().await = dec;
+        Arc::new(sa)
+    }
+
+    #[tokio::test]
+    async fn test_dec_slew_waits_for_acknowledgment() {
+        let sa = create_manual_dec_sa(30.).await;
+        let operator = DecOperator::start(
+            sa.clone(),
+            vec![DecResponse::Acknowledge(Duration::from_millis(50))],
+        );
+
+        let dec_task = sa.slew_dec(40., false).await;
+        assert_float_absolute_eq!(sa.get_pending_dec_change().await, 10.);
+        let result = time::timeout(Duration::from_secs(5), dec_task)
+            .await
+            .expect("dec slew wasn't acknowledged");
+        assert!(matches!(result, AbortResult::Completed(())));
+
+        assert_float_absolute_eq!(*sa.settings.declination.read().await, 40.);
+        assert!(sa.get_pending_dec_slew_plan().await.is_none());
+        let requests = operator.requests();
+        assert_eq!(requests.len(), 1);
+        assert_float_absolute_eq!(requests[0].dec_change, 10.);
+    }
+
+    #[tokio::test]
+    async fn test_dec_slew_blocks_until_answered() {
+        let sa = create_manual_dec_sa(30.).await;
+        let operator = DecOperator::start(sa.clone(), vec![DecResponse::Ignore]);
+
+        let dec_task = sa.slew_dec(20., false).await;
+        assert!(test_util::wait_for_dec_request(&sa, Duration::from_secs(1))
+            .await
+            .is_some());
+        assert!(time::timeout(Duration::from_millis(200), dec_task.clone())
+            .await
+            .is_err());
+        assert_eq!(operator.requests().len(), 1);
+
+        // Still waiting, and the declination isn't changed until the user says so
+        assert_float_absolute_eq!(sa.get_pending_dec_change().await, -10.);
+        assert_float_absolute_eq!(*sa.settings.declination.read().await, 30.);
+
+        sa.complete_dec_slew().await;
+        let result = time::timeout(Duration::from_secs(5), dec_task)
+            .await
+            .unwrap();
+        assert!(matches!(result, AbortResult::Completed(())));
+        assert_float_absolute_eq!(*sa.settings.declination.read().await, 20.);
+    }
+
+    #[tokio::test]
+    async fn test_dec_slew_partial_turns() {
+        let sa = create_manual_dec_sa(30.).await;
+        let _operator = DecOperator::start(sa.clone(), vec![DecResponse::Ignore]);
+        let degrees_per_turn = sa.settings.dec_knob.degrees_per_turn;
+
+        let dec_task = sa.slew_dec(30. + 3. * degrees_per_turn, false).await;
+        assert!(test_util::wait_for_dec_request(&sa, Duration::from_secs(1))
+            .await
+            .is_some());
+        let plan = sa.report_dec_turns(1.).await.unwrap().unwrap();
+        assert_float_absolute_eq!(plan.turns, 2.);
+        assert_float_absolute_eq!(plan.turns_done, 1.);
+        assert_float_absolute_eq!(
+            *sa.settings.declination.read().await,
+            30. + degrees_per_turn
+        );
+
+        // Reporting more than is left finishes the slew without overshooting
+        assert!(sa.report_dec_turns(5.).await.unwrap().is_none());
+        let result = time::timeout(Duration::from_secs(5), dec_task)
+            .await
+            .unwrap();
+        assert!(matches!(result, AbortResult::Completed(())));
+        assert_float_absolute_eq!(
+            *sa.settings.declination.read().await,
+            30. + 3. * degrees_per_turn
+        );
+        assert!(sa.report_dec_turns(1.).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dec_slew_abort() {
+        let sa = create_manual_dec_sa(30.).await;
+        let _operator = DecOperator::start(
+            sa.clone(),
+            vec![
+                DecResponse::Abort(Duration::from_millis(20)),
+                DecResponse::Acknowledge(Duration::ZERO),
+            ],
+        );
+
+        let result = time::timeout(Duration::from_secs(5), sa.slew_dec(50., false).await)
+            .await
+            .unwrap();
+        assert!(matches!(result, AbortResult::Aborted(())));
+        assert_float_absolute_eq!(*sa.settings.declination.read().await, 30.);
+
+        // A meridian flip needs the user even without a declination change
+        let pier_side = sa.settings.pier_side.get();
+        let result = time::timeout(Duration::from_secs(5), sa.slew_dec(30., true).await)
+            .await
+            .unwrap();
+        assert!(matches!(result, AbortResult::Completed(())));
+        assert_eq!(sa.settings.pier_side.get(), pier_side.opposite());
+    }
 }
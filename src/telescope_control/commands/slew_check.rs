@@ -0,0 +1,196 @@
+use std::fmt::{Display, Formatter};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::astro_math;
+use crate::config::{ObservingLocation, SlewSafety};
+use crate::telescope_control::slew_def::Slew;
+use crate::util::*;
+
+use super::super::star_adventurer::StarAdventurer;
+use ascom_alpaca::{ASCOMError, ASCOMResult};
+
+/// Whether a slew to a target would be allowed, and why not if it wouldn't
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum SlewVerdict {
+    Ok,
+    BlockedByLimit {
+        hour_angle: Hours,
+    },
+    BelowHorizon {
+        altitude: Degrees,
+        min_altitude: Degrees,
+    },
+    TooCloseToSun {
+        separation: Degrees,
+        min_separation: Degrees,
+    },
+}
+
+impl SlewVerdict {
+    /// Checks in order of limits, horizon then sun
+    pub(in crate::telescope_control) fn evaluate(
+        slew: Option<&Slew>,
+        ra: Hours,
+        dec: Degrees,
+        time: DateTime<Utc>,
+        location: &ObservingLocation,
+        safety: &SlewSafety,
+    ) -> Self {
+        let ha = astro_math::calculate_hour_angle(time, location.longitude, ra);
+
+        if slew.is_none() {
+            return Self::BlockedByLimit { hour_angle: ha };
+        }
+
+        let azimuth = astro_math::calculate_az_from_ha_dec(ha, dec, location.latitude);
+        if let Some(min_altitude) = safety.min_altitude_at(azimuth) {
+            let altitude = astro_math::calculate_alt_from_ha_dec(ha, dec, location.latitude);
+            if altitude < min_altitude {
+                return Self::BelowHorizon {
+                    altitude,
+                    min_altitude,
+                };
+            }
+        }
+
+        if let Some(min_separation) = safety.min_sun_separation {
+            let (sun_ra, sun_dec) = astro_math::calculate_sun_ra_dec(time);
+            let separation = astro_math::calculate_separation(ra, dec, sun_ra, sun_dec);
+            if separation < min_separation {
+                return Self::TooCloseToSun {
+                    separation,
+                    min_separation,
+                };
+            }
+        }
+
+        Self::Ok
+    }
+
+    pub fn is_ok(&self) -> bool {
+        *self == Self::Ok
+    }
+
+    /// The error to return if a slew is attempted anyway
+    pub fn check(self) -> ASCOMResult<()> {
+        if self.is_ok() {
+            Ok(())
+        } else {
+            Err(ASCOMError::invalid_operation(self))
+        }
+    }
+}
+
+impl Display for SlewVerdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok => write!(f, "Slew is allowed"),
+            Self::BlockedByLimit { hour_angle } => write!(
+                f,
+                "Mount limits block the slew to hour angle {:.2}h",
+                hour_angle
+            ),
+            Self::BelowHorizon {
+                altitude,
+                min_altitude,
+            } => write!(
+                f,
+                "Target altitude of {:.1}° is below the minimum of {:.1}°",
+                altitude, min_altitude
+            ),
+            Self::TooCloseToSun {
+                separation,
+                min_separation,
+            } => write!(
+                f,
+                "Target is {:.1}° from the sun, closer than the minimum of {:.1}°",
+                separation, min_separation
+            ),
+        }
+    }
+}
+
+impl StarAdventurer {
+    /// Checks whether a slew to the given coordinates would be allowed without moving the mount
+    pub async fn can_slew_to(&self, ra: Hours, dec: Degrees) -> ASCOMResult<SlewVerdict> {
+        check_ra(ra)?;
+        check_dec(dec)?;
+
+        Ok(self.plan_slew_to_ra(ra, dec).await?.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HorizonPoint;
+    use crate::telescope_control::mount_limits::MountLimits;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_evaluate() {
+        let time = Utc.with_ymd_and_hms(2023, 6, 21, 0, 0, 0).unwrap();
+        let location = ObservingLocation {
+            latitude: 51.5,
+            longitude: 0.,
+            elevation: 0.,
+            hemisphere: None,
+        };
+        let lst = astro_math::calculate_local_sidereal_time(time, location.longitude);
+        let (sun_ra, sun_dec) = astro_math::calculate_sun_ra_dec(time);
+        let slew = Slew::to_mech_ha(0., 1., MountLimits::new(18., 12., 0.));
+
+        let none = SlewSafety::default();
+        let min_altitude = SlewSafety {
+            min_altitude: Some(0.),
+            ..Default::default()
+        };
+        let high_horizon = SlewSafety {
+            horizon: vec![
+                HorizonPoint {
+                    azimuth: 0.,
+                    min_altitude: 60.,
+                },
+                HorizonPoint {
+                    azimuth: 180.,
+                    min_altitude: 60.,
+                },
+            ],
+            ..Default::default()
+        };
+        let sun = SlewSafety {
+            min_sun_separation: Some(30.),
+            ..Default::default()
+        };
+
+        // Targets on the meridian, so the altitude is 90° less the distance from the zenith
+        let zenith = (lst, location.latitude);
+        let below = (lst, -50.); // -11.5°
+        let low = (lst, 0.); // 38.5°
+
+        let cases = [
+            (Some(&slew), zenith, &none, "ok"),
+            (Some(&slew), zenith, &min_altitude, "ok"),
+            (Some(&slew), zenith, &high_horizon, "ok"),
+            (Some(&slew), zenith, &sun, "ok"),
+            (Some(&slew), below, &none, "ok"),
+            (None, zenith, &none, "blocked_by_limit"),
+            // Limits are checked first
+            (None, below, &min_altitude, "blocked_by_limit"),
+            (Some(&slew), below, &min_altitude, "below_horizon"),
+            (Some(&slew), low, &min_altitude, "ok"),
+            (Some(&slew), low, &high_horizon, "below_horizon"),
+            (Some(&slew), (sun_ra, sun_dec), &sun, "too_close_to_sun"),
+            (Some(&slew), (sun_ra, sun_dec), &none, "ok"),
+        ];
+        for (slew, (ra, dec), safety, expected) in cases {
+            let verdict = SlewVerdict::evaluate(slew, ra, dec, time, &location, safety);
+            let name = serde_json::to_value(verdict).unwrap()["verdict"].clone();
+            assert_eq!(name, expected, "ra {} dec {} with {:?}", ra, dec, safety);
+            assert_eq!(verdict.check().is_ok(), expected == "ok");
+        }
+    }
+}
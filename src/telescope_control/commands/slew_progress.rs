@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use ascom_alpaca::ASCOMResult;
+use serde::Serialize;
+
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+
+/// The RA motion of the running slew
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ActiveSlew {
+    pub start_pos: Degrees, // Motor positions
+    pub dest_pos: Degrees,
+    pub estimate: Duration, // From Slew::estimate_slew_time
+}
+
+/// How far the running slew has got, for clients choosing a timeout
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct SlewProgress {
+    pub percent_complete: f64,
+    pub secs_left: f64,
+    pub target_position: Degrees, // Motor positions
+    pub current_position: Degrees,
+}
+
+impl ActiveSlew {
+    pub fn progress(&self, current_pos: Degrees) -> SlewProgress {
+        let fraction = if self.dest_pos == self.start_pos {
+            1.
+        } else {
+            ((current_pos - self.start_pos) / (self.dest_pos - self.start_pos)).clamp(0., 1.)
+        };
+        SlewProgress {
+            percent_complete: fraction * 100.,
+            secs_left: self.estimate.as_secs_f64() * (1. - fraction),
+            target_position: self.dest_pos,
+            current_position: current_pos,
+        }
+    }
+}
+
+impl StarAdventurer {
+    /// None if no slew is running
+    pub async fn get_slew_progress(&self) -> ASCOMResult<Option<SlewProgress>> {
+        let active_slew = *self.settings.active_slew.lock().unwrap();
+        match active_slew {
+            Some(active_slew) => Ok(Some(active_slew.progress(self.connection.get_pos().await?))),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slew_progress() {
+        let slew = ActiveSlew {
+            start_pos: 10.,
+            dest_pos: -20.,
+            estimate: Duration::from_secs(100),
+        };
+        let progress = slew.progress(-5.);
+        assert_eq!(progress.percent_complete, 50.);
+        assert_eq!(progress.secs_left, 50.);
+        assert_eq!(slew.progress(12.).percent_complete, 0.);
+        assert_eq!(slew.progress(-21.).secs_left, 0.);
+    }
+}
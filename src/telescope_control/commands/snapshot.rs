@@ -0,0 +1,77 @@
+use serde::Serialize;
+
+use crate::astro_math;
+use crate::telescope_control::clock_monitor::ClockJump;
+use crate::telescope_control::commands::parking::PositionProblem;
+use crate::telescope_control::connection::SerialHealthCounts;
+use crate::telescope_control::dec_knob::DeclinationSlewPlan;
+use crate::telescope_control::motor_watchdog::HardwareIncident;
+use crate::telescope_control::settle_timer::SettleSource;
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+
+/// Everything a client polls for, gathered in one request
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub connected: bool,
+    pub parked: Option<bool>,
+    pub tracking: Option<bool>,
+    pub slewing: Option<bool>,
+    pub pulse_guiding: Option<bool>,
+    pub right_ascension: Option<Hours>,
+    pub declination: Option<Degrees>,
+    pub hour_angle: Option<Hours>, // -12..12, negative east of the meridian
+    pub declination_slew_plan: Option<DeclinationSlewPlan>, // The knob turns still to be done
+    pub worm_phase: Option<f64>,   // 0..1, see the worm_phase action
+    pub settling_until: Option<String>, // RFC 3339, None once settled
+    pub settle_remaining_secs: Option<f64>, // Post-motion and vibration guard settle time left. None once settled
+    pub settle_source: Option<SettleSource>,
+    pub awaiting_stability: bool, // The settle time is up, waiting for the settled action or the stability webhook
+    pub last_clock_jump: Option<ClockJump>,
+    pub position_problem: Option<PositionProblem>,
+    pub last_hardware_incident: Option<HardwareIncident>,
+}
+
+impl StarAdventurer {
+    /// Current state of the mount. Values that can't be read while disconnected are None.
+    pub async fn get_snapshot(&self) -> Snapshot {
+        let right_ascension = self.get_ra().await.ok();
+        let hour_angle = match (self.get_sidereal_time().await, right_ascension) {
+            (Ok(lst), Some(ra)) => Some(astro_math::modulo(lst - ra + 12., 24.) - 12.),
+            _ => None,
+        };
+        let settle_remaining = self.settle_timer.remaining();
+        Snapshot {
+            connected: self.is_connected().await,
+            parked: self.is_parked().await.ok(),
+            tracking: self.is_tracking().await.ok(),
+            slewing: self.is_slewing().await.ok(),
+            pulse_guiding: self.is_pulse_guiding().await.ok(),
+            right_ascension,
+            declination: self.get_dec().await.ok(),
+            hour_angle,
+            declination_slew_plan: self.get_pending_dec_slew_plan().await,
+            worm_phase: self.get_worm_phase().await.ok().map(|worm| worm.phase),
+            settling_until: self
+                .settle_timer
+                .settling_until()
+                .map(|until| until.to_rfc3339()),
+            settle_remaining_secs: settle_remaining.map(|remaining| remaining.as_secs_f64()),
+            settle_source: self.settle_timer.source(),
+            awaiting_stability: self.settle_timer.is_awaiting_stability(),
+            last_clock_jump: self.clock_monitor.last_jump(),
+            position_problem: self.get_position_problem().await,
+            last_hardware_incident: self.motor_watchdog.last_incident(),
+        }
+    }
+
+    /// Every time the watchdog had to stop the motor since the driver started
+    pub fn get_hardware_incidents(&self) -> Vec<HardwareIncident> {
+        self.motor_watchdog.incidents()
+    }
+
+    /// Serial timeouts, retries and errors since the driver started
+    pub fn get_serial_health(&self) -> SerialHealthCounts {
+        self.connection.get_serial_health()
+    }
+}
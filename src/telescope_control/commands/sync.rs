@@ -1,26 +1,102 @@
 use super::target::Target;
 use crate::astro_math;
+use crate::telescope_control::vibration_guard::GuardedOperation;
 use crate::telescope_control::StarAdventurer;
 use crate::util::*;
 use ascom_alpaca::api::SideOfPier;
 use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
 
+/// How close a sync has to put the mount from the other side of the pier, and how far off from this side, to flip it
+const PIER_SIDE_INFERENCE_TOLERANCE: Hours = 2.;
+
 impl StarAdventurer {
+    /// Syncs normally correct small pointing errors. One that only makes sense from the other side of the pier
+    /// means the mount was flipped without the driver knowing, e.g. by hand.
+    pub(in crate::telescope_control) fn infer_pier_side(
+        current_mech_ha: Hours,
+        synced_ha: Hours,
+        pier_side: SideOfPier,
+    ) -> SideOfPier {
+        let off_by = |side| {
+            let change = Self::calc_mech_ha_from_ha(synced_ha, side) - current_mech_ha;
+            let change = astro_math::modulo(change, 24.);
+            change.min(24. - change)
+        };
+        let other_side = pier_side.opposite();
+        if off_by(other_side) < PIER_SIDE_INFERENCE_TOLERANCE
+            && PIER_SIDE_INFERENCE_TOLERANCE < off_by(pier_side)
+        {
+            other_side
+        } else {
+            pier_side
+        }
+    }
+
+    /// The pier side to sync with, flipping it if the sync implies the mount is on the other side.
+    /// The current position is only a guide when it's trusted. The caller saves the side.
+    async fn pier_side_for_sync(&self, ha: Hours, motor_pos: Degrees) -> SideOfPier {
+        let pier_side = self.settings.pier_side.get();
+        if pier_side == SideOfPier::Unknown || self.settings.position_problem.read().await.is_some()
+        {
+            return pier_side;
+        }
+        let inferred = Self::infer_pier_side(self.calc_mech_ha_at(motor_pos), ha, pier_side);
+        if inferred != pier_side {
+            tracing::warn!(
+                "Sync only fits with the mount on the other side of the pier. Now {:?}.",
+                inferred
+            );
+        }
+        inferred
+    }
+
     /// Raw helper function that performs no checks
     async fn sync_to_ra_dec(&self, ra: Hours, dec: Degrees) -> ASCOMResult<()> {
         let ha = astro_math::calculate_hour_angle(
-            Self::calculate_utc_date(*self.settings.date_offset.read().await),
-            self.settings.observation_location.read().await.longitude,
+            Self::calculate_utc_date(self.get_sky_date_offset().await),
+            self.settings.observation_location.get().longitude,
             ra,
         );
+        self.sync_to_ha_dec(ha, dec).await
+    }
 
-        let pier_side = *self.settings.pier_side.read().await;
+    /// Moves the origin to the given position, or with a pointing model, adds it as a point
+    async fn sync_to_ha_dec(&self, ha: Hours, dec: Degrees) -> ASCOMResult<()> {
+        if self.settings.alt_az {
+            let latitude = self.settings.observation_location.get().latitude;
+            return self
+                .sync_axis_angles(
+                    astro_math::calculate_alt_from_ha_dec(ha, dec, latitude),
+                    astro_math::calculate_az_from_ha_dec(ha, dec, latitude),
+                )
+                .await;
+        }
+        if self.is_refining_pointing_model().await {
+            self.record_sync_point(false, ha, dec).await?;
+            return self.refresh_smart_tracking_rate().await;
+        }
+
+        let motor_pos = self.connection.get_pos().await?;
+        let pier_side = self.pier_side_for_sync(ha, motor_pos).await;
 
         let mech_ha = Self::calc_mech_ha_from_ha(ha, pier_side);
 
-        *self.settings.mech_ha_offset.write().await =
-            Self::calc_mech_ha_offset(mech_ha, self.connection.get_pos().await?);
+        *self.settings.pier_side.write().await = pier_side;
+        *self.settings.mech_ha_offset.write().await = Self::calc_mech_ha_offset(mech_ha, motor_pos);
         *self.settings.declination.write().await = dec;
+        *self.settings.position_problem.write().await = None;
+        self.record_sync_point(true, ha, dec).await?;
+        self.refresh_smart_tracking_rate().await
+    }
+
+    /// In alt-az mode the RA axis is synced to the azimuth and the declination knob to the altitude
+    async fn sync_axis_angles(&self, alt: Degrees, az: Degrees) -> ASCOMResult<()> {
+        *self.settings.mech_ha_offset.write().await = Self::calc_mech_ha_offset(
+            Self::calc_mech_ha_from_az(az),
+            self.connection.get_pos().await?,
+        );
+        *self.settings.declination.write().await = alt;
+        *self.settings.position_problem.write().await = None;
         Ok(())
     }
 
@@ -49,7 +125,12 @@ impl StarAdventurer {
             ));
         }
 
-        if !self.connection.is_tracking().await? {
+        // Tracking is refused until a re-sync, so allow that sync while stopped.
+        // Alt-az never tracks, and its position doesn't drift from the coordinates.
+        if !self.settings.alt_az
+            && !self.connection.is_tracking().await?
+            && self.get_position_problem().await.is_none()
+        {
             return Err(ASCOMError::new(
                 ASCOMErrorCode::INVALID_OPERATION,
                 "Can't sync to coords unless tracking".to_string(),
@@ -57,14 +138,69 @@ impl StarAdventurer {
         }
 
         // Syncing to ra/dec sets the target as well
-        *self.settings.target.write().await = Target {
-            right_ascension: Some(ra),
-            declination: Some(dec),
-        };
+        *self.settings.target.write().await = Target::fixed(ra, dec);
 
         self.sync_to_ra_dec(ra, dec).await
     }
 
+    /// Syncs to the given coordinates and starts tracking as one operation.
+    /// The position is held locked until tracking has started so no poll sees the synced position while stopped.
+    pub async fn sync_and_start_tracking(&self, ra: Hours, dec: Degrees) -> ASCOMResult<()> {
+        check_ra(ra)?;
+        check_dec(dec)?;
+        self.check_can_track()?;
+
+        if self.connection.is_parked().await? {
+            return Err(ASCOMError::new(
+                ASCOMErrorCode::INVALID_WHILE_PARKED,
+                "Can't sync while parked".to_string(),
+            ));
+        }
+
+        // The model first, in the order readers take them. Polls wait on, or keep seeing, the old position.
+        let mut model = self.settings.pointing_model.write().await;
+        let mut mech_ha_offset = self.settings.mech_ha_offset.write().await;
+        let mut declination = self.settings.declination.write().await;
+
+        let ha = astro_math::calculate_hour_angle(
+            Self::calculate_utc_date(self.get_sky_date_offset().await),
+            self.settings.observation_location.get().longitude,
+            ra,
+        );
+        let motor_pos = self.connection.get_pos().await?;
+        let pier_side = self.pier_side_for_sync(ha, motor_pos).await;
+        let mut pier_side_lock = self.settings.pier_side.write().await;
+        let mut position_problem = self.settings.position_problem.write().await;
+
+        // Can't go through set_is_tracking since it reads the position locked above
+        let tracking_rate = *self.settings.tracking_rate.read().await;
+        let rate = self
+            .calc_tracking_motion_rate(tracking_rate, Some((ha, dec)))
+            .await;
+        self.connection.start_tracking(rate).await?;
+        self.vibration_guard
+            .note_rate_change(GuardedOperation::TrackingChange);
+
+        // Only set once tracking has started, so a failure leaves the old position
+        *mech_ha_offset =
+            Self::calc_mech_ha_offset(Self::calc_mech_ha_from_ha(ha, pier_side), motor_pos);
+        *declination = dec;
+        *pier_side_lock = pier_side;
+        // The sync is what makes the position trusted again
+        *position_problem = None;
+        *self.settings.target.write().await = Target::fixed(ra, dec);
+        // The model restarts from the sync, as with any sync that moves the origin
+        model.reset();
+        drop((
+            mech_ha_offset,
+            declination,
+            pier_side_lock,
+            position_problem,
+            model,
+        ));
+        self.record_sync_point(true, ha, dec).await
+    }
+
     /// True if this telescope is capable of programmed synching to local horizontal coordinates.
     pub async fn can_sync_alt_az(&self) -> ASCOMResult<bool> {
         Ok(true)
@@ -86,20 +222,15 @@ impl StarAdventurer {
             ));
         }
 
+        if self.settings.alt_az {
+            return self.sync_axis_angles(alt, az).await;
+        }
         let (ha, dec) = astro_math::calculate_ha_dec_from_alt_az(
             alt,
             az,
-            self.settings.observation_location.read().await.latitude,
+            self.settings.observation_location.get().latitude,
         );
-
-        let pier_side = *self.settings.pier_side.read().await;
-
-        let mech_ha = Self::calc_mech_ha_from_ha(ha, pier_side);
-
-        *self.settings.mech_ha_offset.write().await =
-            Self::calc_mech_ha_offset(mech_ha, self.connection.get_pos().await?);
-        *self.settings.declination.write().await = dec;
-        Ok(())
+        self.sync_to_ha_dec(ha, dec).await
     }
 
     /// Matches the scope's equatorial coordinates to the TargetRightAscension and TargetDeclination equatorial coordinates.
@@ -111,7 +242,7 @@ impl StarAdventurer {
             ));
         }
 
-        if !self.connection.is_tracking().await? {
+        if !self.settings.alt_az && !self.connection.is_tracking().await? {
             return Err(ASCOMError::new(
                 ASCOMErrorCode::INVALID_OPERATION,
                 "Can't sync to coords unless tracking".to_string(),
@@ -130,9 +261,31 @@ impl StarAdventurer {
 
 #[cfg(test)]
 mod tests {
-    use crate::telescope_control::test_util;
+    use crate::config::Config;
+    use crate::telescope_control::{test_util, StarAdventurer};
+    use ascom_alpaca::api::{AlignmentMode, SideOfPier};
     use assert_float_eq::*;
 
+    #[test]
+    fn test_infer_pier_side() {
+        // Pointing at hour angle 6 from the east side
+        let mech_ha = StarAdventurer::calc_mech_ha_from_ha(6., SideOfPier::East);
+        assert_eq!(
+            StarAdventurer::infer_pier_side(mech_ha, 6.5, SideOfPier::East),
+            SideOfPier::East
+        );
+        // Hour angle 18 is where the mount points from the west side
+        assert_eq!(
+            StarAdventurer::infer_pier_side(mech_ha, 17.5, SideOfPier::East),
+            SideOfPier::West
+        );
+        // Too far off from either side to say
+        assert_eq!(
+            StarAdventurer::infer_pier_side(mech_ha, 12., SideOfPier::East),
+            SideOfPier::East
+        );
+    }
+
     #[tokio::test]
     async fn test_sync() {
         let sa = test_util::create_sa(None).await;
@@ -148,4 +301,31 @@ mod tests {
         assert_float_absolute_eq!(sa.get_ra().await.unwrap(), 12., 1E-4);
         assert_float_absolute_eq!(sa.get_dec().await.unwrap(), -87., 1E-4);
     }
+
+    #[tokio::test]
+    async fn test_sync_alt_az_mode() {
+        let mut config: Config = confy::load_path("test_config.toml").unwrap();
+        config.other.alt_az = true;
+        let sa = test_util::create_sa(Some(config)).await;
+        assert!(matches!(
+            sa.get_alignment_mode().await.unwrap(),
+            AlignmentMode::AltAz
+        ));
+        sa.sync_to_alt_az(40., 100.).await.unwrap();
+        assert_float_absolute_eq!(sa.get_altitude().await.unwrap(), 40., 1E-4);
+        assert_float_absolute_eq!(sa.get_azimuth().await.unwrap(), 100., 1E-4);
+        assert_float_absolute_eq!(
+            sa.get_mech_ha().await.unwrap(),
+            StarAdventurer::calc_mech_ha_from_az(100.),
+            1E-4
+        );
+
+        // Turning the RA axis at a sidereal rate doesn't follow the sky in alt-az
+        assert!(!sa.can_set_tracking().await.unwrap());
+        assert!(sa.set_is_tracking(true).await.is_err());
+        assert!(sa.sync_and_start_tracking(6., 20.).await.is_err());
+        assert!(!sa.is_tracking().await.unwrap());
+        // Syncing to coordinates doesn't need tracking
+        sa.sync_to_coordinates(6., 20.).await.unwrap();
+    }
 }
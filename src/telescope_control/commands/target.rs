@@ -1,17 +1,67 @@
-use crate::astro_math::{Degrees, Hours};
+use crate::astro_math::{self, Degrees, Hours};
 use crate::telescope_control::StarAdventurer;
 use crate::util::*;
 use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Motion of a moving target, e.g. a comet
+#[derive(Debug, PartialEq, Copy, Clone, Serialize)]
+pub struct TargetRates {
+    pub right_ascension_rate: f64, // Seconds of RA per second
+    pub declination_rate: f64,     // Arcseconds per second
+    pub epoch: DateTime<Utc>,      // When the target coordinates were valid
+}
 
 #[derive(Debug, Default, PartialEq, Copy, Clone)]
 pub struct Target {
     pub right_ascension: Option<Hours>,
     pub declination: Option<Degrees>,
+    pub rates: Option<TargetRates>,
 }
 
 impl Target {
+    /// A target that doesn't move
+    pub fn fixed(ra: Hours, dec: Degrees) -> Self {
+        Target {
+            right_ascension: Some(ra),
+            declination: Some(dec),
+            rates: None,
+        }
+    }
+
+    fn elapsed_secs(&self, now: DateTime<Utc>) -> f64 {
+        match self.rates {
+            Some(rates) => (now - rates.epoch).num_milliseconds() as f64 / 1000.,
+            None => 0.,
+        }
+    }
+
+    /// Right ascension extrapolated to `now`
+    pub fn right_ascension_at(&self, now: DateTime<Utc>) -> Option<Hours> {
+        let rate = self.rates.map_or(0., |r| r.right_ascension_rate);
+        self.right_ascension
+            .map(|ra| astro_math::modulo(ra + rate * self.elapsed_secs(now) / 3600., 24.))
+    }
+
+    /// Declination extrapolated to `now`
+    pub fn declination_at(&self, now: DateTime<Utc>) -> Option<Degrees> {
+        let rate = self.rates.map_or(0., |r| r.declination_rate);
+        self.declination
+            .map(|dec| (dec + rate * self.elapsed_secs(now) / 3600.).clamp(-90., 90.))
+    }
+
+    /// Moves the stored coordinates to their extrapolated values so the epoch becomes `now`
+    fn rebase(&mut self, now: DateTime<Utc>) {
+        self.right_ascension = self.right_ascension_at(now);
+        self.declination = self.declination_at(now);
+        if let Some(rates) = self.rates.as_mut() {
+            rates.epoch = now;
+        }
+    }
+
     pub fn try_get_right_ascension(&self) -> ASCOMResult<Hours> {
-        match self.right_ascension {
+        match self.right_ascension_at(Utc::now()) {
             Some(t) => Ok(t),
             None => Err(ASCOMError::new(
                 ASCOMErrorCode::VALUE_NOT_SET,
@@ -21,7 +71,7 @@ impl Target {
     }
 
     pub fn try_get_declination(&self) -> ASCOMResult<Degrees> {
-        match self.declination {
+        match self.declination_at(Utc::now()) {
             Some(t) => Ok(t),
             None => Err(ASCOMError::new(
                 ASCOMErrorCode::VALUE_NOT_SET,
@@ -40,7 +90,9 @@ impl StarAdventurer {
     /// Sets the declination (degrees, positive North) for the target of an equatorial slew or sync operation
     pub async fn set_target_dec(&self, dec: Degrees) -> ASCOMResult<()> {
         check_dec(dec)?;
-        self.settings.target.write().await.declination = Some(dec);
+        let mut target = self.settings.target.write().await;
+        target.rebase(Utc::now());
+        target.declination = Some(dec);
         Ok(())
     }
 
@@ -52,7 +104,64 @@ impl StarAdventurer {
     /// Sets the right ascension (hours) for the target of an equatorial slew or sync operation
     pub async fn set_target_ra(&self, ra: Hours) -> ASCOMResult<()> {
         check_ra(ra)?;
-        self.settings.target.write().await.right_ascension = Some(ra);
+        let mut target = self.settings.target.write().await;
+        target.rebase(Utc::now());
+        target.right_ascension = Some(ra);
         Ok(())
     }
+
+    /// The motion of the target, if it's moving
+    pub async fn get_target_rates(&self) -> Option<TargetRates> {
+        self.settings.target.read().await.rates
+    }
+
+    /// Makes the target move at the given rates (RA seconds per second, arcseconds per second) from now on.
+    /// Setting both rates to 0 makes the target fixed again.
+    pub async fn set_target_rates(&self, ra_rate: f64, dec_rate: f64) -> ASCOMResult<()> {
+        if !ra_rate.is_finite() || !dec_rate.is_finite() {
+            return Err(ASCOMError::invalid_value("Target rates must be finite"));
+        }
+
+        let now = Utc::now();
+        let mut target = self.settings.target.write().await;
+        target.rebase(now);
+        target.rates = if ra_rate == 0. && dec_rate == 0. {
+            None
+        } else {
+            Some(TargetRates {
+                right_ascension_rate: ra_rate,
+                declination_rate: dec_rate,
+                epoch: now,
+            })
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn test_moving_target_extrapolation() {
+        let epoch = Utc::now();
+        let target = Target {
+            right_ascension: Some(23.99),
+            declination: Some(89.99),
+            rates: Some(TargetRates {
+                right_ascension_rate: 36.,
+                declination_rate: 72.,
+                epoch,
+            }),
+        };
+        let later = epoch + chrono::Duration::seconds(10);
+        assert_float_absolute_eq!(target.right_ascension_at(later).unwrap(), 0.09, 1e-9);
+        assert_float_absolute_eq!(target.declination_at(later).unwrap(), 90., 1e-9);
+        assert_eq!(target.right_ascension_at(epoch), Some(23.99));
+
+        let fixed = Target::fixed(5., 10.);
+        assert_eq!(fixed.right_ascension_at(later), Some(5.));
+        assert_eq!(fixed.declination_at(later), Some(10.));
+    }
 }
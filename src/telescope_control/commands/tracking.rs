@@ -1,27 +1,80 @@
+use crate::astro_math;
+use crate::rotation_direction::RotationDirection;
+use crate::telescope_control::commands::target::{Target, TargetRates};
+use crate::telescope_control::vibration_guard::GuardedOperation;
 use crate::telescope_control::StarAdventurer;
+use crate::tracking_direction::TrackingDirection;
 use crate::util::*;
 use ascom_alpaca::api::DriveRate;
 use ascom_alpaca::{ASCOMError, ASCOMResult};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A rate from the config's custom-tracking-rates, active in place of the standard rate
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CustomTrackingRate {
+    pub name: String,
+    pub rate: Degrees, // Degrees/sec with tracking
+}
+
+const SIDEREAL_SECONDS_PER_SECOND: f64 = 1.002_737_909_35;
+// About 5 times sidereal, more than any comet or drift compensation needs
+const MAX_RA_RATE_OFFSET: f64 = 5.;
 
 impl StarAdventurer {
     /// True if the Tracking property can be changed, turning telescope sidereal tracking on and off.
     pub async fn can_set_tracking(&self) -> ASCOMResult<bool> {
-        Ok(true)
+        Ok(!self.settings.alt_az)
     }
 
-    /// The right ascension tracking rate (arcseconds per second, default = 0.0)
+    /// In alt-az mode the RA axis turns in azimuth, where turning at a sidereal rate doesn't follow the sky
+    pub(in crate::telescope_control) fn check_can_track(&self) -> ASCOMResult<()> {
+        if self.settings.alt_az {
+            return Err(ASCOMError::invalid_operation(
+                "Can't track with the mount in alt-az mode",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The right ascension tracking rate offset from the tracking rate (seconds of RA per sidereal second, default = 0.0)
     pub async fn get_ra_rate(&self) -> ASCOMResult<f64> {
-        Ok(0.)
+        Ok(*self.settings.ra_rate_offset.read().await)
     }
 
     /// True if the RightAscensionRate property can be changed to provide offset tracking in the right ascension axis.
     pub async fn can_set_ra_rate(&self) -> ASCOMResult<bool> {
-        Ok(false)
+        Ok(true)
     }
 
-    /// Sets the right ascension tracking rate (arcseconds per second)
-    pub async fn set_ra_rate(&self, _rate: f64) -> ASCOMResult<()> {
-        Err(ASCOMError::NOT_IMPLEMENTED)
+    /// Sets the right ascension tracking rate offset (seconds of RA per sidereal second).
+    /// Positive moves with increasing RA, so the mount tracks slower.
+    pub async fn set_ra_rate(&self, rate: f64) -> ASCOMResult<()> {
+        if !rate.is_finite() || MAX_RA_RATE_OFFSET < rate.abs() {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "RA rate offset of {} is outside the valid range of -{} to {}",
+                rate, MAX_RA_RATE_OFFSET, MAX_RA_RATE_OFFSET
+            )));
+        }
+
+        let mut lock = self.settings.ra_rate_offset.write().await;
+        *lock = rate;
+        drop(lock);
+
+        let tracking_rate = *self.settings.tracking_rate.read().await;
+        let tracking_motion_rate = self
+            .calc_current_tracking_motion_rate(tracking_rate)
+            .await?;
+        self.connection
+            .update_tracking_rate(tracking_motion_rate)
+            .await?;
+
+        if self.connection.is_tracking().await? {
+            self.vibration_guard
+                .note_rate_change(GuardedOperation::TrackingChange);
+        }
+        Ok(())
     }
 
     /// The declination tracking rate (arcseconds per second, default = 0.0)
@@ -58,25 +111,28 @@ impl StarAdventurer {
     pub async fn set_tracking_rate(&self, tracking_rate: DriveRate) -> ASCOMResult<()> {
         // No change needed
         let mut lock = self.settings.tracking_rate.write().await;
-        if *lock == tracking_rate {
+        let custom = self.settings.custom_tracking_rate.write().await.take();
+        if *lock == tracking_rate && custom.is_none() {
             return Ok(());
         }
 
         *lock = tracking_rate;
 
-        let tracking_motion_rate = tracking_rate.into_motion_rate(
-            self.settings
-                .observation_location
-                .read()
-                .await
-                .get_rotation_direction_key(),
-        );
+        let tracking_motion_rate = self
+            .calc_current_tracking_motion_rate(tracking_rate)
+            .await?;
 
         self.connection
             .update_tracking_rate(tracking_motion_rate)
             .await?;
 
-        Ok(())
+        if self.connection.is_tracking().await? {
+            self.vibration_guard
+                .note_rate_change(GuardedOperation::TrackingChange);
+        }
+
+        drop(lock);
+        self.publish_guide_rates().await
     }
 
     /// Returns the state of the telescope's sidereal tracking drive.
@@ -90,20 +146,254 @@ impl StarAdventurer {
     /// TODO Going with can only set it while not gotoing
     pub async fn set_is_tracking(&self, should_track: bool) -> ASCOMResult<()> {
         if should_track {
-            let tracking_rate = self.settings.tracking_rate.read().await;
-            let key = self
+            self.check_can_track()?;
+            self.check_position_trusted().await?;
+            let tracking_rate = *self.settings.tracking_rate.read().await;
+            let rate = self
+                .calc_current_tracking_motion_rate(tracking_rate)
+                .await?;
+
+            self.connection.start_tracking(rate).await?;
+            self.session.record_tracking_resumed();
+        } else {
+            let stopped_target = self.get_tracked_target().await;
+            self.connection.stop_tracking().await?;
+            if stopped_target.is_some() {
+                *self.settings.stopped_target.write().await = stopped_target;
+                self.session.record_tracking_stopped("Tracking turned off");
+            }
+        }
+        self.vibration_guard
+            .note_rate_change(GuardedOperation::TrackingChange);
+        Ok(())
+    }
+
+    /// The pointing while tracking, moving on with the target's rates if it has any
+    async fn get_tracked_target(&self) -> Option<Target> {
+        if !self.is_tracking().await.ok()? {
+            return None;
+        }
+        let (ra, dec) = (self.get_ra().await.ok()?, self.get_dec().await.ok()?);
+        let now = Utc::now();
+        Some(Target {
+            rates: self
                 .settings
-                .observation_location
+                .target
                 .read()
                 .await
-                .get_rotation_direction_key();
+                .rates
+                .map(|rates| TargetRates {
+                    epoch: now,
+                    ..rates
+                }),
+            ..Target::fixed(ra, dec)
+        })
+    }
+
+    /// Where the target tracked before the last stop is now
+    pub async fn get_catch_up_position(&self) -> Option<(Hours, Degrees)> {
+        let target = (*self.settings.stopped_target.read().await)?;
+        let now = Utc::now();
+        Some((target.right_ascension_at(now)?, target.declination_at(now)?))
+    }
+
+    /// Slews back to what was being tracked before tracking was stopped and resumes tracking.
+    /// Returns where it slewed to.
+    pub async fn catch_up(&self) -> ASCOMResult<(Hours, Degrees)> {
+        let (ra, dec) = self.get_catch_up_position().await.ok_or_else(|| {
+            ASCOMError::invalid_operation("Nothing was being tracked to catch up with")
+        })?;
+        if self.is_tracking().await? {
+            return Err(ASCOMError::invalid_operation("Already tracking"));
+        }
+
+        tracing::info!("Catching up with RA {}, dec {}", ra, dec);
+        // Slews keep the tracking state, so this one ends tracking from the moment it arrives
+        self.set_is_tracking(true).await?;
+        self.slew_to_coordinates(ra, dec).await?;
+        *self.settings.stopped_target.write().await = None;
+        Ok((ra, dec))
+    }
+
+    /// The motor rate for a tracking rate. With smart tracking on, the sidereal rate
+    /// is adjusted for refraction at the given hour angle and declination.
+    /// With the refined King rate on, the King rate is worked out the same way.
+    /// With ephemeris lunar tracking on, the lunar rate is the Moon's current rate.
+    /// The RightAscensionRate offset applies on top.
+    pub(in crate::telescope_control) async fn calc_tracking_motion_rate(
+        &self,
+        tracking_rate: DriveRate,
+        position: Option<(Hours, Degrees)>,
+    ) -> MotionRate {
+        let location = self.settings.observation_location.get();
+        let key = location.get_rotation_direction_key();
+
+        let offset = MotionRate::new(
+            Self::ra_rate_to_degrees(*self.settings.ra_rate_offset.read().await),
+            TrackingDirection::WithTracking.using(key).into(),
+        );
+        if let Some(custom) = &*self.settings.custom_tracking_rate.read().await {
+            return MotionRate::new(
+                custom.rate,
+                TrackingDirection::WithTracking.using(key).into(),
+            ) - offset;
+        }
+        let base_rate = match position {
+            Some((ha, dec)) if self.is_refraction_corrected(tracking_rate) => {
+                let factor =
+                    astro_math::calculate_refraction_rate_factor(ha, dec, location.latitude);
+                MotionRate::new(
+                    DriveRate::Sidereal.to_degrees() * factor,
+                    TrackingDirection::WithTracking.using(key).into(),
+                )
+            }
+            _ if self.settings.ephemeris_lunar_tracking && tracking_rate == DriveRate::Lunar => {
+                let time = Self::calculate_utc_date(self.get_sky_date_offset().await);
+                MotionRate::new(
+                    astro_math::calculate_moon_tracking_rate(
+                        time,
+                        location.latitude,
+                        location.longitude,
+                    ),
+                    TrackingDirection::WithTracking.using(key).into(),
+                )
+            }
+            _ => tracking_rate.into_motion_rate(key),
+        };
+        base_rate - offset
+    }
+
+    /// Whether the rate is the sidereal rate corrected for refraction at the pointing
+    fn is_refraction_corrected(&self, tracking_rate: DriveRate) -> bool {
+        match tracking_rate {
+            DriveRate::Sidereal => self.settings.smart_tracking,
+            DriveRate::King => self.settings.refined_king_rate,
+            _ => false,
+        }
+    }
+
+    /// Whether the tracking rate depends on the pointing
+    pub(in crate::telescope_control) fn rate_follows_pointing(&self) -> bool {
+        self.settings.smart_tracking || self.settings.refined_king_rate
+    }
 
-            self.connection
-                .start_tracking(tracking_rate.into_motion_rate(key))
-                .await?
+    pub fn get_custom_tracking_rates(&self) -> &BTreeMap<String, Degrees> {
+        &self.settings.custom_tracking_rates
+    }
+
+    pub async fn get_custom_tracking_rate(&self) -> Option<CustomTrackingRate> {
+        self.settings.custom_tracking_rate.read().await.clone()
+    }
+
+    /// Tracks at the named rate from the config until another rate is set
+    pub async fn set_custom_tracking_rate(&self, name: &str) -> ASCOMResult<CustomTrackingRate> {
+        let rate = *self
+            .settings
+            .custom_tracking_rates
+            .get(name)
+            .ok_or_else(|| {
+                ASCOMError::invalid_value(format_args!(
+                    "Unknown custom tracking rate: \"{}\"",
+                    name
+                ))
+            })?;
+        let custom = CustomTrackingRate {
+            name: name.to_string(),
+            rate,
+        };
+
+        let tracking_rate = *self.settings.tracking_rate.read().await;
+        *self.settings.custom_tracking_rate.write().await = Some(custom.clone());
+        let tracking_motion_rate = self
+            .calc_current_tracking_motion_rate(tracking_rate)
+            .await?;
+        self.connection
+            .update_tracking_rate(tracking_motion_rate)
+            .await?;
+
+        if self.connection.is_tracking().await? {
+            self.vibration_guard
+                .note_rate_change(GuardedOperation::TrackingChange);
+        }
+        tracing::info!("Tracking at custom rate \"{}\" of {}°/s", name, rate);
+        Ok(custom)
+    }
+
+    /// Seconds of RA per sidereal second to degrees per second
+    fn ra_rate_to_degrees(ra_rate: f64) -> Degrees {
+        ra_rate * 15. / 3600. * SIDEREAL_SECONDS_PER_SECOND
+    }
+
+    /// The motor rate for a tracking rate at the current pointing
+    async fn calc_current_tracking_motion_rate(
+        &self,
+        tracking_rate: DriveRate,
+    ) -> ASCOMResult<MotionRate> {
+        let position = if self.rate_follows_pointing() {
+            Some((
+                self.get_ha().await?,
+                *self.settings.declination.read().await,
+            ))
         } else {
-            self.connection.stop_tracking().await?;
+            None
+        };
+        Ok(self
+            .calc_tracking_motion_rate(tracking_rate, position)
+            .await)
+    }
+
+    /// Brings a smart tracking rate up to date after the pointing changes without a slew
+    pub(in crate::telescope_control) async fn refresh_smart_tracking_rate(
+        &self,
+    ) -> ASCOMResult<()> {
+        if !self.rate_follows_pointing() {
+            return Ok(());
         }
-        Ok(())
+        let tracking_rate = *self.settings.tracking_rate.read().await;
+        let rate = self
+            .calc_current_tracking_motion_rate(tracking_rate)
+            .await?;
+        self.connection.update_tracking_rate(rate).await
+    }
+
+    /// Brings an ephemeris lunar or refined King rate up to date as the Moon or the pointing moves
+    pub(in crate::telescope_control) async fn refresh_computed_tracking_rate(
+        &self,
+    ) -> ASCOMResult<()> {
+        let tracking_rate = *self.settings.tracking_rate.read().await;
+        let computed = match tracking_rate {
+            DriveRate::Lunar => self.settings.ephemeris_lunar_tracking,
+            DriveRate::King => self.settings.refined_king_rate,
+            _ => false,
+        };
+        if !computed || self.settings.custom_tracking_rate.read().await.is_some() {
+            return Ok(());
+        }
+        let rate = self
+            .calc_current_tracking_motion_rate(tracking_rate)
+            .await?;
+        tracing::debug!(
+            "{:?} tracking rate now {}°/s",
+            tracking_rate,
+            rate.clockwise_rate().abs()
+        );
+        self.connection.update_tracking_rate(rate).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn test_ra_rate_to_degrees() {
+        // One second of RA per sidereal second is the sidereal rate
+        assert_float_relative_eq!(
+            StarAdventurer::ra_rate_to_degrees(1.),
+            DriveRate::Sidereal.to_degrees(),
+            1E-5
+        );
+        assert_eq!(StarAdventurer::ra_rate_to_degrees(0.), 0.);
     }
 }
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+use serde::Serialize;
+
+use crate::astro_math;
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+
+/// How long tracking can go on before it runs into the west limit, less the flip margin
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct TrackingLimit {
+    pub hour_angle_left: Hours, // Negative once past
+    pub secs_left: Option<f64>, // At the current tracking rate. None if not tracking
+}
+
+impl TrackingLimit {
+    pub fn is_reached(&self) -> bool {
+        self.hour_angle_left <= 0.
+    }
+}
+
+impl StarAdventurer {
+    pub async fn get_tracking_limit(&self) -> ASCOMResult<TrackingLimit> {
+        let mech_ha = self.get_mech_ha().await?;
+        let hour_angle_left = self
+            .settings
+            .mount_limits
+            .read()
+            .await
+            .tracking_left(mech_ha);
+        let secs_left = if self.is_tracking().await? {
+            let rate = self.get_tracking_rate().await?.to_degrees();
+            Some(hour_angle_left * 15. / rate)
+        } else {
+            None
+        };
+        Ok(TrackingLimit {
+            hour_angle_left,
+            secs_left,
+        })
+    }
+
+    /// Whether moving at rate for the lookahead would take the mount further past the east or west limit
+    pub(in crate::telescope_control) async fn is_heading_past_limit(
+        &self,
+        rate: MotionRate,
+        lookahead: Duration,
+    ) -> ASCOMResult<bool> {
+        let pos = self.connection.get_pos().await?;
+        let ahead = pos + rate.clockwise_rate() * lookahead.as_secs_f64();
+        Ok(self
+            .settings
+            .mount_limits
+            .read()
+            .await
+            .is_moving_out(self.calc_mech_ha_at(pos), self.calc_mech_ha_at(ahead)))
+    }
+
+    /// Whether moving the dec motor at rate for the lookahead would take dec further past a pole or below the horizon.
+    /// Counts the motion of a MoveAxis(Secondary) so far, which dec isn't updated with until it's stopped.
+    pub(in crate::telescope_control) async fn is_dec_heading_past_limit(
+        &self,
+        rate: MotionRate,
+        lookahead: Duration,
+    ) -> ASCOMResult<bool> {
+        let pos = self.connection.get_dec_motor_pos().await?;
+        let start = self.settings.dec_move_start.read().await.unwrap_or(pos);
+        let ahead = pos + rate.clockwise_rate() * lookahead.as_secs_f64();
+        let dec = *self.settings.declination.read().await;
+        let (now, ahead) = match (
+            self.dec_after_motor_change(dec, pos - start),
+            self.dec_after_motor_change(dec, ahead - start),
+        ) {
+            (Some(now), Some(ahead)) => (now, ahead),
+            _ => return Ok(true), // Which way is which isn't known
+        };
+        if 90. < ahead.abs() && now.abs() < ahead.abs() {
+            return Ok(true);
+        }
+
+        let ha = self.get_mount_ha().await?;
+        let latitude = self.settings.observation_location.get().latitude;
+        let (altitude_now, altitude_ahead) = (
+            astro_math::calculate_alt_from_ha_dec(ha, now, latitude),
+            astro_math::calculate_alt_from_ha_dec(ha, ahead, latitude),
+        );
+        let azimuth = astro_math::calculate_az_from_ha_dec(ha, ahead, latitude);
+        let min_altitude = self
+            .settings
+            .slew_safety
+            .read()
+            .await
+            .min_altitude_at(azimuth);
+        Ok(min_altitude.map_or(false, |min_altitude| {
+            altitude_ahead < min_altitude && altitude_ahead < altitude_now
+        }))
+    }
+
+    pub(in crate::telescope_control) fn mount_limit_error() -> ASCOMError {
+        ASCOMError::new(
+            ASCOMErrorCode::new_for_driver(11),
+            "Stopped at the mount limit",
+        )
+    }
+}
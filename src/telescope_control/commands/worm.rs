@@ -0,0 +1,64 @@
+use ascom_alpaca::api::DriveRate;
+use ascom_alpaca::ASCOMResult;
+use serde::Serialize;
+
+use crate::astro_math;
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+
+// The RA worm wheel has 144 teeth, so the worm turns once per 2.5° of RA axis rotation
+const WORM_PERIOD: Degrees = 360. / 144.;
+
+/// Where the RA worm is in its turn, for timing dithers against the periodic error.
+/// The phase is relative: 0 is wherever the motor position counter happened to start,
+/// not a particular point on the worm, and it restarts with the mount.
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct WormPhase {
+    pub phase: f64,                      // 0..1, increasing while tracking
+    pub period_secs: Option<f64>,        // At the current tracking rate. None if not tracking
+    pub next_cycle_in_secs: Option<f64>, // Until the phase wraps back to 0
+}
+
+impl StarAdventurer {
+    pub async fn get_worm_phase(&self) -> ASCOMResult<WormPhase> {
+        let motor_pos = self.connection.get_pos().await?;
+        let key = self
+            .settings
+            .observation_location
+            .get()
+            .get_rotation_direction_key();
+        let tracking_direction = DriveRate::Sidereal.into_motion_rate(key).clockwise_rate();
+        let phase = Self::calc_worm_phase(motor_pos, tracking_direction);
+
+        let period_secs = if self.is_tracking().await? {
+            Some(WORM_PERIOD / self.get_tracking_rate().await?.to_degrees())
+        } else {
+            None
+        };
+        Ok(WormPhase {
+            phase,
+            period_secs,
+            next_cycle_in_secs: period_secs.map(|period| (1. - phase) * period),
+        })
+    }
+
+    /// The worm phase at a motor position, counted in the direction the motor tracks
+    fn calc_worm_phase(motor_pos: Degrees, tracking_direction: Degrees) -> f64 {
+        astro_math::modulo(motor_pos * tracking_direction.signum(), WORM_PERIOD) / WORM_PERIOD
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calc_worm_phase() {
+        assert_eq!(StarAdventurer::calc_worm_phase(0., 1.), 0.);
+        assert_eq!(StarAdventurer::calc_worm_phase(1.25, 1.), 0.5);
+        assert_eq!(StarAdventurer::calc_worm_phase(5.625, 1.), 0.25);
+        // Counterclockwise tracking counts down the motor position
+        assert_eq!(StarAdventurer::calc_worm_phase(-0.625, -1.), 0.25);
+        assert_eq!(StarAdventurer::calc_worm_phase(0.625, -1.), 0.75);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use ascom_alpaca::ASCOMResult;
+use serde_json::Value;
+
+use crate::config::{Config, ParkStore};
+use crate::telescope_control::events::Event;
+use crate::telescope_control::mount_limits::MountLimits;
+use crate::telescope_control::StarAdventurer;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Settings as "section.key" that can change without reconnecting
+const LIVE_SETTINGS: [&str; 13] = [
+    "observation-location.latitude",
+    "observation-location.longitude",
+    "observation-location.elevation",
+    "other.slew-settle-time",
+    "other.auto-guide-speed",
+    "other.mount-limit-east",
+    "other.mount-limit-west",
+    "other.flip-before-limit",
+    "other.park-hour-angle", // Also saved by SetPark
+    "slew-safety.min-altitude",
+    "slew-safety.min-sun-separation",
+    "slew-safety.horizon",
+    "slew-safety.below-horizon-action",
+];
+
+/// Watches the config file, and the state file saved over it, and applies edits to the live settings.
+/// Anything else is only logged, since it needs a restart to take effect.
+pub fn start(sa: Arc<StarAdventurer>, store: ParkStore) {
+    // The files as they were, before any command line overrides
+    let load = {
+        let store = store.clone();
+        move || store.load_config()
+    };
+    let modified = move || {
+        [&store.config_path, &store.state_path]
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+    };
+
+    tokio::spawn(async move {
+        let mut last_config = match load() {
+            Ok(config) => config,
+            Err(_) => return,
+        };
+        let mut last_modified: [Option<SystemTime>; 2] = modified();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let now_modified = modified();
+            if now_modified == last_modified {
+                continue;
+            }
+            last_modified = now_modified;
+
+            // An editor may be midway through saving. It'll be read again after the next write.
+            let config = match load() {
+                Ok(config) => config,
+                Err(e) => {
+                    tracing::warn!("Couldn't reload the config: {}", e);
+                    continue;
+                }
+            };
+
+            let (applied, rejected): (Vec<String>, Vec<String>) =
+                changed_settings(&last_config, &config)
+                    .into_iter()
+                    .partition(|setting| LIVE_SETTINGS.contains(&setting.as_str()));
+            if applied.is_empty() && rejected.is_empty() {
+                continue;
+            }
+            if !rejected.is_empty() {
+                tracing::warn!(
+                    "Config changes to {} need a restart to take effect",
+                    rejected.join(", ")
+                );
+            }
+            if !applied.is_empty() {
+                match sa.apply_live_settings(&config).await {
+                    Ok(()) => tracing::info!("Applied config changes to {}", applied.join(", ")),
+                    Err(e) => tracing::warn!("Couldn't apply the reloaded config: {}", e),
+                }
+            }
+            sa.events
+                .publish(Event::ConfigReloaded { applied, rejected });
+            last_config = config;
+        }
+    });
+}
+
+/// The settings that differ, as "section.key", or just "section" for a value that isn't a table
+fn changed_settings(old: &Config, new: &Config) -> Vec<String> {
+    let as_table = |config: &Config| match serde_json::to_value(config) {
+        Ok(Value::Object(table)) => table,
+        _ => unreachable!("Config serializes to a table"),
+    };
+    let (old, new) = (as_table(old), as_table(new));
+
+    let mut changed = Vec::new();
+    for section in old.keys().chain(new.keys()).collect::<BTreeSet<_>>() {
+        let (old_section, new_section) = (old.get(section), new.get(section));
+        if old_section == new_section {
+            continue;
+        }
+        match (old_section, new_section) {
+            (Some(Value::Object(old_keys)), Some(Value::Object(new_keys))) => {
+                for key in old_keys
+                    .keys()
+                    .chain(new_keys.keys())
+                    .collect::<BTreeSet<_>>()
+                {
+                    if old_keys.get(key) != new_keys.get(key) {
+                        changed.push(format!("{}.{}", section, key));
+                    }
+                }
+            }
+            _ => changed.push(section.clone()),
+        }
+    }
+    changed
+}
+
+impl StarAdventurer {
+    /// Takes the settings that can change while connected from a reloaded config
+    async fn apply_live_settings(&self, config: &Config) -> ASCOMResult<()> {
+        let location = config.observation_location;
+        self.set_latitude(location.latitude).await?;
+        self.set_longitude(location.longitude).await?;
+        self.set_elevation(location.elevation).await?;
+        self.set_slew_settle_time(config.other.slew_settle_time)
+            .await?;
+
+        *self.settings.mount_limits.write().await = MountLimits::new(
+            config.other.mount_limit_east,
+            config.other.mount_limit_west,
+            config.other.flip_before_limit,
+        );
+        *self.settings.park_ha.write().await = Self::calc_park_ha(config);
+        *self.settings.slew_safety.write().await = config.slew_safety.clone();
+
+        let speed = config.other.auto_guide_speed;
+        if self.apply_autoguide_speed(speed).await.is_err() {
+            // Disconnected. It's set on the motor when connecting.
+            *self.settings.autoguide_speed.write().await = speed;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_settings() {
+        let old: Config = confy::load_path("test_config.toml").unwrap();
+        let mut new = old.clone();
+        assert!(changed_settings(&old, &new).is_empty());
+
+        new.observation_location.latitude += 1.;
+        new.com.path = Some("/dev/ttyUSB1".to_string());
+        let changed = changed_settings(&old, &new);
+        assert_eq!(changed, ["com.path", "observation-location.latitude"]);
+        assert!(LIVE_SETTINGS.contains(&changed[1].as_str()));
+        assert!(!LIVE_SETTINGS.contains(&changed[0].as_str()));
+    }
+}
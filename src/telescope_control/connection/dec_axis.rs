@@ -0,0 +1,262 @@
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::tasks::write_when_steady;
+use super::transport::{Motor, MotorAxis, MotorResult, Priority, SpeedCapabilities, MC};
+use super::Connection;
+use crate::util::*;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+
+type DecGoto = AbortableTask<MotorResult<()>, MotorResult<()>>;
+
+/// What the dec motor is doing, as AbortableTaskType is for RA
+enum DecTask {
+    Slewing(DecGoto), // Going to a position, e.g. for a slew
+    Moving,           // MoveAxis, until stopped
+    Guiding,          // A pulse, which stops the motor itself when it ends
+    None,
+}
+
+/// The dec motor of a two-axis mount, changing rate and going to positions through the same ramps and gotos as RA.
+/// Stops don't wait on a pulse, going through their own handle instead.
+pub struct DecMotor {
+    motor: Arc<RwLock<Motor>>,
+    task: std::sync::Mutex<DecTask>,
+    pulse_lock: Mutex<()>, // Held through a pulse. Moves wait for it to end
+    stopper: MC,
+}
+
+#[async_trait]
+impl RWLockable<Motor> for Arc<RwLock<Motor>> {
+    async fn read(&self) -> RwLockReadGuard<'_, Motor> {
+        RwLock::read(self).await
+    }
+
+    async fn write(&self) -> RwLockWriteGuard<'_, Motor> {
+        RwLock::write(self).await
+    }
+}
+
+impl DecMotor {
+    pub fn new(motor: Motor) -> Self {
+        Self {
+            stopper: motor.mc.at(Priority::Stop),
+            motor: Arc::new(RwLock::new(motor)),
+            task: std::sync::Mutex::new(DecTask::None),
+            pulse_lock: Mutex::new(()),
+        }
+    }
+
+    /// Starts ramping to rate, once any ramp running has finished
+    async fn start_rate_change(
+        &self,
+        rate: MotionRate,
+        priority: Priority,
+    ) -> MotorResult<WaitableTask<MotorResult<()>>> {
+        let mut motor = write_when_steady(&self.motor).await?;
+        motor
+            .change_rate_at(self.motor.clone(), rate, priority)
+            .await
+    }
+
+    /// Aborts a goto, or stops a move at once without waiting out its ramp. A pulse is cut short instead.
+    async fn stop(&self) -> MotorResult<()> {
+        let task = {
+            let mut task = self.task.lock().unwrap();
+            if matches!(*task, DecTask::Guiding) {
+                None
+            } else {
+                Some(mem::replace(&mut *task, DecTask::None))
+            }
+        };
+        match task {
+            Some(DecTask::Slewing(goto)) => match goto.abort().await {
+                AbortResult::Completed(result) | AbortResult::Aborted(result) => result,
+            },
+            Some(_) => {
+                let stopped = {
+                    let mut motor = self.motor.write().await;
+                    motor.stop_now(self.motor.clone(), Priority::Stop).await?
+                };
+                stopped.await
+            }
+            // The pulse puts the motor state right when it ends
+            None => self.stopper.stop_motion().await,
+        }
+    }
+
+    /// Stops whatever was running first
+    async fn goto_by(&self, change: Degrees) -> MotorResult<DecGoto> {
+        let _pulse = self.pulse_lock.lock().await;
+        self.stop().await?;
+        let mut motor = write_when_steady(&self.motor).await?;
+        let pos = motor.get_pos().await?;
+        let goto = motor.goto(self.motor.clone(), pos + change).await?;
+        *self.task.lock().unwrap() = DecTask::Slewing(goto.clone());
+        Ok(goto)
+    }
+
+    /// Returns once the motor is at rate
+    async fn move_at(&self, rate: MotionRate) -> MotorResult<()> {
+        let pulse = self.pulse_lock.lock().await;
+        let slewing = matches!(*self.task.lock().unwrap(), DecTask::Slewing(_));
+        if slewing {
+            self.stop().await?;
+        }
+        let ramp = self.start_rate_change(rate, Priority::Motion).await?;
+        *self.task.lock().unwrap() = if rate.is_zero() {
+            DecTask::None
+        } else {
+            DecTask::Moving
+        };
+        drop(pulse);
+        ramp.await
+    }
+
+    async fn pulse(&self, rate: MotionRate, duration: Duration) -> MotorResult<ASCOMResult<()>> {
+        let _pulse = self.pulse_lock.lock().await;
+        {
+            let mut task = self.task.lock().unwrap();
+            if !matches!(*task, DecTask::None) {
+                return Ok(Err(ASCOMError::invalid_operation(
+                    "Can't guide in declination while dec is moving",
+                )));
+            }
+            *task = DecTask::Guiding;
+        }
+        let result = async {
+            self.start_rate_change(rate, Priority::Guide).await?.await?;
+            tokio::time::sleep(duration).await;
+            self.start_rate_change(MotionRate::ZERO, Priority::Guide)
+                .await?
+                .await
+        }
+        .await;
+        *self.task.lock().unwrap() = DecTask::None;
+        result.map(Ok)
+    }
+
+    fn is_slewing(&self) -> bool {
+        match &*self.task.lock().unwrap() {
+            DecTask::Slewing(goto) => !goto.get_waitable_task().is_ready(),
+            DecTask::Moving => true,
+            DecTask::Guiding | DecTask::None => false,
+        }
+    }
+
+    fn is_guiding(&self) -> bool {
+        matches!(*self.task.lock().unwrap(), DecTask::Guiding)
+    }
+}
+
+impl Connection {
+    /// Drives dec on the controller's second channel, as on the GTi. Dec is left stopped.
+    pub async fn connect_dec_axis(&self) -> ASCOMResult<()> {
+        let mut lock = self.write_con().await?;
+        if lock.dec_motor.is_some() {
+            return Ok(());
+        }
+        let mut dec_motor = self.cb.create_dec_axis(&lock.motor).await.map_err(|e| {
+            ASCOMError::new(
+                ASCOMErrorCode::new_for_driver(0),
+                format_args!("Could not connect to the dec motor: {}", e),
+            )
+        })?;
+        dec_motor
+            .change_rate_open(MotionRate::ZERO)
+            .await
+            .map_err(|e| {
+                ASCOMError::new(
+                    ASCOMErrorCode::new_for_driver(2),
+                    format_args!("Error stopping dec motor: {}", e),
+                )
+            })?;
+        self.stoppers
+            .lock()
+            .unwrap()
+            .push(lock.motor.mc.at(Priority::Stop).on_axis(MotorAxis::Dec));
+        lock.dec_motor = Some(Arc::new(DecMotor::new(dec_motor)));
+        Ok(())
+    }
+
+    async fn dec_motor(&self) -> ASCOMResult<Arc<DecMotor>> {
+        let lock = self.read_con().await?;
+        lock.dec_motor
+            .clone()
+            .ok_or_else(|| ASCOMError::invalid_operation("This mount has no dec motor"))
+    }
+
+    /// Degrees from where the dec motor was powered on
+    pub async fn get_dec_motor_pos(&self) -> ASCOMResult<Degrees> {
+        let dec = self.dec_motor().await?;
+        let result = dec.motor.read().await.get_pos().await;
+        self.check_motor_result(result).await
+    }
+
+    /// The rate the dec motor is moving at or ramping to, without asking it. None while gotoing.
+    pub async fn get_dec_heading_rate(&self) -> ASCOMResult<Option<MotionRate>> {
+        let dec = self.dec_motor().await?;
+        let rate = dec.motor.read().await.get_heading_rate();
+        Ok(rate)
+    }
+
+    pub async fn get_dec_speed_capabilities(&self) -> ASCOMResult<SpeedCapabilities> {
+        let dec = self.dec_motor().await?;
+        let speed = dec.motor.read().await.speed;
+        Ok(speed)
+    }
+
+    /// Turns the dec motor by change, returning once it has stopped there. Stopping the motor aborts it.
+    pub async fn move_dec_motor_by(&self, change: Degrees) -> ASCOMResult<()> {
+        let dec = self.dec_motor().await?;
+        let goto = self.check_motor_result(dec.goto_by(change).await).await?;
+        match goto.await {
+            AbortResult::Completed(result) => self.check_motor_result(result).await,
+            AbortResult::Aborted(result) => {
+                self.check_motor_result(result).await?;
+                Err(ASCOMError::invalid_operation("The dec slew was aborted"))
+            }
+        }
+    }
+
+    /// Moves the dec motor at rate until stopped, as MoveAxis does RA
+    pub async fn move_dec_motor(&self, rate: MotionRate) -> ASCOMResult<()> {
+        let dec = self.dec_motor().await?;
+        let result = dec.move_at(rate).await;
+        self.check_motor_result(result).await
+    }
+
+    /// Runs the dec motor at rate for the duration, then stops it
+    pub async fn pulse_dec_motor(&self, rate: MotionRate, duration: Duration) -> ASCOMResult<()> {
+        let dec = self.dec_motor().await?;
+        let result = dec.pulse(rate, duration).await;
+        self.check_motor_result(result).await?
+    }
+
+    /// Stopping a move underneath it ends the move early
+    pub async fn stop_dec_motor(&self) -> ASCOMResult<()> {
+        let dec = self.dec_motor().await?;
+        let result = dec.stop().await;
+        self.check_motor_result(result).await
+    }
+
+    pub async fn is_dec_slewing(&self) -> ASCOMResult<bool> {
+        let lock = self.read_con().await?;
+        Ok(lock
+            .dec_motor
+            .as_ref()
+            .map_or(false, |dec| dec.is_slewing()))
+    }
+
+    pub async fn is_dec_guiding(&self) -> ASCOMResult<bool> {
+        let lock = self.read_con().await?;
+        Ok(lock
+            .dec_motor
+            .as_ref()
+            .map_or(false, |dec| dec.is_guiding()))
+    }
+}
@@ -1,24 +1,42 @@
+//! The connection to the mount, in three layers:
+//! - transport: the serial motor controller, with retries and motor state tracking
+//! - state_machine: pure ASCOM state transitions, testable without a mount or runtime
+//! - this module: the async facade that locks the connection and runs tasks applying transitions
+
+use std::mem;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 use std::time::Duration;
 
-use synscan::AutoGuideSpeed;
+use synscan::{AutoGuideSpeed, DriveMode};
 use tokio::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use tokio::{select, task};
 
-use ascom_state::*;
-pub use motor::consts;
+use dec_axis::DecMotor;
 use potential_connection::*;
+use state_machine::*;
+use task_guard::TaskGuard;
+pub use transport::consts;
+pub use transport::trace_replay;
+pub use transport::PositionCache;
+pub use transport::{FirmwareInfo, SpeedCapabilities};
+pub use transport::{RetryPolicy, SerialHealthCounts};
 
-use crate::telescope_control::connection::motor::{MotorBuilder, MotorError, MotorResult};
 use crate::telescope_control::connection::tasks::*;
+use crate::telescope_control::connection::transport::{
+    MotorBuilder, MotorError, MotorResult, Priority, MC,
+};
+use crate::telescope_control::events::{Event, EventBus};
+use crate::telescope_control::guide_latency::PulseTimer;
 use crate::util::*;
 use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
 
-mod ascom_state;
-mod motor;
+mod dec_axis;
 mod potential_connection;
+mod state_machine;
+mod task_guard;
 mod tasks;
+mod transport;
 
 pub type ConnectionBuilder = MotorBuilder;
 
@@ -26,7 +44,10 @@ pub type ConnectionBuilder = MotorBuilder;
 pub struct Connection {
     c: Arc<RwLock<PotentialConnection>>,
     task_lock: Arc<Mutex<AbortableTaskType>>,
+    pulse_extender: Arc<std::sync::Mutex<Option<PulseExtender>>>, // Of the last pulse started
+    stoppers: Arc<std::sync::Mutex<Vec<MC>>>, // Of the connected motors, reachable without any lock held by a task
     cb: ConnectionBuilder,
+    events: EventBus, // For reporting the connection being lost
 }
 
 pub struct CSReadLock<'a> {
@@ -72,15 +93,20 @@ impl DerefMut for CSWriteLock<'_> {
 }
 
 impl Connection {
-    pub fn new(cb: ConnectionBuilder) -> Self {
+    pub fn new(cb: ConnectionBuilder, events: EventBus) -> Self {
         Connection {
             c: Arc::new(RwLock::new(PotentialConnection::Disconnected)),
             task_lock: Arc::new(Mutex::new(AbortableTaskType::None)),
+            pulse_extender: Arc::new(std::sync::Mutex::new(None)),
+            stoppers: Arc::new(std::sync::Mutex::new(Vec::new())),
             cb,
+            events,
         }
     }
 
-    pub async fn connect(&self, autoguide_speed: AutoGuideSpeed) -> ASCOMResult<()> {
+    /// Tracking found running near tracking_rate is carried on, and a goto found running is restarted.
+    /// Any other motion is stopped.
+    pub async fn connect(&self, tracking_rate: MotionRate) -> ASCOMResult<()> {
         let mut con = self.c.write().await;
         if matches!(*con, PotentialConnection::Connected(_)) {
             return Ok(());
@@ -93,35 +119,57 @@ impl Connection {
             )
         })?;
 
-        motor
-            .set_autoguide_speed(autoguide_speed)
-            .await
-            .map_err(|e| {
-                ASCOMError::new(
-                    ASCOMErrorCode::new_for_driver(1),
-                    format_args!("Error setting autoguide speed: {}", e),
-                )
-            })?;
-
-        // TODO currently stopping the motor on connection. We should restore the state maybe
-        motor
-            .change_rate_open(MotionRate::ZERO)
-            .await
-            .map_err(|e| {
-                ASCOMError::new(
-                    ASCOMErrorCode::new_for_driver(2),
-                    format_args!("Error stopping motor: {}", e),
-                )
-            })?;
+        let found_rate = motor.get_state().try_get_rate().unwrap_or(MotionRate::ZERO);
+        let state = match AscomState::adopted(found_rate, tracking_rate) {
+            Some(state) => {
+                if state.is_tracking() {
+                    tracing::info!(
+                        "Motor was tracking at {}°/s. Carrying on.",
+                        found_rate.clockwise_rate()
+                    );
+                }
+                state
+            }
+            None => {
+                // A rate left by a pulse or slew that never finished, e.g. when the driver was killed
+                tracing::warn!(
+                    "Motor was left moving at {}°/s. Stopping it.",
+                    found_rate.clockwise_rate()
+                );
+                self.events.publish(Event::LeftoverMotionStopped {
+                    rate: found_rate.clockwise_rate(),
+                });
+                motor
+                    .change_rate_open(MotionRate::ZERO)
+                    .await
+                    .map_err(|e| {
+                        ASCOMError::new(
+                            ASCOMErrorCode::new_for_driver(2),
+                            format_args!("Error stopping motor: {}", e),
+                        )
+                    })?;
+                AscomState::Idle(GuideState::Idle)
+            }
+        };
+        let interrupted_goto = motor.interrupted_goto.take();
 
-        let state = AscomState::Idle(GuideState::Idle);
+        *self.stoppers.lock().unwrap() = vec![motor.mc.at(Priority::Stop)];
 
         let cs = ConnectedState {
             ascom_state: state,
             motor,
+            dec_motor: None, // Connected once the mount is known to have one
         };
 
         *con = PotentialConnection::Connected(cs);
+        drop(con);
+
+        // Only stopped while reading the motor state, since nothing was waiting on it
+        if let Some(target) = interrupted_goto {
+            tracing::info!("Motor was going to {}°. Restarting the goto.", target);
+            // Runs on like any other slew
+            let _slew = self.slew_to(target, None).await?;
+        }
 
         Ok(())
     }
@@ -129,6 +177,7 @@ impl Connection {
     pub async fn disconnect(&self) {
         let mut con = self.c.write().await;
         *con = PotentialConnection::Disconnected;
+        self.stoppers.lock().unwrap().clear();
     }
 
     pub async fn read_con(&self) -> ASCOMResult<CSReadLock<'_>> {
@@ -159,9 +208,52 @@ impl Connection {
         Ok(lock.motor.get_max_speed())
     }
 
+    pub async fn get_speed_capabilities(&self) -> ASCOMResult<SpeedCapabilities> {
+        let lock = self.read_con().await?;
+        Ok(lock.motor.speed)
+    }
+
+    /// None if the controller didn't report it
+    pub async fn get_firmware(&self) -> ASCOMResult<Option<FirmwareInfo>> {
+        let lock = self.read_con().await?;
+        Ok(lock.motor.firmware.clone())
+    }
+
+    /// The serial port of the motor controller
+    pub async fn get_port(&self) -> ASCOMResult<String> {
+        let lock = self.read_con().await?;
+        Ok(lock.motor.port.clone())
+    }
+
     pub async fn get_pos(&self) -> ASCOMResult<Degrees> {
         let lock = self.read_con().await?;
-        self.check_motor_result(lock.motor.get_pos().await).await
+        self.check_motor_result(lock.motor.get_recent_pos().await)
+            .await
+    }
+
+    /// The rate the motor is known to be moving at, without asking it. None while it's changing or gotoing.
+    pub async fn get_known_rate(&self) -> ASCOMResult<Option<MotionRate>> {
+        let lock = self.read_con().await?;
+        Ok(lock.motor.get_state().try_get_rate())
+    }
+
+    /// Unlike get_known_rate, known through a ramp, as the rate it's ramping to
+    pub async fn get_heading_rate(&self) -> ASCOMResult<Option<MotionRate>> {
+        let lock = self.read_con().await?;
+        Ok(lock.motor.get_heading_rate())
+    }
+
+    /// Since the driver started, whether connected or not
+    pub fn get_serial_health(&self) -> SerialHealthCounts {
+        self.cb.serial_health()
+    }
+
+    /// Asks the motor controller directly, regardless of the tracked state
+    pub async fn is_motor_running(&self) -> ASCOMResult<bool> {
+        let lock = self.read_con().await?;
+        let result = lock.motor.mc.inquire_status().await;
+        drop(lock);
+        Ok(self.check_motor_result(result).await?.running)
     }
 
     pub async fn is_guiding(&self) -> ASCOMResult<bool> {
@@ -169,6 +261,11 @@ impl Connection {
         Ok(lock.ascom_state.is_guiding())
     }
 
+    pub async fn is_moving_unchecked(&self) -> ASCOMResult<bool> {
+        let lock = self.read_con().await?;
+        Ok(lock.ascom_state.is_moving_unchecked())
+    }
+
     pub async fn is_slewing(&self) -> ASCOMResult<bool> {
         let lock = self.read_con().await?;
         Ok(lock.ascom_state.is_slewing())
@@ -184,6 +281,144 @@ impl Connection {
         Ok(lock.ascom_state.is_tracking())
     }
 
+    pub async fn is_stationary(&self) -> ASCOMResult<bool> {
+        let lock = self.read_con().await?;
+        Ok(lock.ascom_state.is_stationary())
+    }
+
+    /// Checks for a goto started behind our back, e.g. by the vendor hand app used in parallel.
+    /// If found, stops the motor and returns to a stopped state. Returns true if this happened.
+    pub async fn recover_from_foreign_goto(&self) -> ASCOMResult<bool> {
+        // Hold the task lock so none of our own gotos can start meanwhile
+        let mut task_lock = self.task_lock.lock().await;
+        if matches!(
+            &*task_lock,
+            AbortableTaskType::Slewing(_) | AbortableTaskType::Parking(_)
+        ) || self.is_slewing().await?
+        {
+            return Ok(false);
+        }
+
+        let lock = self.read_con().await?;
+        let result = lock.motor.mc.inquire_status().await;
+        drop(lock);
+        let status = self.check_motor_result(result).await?;
+        if !(status.running && status.mode == DriveMode::Goto) {
+            return Ok(false);
+        }
+
+        tracing::error!("Motor controller is in an unexpected goto. Stopping it.");
+        if let AbortableTaskType::Guiding(guide_task) = &mut *task_lock {
+            guide_task.abort().await.unwrap()?;
+        }
+        *task_lock = AbortableTaskType::None;
+
+        let mut lock = self.write_con().await?;
+        let result = lock.motor.rederive_state().await;
+        if result.is_ok() && !lock.ascom_state.is_parked() {
+            lock.ascom_state = AscomState::Idle(GuideState::Idle);
+        }
+        drop(lock);
+        self.check_motor_result(result).await?;
+        Ok(true)
+    }
+
+    /// The rate the motor should be moving at and the rate the controller reports.
+    /// None during gotos and rate changes, when the two legitimately differ.
+    pub async fn read_motor_rates(&self) -> ASCOMResult<Option<(MotionRate, MotionRate)>> {
+        let lock = self.read_con().await?;
+        let commanded = match lock.motor.get_state().try_get_rate() {
+            Some(rate) => rate,
+            None => return Ok(None),
+        };
+        let result = lock.motor.inquire_motion_rate().await;
+        drop(lock);
+        let reported = self.check_motor_result(result).await?;
+        Ok(Some((commanded, reported)))
+    }
+
+    /// Stops a motor that ran away from its commanded rate. Tracking is off afterwards.
+    /// False if it was left alone because a slew or park had taken over the motor.
+    pub async fn stop_runaway_motor(&self) -> ASCOMResult<bool> {
+        let mut task_lock = self.task_lock.lock().await;
+        match &mut *task_lock {
+            // Those own the motor, and the rates aren't compared during them anyway
+            AbortableTaskType::Slewing(_) | AbortableTaskType::Parking(_) => return Ok(false),
+            AbortableTaskType::Guiding(guide_task) => {
+                guide_task.abort().await.unwrap()?;
+            }
+            AbortableTaskType::None => {}
+        }
+        *task_lock = AbortableTaskType::None;
+
+        let mut lock = self.write_con().await?;
+        let result = lock.motor.force_stop().await;
+        if result.is_ok() && !lock.ascom_state.is_parked() {
+            lock.ascom_state = AscomState::Idle(GuideState::Idle);
+        }
+        drop(lock);
+        self.check_motor_result(result).await?;
+        Ok(true)
+    }
+
+    /// Stops the motors without waiting on the task lock or the connection, for when a task has wedged.
+    /// Queued motion is dropped. The running task is aborted and the state reset to stopped once they're free.
+    pub async fn panic_stop(&self) -> ASCOMResult<()> {
+        let stoppers = self.stoppers.lock().unwrap().clone();
+        if stoppers.is_empty() {
+            return Err(ASCOMError::NOT_CONNECTED);
+        }
+        let mut result = Ok(());
+        for stopper in stoppers {
+            // Try every motor even if one fails
+            let stopped = stopper.stop_motion().await;
+            result = result.and(stopped);
+        }
+
+        let connection = self.clone();
+        task::spawn(async move {
+            if let Err(e) = connection.reset_after_panic_stop().await {
+                tracing::error!("Couldn't reset the state after a panic stop: {}", e);
+            }
+        });
+
+        // Not through check_motor_result, which would wait on the connection to disconnect
+        result.map_err(|e| {
+            ASCOMError::new(
+                ASCOMErrorCode::new_for_driver(2),
+                format_args!("Error stopping motor: {}", e),
+            )
+        })
+    }
+
+    async fn reset_after_panic_stop(&self) -> ASCOMResult<()> {
+        let mut task_lock = self.task_lock.lock().await;
+        match mem::replace(&mut *task_lock, AbortableTaskType::None) {
+            // Finishing a slew may restore tracking, which is stopped again below
+            AbortableTaskType::Slewing(task)
+            | AbortableTaskType::Parking(task)
+            | AbortableTaskType::Guiding(task) => {
+                let _ = task.abort().await;
+            }
+            AbortableTaskType::None => {}
+        }
+        *self.pulse_extender.lock().unwrap() = None;
+
+        let mut lock = self.write_con().await?;
+        let result = lock.motor.force_stop().await;
+        if result.is_ok() && !lock.ascom_state.is_parked() {
+            lock.ascom_state = AscomState::Idle(GuideState::Idle);
+        }
+        let has_dec_motor = lock.dec_motor.is_some();
+        drop(lock);
+        drop(task_lock);
+        self.check_motor_result(result).await?;
+        if has_dec_motor {
+            self.stop_dec_motor().await?;
+        }
+        Ok(())
+    }
+
     /* PUT/Write */
 
     /// Convenience function that internally locks and unlocks the connection
@@ -197,10 +432,21 @@ impl Connection {
         match res {
             Ok(r) => return Ok(r),
             Err(MotorError::Disconnected) => {}
+            Err(e @ (MotorError::Rejected(_) | MotorError::UnexpectedState(_))) => {
+                // The link is fine, so stay connected
+                tracing::error!("{}", e);
+                return Err(ASCOMError::new(
+                    ASCOMErrorCode::new_for_driver(2),
+                    format_args!("{}", e),
+                ));
+            }
             Err(motor_error) => {
                 // Error means we disconnect
                 tracing::error!("Disconnecting due to motor error {}", motor_error);
                 self.disconnect().await;
+                self.events.publish(Event::ConnectionLost {
+                    reason: motor_error.to_string(),
+                });
             }
         }
         Err(ASCOMError::NOT_CONNECTED)
@@ -232,6 +478,12 @@ impl Connection {
         *task_lock = long_task.get_abortable_task(task.clone());
 
         let connection = self.clone();
+        let guard = TaskGuard::new(
+            self.clone(),
+            finisher,
+            abort_waiter.clone(),
+            long_task.base_rate(),
+        );
 
         task::spawn(async move {
             let lock_task = connection.task_lock.lock();
@@ -244,11 +496,11 @@ impl Connection {
                 mut task_lock = completion => {
                     *task_lock = AbortableTaskType::None;
                     let result = connection.check_motor_result(long_task.complete(&locker).await).await;
-                    finisher.finish(result);
+                    guard.finish(result);
                 }
                 _ = abort_waiter => {
                     let result = connection.check_motor_result(long_task.abort(&locker).await).await;
-                    finisher.aborted(result);
+                    guard.aborted(result);
                 }
             }
         });
@@ -356,37 +608,58 @@ impl Connection {
         self.run_short_task(move_motor_task).await
     }
 
+    /// A pulse arriving while another runs extends it if it goes the same way, and otherwise waits for it to end
     pub async fn pulse_guide(
         &self,
         guide_rate: MotionRate,
         duration: Duration,
+        timer: Option<PulseTimer>,
     ) -> ASCOMResult<WaitableTask<AbortResult<ASCOMResult<()>, ASCOMResult<()>>>> {
-        let mut task_lock = self.task_lock.lock().await;
+        loop {
+            let mut task_lock = self.task_lock.lock().await;
 
-        match &mut *task_lock {
-            AbortableTaskType::Slewing(_) => {
-                return Err(ASCOMError::invalid_operation("Can't guide while slewing"));
-            }
-            AbortableTaskType::Parking(_) => {
-                return Err(ASCOMError::invalid_operation("Can't guide while parking"));
-            }
-            AbortableTaskType::Guiding(_) => {
-                return Err(ASCOMError::invalid_operation("Already guiding"));
+            match &mut *task_lock {
+                AbortableTaskType::Slewing(_) => {
+                    return Err(ASCOMError::invalid_operation("Can't guide while slewing"));
+                }
+                AbortableTaskType::Parking(_) => {
+                    return Err(ASCOMError::invalid_operation("Can't guide while parking"));
+                }
+                AbortableTaskType::Guiding(guide_task) => {
+                    let extended = self
+                        .pulse_extender
+                        .lock()
+                        .unwrap()
+                        .as_ref()
+                        .map_or(false, |extender| extender.try_extend(guide_rate, duration));
+                    if extended {
+                        tracing::debug!("Extended the running pulse by {:?}", duration);
+                        return Ok(guide_task.clone().into());
+                    }
+
+                    let running = guide_task.clone();
+                    drop(task_lock);
+                    tracing::debug!("Queueing pulse behind the running one");
+                    let _ = running.await;
+                    continue;
+                }
+                AbortableTaskType::None => {}
             }
-            AbortableTaskType::None => {}
-        }
-        *task_lock = AbortableTaskType::None;
 
-        let pulse_guide_task = PulseGuideTask::new(guide_rate, duration);
+            let pulse_guide_task = PulseGuideTask::new(guide_rate, duration, timer);
+            *self.pulse_extender.lock().unwrap() = Some(pulse_guide_task.extender());
 
-        self.run_long_task(pulse_guide_task, task_lock).await
+            return self.run_long_task(pulse_guide_task, task_lock).await;
+        }
     }
 
     /// pos in degrees relative to turning on mount
     /// pos can be negative or positive or past 360 deg
+    /// If given, slews that go back to tracking end with a leg of `approach` degrees moving with it
     pub async fn slew_to(
         &self,
         target_pos: Degrees,
+        approach: Option<Degrees>,
     ) -> ASCOMResult<WaitableTask<AbortResult<ASCOMResult<()>, ASCOMResult<()>>>> {
         let mut task_lock = self.task_lock.lock().await;
 
@@ -403,14 +676,16 @@ impl Connection {
             AbortableTaskType::None => {}
         }
 
-        let slew_task = SlewToTask::new(target_pos);
+        let slew_task = SlewToTask::new(target_pos, approach);
 
         self.run_long_task(slew_task, task_lock).await
     }
 
+    /// If given, the approach position is gone to first so the park always finishes moving the same way
     pub async fn park(
         &self,
         park_pos: Degrees,
+        approach_pos: Option<Degrees>,
     ) -> ASCOMResult<WaitableTask<AbortResult<ASCOMResult<()>, ASCOMResult<()>>>> {
         let mut task_lock = self.task_lock.lock().await;
 
@@ -427,7 +702,7 @@ impl Connection {
             AbortableTaskType::None => {}
         }
 
-        let park_task = ParkTask::new(park_pos);
+        let park_task = ParkTask::new(park_pos, approach_pos);
 
         self.run_long_task(park_task, task_lock).await
     }
@@ -455,6 +730,11 @@ impl Connection {
     }
 
     pub async fn abort_slew(&self) -> ASCOMResult<()> {
+        // Before waiting on the task lock, which a MoveAxis holds until its ramp is done
+        self.read_con().await?;
+        self.check_motor_result(stop_moving_axis(&self.c).await)
+            .await?;
+
         let mut task_lock = self.task_lock.lock().await;
 
         match &mut *task_lock {
@@ -483,3 +763,107 @@ impl Deref for Connection {
         &self.c
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The serial timeout, so the longest a command may take to be answered
+    const ROUND_TRIP: Duration = Duration::from_millis(50);
+
+    async fn connect(cb: ConnectionBuilder) -> Connection {
+        let connection = Connection::new(
+            cb.with_path("sim://").with_timeout(ROUND_TRIP),
+            EventBus::new(),
+        );
+        connection.connect(MotionRate::ZERO).await.unwrap();
+        connection
+    }
+
+    async fn connect_simulator() -> Connection {
+        connect(ConnectionBuilder::new()).await
+    }
+
+    #[tokio::test]
+    async fn test_connect_to_simulator() {
+        let connection = connect_simulator().await;
+        let firmware = connection.get_firmware().await.unwrap().unwrap();
+        assert_eq!(firmware.mount_code, 0x0A);
+        assert!(connection.get_pos().await.unwrap().abs() < 1e-6);
+        assert!(connection.is_stationary().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_move_and_stop() {
+        let connection = connect_simulator().await;
+        let rate = MotionRate::from_clockwise_rate(0.1);
+        connection.move_motor(rate).await.unwrap();
+        assert!(connection.is_motor_running().await.unwrap());
+        assert_eq!(connection.get_known_rate().await.unwrap(), Some(rate));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        connection.move_motor(MotionRate::ZERO).await.unwrap();
+        assert!(connection.is_stationary().await.unwrap());
+        assert!(connection.get_pos().await.unwrap() > 0.);
+    }
+
+    #[tokio::test]
+    async fn test_slew_and_abort() {
+        let connection = connect_simulator().await;
+        let slew = connection.slew_to(0.1, None).await.unwrap();
+        assert!(matches!(slew.await, AbortResult::Completed(Ok(()))));
+        assert!((connection.get_pos().await.unwrap() - 0.1).abs() < 1e-3);
+
+        // Far enough to still be going when aborted
+        let slew = connection.slew_to(10., None).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        connection.abort_slew().await.unwrap();
+        assert!(matches!(slew.await, AbortResult::Aborted(_)));
+        assert!(connection.is_stationary().await.unwrap());
+        assert!(connection.get_pos().await.unwrap() < 1.);
+    }
+
+    #[tokio::test]
+    async fn test_abort_cuts_ramp_short() {
+        // Several seconds to reach the max speed
+        let acceleration = 0.1;
+        let connection =
+            connect(ConnectionBuilder::new().with_max_acceleration(acceleration)).await;
+        let max_speed = connection.get_max_speed().await.unwrap();
+        let rate = MotionRate::from_clockwise_rate(max_speed);
+        let moving = connection.clone();
+        let move_axis = tokio::spawn(async move { moving.move_motor(rate).await });
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert!(!move_axis.is_finished());
+        assert_ne!(connection.get_known_rate().await.unwrap(), Some(rate));
+
+        connection.abort_slew().await.unwrap();
+        move_axis.await.unwrap().unwrap();
+        // Ramping all the way up alone would have covered this much
+        let full_ramp = max_speed * max_speed / (2. * acceleration);
+        assert!(connection.get_pos().await.unwrap() < full_ramp / 2.);
+        assert!(connection.is_stationary().await.unwrap());
+        assert_eq!(
+            connection.get_known_rate().await.unwrap(),
+            Some(MotionRate::ZERO)
+        );
+        assert!(!connection.is_motor_running().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_heading_rate_through_ramp() {
+        let connection = connect(ConnectionBuilder::new().with_max_acceleration(0.1)).await;
+        let rate = MotionRate::from_clockwise_rate(connection.get_max_speed().await.unwrap());
+        let moving = connection.clone();
+        let move_axis = tokio::spawn(async move { moving.move_motor(rate).await });
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(connection.get_heading_rate().await.unwrap(), Some(rate));
+        assert_ne!(connection.get_known_rate().await.unwrap(), Some(rate));
+
+        connection.abort_slew().await.unwrap();
+        move_axis.await.unwrap().unwrap();
+        assert_eq!(
+            connection.get_heading_rate().await.unwrap(),
+            Some(MotionRate::ZERO)
+        );
+    }
+}
@@ -1,5 +1,5 @@
-use crate::telescope_control::connection::motor::locked::HasMotor;
-use crate::telescope_control::connection::motor::Motor;
+use crate::telescope_control::connection::transport::locked::HasMotor;
+use crate::telescope_control::connection::transport::Motor;
 
 use super::*;
 use async_trait::async_trait;
@@ -68,4 +68,24 @@ impl HasMotor for PotentialConnection {
 pub struct ConnectedState {
     pub ascom_state: AscomState,
     pub motor: Motor,
+    pub dec_motor: Option<Arc<DecMotor>>, // Only on two-axis mounts
+}
+
+impl ConnectedState {
+    /// Plans the command, or fails if the motor isn't in a state it can be planned from
+    pub fn plan(&self, command: Command) -> MotorResult<Transition> {
+        match self
+            .ascom_state
+            .plan(self.motor.get_state().try_get_rate(), command)
+        {
+            Transition::Unplannable(reason) => Err(MotorError::UnexpectedState(format!(
+                "{:?} from {:?} with the motor {:?}: {}",
+                command,
+                self.ascom_state,
+                self.motor.get_state(),
+                reason
+            ))),
+            transition => Ok(transition),
+        }
+    }
 }
@@ -0,0 +1,498 @@
+//! Pure ASCOM state machine. Decides what a command does in each state without touching the motor.
+
+use crate::util::*;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode};
+
+/// Fraction of the tracking rate a rate found on connecting may be off by and still be taken for tracking.
+/// Covers the lunar, solar and King rates, but not a guide pulse at even 0.125x.
+const ADOPTED_TRACKING_TOLERANCE: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AscomState {
+    Parked,
+    Idle(GuideState),
+    Tracking(GuideState),
+    Slewing(SlewingState),
+}
+
+impl AscomState {
+    #[allow(unused)]
+    pub fn is_guiding(&self) -> bool {
+        matches!(self.guide_ref(), GuideState::Guiding)
+    }
+
+    /// MoveAxis motion and guide pulses, which aren't checked against the mount limits before they start
+    pub fn is_moving_unchecked(&self) -> bool {
+        matches!(
+            self,
+            AscomState::Slewing(SlewingState::MoveAxis(..))
+                | AscomState::Idle(GuideState::Guiding)
+                | AscomState::Tracking(GuideState::Guiding)
+        )
+    }
+
+    pub fn is_parked(&self) -> bool {
+        matches!(self, AscomState::Parked)
+    }
+
+    pub fn is_tracking(&self) -> bool {
+        matches!(self, AscomState::Tracking(..))
+    }
+
+    pub fn is_slewing(&self) -> bool {
+        matches!(self, AscomState::Slewing(..))
+    }
+
+    /// True if the motor isn't turning at all
+    pub fn is_stationary(&self) -> bool {
+        matches!(
+            self,
+            AscomState::Parked | AscomState::Idle(GuideState::Idle)
+        )
+    }
+
+    pub fn guide_ref(&self) -> &GuideState {
+        match self {
+            AscomState::Parked => {
+                panic!("No guiding on parked")
+            }
+            AscomState::Idle(gs) => gs,
+            AscomState::Tracking(gs) => gs,
+            AscomState::Slewing(SlewingState::MoveAxis(_, gs)) => gs,
+            AscomState::Slewing(SlewingState::SlewTo, ..) => {
+                panic!("No guiding on SlewTo")
+            }
+        }
+    }
+
+    pub fn guide_ref_mut(&mut self) -> &mut GuideState {
+        match self {
+            AscomState::Parked => {
+                panic!("No guiding on parked")
+            }
+            AscomState::Idle(gs) => gs,
+            AscomState::Tracking(gs) => gs,
+            AscomState::Slewing(SlewingState::MoveAxis(_, gs)) => gs,
+            AscomState::Slewing(SlewingState::SlewTo, ..) => {
+                panic!("No guiding on SlewTo")
+            }
+        }
+    }
+
+    /// The state once a long task has gone without finishing and the motor is back at its base rate.
+    /// An unfinished goto leaves the motor stopped.
+    pub fn without_task(self) -> AscomState {
+        match self {
+            AscomState::Parked => AscomState::Parked,
+            AscomState::Slewing(SlewingState::SlewTo) => AscomState::Idle(GuideState::Idle),
+            mut state => {
+                *state.guide_ref_mut() = GuideState::Idle;
+                state
+            }
+        }
+    }
+
+    /// The state to carry on in over a motor found turning at found_rate when connecting, or None if it should be stopped.
+    /// Tracking near tracking_rate is kept, e.g. through a driver restart mid-exposure. Anything else is left over from
+    /// a pulse or move that never finished.
+    pub fn adopted(found_rate: MotionRate, tracking_rate: MotionRate) -> Option<AscomState> {
+        if found_rate.is_zero() {
+            return Some(AscomState::Idle(GuideState::Idle));
+        }
+        let expected = tracking_rate.clockwise_rate();
+        let off_by = (found_rate.clockwise_rate() - expected).abs();
+        (off_by <= ADOPTED_TRACKING_TOLERANCE * expected.abs())
+            .then_some(AscomState::Tracking(GuideState::Idle))
+    }
+
+    /// Decides what a command does from this state.
+    /// motor_rate is the steady rate of the motor, None while it's changing or going to a position.
+    /// Guide pulses and slews are aborted by the facade before other commands are planned.
+    pub fn plan(&self, motor_rate: Option<MotionRate>, command: Command) -> Transition {
+        // Only None outside of a goto if something else left the motor changing
+        let motor_rate = match motor_rate {
+            Some(rate) => rate,
+            None if self.needs_motor_rate(command) => {
+                return Transition::Unplannable("Motor rate unknown outside of a goto")
+            }
+            None => MotionRate::ZERO, // Not read
+        };
+
+        match (*self, command) {
+            (AscomState::Slewing(SlewingState::SlewTo), Command::AbortSlew) => Transition::Stay, // The slew aborts when we cancel the task
+            (AscomState::Slewing(SlewingState::SlewTo), _) => {
+                Transition::Unplannable("Command planned during a goto")
+            }
+            (AscomState::Parked, _) => Self::plan_parked(command),
+            (state, _) if state.is_guiding() => {
+                Transition::Unplannable("Command planned during a guide pulse")
+            }
+
+            /* Tracking */
+            (AscomState::Idle(_), Command::StopTracking) => Transition::Stay,
+            (AscomState::Tracking(_), Command::StopTracking) => Transition::ChangeRate {
+                rate: MotionRate::ZERO,
+                next: AscomState::Idle(GuideState::Idle),
+            },
+            (AscomState::Slewing(_), Command::StopTracking) => Transition::Reject(
+                Rejection::InvalidOperation("Can't stop tracking while moving axis"),
+            ),
+
+            (AscomState::Idle(_), Command::StartTracking(rate)) => Transition::ChangeRate {
+                rate,
+                next: AscomState::Tracking(GuideState::Idle),
+            },
+            (
+                AscomState::Tracking(_),
+                Command::StartTracking(rate) | Command::UpdateTrackingRate(rate),
+            ) => {
+                if motor_rate == rate {
+                    Transition::Stay
+                } else {
+                    Transition::ChangeRate {
+                        rate,
+                        next: AscomState::Tracking(GuideState::Idle),
+                    }
+                }
+            }
+            (AscomState::Slewing(_), Command::StartTracking(_)) => Transition::Reject(
+                Rejection::InvalidOperation("Can't start tracking while moving axis"),
+            ),
+
+            // Only do anything if tracking currently
+            (_, Command::UpdateTrackingRate(_)) => Transition::Stay,
+
+            /* Move Axis */
+            (AscomState::Idle(_), Command::MoveAxis(rate)) => Transition::ChangeRate {
+                rate,
+                next: AscomState::Slewing(SlewingState::MoveAxis(
+                    RestorableState::Idle,
+                    GuideState::Idle,
+                )),
+            },
+            (AscomState::Tracking(_), Command::MoveAxis(rate)) => Transition::ChangeRate {
+                rate,
+                next: AscomState::Slewing(SlewingState::MoveAxis(
+                    RestorableState::Tracking(motor_rate),
+                    GuideState::Idle,
+                )),
+            },
+            (AscomState::Slewing(SlewingState::MoveAxis(rs, _)), Command::MoveAxis(rate)) => {
+                if motor_rate == rate {
+                    Transition::Stay
+                } else {
+                    Transition::ChangeRate {
+                        rate,
+                        next: AscomState::Slewing(SlewingState::MoveAxis(rs, GuideState::Idle)),
+                    }
+                }
+            }
+
+            /* Abort */
+            (AscomState::Idle(_) | AscomState::Tracking(_), Command::AbortSlew) => Transition::Stay,
+            (AscomState::Slewing(SlewingState::MoveAxis(rs, _)), Command::AbortSlew) => match rs {
+                RestorableState::Tracking(rate) => Transition::ChangeRate {
+                    rate,
+                    next: AscomState::Tracking(GuideState::Idle),
+                },
+                RestorableState::Idle => Transition::ChangeRate {
+                    rate: MotionRate::ZERO,
+                    next: AscomState::Idle(GuideState::Idle),
+                },
+            },
+
+            /* Goto */
+            (AscomState::Idle(_), Command::SlewTo) => {
+                Transition::Goto(GotoEnd::Restore(RestorableState::Idle))
+            }
+            (AscomState::Tracking(_), Command::SlewTo) => {
+                Transition::Goto(GotoEnd::Restore(RestorableState::Tracking(motor_rate)))
+            }
+            (AscomState::Slewing(SlewingState::MoveAxis(rs, _)), Command::SlewTo) => {
+                Transition::Goto(GotoEnd::Restore(rs))
+            }
+            (_, Command::Park) => Transition::Goto(GotoEnd::Park),
+
+            /* Other */
+            (_, Command::PulseGuide) => Transition::StartGuiding,
+            (_, Command::Unpark) => Transition::Stay,
+        }
+    }
+
+    /// Whether planning the command from this state reads the motor's rate
+    fn needs_motor_rate(&self, command: Command) -> bool {
+        matches!(
+            (self, command),
+            (
+                AscomState::Tracking(_),
+                Command::StartTracking(_)
+                    | Command::UpdateTrackingRate(_)
+                    | Command::MoveAxis(_)
+                    | Command::SlewTo
+            ) | (
+                AscomState::Slewing(SlewingState::MoveAxis(..)),
+                Command::MoveAxis(_)
+            )
+        )
+    }
+
+    fn plan_parked(command: Command) -> Transition {
+        match command {
+            Command::StartTracking(_) => {
+                Transition::Reject(Rejection::WhileParked("Can't start tracking while parked"))
+            }
+            Command::StopTracking => {
+                Transition::Reject(Rejection::WhileParked("Can't stop tracking while parked"))
+            }
+            Command::UpdateTrackingRate(_) => Transition::Stay,
+            Command::MoveAxis(_) => {
+                Transition::Reject(Rejection::WhileParked("Can't move axis while parked"))
+            }
+            Command::AbortSlew => {
+                Transition::Reject(Rejection::WhileParked("Can't abort slew while parked"))
+            }
+            Command::SlewTo => {
+                Transition::Reject(Rejection::WhileParked("Can't slew to while parked"))
+            }
+            Command::PulseGuide => {
+                Transition::Reject(Rejection::WhileParked("Can't pulse guide while parked"))
+            }
+            // FIXME better parking logic
+            Command::Park => Transition::Stay,
+            Command::Unpark => Transition::Enter(AscomState::Idle(GuideState::Idle)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlewingState {
+    SlewTo, //TODO Declination slew?
+    MoveAxis(RestorableState, GuideState),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestorableState {
+    Idle,
+    Tracking(MotionRate),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GuideState {
+    Idle,
+    Guiding,
+}
+
+/// Requests the facade makes of the mount
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    StartTracking(MotionRate),
+    StopTracking,
+    UpdateTrackingRate(MotionRate),
+    MoveAxis(MotionRate),
+    AbortSlew,
+    SlewTo,
+    Park,
+    Unpark,
+    PulseGuide,
+}
+
+/// What happens when a goto finishes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GotoEnd {
+    Restore(RestorableState),
+    Park,
+}
+
+/// What has to be done to the motor to carry out a command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transition {
+    /// Nothing to do
+    Stay,
+    Reject(Rejection),
+    /// Change state without touching the motor
+    Enter(AscomState),
+    /// Change the motor rate and enter the next state
+    ChangeRate {
+        rate: MotionRate,
+        next: AscomState,
+    },
+    /// Stop the motor if it's moving, then go to a position
+    Goto(GotoEnd),
+    /// Add the guide rate to the current rate
+    StartGuiding,
+    /// The motor or state isn't one the command can be planned from, e.g. left by something outside the driver
+    Unplannable(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    WhileParked(&'static str),
+    InvalidOperation(&'static str),
+}
+
+impl From<Rejection> for ASCOMError {
+    fn from(rejection: Rejection) -> Self {
+        match rejection {
+            Rejection::WhileParked(msg) => {
+                ASCOMError::new(ASCOMErrorCode::INVALID_WHILE_PARKED, msg.to_string())
+            }
+            Rejection::InvalidOperation(msg) => ASCOMError::invalid_operation(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use synscan::Direction;
+
+    const IDLE: AscomState = AscomState::Idle(GuideState::Idle);
+    const TRACKING: AscomState = AscomState::Tracking(GuideState::Idle);
+
+    fn rate(r: Degrees) -> MotionRate {
+        MotionRate::new(r, Direction::Clockwise)
+    }
+
+    #[test]
+    fn test_tracking() {
+        let sidereal = rate(0.004178);
+
+        assert_eq!(
+            IDLE.plan(Some(MotionRate::ZERO), Command::StartTracking(sidereal)),
+            Transition::ChangeRate {
+                rate: sidereal,
+                next: TRACKING
+            }
+        );
+        assert_eq!(
+            TRACKING.plan(Some(sidereal), Command::StartTracking(sidereal)),
+            Transition::Stay
+        );
+        assert_eq!(
+            TRACKING.plan(Some(sidereal), Command::StopTracking),
+            Transition::ChangeRate {
+                rate: MotionRate::ZERO,
+                next: IDLE
+            }
+        );
+        assert_eq!(
+            IDLE.plan(
+                Some(MotionRate::ZERO),
+                Command::UpdateTrackingRate(sidereal)
+            ),
+            Transition::Stay
+        );
+    }
+
+    #[test]
+    fn test_move_axis_restores_tracking() {
+        let sidereal = rate(0.004178);
+        let fast = rate(1.);
+
+        let transition = TRACKING.plan(Some(sidereal), Command::MoveAxis(fast));
+        let moving = AscomState::Slewing(SlewingState::MoveAxis(
+            RestorableState::Tracking(sidereal),
+            GuideState::Idle,
+        ));
+        assert_eq!(
+            transition,
+            Transition::ChangeRate {
+                rate: fast,
+                next: moving
+            }
+        );
+
+        assert_eq!(
+            moving.plan(Some(fast), Command::StartTracking(sidereal)),
+            Transition::Reject(Rejection::InvalidOperation(
+                "Can't start tracking while moving axis"
+            ))
+        );
+        assert_eq!(
+            moving.plan(Some(fast), Command::AbortSlew),
+            Transition::ChangeRate {
+                rate: sidereal,
+                next: TRACKING
+            }
+        );
+        assert_eq!(
+            moving.plan(None, Command::SlewTo),
+            Transition::Goto(GotoEnd::Restore(RestorableState::Tracking(sidereal)))
+        );
+    }
+
+    #[test]
+    fn test_unknown_rate_is_unplannable() {
+        assert!(matches!(
+            TRACKING.plan(None, Command::StartTracking(rate(0.004178))),
+            Transition::Unplannable(_)
+        ));
+        assert!(matches!(
+            AscomState::Slewing(SlewingState::SlewTo).plan(None, Command::MoveAxis(rate(1.))),
+            Transition::Unplannable(_)
+        ));
+        // Rates that aren't read don't have to be known
+        assert_eq!(IDLE.plan(None, Command::StopTracking), Transition::Stay);
+    }
+
+    #[test]
+    fn test_parked() {
+        let parked = AscomState::Parked;
+        for command in [
+            Command::StartTracking(rate(0.004178)),
+            Command::StopTracking,
+            Command::MoveAxis(rate(1.)),
+            Command::AbortSlew,
+            Command::SlewTo,
+            Command::PulseGuide,
+        ] {
+            assert!(matches!(
+                parked.plan(None, command),
+                Transition::Reject(Rejection::WhileParked(_))
+            ));
+        }
+        assert_eq!(parked.plan(None, Command::Park), Transition::Stay);
+        assert_eq!(parked.plan(None, Command::Unpark), Transition::Enter(IDLE));
+        assert_eq!(IDLE.plan(None, Command::Unpark), Transition::Stay);
+        assert_eq!(
+            TRACKING.plan(None, Command::Park),
+            Transition::Goto(GotoEnd::Park)
+        );
+    }
+
+    #[test]
+    fn test_without_task() {
+        assert_eq!(
+            AscomState::Tracking(GuideState::Guiding).without_task(),
+            TRACKING
+        );
+        assert_eq!(
+            AscomState::Slewing(SlewingState::SlewTo).without_task(),
+            IDLE
+        );
+        let moving = SlewingState::MoveAxis(RestorableState::Idle, GuideState::Guiding);
+        assert_eq!(
+            AscomState::Slewing(moving).without_task(),
+            AscomState::Slewing(SlewingState::MoveAxis(
+                RestorableState::Idle,
+                GuideState::Idle
+            ))
+        );
+        assert_eq!(AscomState::Parked.without_task(), AscomState::Parked);
+    }
+
+    #[test]
+    fn test_adopted() {
+        let sidereal = rate(0.004178);
+        assert_eq!(AscomState::adopted(MotionRate::ZERO, sidereal), Some(IDLE));
+        assert_eq!(AscomState::adopted(sidereal, sidereal), Some(TRACKING));
+        // Lunar
+        assert_eq!(
+            AscomState::adopted(rate(0.004024), sidereal),
+            Some(TRACKING)
+        );
+        // A pulse that never finished, and tracking the other way
+        assert_eq!(AscomState::adopted(rate(0.004178 * 1.5), sidereal), None);
+        assert_eq!(AscomState::adopted(rate(-0.004178), sidereal), None);
+    }
+}
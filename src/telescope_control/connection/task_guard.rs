@@ -0,0 +1,121 @@
+use tokio::runtime::Handle;
+
+use super::state_machine::*;
+use super::Connection;
+use crate::util::*;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+
+/// Finishes a long task for its runner. If the runner is dropped first, e.g. by a panic partway through a pulse,
+/// the motor is put back to the task's base rate and anyone waiting on the task gets an error instead of waiting forever.
+pub(super) struct TaskGuard<F>
+where
+    F: AbortFinisher<ASCOMResult<()>, ASCOMResult<()>> + Send + 'static,
+{
+    finisher: Option<F>,
+    abort_waiter: WaitableTask<()>, // Ready once the task has been asked to abort
+    connection: Connection,
+    base_rate: MotionRate,
+}
+
+impl<F> TaskGuard<F>
+where
+    F: AbortFinisher<ASCOMResult<()>, ASCOMResult<()>> + Send + 'static,
+{
+    pub fn new(
+        connection: Connection,
+        finisher: F,
+        abort_waiter: WaitableTask<()>,
+        base_rate: MotionRate,
+    ) -> Self {
+        Self {
+            finisher: Some(finisher),
+            abort_waiter,
+            connection,
+            base_rate,
+        }
+    }
+
+    pub fn finish(mut self, result: ASCOMResult<()>) {
+        self.finisher.take().unwrap().finish(result);
+    }
+
+    pub fn aborted(mut self, result: ASCOMResult<()>) {
+        self.finisher.take().unwrap().aborted(result);
+    }
+}
+
+impl<F> Drop for TaskGuard<F>
+where
+    F: AbortFinisher<ASCOMResult<()>, ASCOMResult<()>> + Send + 'static,
+{
+    fn drop(&mut self) {
+        let finisher = match self.finisher.take() {
+            Some(finisher) => finisher,
+            None => return,
+        };
+        // Nothing can be restored while the runtime shuts down. The next connect stops the motor.
+        let handle = match Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => return,
+        };
+
+        tracing::error!(
+            "A motor task ended without finishing. Restoring the motor to {}°/s.",
+            self.base_rate.clockwise_rate()
+        );
+        // Whoever is aborting holds the task lock until the abort finishes
+        let aborting = self.abort_waiter.is_ready();
+        let (connection, base_rate) = (self.connection.clone(), self.base_rate);
+        handle.spawn(async move {
+            if let Err(e) = connection.restore_base_rate(base_rate, aborting).await {
+                tracing::error!("Couldn't restore the motor rate: {}", e);
+            }
+            let error = Err(ASCOMError::new(
+                ASCOMErrorCode::new_for_driver(9),
+                "Motor task ended unexpectedly",
+            ));
+            if aborting {
+                finisher.aborted(error);
+            } else {
+                finisher.finish(error);
+            }
+        });
+    }
+}
+
+impl Connection {
+    /// Puts the motor back to the rate under a long task that ended without finishing
+    async fn restore_base_rate(
+        &self,
+        base_rate: MotionRate,
+        holding_task_lock: bool,
+    ) -> ASCOMResult<()> {
+        let _task_lock = if holding_task_lock {
+            None
+        } else {
+            let mut task_lock = self.task_lock.lock().await;
+            match &mut *task_lock {
+                // They've taken over the motor since
+                AbortableTaskType::Slewing(_) | AbortableTaskType::Parking(_) => return Ok(()),
+                // Started on top of the rate we're replacing
+                AbortableTaskType::Guiding(guide_task) => {
+                    guide_task.abort().await.unwrap()?;
+                }
+                AbortableTaskType::None => {}
+            }
+            *task_lock = AbortableTaskType::None;
+            Some(task_lock)
+        };
+
+        let mut lock = self.write_con().await?;
+        let mut result = lock.motor.rederive_state().await;
+        if result.is_ok() {
+            result = lock.motor.change_rate_open(base_rate).await;
+        }
+        if result.is_ok() {
+            lock.ascom_state = lock.ascom_state.without_task();
+        }
+        drop(lock);
+        self.check_motor_result(result).await
+    }
+}
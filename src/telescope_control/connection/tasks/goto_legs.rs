@@ -0,0 +1,60 @@
+use crate::telescope_control::connection::transport::MotorError;
+use tokio::sync::Mutex;
+
+use super::*;
+
+pub type MotorGotoTask = AbortableTask<MotorResult<()>, MotorResult<()>>;
+
+/// The goto currently carrying out a move made in two legs. Shared with the task that starts the final leg.
+#[derive(Default)]
+pub struct GotoLegs {
+    pub current: Option<MotorGotoTask>,
+    pub failure: Option<MotorError>, // The final leg couldn't be started
+    pub aborted: bool,
+}
+
+impl GotoLegs {
+    pub fn new(first_leg: MotorGotoTask) -> Self {
+        Self {
+            current: Some(first_leg),
+            ..Default::default()
+        }
+    }
+}
+
+pub async fn start_goto<L, T>(locker: &L, pos: Degrees) -> MotorResult<MotorGotoTask>
+where
+    L: 'static + RWLockable<T> + Clone + Send + Sync,
+    T: HasCS + HasMotor + Send + Sync,
+{
+    let mut lock = locker.write().await;
+    let cs = HasCS::get_mut(&mut *lock)?;
+    cs.motor.goto(locker.clone(), pos).await
+}
+
+/// Waits out the first leg then, unless it was aborted, starts and waits out the final leg to `final_pos`.
+/// Results are left in the legs to be checked later.
+pub async fn run_legs<L, T>(
+    locker: &L,
+    legs: &Mutex<GotoLegs>,
+    first_leg: MotorGotoTask,
+    final_pos: Option<Degrees>,
+) where
+    L: 'static + RWLockable<T> + Clone + Send + Sync,
+    T: HasCS + HasMotor + Send + Sync,
+{
+    let result = first_leg.await;
+    if let (Some(final_pos), AbortResult::Completed(Ok(_))) = (final_pos, result) {
+        let mut legs_lock = legs.lock().await;
+        if !legs_lock.aborted {
+            match start_goto(locker, final_pos).await {
+                Ok(final_goto_task) => {
+                    legs_lock.current = Some(final_goto_task.clone());
+                    drop(legs_lock);
+                    let _result = final_goto_task.await;
+                }
+                Err(e) => legs_lock.failure = Some(e),
+            }
+        }
+    }
+}
@@ -2,19 +2,25 @@
 pub use abort_slew::AbortSlewTask;
 pub use move_motor::MoveMotorTask;
 pub use park::{ParkTask, UnparkTask};
-pub use pulse_guide::PulseGuideTask;
+pub use pulse_guide::{PulseExtender, PulseGuideTask};
 pub use set_tracking::{StartTrackingTask, StopTrackingTask, UpdateTrackingRateTask};
 pub use slew_to::SlewToTask;
 
-pub use crate::telescope_control::connection::motor::locked::HasMotor;
-pub use crate::telescope_control::connection::motor::MotorResult;
 use crate::telescope_control::connection::potential_connection::ConnectedState;
 use crate::telescope_control::connection::potential_connection::PotentialConnection;
+use crate::telescope_control::connection::state_machine::{
+    AscomState, Command, SlewingState, Transition,
+};
+pub use crate::telescope_control::connection::transport::locked::HasMotor;
+pub use crate::telescope_control::connection::transport::MotorResult;
+use crate::telescope_control::connection::transport::{MotorError, Priority};
 use crate::util::*;
 use ascom_alpaca::ASCOMResult;
 use async_trait::async_trait;
+use tokio::sync::RwLockWriteGuard;
 
 mod abort_slew;
+mod goto_legs;
 mod move_motor;
 mod park;
 mod pulse_guide;
@@ -68,6 +74,11 @@ pub trait LongTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync;
 
+    /// The rate to put the motor back to if the task is dropped without finishing. Stopped unless the task knows better.
+    fn base_rate(&self) -> MotionRate {
+        MotionRate::ZERO
+    }
+
     // TODO do this better?
     fn get_abortable_task(&self, task: LongRunningTask) -> AbortableTaskType;
 }
@@ -79,3 +90,79 @@ pub trait ShortTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync;
 }
+
+/// Write locks once no rate change is in progress, so the motor has a rate to plan from.
+/// Commands arriving mid ramp are applied after it instead of finding the motor Changing.
+pub(in crate::telescope_control::connection) async fn write_when_steady<L, T>(
+    locker: &L,
+) -> MotorResult<RwLockWriteGuard<'_, T>>
+where
+    L: 'static + RWLockable<T> + Clone + Send + Sync,
+    T: HasMotor + Send + Sync,
+{
+    loop {
+        let lock = locker.write().await;
+        match HasMotor::get(&*lock)?.get_ramp() {
+            Some(ramp) => {
+                drop(lock);
+                ramp.await?;
+            }
+            None => return Ok(lock),
+        }
+    }
+}
+
+/// Stops a MoveAxis straight away, cutting short any ramp rather than waiting it out.
+/// An abort then plans from the stopped motor, e.g. to go back to tracking.
+pub(in crate::telescope_control::connection) async fn stop_moving_axis<L, T>(
+    locker: &L,
+) -> MotorResult<()>
+where
+    L: 'static + RWLockable<T> + Clone + Send + Sync,
+    T: HasCS + HasMotor + Send + Sync,
+{
+    let stopped = {
+        let mut lock = locker.write().await;
+        let cs = HasCS::get_mut(&mut *lock)?;
+        if !matches!(
+            cs.ascom_state,
+            AscomState::Slewing(SlewingState::MoveAxis(..))
+        ) {
+            return Ok(());
+        }
+        cs.motor.stop_now(locker.clone(), Priority::Stop).await?
+    };
+    stopped.await
+}
+
+/// Plans a command and carries it out. Only for commands that finish once the motor reaches its new rate.
+async fn run_command<L, T>(locker: &L, command: Command) -> MotorResult<ASCOMResult<()>>
+where
+    L: 'static + RWLockable<T> + Clone + Send + Sync,
+    T: HasCS + HasMotor + Send + Sync,
+{
+    let mut lock = write_when_steady(locker).await?;
+    let cs = HasCS::get_mut(&mut *lock)?;
+
+    match cs.plan(command)? {
+        Transition::Stay => Ok(Ok(())),
+        Transition::Reject(rejection) => Ok(Err(rejection.into())),
+        Transition::Enter(state) => {
+            cs.ascom_state = state;
+            Ok(Ok(()))
+        }
+        Transition::ChangeRate { rate, next } => {
+            let change_rate_task = cs.motor.change_rate(locker.clone(), rate).await?;
+            cs.ascom_state = next;
+            drop(lock);
+            change_rate_task.await?;
+            Ok(Ok(()))
+        }
+        transition @ (Transition::Goto(_)
+        | Transition::StartGuiding
+        | Transition::Unplannable(_)) => Err(MotorError::UnexpectedState(format!(
+            "{:?} planned as {:?}",
+            command, transition
+        ))),
+    }
+}
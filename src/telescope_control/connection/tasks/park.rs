@@ -1,22 +1,27 @@
 use std::mem;
+use std::sync::Arc;
 
+use tokio::sync::Mutex;
 use tokio::task;
 
-use crate::telescope_control::connection::ascom_state::*;
-use crate::telescope_control::connection::motor::MotorState;
+use crate::telescope_control::connection::state_machine::*;
+use crate::telescope_control::connection::transport::MotorState;
 
+use super::goto_legs::*;
 use super::*;
 
 pub struct ParkTask {
     park_pos: Degrees,
-    motor_goto_task: Option<AbortableTask<MotorResult<()>, MotorResult<()>>>,
+    approach_pos: Option<Degrees>, // Gone to first so the final leg always moves the same way
+    legs: Arc<Mutex<GotoLegs>>,
 }
 
 impl ParkTask {
-    pub fn new(pos: Degrees) -> Self {
+    pub fn new(pos: Degrees, approach_pos: Option<Degrees>) -> Self {
         Self {
             park_pos: pos,
-            motor_goto_task: None,
+            approach_pos,
+            legs: Arc::new(Mutex::new(GotoLegs::default())),
         }
     }
 }
@@ -30,25 +35,26 @@ impl LongTask for ParkTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let mut lock = locker.write().await;
+        let mut lock = write_when_steady(locker).await?;
         let mut cs = HasCS::get_mut(&mut *lock)?;
 
-        // State checks
-        match &cs.ascom_state {
-            // FIXME better parking logic
-            AscomState::Parked => return Ok(Ok(WaitableTask::new_completed(()))),
-            AscomState::Idle(GuideState::Idle) => {
-                if cs.motor.get_pos().await? == self.park_pos {
-                    cs.ascom_state = AscomState::Parked;
-                    return Ok(Ok(WaitableTask::new_completed(())));
-                }
+        match cs.plan(Command::Park)? {
+            Transition::Stay => return Ok(Ok(WaitableTask::new_completed(()))),
+            Transition::Reject(rejection) => return Ok(Err(rejection.into())),
+            Transition::Goto(GotoEnd::Park) => {}
+            transition => {
+                return Err(MotorError::UnexpectedState(format!(
+                    "Park planned as {:?}",
+                    transition
+                )))
             }
-            AscomState::Tracking(GuideState::Idle) => {}
-            AscomState::Slewing(SlewingState::MoveAxis(_, GuideState::Idle)) => {}
-            AscomState::Idle(GuideState::Guiding) => unreachable!(),
-            AscomState::Tracking(GuideState::Guiding) => unreachable!(),
-            AscomState::Slewing(SlewingState::MoveAxis(_, GuideState::Guiding)) => unreachable!(),
-            AscomState::Slewing(SlewingState::SlewTo) => unreachable!(),
+        }
+
+        if cs.ascom_state == AscomState::Idle(GuideState::Idle)
+            && cs.motor.get_pos().await? == self.park_pos
+        {
+            cs.ascom_state = AscomState::Parked;
+            return Ok(Ok(WaitableTask::new_completed(())));
         }
 
         if !matches!(cs.motor.get_state(), MotorState::Stationary) {
@@ -59,19 +65,23 @@ impl LongTask for ParkTask {
             drop(lock);
             stop_task.await?;
 
-            lock = locker.write().await;
+            lock = write_when_steady(locker).await?;
             cs = HasCS::get_mut(&mut *lock)?;
         }
 
-        let motor_goto_task = cs.motor.goto(locker.clone(), self.park_pos).await?;
+        let first_pos = self.approach_pos.unwrap_or(self.park_pos);
+        let final_leg = self.approach_pos.map(|_| self.park_pos);
+        let motor_goto_task = cs.motor.goto(locker.clone(), first_pos).await?;
         cs.ascom_state = AscomState::Slewing(SlewingState::SlewTo);
+        drop(lock);
 
-        self.motor_goto_task = Some(motor_goto_task.clone());
+        *self.legs.lock().await = GotoLegs::new(motor_goto_task.clone());
 
         let (park_task, finisher) = WaitableTask::new();
+        let legs = self.legs.clone();
+        let locker = locker.clone();
         task::spawn(async move {
-            // ignore result for now
-            let _result = motor_goto_task.await; // this is checked later
+            run_legs(&locker, &legs, motor_goto_task, final_leg).await;
             finisher.finish(())
         });
 
@@ -83,10 +93,13 @@ impl LongTask for ParkTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let task = mem::replace(&mut self.motor_goto_task, None);
-        if task.is_some() {
+        let legs = mem::take(&mut *self.legs.lock().await);
+        if let Some(e) = legs.failure {
+            return Err(e);
+        }
+        if let Some(task) = legs.current {
             // None means we were already parked
-            task.unwrap().await.unwrap()?; // Check if the slew failed
+            task.await.unwrap()?; // Check if the slew failed
         }
         let mut lock = locker.write().await;
         HasCS::get_mut(&mut *lock)?.ascom_state = AscomState::Parked;
@@ -98,11 +111,13 @@ impl LongTask for ParkTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let task = mem::replace(&mut self.motor_goto_task, None);
-        if task.is_some() {
+        let mut legs = self.legs.lock().await;
+        legs.aborted = true; // Keeps the final leg from starting
+        if let Some(task) = legs.current.take() {
             // None means we were already parked
-            task.unwrap().abort().await.unwrap()?;
+            task.abort().await.unwrap()?;
         }
+        drop(legs);
         let mut lock = locker.write().await;
         HasCS::get_mut(&mut *lock)?.ascom_state = AscomState::Idle(GuideState::Idle);
         Ok(())
@@ -128,11 +143,6 @@ impl ShortTask for UnparkTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let mut lock = locker.write().await;
-        let cs = HasCS::get_mut(&mut *lock)?;
-        if cs.ascom_state.is_parked() {
-            cs.ascom_state = AscomState::Idle(GuideState::Idle);
-        }
-        Ok(Ok(()))
+        run_command(locker, Command::Unpark).await
     }
 }
@@ -1,27 +1,89 @@
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use tokio::task;
 use tokio::time::{sleep_until, Instant};
 
-use crate::telescope_control::connection::ascom_state::*;
+use crate::telescope_control::connection::state_machine::*;
+use crate::telescope_control::connection::transport::Priority;
+use crate::telescope_control::guide_latency::{GuideStage, PulseTimer};
 
 use super::*;
-use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+use ascom_alpaca::ASCOMResult;
 
 const EARLY_RETURN_MILLIS: u64 = 5;
 
+struct PulseDeadline {
+    guide_rate: MotionRate,
+    finish_time: Instant,
+    closed: bool, // The pulse is winding down and can't be extended any more
+}
+
+/// Lets a pulse in the same direction extend the running pulse rather than wait for it to end
+#[derive(Clone)]
+pub struct PulseExtender(Arc<Mutex<PulseDeadline>>);
+
+impl PulseExtender {
+    fn new(guide_rate: MotionRate) -> Self {
+        Self(Arc::new(Mutex::new(PulseDeadline {
+            guide_rate,
+            finish_time: Instant::now(), // temporary, set when the pulse starts
+            closed: false,
+        })))
+    }
+
+    /// False if the running pulse goes another way or is already ending
+    pub fn try_extend(&self, guide_rate: MotionRate, duration: Duration) -> bool {
+        let mut deadline = self.0.lock().unwrap();
+        if deadline.closed || deadline.guide_rate != guide_rate {
+            return false;
+        }
+        deadline.finish_time += duration;
+        true
+    }
+
+    fn finish_time(&self) -> Instant {
+        self.0.lock().unwrap().finish_time
+    }
+
+    fn set_finish_time(&self, finish_time: Instant) {
+        self.0.lock().unwrap().finish_time = finish_time;
+    }
+
+    /// Closes the pulse to extensions if it wasn't extended while waking up for `finish_time`
+    fn close_if_unchanged(&self, finish_time: Instant) -> bool {
+        let mut deadline = self.0.lock().unwrap();
+        deadline.closed = deadline.finish_time == finish_time;
+        deadline.closed
+    }
+}
+
 pub struct PulseGuideTask {
     guide_rate: MotionRate,
     duration: Duration,
-    finish_time: Instant,
+    extender: PulseExtender,
+    timer: Option<PulseTimer>,
+    base_rate: MotionRate, // The rate under the pulse, set when it starts
 }
 
 impl PulseGuideTask {
-    pub fn new(guide_rate: MotionRate, duration: Duration) -> Self {
+    pub fn new(guide_rate: MotionRate, duration: Duration, timer: Option<PulseTimer>) -> Self {
         Self {
             guide_rate,
             duration,
-            finish_time: Instant::now(), // temporary, unused value
+            extender: PulseExtender::new(guide_rate),
+            timer,
+            base_rate: MotionRate::ZERO,
+        }
+    }
+
+    pub fn extender(&self) -> PulseExtender {
+        self.extender.clone()
+    }
+
+    fn mark(&mut self, stage: GuideStage) {
+        if let Some(timer) = self.timer.as_mut() {
+            timer.mark(stage);
         }
     }
 }
@@ -36,39 +98,52 @@ impl LongTask for PulseGuideTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let mut lock = locker.write().await;
+        let mut lock = write_when_steady(locker).await?;
+        self.mark(GuideStage::StateLock);
         let cs = HasCS::get_mut(&mut *lock)?;
 
-        match &cs.ascom_state {
-            AscomState::Parked => {
-                return Ok(Err(ASCOMError::new(
-                    ASCOMErrorCode::INVALID_WHILE_PARKED,
-                    "Can't pulse guide while parked".to_string(),
-                )));
+        match cs.plan(Command::PulseGuide)? {
+            Transition::Reject(rejection) => return Ok(Err(rejection.into())),
+            Transition::StartGuiding => {}
+            transition => {
+                return Err(MotorError::UnexpectedState(format!(
+                    "Pulse guide planned as {:?}",
+                    transition
+                )))
             }
-            AscomState::Slewing(SlewingState::SlewTo) => {
-                unreachable!()
-            }
-            st => {
-                assert!(!st.is_guiding())
-            }
-        };
+        }
 
-        let current_rate = cs.motor.get_state().get_rate();
+        let current_rate = cs.motor.steady_rate()?;
+        self.base_rate = current_rate;
 
-        let finish_time = Instant::now() + self.duration;
-        self.finish_time = finish_time;
+        self.extender
+            .set_finish_time(Instant::now() + self.duration);
         let rate_change_task = cs
             .motor
-            .change_rate(locker.clone(), current_rate + self.guide_rate)
+            .change_rate_at(
+                locker.clone(),
+                current_rate + self.guide_rate,
+                Priority::Guide,
+            )
             .await?;
         *cs.ascom_state.guide_ref_mut() = GuideState::Guiding;
         drop(lock);
+        self.mark(GuideStage::SerialWrite);
         rate_change_task.await?;
+        if let Some(timer) = self.timer.take() {
+            timer.finish(GuideStage::RateConfirmed);
+        }
 
         let (guide_task, finisher) = WaitableTask::new();
+        let extender = self.extender.clone();
         task::spawn(async move {
-            sleep_until(finish_time - Duration::from_millis(EARLY_RETURN_MILLIS)).await; // Come back early so we can spin sleep the rest
+            loop {
+                let finish_time = extender.finish_time();
+                sleep_until(finish_time - Duration::from_millis(EARLY_RETURN_MILLIS)).await; // Come back early so we can spin sleep the rest
+                if extender.close_if_unchanged(finish_time) {
+                    break;
+                }
+            }
             finisher.finish(())
         });
 
@@ -80,13 +155,17 @@ impl LongTask for PulseGuideTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let mut lock = locker.write().await;
+        let mut lock = write_when_steady(locker).await?;
         let cs = HasCS::get_mut(&mut *lock)?;
-        spin_sleep::sleep(self.finish_time - Instant::now());
-        let current_rate = cs.motor.get_state().get_rate();
+        spin_sleep::sleep(self.extender.finish_time() - Instant::now());
+        let current_rate = cs.motor.steady_rate()?;
         let rate_change_task = cs
             .motor
-            .change_rate(locker.clone(), current_rate - self.guide_rate)
+            .change_rate_at(
+                locker.clone(),
+                current_rate - self.guide_rate,
+                Priority::Guide,
+            )
             .await?;
         *cs.ascom_state.guide_ref_mut() = GuideState::Idle;
         drop(lock);
@@ -104,7 +183,36 @@ impl LongTask for PulseGuideTask {
         Ok(())
     }
 
+    fn base_rate(&self) -> MotionRate {
+        self.base_rate
+    }
+
     fn get_abortable_task(&self, task: LongRunningTask) -> AbortableTaskType {
         AbortableTaskType::Guiding(task)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use synscan::Direction;
+
+    #[test]
+    fn test_extend_pulse() {
+        let rate = MotionRate::new(0.002, Direction::Clockwise);
+        let extender = PulseExtender::new(rate);
+        let start = Instant::now();
+        extender.set_finish_time(start);
+
+        assert!(extender.try_extend(rate, Duration::from_millis(100)));
+        assert!(!extender.try_extend(
+            MotionRate::new(0.002, Direction::CounterClockwise),
+            Duration::from_millis(100)
+        ));
+        assert_eq!(extender.finish_time(), start + Duration::from_millis(100));
+
+        assert!(!extender.close_if_unchanged(start));
+        assert!(extender.close_if_unchanged(extender.finish_time()));
+        assert!(!extender.try_extend(rate, Duration::from_millis(100)));
+    }
+}
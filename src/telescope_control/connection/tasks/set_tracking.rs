@@ -1,7 +1,7 @@
-use crate::telescope_control::connection::ascom_state::*;
+use crate::telescope_control::connection::state_machine::*;
 
 use super::*;
-use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+use ascom_alpaca::ASCOMResult;
 
 pub struct StopTrackingTask {}
 
@@ -18,40 +18,7 @@ impl ShortTask for StopTrackingTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let mut lock = locker.write().await;
-        let cs = HasCS::get_mut(&mut *lock)?;
-
-        match &cs.ascom_state {
-            AscomState::Parked => {
-                return Ok(Err(ASCOMError::new(
-                    ASCOMErrorCode::INVALID_WHILE_PARKED,
-                    "Can't stop tracking while parked".to_string(),
-                )));
-            }
-            AscomState::Idle(GuideState::Idle) => {
-                return Ok(Ok(()));
-            }
-            AscomState::Slewing(SlewingState::MoveAxis(_, GuideState::Idle)) => {
-                return Ok(Err(ASCOMError::invalid_operation("Can't stop tracking while moving axis")));
-            }
-            AscomState::Idle(GuideState::Guiding) => unreachable!(),
-            AscomState::Tracking(GuideState::Guiding) => unreachable!(),
-            AscomState::Slewing(SlewingState::MoveAxis(_, GuideState::Guiding)) => unreachable!(),
-            AscomState::Slewing(SlewingState::SlewTo) => unreachable!(),
-            AscomState::Tracking(GuideState::Idle) => {
-                // Only state we do anything
-            }
-        }
-
-        let change_rate_task = cs
-            .motor
-            .change_rate(locker.clone(), MotionRate::ZERO)
-            .await?;
-        cs.ascom_state = AscomState::Idle(GuideState::Idle);
-        drop(lock);
-        change_rate_task.await?;
-
-        Ok(Ok(()))
+        run_command(locker, Command::StopTracking).await
     }
 }
 
@@ -73,41 +40,11 @@ impl ShortTask for StartTrackingTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let mut lock = locker.write().await;
-        let cs = HasCS::get_mut(&mut *lock)?;
-
-        match &cs.ascom_state {
-            AscomState::Parked => {
-                return Ok(Err(ASCOMError::new(
-                    ASCOMErrorCode::INVALID_WHILE_PARKED,
-                    "Can't start tracking while parked".to_string(),
-                )));
-            }
-            AscomState::Idle(GuideState::Idle) => {}
-            AscomState::Tracking(GuideState::Idle) => {
-                if cs.motor.state.get_rate() == self.rate {
-                    return Ok(Ok(()));
-                }
-            }
-            AscomState::Slewing(SlewingState::MoveAxis(_, GuideState::Idle)) => {
-                return Ok(Err(ASCOMError::invalid_operation("Can't start tracking while moving axis")));
-            }
-            AscomState::Idle(GuideState::Guiding) => unreachable!(),
-            AscomState::Tracking(GuideState::Guiding) => unreachable!(),
-            AscomState::Slewing(SlewingState::MoveAxis(_, GuideState::Guiding)) => unreachable!(),
-            AscomState::Slewing(SlewingState::SlewTo) => unreachable!(),
-        }
-
-        let change_rate_task = cs.motor.change_rate(locker.clone(), self.rate).await?;
-        cs.ascom_state = AscomState::Tracking(GuideState::Idle);
-        drop(lock);
-        change_rate_task.await?;
-
-        Ok(Ok(()))
+        run_command(locker, Command::StartTracking(self.rate)).await
     }
 }
 
-/// Can also used to change tracking rate
+/// Changes the tracking rate only if currently tracking
 pub struct UpdateTrackingRateTask {
     rate: MotionRate,
 }
@@ -125,30 +62,6 @@ impl ShortTask for UpdateTrackingRateTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let mut lock = locker.write().await;
-        let cs = HasCS::get_mut(&mut *lock)?;
-
-        match &cs.ascom_state {
-            AscomState::Parked => return Ok(Ok(())),
-            AscomState::Idle(GuideState::Idle) => return Ok(Ok(())),
-            AscomState::Tracking(GuideState::Idle) => {
-                if cs.motor.state.get_rate() == self.rate {
-                    return Ok(Ok(()));
-                }
-                // Only do anything if tracking currently
-            }
-            AscomState::Slewing(SlewingState::MoveAxis(_, GuideState::Idle)) => return Ok(Ok(())),
-            AscomState::Idle(GuideState::Guiding) => unreachable!(),
-            AscomState::Tracking(GuideState::Guiding) => unreachable!(),
-            AscomState::Slewing(SlewingState::MoveAxis(_, GuideState::Guiding)) => unreachable!(),
-            AscomState::Slewing(SlewingState::SlewTo) => unreachable!(),
-        }
-
-        let change_rate_task = cs.motor.change_rate(locker.clone(), self.rate).await?;
-        cs.ascom_state = AscomState::Tracking(GuideState::Idle);
-        drop(lock);
-        change_rate_task.await?;
-
-        Ok(Ok(()))
+        run_command(locker, Command::UpdateTrackingRate(self.rate)).await
     }
 }
@@ -1,29 +1,49 @@
 use std::mem;
+use std::sync::Arc;
 
+use tokio::sync::Mutex;
 use tokio::task;
 
-use crate::telescope_control::connection::ascom_state::*;
-use crate::telescope_control::connection::motor::MotorState;
+use crate::telescope_control::connection::state_machine::*;
+use crate::telescope_control::connection::transport::MotorState;
 
+use super::goto_legs::*;
 use super::*;
-use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+use ascom_alpaca::ASCOMResult;
 
 pub struct SlewToTask {
     target_pos: Degrees,
+    approach: Option<Degrees>, // Length of the final leg moving with tracking, when tracking afterwards
     after_state: RestorableState,
-    motor_goto_task: Option<AbortableTask<MotorResult<()>, MotorResult<()>>>,
+    legs: Arc<Mutex<GotoLegs>>,
 }
 
 impl SlewToTask {
     /// pos in degrees relative to turning on mount
     /// pos can be negative or positive or past 360 deg
-    pub fn new(target_pos: Degrees) -> Self {
+    pub fn new(target_pos: Degrees, approach: Option<Degrees>) -> Self {
         Self {
             target_pos,
+            approach,
             after_state: RestorableState::Idle, // unused initiator
-            motor_goto_task: None,
+            legs: Arc::new(Mutex::new(GotoLegs::default())),
         }
     }
+
+    /// Where to go first so the slew ends moving the way tracking will, with the gear backlash taken up.
+    /// None if the slew already ends with a long enough move that way.
+    fn calc_approach_pos(
+        current_pos: Degrees,
+        target_pos: Degrees,
+        tracking: MotionRate,
+        approach: Degrees,
+    ) -> Option<Degrees> {
+        let tracking_sign = tracking.clockwise_rate().signum();
+        if approach <= (target_pos - current_pos) * tracking_sign {
+            return None;
+        }
+        Some(target_pos - tracking_sign * approach)
+    }
 }
 
 #[async_trait]
@@ -34,33 +54,25 @@ impl LongTask for SlewToTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let mut lock = locker.write().await;
+        let mut lock = write_when_steady(locker).await?;
         let mut cs = HasCS::get_mut(&mut *lock)?;
 
-        self.after_state = match &cs.ascom_state {
-            AscomState::Parked => {
-                return Ok(Err(ASCOMError::new(
-                    ASCOMErrorCode::INVALID_WHILE_PARKED,
-                    "Can't slew to while parked".to_string(),
-                )));
-            }
-            AscomState::Idle(GuideState::Idle) => {
-                if cs.motor.get_pos().await? == self.target_pos {
-                    return Ok(Ok(WaitableTask::new_completed(())));
-                }
-                RestorableState::Idle
+        self.after_state = match cs.plan(Command::SlewTo)? {
+            Transition::Reject(rejection) => return Ok(Err(rejection.into())),
+            Transition::Goto(GotoEnd::Restore(after_state)) => after_state,
+            transition => {
+                return Err(MotorError::UnexpectedState(format!(
+                    "Slew planned as {:?}",
+                    transition
+                )))
             }
-            AscomState::Tracking(GuideState::Idle) => {
-                let rate = cs.motor.state.get_rate();
-                RestorableState::Tracking(rate)
-            }
-            AscomState::Slewing(SlewingState::MoveAxis(rs, GuideState::Idle)) => *rs,
-            AscomState::Idle(GuideState::Guiding) => unreachable!(),
-            AscomState::Tracking(GuideState::Guiding) => unreachable!(),
-            AscomState::Slewing(SlewingState::MoveAxis(_, GuideState::Guiding)) => unreachable!(),
-            AscomState::Slewing(SlewingState::SlewTo) => unreachable!(),
         };
 
+        let current_pos = cs.motor.get_pos().await?;
+        if cs.ascom_state == AscomState::Idle(GuideState::Idle) && current_pos == self.target_pos {
+            return Ok(Ok(WaitableTask::new_completed(())));
+        }
+
         if !matches!(cs.motor.get_state(), MotorState::Stationary) {
             let stop_task = cs
                 .motor
@@ -69,18 +81,29 @@ impl LongTask for SlewToTask {
             drop(lock);
             stop_task.await?;
 
-            lock = locker.write().await;
+            lock = write_when_steady(locker).await?;
             cs = HasCS::get_mut(&mut *lock)?;
         }
 
-        let motor_goto_task = cs.motor.goto(locker.clone(), self.target_pos).await?;
+        let approach_pos = match (&self.after_state, self.approach) {
+            (RestorableState::Tracking(mr), Some(approach)) => {
+                Self::calc_approach_pos(current_pos, self.target_pos, *mr, approach)
+            }
+            _ => None,
+        };
+        let first_pos = approach_pos.unwrap_or(self.target_pos);
+        let final_leg = approach_pos.map(|_| self.target_pos);
+        let motor_goto_task = cs.motor.goto(locker.clone(), first_pos).await?;
         cs.ascom_state = AscomState::Slewing(SlewingState::SlewTo);
+        drop(lock);
 
-        self.motor_goto_task = Some(motor_goto_task.clone());
+        *self.legs.lock().await = GotoLegs::new(motor_goto_task.clone());
 
         let (slew_to_task, finisher) = WaitableTask::new();
+        let legs = self.legs.clone();
+        let locker = locker.clone();
         task::spawn(async move {
-            let _result = motor_goto_task.await; // this is checked later
+            run_legs(&locker, &legs, motor_goto_task, final_leg).await; // this is checked later
             finisher.finish(())
         });
 
@@ -92,13 +115,16 @@ impl LongTask for SlewToTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let task = mem::replace(&mut self.motor_goto_task, None);
-        if task.is_some() {
+        let legs = mem::take(&mut *self.legs.lock().await);
+        if let Some(e) = legs.failure {
+            return Err(e);
+        }
+        if let Some(task) = legs.current {
             // if none, we were already in the right place and didn't need to slew
-            task.unwrap().await.unwrap()?; // Check if the slew failed
+            task.await.unwrap()?; // Check if the slew failed
         }
 
-        let mut lock = locker.write().await;
+        let mut lock = write_when_steady(locker).await?;
         let cs = HasCS::get_mut(&mut *lock)?;
         cs.ascom_state = AscomState::Idle(GuideState::Idle);
 
@@ -117,11 +143,13 @@ impl LongTask for SlewToTask {
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasCS + HasMotor + Send + Sync,
     {
-        let task = mem::replace(&mut self.motor_goto_task, None);
-        if task.is_some() {
+        let mut legs = self.legs.lock().await;
+        legs.aborted = true; // Keeps the final leg from starting
+        if let Some(task) = legs.current.take() {
             // if none, we were already in the right place and didn't need to slew
-            task.unwrap().abort().await.unwrap()?;
+            task.abort().await.unwrap()?;
         }
+        drop(legs);
         self.complete(locker).await
     }
 
@@ -129,3 +157,25 @@ impl LongTask for SlewToTask {
         AbortableTaskType::Slewing(task)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use synscan::Direction;
+
+    #[test]
+    fn test_approach_pos() {
+        let tracking = MotionRate::new(0.004, Direction::CounterClockwise);
+        // Going back against tracking overshoots, then comes forward
+        assert_eq!(
+            SlewToTask::calc_approach_pos(10., 20., tracking, 0.5),
+            Some(20.5)
+        );
+        // Already ends moving with tracking for long enough
+        assert_eq!(SlewToTask::calc_approach_pos(20., 10., tracking, 0.5), None);
+        assert_eq!(
+            SlewToTask::calc_approach_pos(10.2, 10., tracking, 0.5),
+            Some(10.5)
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use synscan::Direction;
+
+use crate::util::*;
+
+/// Slack in the gears, which the motor turns through without moving the scope whenever it reverses.
+/// Positions are kept as if there were none, by offsetting the motor's by the slack taken up so far.
+#[derive(Debug, Default)]
+pub struct Backlash {
+    amount: Degrees,
+    offset: Degrees,              // Added to the motor position to get the scope's
+    direction: Option<Direction>, // The way the slack was last taken up. None until the motor first turns
+}
+
+impl Backlash {
+    pub fn new(amount: Degrees) -> Self {
+        Self {
+            amount,
+            ..Default::default()
+        }
+    }
+
+    /// Records the motor starting to turn, returning the slack it has to take up first, signed in its direction.
+    /// Nothing the first time, since which way the slack is isn't known.
+    pub fn turning(&mut self, direction: Direction) -> Degrees {
+        let reversed = self
+            .direction
+            .replace(direction)
+            .map_or(false, |last| last != direction);
+        if !reversed {
+            return 0.;
+        }
+        MotionRate::new(self.amount, direction).clockwise_rate()
+    }
+
+    /// Once the motor has turned through the slack returned by `turning`
+    pub fn taken_up(&mut self, slack: Degrees) {
+        self.offset -= slack;
+    }
+
+    pub fn to_position(&self, motor_pos: Degrees) -> Degrees {
+        motor_pos + self.offset
+    }
+
+    pub fn to_motor_pos(&self, pos: Degrees) -> Degrees {
+        pos - self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn test_backlash() {
+        let mut backlash = Backlash::new(0.1);
+        assert_eq!(backlash.turning(Direction::Clockwise), 0.);
+        assert_eq!(backlash.turning(Direction::Clockwise), 0.);
+
+        // A goto back to 5° has to turn the motor past it through the slack
+        let slack = backlash.turning(Direction::CounterClockwise);
+        assert_float_absolute_eq!(slack, -0.1);
+        backlash.taken_up(slack);
+        assert_float_absolute_eq!(backlash.to_motor_pos(5.), 4.9);
+        assert_float_absolute_eq!(backlash.to_position(4.9), 5.);
+
+        let slack = backlash.turning(Direction::Clockwise);
+        backlash.taken_up(slack);
+        assert_float_absolute_eq!(backlash.to_motor_pos(5.), 5.);
+    }
+}
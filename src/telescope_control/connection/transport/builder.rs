@@ -0,0 +1,219 @@
+use super::motor_trace::MotorTrace;
+use super::network_port::NetworkPort;
+use super::simulated_port::SimulatedMount;
+use super::*;
+use std::sync::Arc;
+use std::time::Duration;
+use synscan::serialport::SPSerialPort;
+use synscan::MotorController;
+
+#[derive(Clone, Default, Debug)]
+pub struct MotorBuilder {
+    path: Option<String>,
+    timeout: Option<Duration>,
+    trace_path: Option<String>, // JSON lines file recording every command
+    max_acceleration: Degrees,  // deg/s². 0 to change rate in one step
+    pos_freshness: Duration,    // How long a position read is shared. 0 to always inquire
+    backlash: Degrees,          // Of the RA gears, taken up whenever the motor reverses
+    retry: RetryPolicy,
+    health: Arc<SerialHealth>, // Shared by every connection made, so it counts across reconnects
+    simulated: SimulatedMount, // Only powered on if the path is sim://, then kept across reconnects
+}
+
+impl MotorBuilder {
+    fn determine_serial_port() -> Result<String, String> {
+        let available_ports = serialport::available_ports();
+        if let Err(e) = available_ports {
+            return Err(e.description);
+        }
+
+        let port = available_ports.unwrap().into_iter().find(|p| {
+            let t = &p.port_type;
+
+            if let serialport::SerialPortType::UsbPort(i) = t {
+                if i.vid == 0x67b && // Prolific
+                    i.pid == 0x2303
+                // PL2303 usb to serial
+                {
+                    let mc = MotorController::new_serialport(
+                        &p.port_name,
+                        consts::BAUD_RATE,
+                        Duration::from_millis(consts::DEFAULT_TIMEOUT_MILLIS),
+                    );
+
+                    if let Err(_e) = mc {
+                        return false;
+                    }
+
+                    let mc = mc.unwrap();
+
+                    if let Err(_e) = mc.test() {
+                        return false;
+                    }
+
+                    return true;
+                }
+            }
+
+            false
+        });
+
+        if let Some(port) = port {
+            Ok(port.port_name)
+        } else {
+            Err("StarAdventurer Port not found".to_string())
+        }
+    }
+
+    pub fn new() -> Self {
+        MotorBuilder::default()
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_trace(mut self, trace_path: impl Into<String>) -> Self {
+        self.trace_path = Some(trace_path.into());
+        self
+    }
+
+    pub fn with_max_acceleration(mut self, max_acceleration: Degrees) -> Self {
+        self.max_acceleration = max_acceleration;
+        self
+    }
+
+    pub fn with_position_freshness(mut self, pos_freshness: Duration) -> Self {
+        self.pos_freshness = pos_freshness;
+        self
+    }
+
+    pub fn with_backlash(mut self, backlash: Degrees) -> Self {
+        self.backlash = backlash;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn serial_health(&self) -> SerialHealthCounts {
+        self.health.counts()
+    }
+
+    /// The dec motor on the same controller as ra, sharing its port
+    pub async fn create_dec_axis(&self, ra: &Motor) -> Result<Motor, String> {
+        let motor = Motor {
+            mc: ra.mc.on_axis(MotorAxis::Dec),
+            state: MotorState::Stationary, // Temporary
+            port: ra.port.clone(),
+            ramp: None,
+            rate_changes: 0,
+            max_acceleration: self.max_acceleration,
+            speed: SpeedCapabilities::default(), // Read below
+            firmware: None,
+            pos_cache: PositionCache::new(self.pos_freshness),
+            interrupted_goto: None,
+            backlash: Backlash::default(), // Not compensated on dec
+        };
+        Self::read_motor(motor).await
+    }
+
+    pub async fn create(&self) -> Result<Motor, String> {
+        let path = if self.path.is_some() {
+            self.path.clone().unwrap()
+        } else {
+            let port = Self::determine_serial_port()?;
+            tracing::warn!("Found StarAdventurer COM port at {}", port);
+            port
+        };
+        let timeout = self
+            .timeout
+            .unwrap_or_else(|| Duration::from_millis(consts::DEFAULT_TIMEOUT_MILLIS));
+        let mc = match ComPath::parse(&path) {
+            ComPath::Serial(path) => {
+                MotorController::new_serialport(&path, consts::BAUD_RATE, timeout).ok()
+            }
+            ComPath::Simulated(mount_code) => {
+                let port = self.simulated.open(mount_code, timeout);
+                MotorController::new(SPSerialPort::new(Box::new(port))).ok()
+            }
+            network_path => match NetworkPort::open(&network_path, timeout) {
+                Ok(port) => MotorController::new(SPSerialPort::new(Box::new(port))).ok(),
+                Err(e) => {
+                    tracing::warn!("Couldn't reach {}: {}", path, e);
+                    None
+                }
+            },
+        };
+        if mc.is_none() {
+            return Err("Couldn't connect to StarAdventurer".to_string());
+        }
+
+        let trace = match &self.trace_path {
+            Some(trace_path) => match MotorTrace::open(trace_path, &path) {
+                Ok(trace) => Some(trace),
+                Err(e) => {
+                    tracing::warn!("Couldn't open motor trace {}: {}", trace_path, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let mc = MC::new(mc.unwrap(), trace, self.retry, self.health.clone());
+
+        let motor = Motor {
+            mc,
+            state: MotorState::Stationary, // Temporary
+            port: path,
+            ramp: None,
+            rate_changes: 0,
+            max_acceleration: self.max_acceleration,
+            speed: SpeedCapabilities::default(), // Read below
+            firmware: None,
+            pos_cache: PositionCache::new(self.pos_freshness),
+            interrupted_goto: None,
+            backlash: Backlash::new(self.backlash),
+        };
+        Self::read_motor(motor).await
+    }
+
+    /// Fills in what the controller reports about the motor
+    async fn read_motor(mut motor: Motor) -> Result<Motor, String> {
+        motor.speed = match motor.mc.inquire_parameters().await {
+            Ok(Some(parameters)) => SpeedCapabilities::from_parameters(parameters),
+            Ok(None) => {
+                tracing::warn!("Motor controller didn't report its parameters. Assuming a Star Adventurer's speeds.");
+                SpeedCapabilities::default()
+            }
+            Err(_) => return Err("Couldn't read the motor controller's parameters".to_string()),
+        };
+        motor.firmware = match motor.mc.inquire_firmware().await {
+            Ok(Some(firmware)) => {
+                tracing::info!(
+                    "Motor controller firmware {}, mount code {:#04x}",
+                    firmware.version,
+                    firmware.mount_code
+                );
+                Some(firmware)
+            }
+            Ok(None) => None,
+            Err(_) => {
+                return Err("Couldn't read the motor controller's firmware version".to_string())
+            }
+        };
+
+        if motor.determine_motor_state().await.is_err() {
+            return Err("Couldn't determine motor state".to_string());
+        }
+
+        Ok(motor)
+    }
+}
@@ -0,0 +1,49 @@
+#![allow(unused)]
+use std::time::Duration;
+use synscan::SingleChannel;
+
+pub const NUM_TRIES: u32 = 3;
+pub const RETRY_MILLIS: u64 = 10;
+pub const RETRY_BACKOFF: f64 = 10.;
+/// Longest wait between tries of a command, whatever the backoff
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// How often a retry's wait checks for a stop to give way to
+pub const RETRY_SLEEP_SLICE: Duration = Duration::from_millis(5);
+
+pub const BAUD_RATE: u32 = 115_200;
+pub const DEFAULT_TIMEOUT_MILLIS: u64 = 50;
+
+pub const SYNSCAN_WIFI_PORT: u16 = 11880;
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const SIDEREAL_PERIOD: u32 = 110_359;
+pub const LUNAR_PERIOD: u32 = 114_581;
+pub const SOLAR_PERIOD: u32 = 110_662;
+pub const KING_PERIOD: u32 = 110_390;
+
+// Step periods the controller accepts, in timer ticks. Found experimentally
+pub const MIN_STEP_PERIOD: u32 = 1_103;
+pub const MAX_STEP_PERIOD: u32 = 16_000_000;
+// deg/sec, for a controller that doesn't report its parameters
+pub const FALLBACK_MIN_SPEED: f64 = 0.000029;
+pub const FALLBACK_MAX_SPEED: f64 = 0.418;
+pub const SLOW_GOTO_SPEED: f64 = 0.133727; // deg/sec. Fastest of the continuous slow rates
+pub const SLEW_SPEED_WITH_TRACKING: f64 = 0.2817; // deg/sec empirically determined
+pub const SLEW_SPEED_AGAINST_TRACKING: f64 = 0.3072; // deg/sec empirically determined
+
+pub(in crate::telescope_control::connection::transport) const RA_CHANNEL: SingleChannel =
+    SingleChannel::Channel1;
+pub(in crate::telescope_control::connection::transport) const DEC_CHANNEL: SingleChannel =
+    SingleChannel::Channel2;
+
+/// Used when waiting for rate change
+pub(in crate::telescope_control::connection::transport) const ALLOWABLE_RATE_DIFFERENCE: f64 =
+    0.0001;
+/// Fast rates are coarsely quantized by the controller so can't get within the absolute difference
+pub(in crate::telescope_control::connection::transport) const ALLOWABLE_RATE_FRACTION: f64 = 0.01;
+/// Longest a rate change is waited for before its rate is taken as reached
+pub(in crate::telescope_control::connection::transport) const RAMP_TIMEOUT: Duration =
+    Duration::from_secs(10);
+/// How often a ramp steps the rate
+pub(in crate::telescope_control::connection::transport) const RAMP_STEP_INTERVAL: Duration =
+    Duration::from_millis(250);
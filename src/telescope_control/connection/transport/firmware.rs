@@ -0,0 +1,35 @@
+use serde::Serialize;
+
+/// What the motor controller reports about its board
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FirmwareInfo {
+    pub version: String,
+    pub mount_code: u8, // Identifies the model of mount the board is built into
+}
+
+impl FirmwareInfo {
+    /// From the board version inquiry, decoded like every other 24 bit value in the protocol,
+    /// so "=020406" (version 2.04 in mount 0x06) is 0x060402
+    pub fn from_board_version(board_version: u32) -> Self {
+        Self {
+            version: format!(
+                "{:x}.{:02x}",
+                board_version & 0xFF,
+                (board_version >> 8) & 0xFF
+            ),
+            mount_code: (board_version >> 16) as u8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_board_version() {
+        let firmware = FirmwareInfo::from_board_version(0xA50302);
+        assert_eq!(firmware.version, "2.03");
+        assert_eq!(firmware.mount_code, 0xA5);
+    }
+}
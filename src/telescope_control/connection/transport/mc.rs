@@ -0,0 +1,253 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use super::consts::*;
+use super::motor_trace::MotorTrace;
+use super::serial_health::SerialHealth;
+use super::serial_thread::{RetryPolicy, SerialThread};
+use super::*;
+use synscan::serialport::SPSerialPort;
+use synscan::util::{SynScanError, SynScanResult};
+use synscan::{AutoGuideSpeed, Direction, MotorController, MotorStatus, SingleChannel};
+
+/// Which of the controller's channels a handle drives
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MotorAxis {
+    Ra,
+    Dec, // Only motorized on two-axis mounts such as the GTi
+}
+
+impl MotorAxis {
+    pub(super) fn channel(self) -> SingleChannel {
+        match self {
+            Self::Ra => RA_CHANNEL,
+            Self::Dec => DEC_CHANNEL,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MC {
+    thread: SerialThread,
+    min_priority: Priority, // Commands through this handle are sent at least at this priority
+    axis: MotorAxis,
+}
+
+impl MC {
+    pub(in crate::telescope_control::connection::transport) fn new(
+        mc: MotorController<SPSerialPort>,
+        trace: Option<MotorTrace>,
+        retry: RetryPolicy,
+        health: Arc<SerialHealth>,
+    ) -> Self {
+        MC {
+            thread: SerialThread::spawn(mc, trace, retry, health),
+            min_priority: Priority::Inquiry,
+            axis: MotorAxis::Ra,
+        }
+    }
+
+    /// A handle to the same motor that sends everything at no less than `priority`
+    pub fn at(&self, priority: Priority) -> MC {
+        MC {
+            thread: self.thread.clone(),
+            min_priority: self.min_priority.max(priority),
+            axis: self.axis,
+        }
+    }
+
+    /// A handle to the other motor on the same controller, sharing the port
+    pub fn on_axis(&self, axis: MotorAxis) -> MC {
+        MC {
+            thread: self.thread.clone(),
+            min_priority: self.min_priority,
+            axis,
+        }
+    }
+
+    /// Run a command on the motor.
+    /// On failure, the command will be retried up to a set number of tries.
+    /// As such, the command should be idempotent.
+    async fn do_command_with_retries<F, T>(
+        &self,
+        priority: Priority,
+        name: String,
+        f: F,
+    ) -> MotorResult<T>
+    where
+        F: Fn(&MotorController<SPSerialPort>) -> SynScanResult<T> + Send + 'static,
+        T: 'static + Send + Debug,
+    {
+        let priority = priority.max(self.min_priority);
+        let name = match self.axis {
+            MotorAxis::Ra => name,
+            MotorAxis::Dec => format!("dec {}", name),
+        };
+        let result = match self.thread.command(priority, name, f).await {
+            Some(result) => result,
+            None => return Err(MotorError::Disconnected), // Serial thread is gone
+        };
+
+        match result {
+            Ok(v) => Ok(v),
+            Err(SynScanError::CommunicationError(e)) => {
+                // Cable unplugged or something like that
+                Err(e.into())
+            }
+            Err(e) => {
+                // We did something wrong
+                tracing::error!("Misused motor: {:?}", e);
+                Err(MotorError::Rejected(format!("{:?}", e)))
+            }
+        }
+    }
+
+    pub async fn set_tracking_mode(&self, direction: Direction) -> MotorResult<()> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Motion,
+            format!("set_tracking_motion_mode {:?}", direction),
+            move |mc| mc.set_tracking_motion_mode(axis.channel(), false, direction),
+        )
+        .await
+    }
+
+    pub async fn set_motion_rate(&self, rate: Degrees) -> MotorResult<()> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Motion,
+            format!("set_motion_rate_degrees {}", rate),
+            move |mc| mc.set_motion_rate_degrees(axis.channel(), rate),
+        )
+        .await
+    }
+
+    pub async fn start_motion(&self) -> MotorResult<()> {
+        let axis = self.axis;
+        self.do_command_with_retries(Priority::Motion, "start_motion".to_string(), move |mc| {
+            mc.start_motion(axis.channel())
+        })
+        .await
+    }
+
+    pub async fn stop_motion(&self) -> MotorResult<()> {
+        let axis = self.axis;
+        self.do_command_with_retries(Priority::Motion, "stop_motion".to_string(), move |mc| {
+            mc.stop_motion(axis.channel())
+        })
+        .await
+    }
+
+    pub async fn inquire_pos(&self) -> MotorResult<Degrees> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Inquiry,
+            "inquire_pos_degrees".to_string(),
+            move |mc| mc.inquire_pos_degrees(axis.channel()),
+        )
+        .await
+    }
+
+    pub async fn set_autoguide_speed(&self, speed: AutoGuideSpeed) -> MotorResult<()> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Motion,
+            format!("set_autoguide_speed {:?}", speed),
+            move |mc| mc.set_autoguide_speed(axis.channel(), speed),
+        )
+        .await
+    }
+
+    pub async fn set_goto_mode(&self) -> MotorResult<()> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Motion,
+            "set_goto_motion_mode".to_string(),
+            move |mc| mc.set_goto_motion_mode(axis.channel(), true),
+        )
+        .await
+    }
+
+    pub async fn set_goto_target(&self, target: Degrees) -> MotorResult<()> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Motion,
+            format!("set_goto_target_degrees {}", target),
+            move |mc| mc.set_goto_target_degrees(axis.channel(), target),
+        )
+        .await
+    }
+
+    pub async fn inquire_rate(&self) -> MotorResult<Degrees> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Inquiry,
+            "inquire_motion_rate_degrees".to_string(),
+            move |mc| mc.inquire_motion_rate_degrees(axis.channel()),
+        )
+        .await
+    }
+
+    pub async fn inquire_status(&self) -> MotorResult<MotorStatus> {
+        let axis = self.axis;
+        self.do_command_with_retries(Priority::Inquiry, "inquire_status".to_string(), move |mc| {
+            mc.inquire_status(axis.channel())
+        })
+        .await
+    }
+
+    /// None if the controller doesn't answer the inquiries, as some older boards don't
+    pub async fn inquire_parameters(&self) -> MotorResult<Option<ControllerParameters>> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Inquiry,
+            "inquire_parameters".to_string(),
+            move |mc| match Self::read_parameters(mc, axis) {
+                Ok(parameters) => Ok(Some(parameters)),
+                Err(SynScanError::CommunicationError(e)) => {
+                    Err(SynScanError::CommunicationError(e))
+                }
+                Err(_) => Ok(None),
+            },
+        )
+        .await
+    }
+
+    /// None if the controller doesn't answer the inquiry
+    pub async fn inquire_firmware(&self) -> MotorResult<Option<FirmwareInfo>> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Inquiry,
+            "inquire_motor_board_version".to_string(),
+            move |mc| match mc.inquire_motor_board_version(axis.channel()) {
+                Ok(board_version) => Ok(Some(FirmwareInfo::from_board_version(board_version))),
+                Err(SynScanError::CommunicationError(e)) => {
+                    Err(SynScanError::CommunicationError(e))
+                }
+                Err(_) => Ok(None),
+            },
+        )
+        .await
+    }
+
+    pub(super) fn read_parameters(
+        mc: &MotorController<SPSerialPort>,
+        axis: MotorAxis,
+    ) -> SynScanResult<ControllerParameters> {
+        Ok(ControllerParameters {
+            counts_per_revolution: mc.inquire_counts_per_revolution(axis.channel())?,
+            timer_frequency: mc.inquire_timer_interrupt_freq(axis.channel())?,
+            high_speed_ratio: mc.inquire_high_speed_ratio(axis.channel())?,
+        })
+    }
+
+    pub async fn inquire_goto_target(&self) -> MotorResult<Degrees> {
+        let axis = self.axis;
+        self.do_command_with_retries(
+            Priority::Inquiry,
+            "inquire_goto_target_degrees".to_string(),
+            move |mc| mc.inquire_goto_target_degrees(axis.channel()),
+        )
+        .await
+    }
+}
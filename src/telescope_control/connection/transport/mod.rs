@@ -0,0 +1,162 @@
+use synscan::{AutoGuideSpeed, DriveMode};
+
+pub use backlash::Backlash;
+pub use builder::*;
+use consts::*;
+pub use firmware::FirmwareInfo;
+pub use mc::{MotorAxis, MC};
+pub use motor_accessor_types::locked;
+pub use motor_accessor_types::open;
+pub use motor_state::*;
+pub use network_port::ComPath;
+pub use position_cache::PositionCache;
+pub use result::*;
+pub use serial_health::{SerialHealth, SerialHealthCounts};
+pub use serial_thread::{Priority, RetryPolicy};
+pub use speed_capabilities::*;
+pub use waiters::*;
+
+use crate::util::*;
+use ramp::Ramp;
+
+mod backlash;
+pub mod consts;
+mod firmware;
+mod mc;
+mod motor_state;
+mod motor_trace;
+mod network_port;
+mod position_cache;
+mod ramp;
+mod simulated_port;
+mod motor_accessor_types {
+    pub mod locked;
+    pub mod open;
+}
+mod builder;
+mod result;
+mod serial_health;
+mod serial_thread;
+mod speed_capabilities;
+pub mod trace_replay;
+mod waiters;
+
+pub struct Motor {
+    pub mc: MC,
+    pub state: MotorState,
+    pub port: String,   // Serial port path or network address
+    ramp: Option<Ramp>, // Last rate change
+    /// Counts rate changes started, so a waiter overtaken by a later one leaves the state alone
+    rate_changes: u64,
+    pub max_acceleration: Degrees, // deg/s². 0 to change rate in one step
+    pub speed: SpeedCapabilities,  // Read from the controller when connecting
+    pub firmware: Option<FirmwareInfo>, // None if the controller didn't report it
+    pub pos_cache: PositionCache,
+    pub interrupted_goto: Option<Degrees>, // Target of a goto found running, which was stopped
+    pub backlash: Backlash, // Positions in and out of the motor are corrected by its slack
+}
+
+impl Motor {
+    pub async fn get_pos(&self) -> MotorResult<f64> {
+        Ok(self.backlash.to_position(self.mc.inquire_pos().await?))
+    }
+
+    /// A position read within the cache's freshness, moved on by the rate, or else a new one.
+    /// For reporting. Anything that compares positions exactly should use get_pos.
+    pub async fn get_recent_pos(&self) -> MotorResult<Degrees> {
+        let motor_pos = self
+            .pos_cache
+            .get_or_inquire(self.state.try_get_rate(), self.mc.inquire_pos())
+            .await?;
+        Ok(self.backlash.to_position(motor_pos))
+    }
+
+    pub async fn set_autoguide_speed(&mut self, speed: AutoGuideSpeed) -> MotorResult<()> {
+        self.mc.set_autoguide_speed(speed).await
+    }
+
+    pub fn get_state(&self) -> &MotorState {
+        &self.state
+    }
+
+    /// The rate the motor is steady at, or an error if it's changing or gotoing
+    pub fn steady_rate(&self) -> MotorResult<MotionRate> {
+        self.state.try_get_rate().ok_or_else(|| {
+            MotorError::UnexpectedState(format!("No steady rate while {:?}", self.state))
+        })
+    }
+
+    /// The rate the motor is at, or heading to while a rate change is in progress. None while gotoing.
+    /// For checking where the motor is headed. Anything extrapolating its position should use its steady rate.
+    pub fn get_heading_rate(&self) -> Option<MotionRate> {
+        match &self.ramp {
+            Some(ramp) if !ramp.is_ready() => Some(ramp.target()),
+            _ => self.state.try_get_rate(),
+        }
+    }
+
+    /// The rate change still in progress, if any
+    pub fn get_ramp(&self) -> Option<WaitableTask<MotorResult<()>>> {
+        self.ramp
+            .as_ref()
+            .filter(|ramp| !ramp.is_ready())
+            .map(Ramp::task)
+    }
+
+    #[inline]
+    pub fn get_min_speed(&self) -> Degrees {
+        self.speed.min_speed
+    }
+
+    #[inline]
+    pub fn get_max_speed(&self) -> Degrees {
+        self.speed.max_speed
+    }
+
+    /// The rate the controller reports, whatever we believe it should be
+    pub async fn inquire_motion_rate(&self) -> MotorResult<MotionRate> {
+        let s = self.mc.inquire_status().await?;
+        if !s.running {
+            return Ok(MotionRate::ZERO);
+        }
+        Ok(MotionRate::new(self.mc.inquire_rate().await?, s.direction))
+    }
+
+    /// Stops the motor whatever it's doing
+    pub async fn force_stop(&mut self) -> MotorResult<()> {
+        self.mc.stop_motion().await?;
+        self.wait_for_stop_open().await
+    }
+
+    /// Re-reads the state from the controller, stopping any goto we didn't start
+    pub async fn rederive_state(&mut self) -> MotorResult<()> {
+        self.determine_motor_state().await?;
+        self.interrupted_goto = None; // Stopped on purpose, so not to be restarted
+        Ok(())
+    }
+
+    pub(in crate::telescope_control::connection::transport) async fn determine_motor_state(
+        &mut self,
+    ) -> MotorResult<()> {
+        let s = self.mc.inquire_status().await?;
+
+        self.state = match (s.mode, s.running) {
+            (_, false) => MotorState::Stationary,
+            (DriveMode::Tracking, true) => {
+                let rate = self.mc.inquire_rate().await?;
+                self.backlash.turning(s.direction); // Already taken up
+                MotorState::Moving(MotionRate::new(rate, s.direction))
+            }
+            (DriveMode::Goto, true) => {
+                // Nothing would be waiting on it, so it's stopped here and left for the connection to restart
+                self.interrupted_goto = Some(self.mc.inquire_goto_target().await?);
+                self.mc.stop_motion().await?;
+                self.wait_for_stop_open().await?;
+                MotorState::Stationary
+                // let target = self.mc.inquire_goto_target().await?;
+                // MotorState::Gotoing(target)
+            }
+        };
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
+use super::super::ramp::{ramp_steps, Ramp};
 use super::super::*;
-use tokio::{select, task};
+use tokio::{select, task, time};
 use tracing::warn;
 
 pub trait HasMotor {
@@ -18,7 +19,7 @@ impl HasMotor for Motor {
 }
 
 impl Motor {
-    fn wait_for_stop<L, T>(locker: L) -> WaitableTask<MotorResult<()>>
+    fn wait_for_stop<L, T>(locker: L, priority: Priority) -> WaitableTask<MotorResult<()>>
     where
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasMotor + Send + Sync,
@@ -26,7 +27,7 @@ impl Motor {
         let (task, finisher) = WaitableTask::new();
 
         task::spawn(async move {
-            let result = StopWaiter.wait(locker.clone()).await;
+            let result = StopWaiter.wait(locker.clone(), priority).await;
 
             match result {
                 Ok(_) => {
@@ -63,7 +64,7 @@ impl Motor {
         let abort_waiter = task.get_abort_waiter();
         task::spawn(async move {
             select! {
-                result = GotoEndWaiter.wait(locker.clone()) => {
+                result = GotoEndWaiter.wait(locker.clone(), Priority::Inquiry) => {
                     if result.is_ok() {
                         let mut ml = locker.write().await;
                         let result = ml.get_mut();
@@ -89,14 +90,33 @@ impl Motor {
         task
     }
 
-    fn wait_for_rate<L, T>(locker: L, target_rate: MotionRate) -> WaitableTask<MotorResult<()>>
+    /// Once at the rate, the motor is Moving at it, unless `change` has been overtaken by a later rate change
+    fn wait_for_rate<L, T>(
+        locker: L,
+        target_rate: MotionRate,
+        change: u64,
+        priority: Priority,
+    ) -> WaitableTask<MotorResult<()>>
     where
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasMotor + Send + Sync,
     {
         let (task, finisher) = WaitableTask::new();
         task::spawn(async move {
-            let result = RateWaiter(target_rate.rate()).wait(locker.clone()).await;
+            let result = time::timeout(
+                consts::RAMP_TIMEOUT,
+                RateWaiter(target_rate.rate()).wait(locker.clone(), priority),
+            )
+            .await
+            .unwrap_or_else(|_| {
+                // Leaving the motor Changing would block every later command
+                warn!(
+                    "Motor didn't reach a rate of {} within {:?}",
+                    target_rate.rate(),
+                    consts::RAMP_TIMEOUT
+                );
+                Ok(())
+            });
             match result {
                 Ok(_) => {
                     let mut ml = locker.write().await;
@@ -108,7 +128,9 @@ impl Motor {
                             return;
                         }
                     };
-                    motor.state = MotorState::Moving(target_rate);
+                    if motor.rate_changes == change {
+                        motor.state = MotorState::Moving(target_rate);
+                    }
                     finisher.finish(MotorResult::Ok(()))
                 }
                 Err(e) => finisher.finish(MotorResult::Err(e)),
@@ -118,7 +140,11 @@ impl Motor {
     }
 
     // Cannot be called while gotoing. Cancel the goto task instead
-    pub async fn stop<L, T>(&mut self, locker: L) -> MotorResult<WaitableTask<MotorResult<()>>>
+    pub async fn stop<L, T>(
+        &mut self,
+        locker: L,
+        priority: Priority,
+    ) -> MotorResult<WaitableTask<MotorResult<()>>>
     where
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasMotor + Send + Sync,
@@ -128,8 +154,39 @@ impl Motor {
             MotorState::Gotoing(_) => panic!("stop cannot be called while Gotoing"),
             MotorState::Changing => panic!("stop cannot while state Changing"),
             MotorState::Moving(_) => {
-                self.mc.stop_motion().await?;
-                Ok(Self::wait_for_stop(locker))
+                self.rate_changes += 1;
+                self.mc.at(priority).stop_motion().await?;
+                Ok(Self::wait_for_stop(locker, priority))
+            }
+        }
+    }
+
+    /// Stops the motor straight away, cutting short any ramp in progress instead of waiting it out.
+    /// Cannot be called while gotoing. Cancel the goto task instead
+    pub async fn stop_now<L, T>(
+        &mut self,
+        locker: L,
+        priority: Priority,
+    ) -> MotorResult<WaitableTask<MotorResult<()>>>
+    where
+        L: 'static + RWLockable<T> + Clone + Send + Sync,
+        T: HasMotor + Send + Sync,
+    {
+        if let Some(ramp) = self.ramp.take() {
+            ramp.abort();
+        }
+        // Overtakes the step the ramp was waiting on, or the start of a reversal
+        self.rate_changes += 1;
+        match self.state {
+            MotorState::Stationary => Ok(WaitableTask::new_completed(Ok(()))),
+            MotorState::Gotoing(_) => panic!("stop_now cannot be called while Gotoing"),
+            MotorState::Moving(_) | MotorState::Changing => {
+                self.mc.at(priority).stop_motion().await?;
+                self.state = MotorState::Changing;
+                let stopped = Self::wait_for_stop(locker, priority);
+                // Waited on like any other rate change
+                self.ramp = Some(Ramp::single(stopped.clone(), MotionRate::ZERO));
+                Ok(stopped)
             }
         }
     }
@@ -139,6 +196,7 @@ impl Motor {
         &mut self,
         locker: L,
         mut motion_rate: MotionRate,
+        priority: Priority,
     ) -> MotorResult<WaitableTask<MotorResult<()>>>
     where
         L: 'static + RWLockable<T> + Clone + Send + Sync,
@@ -156,18 +214,27 @@ impl Motor {
             motion_rate.set_rate(self.get_max_speed())
         }
 
-        self.mc.set_tracking_mode(motion_rate.direction()).await?;
-        self.mc.set_motion_rate(motion_rate.rate()).await?;
-        self.mc.start_motion().await?;
+        self.take_up_backlash(motion_rate.direction()).await?;
+        let mc = self.mc.at(priority);
+        mc.set_tracking_mode(motion_rate.direction()).await?;
+        mc.set_motion_rate(motion_rate.rate()).await?;
+        mc.start_motion().await?;
 
         self.state = MotorState::Changing;
-        Ok(Self::wait_for_rate(locker, motion_rate))
+        self.rate_changes += 1;
+        Ok(Self::wait_for_rate(
+            locker,
+            motion_rate,
+            self.rate_changes,
+            priority,
+        ))
     }
 
     async fn change_rotation_speed<L, T>(
         &mut self,
         locker: L,
         mut rate: Degrees,
+        priority: Priority,
     ) -> MotorResult<WaitableTask<MotorResult<()>>>
     where
         L: 'static + RWLockable<T> + Clone + Send + Sync,
@@ -184,23 +251,132 @@ impl Motor {
             rate = self.get_max_speed()
         }
 
-        self.mc.set_motion_rate(rate).await?;
+        self.mc.at(priority).set_motion_rate(rate).await?;
 
         let direction = self.state.get_rate().direction();
         self.state = MotorState::Changing;
+        self.rate_changes += 1;
 
         Ok(Self::wait_for_rate(
             locker,
             MotionRate::new(rate, direction),
+            self.rate_changes,
+            priority,
         ))
     }
 
-    // Cannot be called while gotoing. Cancel the goto task first
+    // Cannot be called while gotoing or ramping. Cancel the goto task or wait out the ramp first
     pub async fn change_rate<L, T>(
         &mut self,
         locker: L,
         to: MotionRate,
     ) -> MotorResult<WaitableTask<MotorResult<()>>>
+    where
+        L: 'static + RWLockable<T> + Clone + Send + Sync,
+        T: HasMotor + Send + Sync,
+    {
+        self.change_rate_at(locker, to, Priority::Motion).await
+    }
+
+    /// change_rate with the commands sent at `priority`, e.g. ahead of everything else for a guide pulse
+    pub async fn change_rate_at<L, T>(
+        &mut self,
+        locker: L,
+        to: MotionRate,
+        priority: Priority,
+    ) -> MotorResult<WaitableTask<MotorResult<()>>>
+    where
+        L: 'static + RWLockable<T> + Clone + Send + Sync,
+        T: HasMotor + Send + Sync,
+    {
+        let max_step = self.max_acceleration * consts::RAMP_STEP_INTERVAL.as_secs_f64();
+        let steps = match self.state.try_get_rate() {
+            Some(from) => ramp_steps(from, to, max_step, self.get_min_speed()),
+            None => Vec::new(), // Rejected below
+        };
+        let ramp = if steps.len() <= 1 {
+            Ramp::single(self.start_rate_change(locker, to, priority).await?, to)
+        } else {
+            self.start_ramp(locker, steps, priority).await?
+        };
+        let task = ramp.task();
+        self.ramp = Some(ramp);
+        Ok(task)
+    }
+
+    /// Changes rate a step at a time so the acceleration stays under the max.
+    /// Aborting the ramp leaves the motor at the last step taken.
+    async fn start_ramp<L, T>(
+        &mut self,
+        locker: L,
+        steps: Vec<MotionRate>,
+        priority: Priority,
+    ) -> MotorResult<Ramp>
+    where
+        L: 'static + RWLockable<T> + Clone + Send + Sync,
+        T: HasMotor + Send + Sync,
+    {
+        let first_step = self
+            .start_rate_change(locker.clone(), steps[0], priority)
+            .await?;
+        let target = steps[steps.len() - 1];
+        let (task, finisher) = WaitableTask::new();
+        let (stepping, _) = AbortableTask::new();
+        let aborted = stepping.get_abort_waiter();
+        task::spawn(async move {
+            let mut step_task = first_step;
+            for step in steps.into_iter().skip(1) {
+                let result = select! {
+                    result = step_task => result,
+                    _ = aborted.clone() => Ok(()),
+                };
+                if result.is_err() {
+                    finisher.finish(result);
+                    return;
+                }
+                select! {
+                    _ = time::sleep(consts::RAMP_STEP_INTERVAL) => {}
+                    _ = aborted.clone() => {}
+                }
+
+                let mut ml = locker.write().await;
+                if aborted.is_ready() {
+                    finisher.finish(Ok(()));
+                    return;
+                }
+                let motor = match ml.get_mut() {
+                    Ok(motor) => motor,
+                    Err(e) => {
+                        finisher.finish(Err(e));
+                        return;
+                    }
+                };
+                step_task = match motor
+                    .start_rate_change(locker.clone(), step, priority)
+                    .await
+                {
+                    Ok(step_task) => step_task,
+                    Err(e) => {
+                        finisher.finish(Err(e));
+                        return;
+                    }
+                };
+            }
+            let result = select! {
+                result = step_task => result,
+                _ = aborted.clone() => Ok(()),
+            };
+            finisher.finish(result);
+        });
+        Ok(Ramp::new(task, stepping, target))
+    }
+
+    async fn start_rate_change<L, T>(
+        &mut self,
+        locker: L,
+        to: MotionRate,
+        priority: Priority,
+    ) -> MotorResult<WaitableTask<MotorResult<()>>>
     where
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasMotor + Send + Sync,
@@ -216,14 +392,16 @@ impl Motor {
         if from == to {
             Ok(WaitableTask::new_completed(Ok(())))
         } else if from.is_zero() {
-            self.start_rotation(locker, to).await
+            self.start_rotation(locker, to, priority).await
         } else if to.is_zero() {
-            self.stop(locker).await
+            self.stop(locker, priority).await
         } else if to.direction() == from.direction() {
-            self.change_rotation_speed(locker, to.rate()).await
+            self.change_rotation_speed(locker, to.rate(), priority)
+                .await
         } else {
             let (task, finisher) = WaitableTask::new();
-            let stop_task = self.stop(locker.clone()).await?;
+            let stop_task = self.stop(locker.clone(), priority).await?;
+            let change = self.rate_changes;
             task::spawn(async move {
                 let stop_result = stop_task.await;
                 if stop_result.is_err() {
@@ -241,7 +419,12 @@ impl Motor {
                             return;
                         }
                     };
-                    motor.start_rotation(locker.clone(), to).await
+                    // Stopped for good by stop_now
+                    if motor.rate_changes != change {
+                        finisher.finish(Ok(()));
+                        return;
+                    }
+                    motor.start_rotation(locker.clone(), to, priority).await
                 };
 
                 if let Err(e) = result {
@@ -270,10 +453,12 @@ impl Motor {
         if !matches!(self.state, MotorState::Stationary) {
             panic!("goto called on motor not stopped")
         }
+        let motor_target = self.goto_target_through_backlash(deg).await?;
         self.mc.set_goto_mode().await?;
-        self.mc.set_goto_target(deg).await?;
+        self.mc.set_goto_target(motor_target).await?;
         self.mc.start_motion().await?;
         self.state = MotorState::Gotoing(deg);
+        self.interrupted_goto = None; // Superseded
 
         let (abortable_task, finisher) = AbortableTask::new();
         let abort_waiter = abortable_task.get_abort_waiter();
@@ -313,7 +498,7 @@ impl Motor {
                 }
             };
 
-            let stop_result = Self::wait_for_stop(locker).await;
+            let stop_result = Self::wait_for_stop(locker, Priority::Inquiry).await;
             if stop_result.is_err() {
                 if aborted {
                     finisher.aborted(stop_result);
@@ -1,10 +1,11 @@
 #![allow(unused)]
 use super::super::*;
 use crate::util::*;
+use synscan::Direction;
 use tracing::warn;
 
 impl Motor {
-    pub(in crate::telescope_control::connection::motor) async fn wait_for_stop_open(
+    pub(in crate::telescope_control::connection::transport) async fn wait_for_stop_open(
         &mut self,
     ) -> MotorResult<()> {
         StopWaiter.wait_sync(self).await?;
@@ -19,11 +20,57 @@ impl Motor {
     }
 
     async fn wait_for_rate_open(&mut self, target_rate: MotionRate) -> MotorResult<()> {
-        RateWaiter(target_rate.rate()).wait_sync(self).await?;
+        let waited = tokio::time::timeout(
+            consts::RAMP_TIMEOUT,
+            RateWaiter(target_rate.rate()).wait_sync(self),
+        )
+        .await;
+        match waited {
+            Ok(result) => result?,
+            Err(_) => warn!(
+                "Motor didn't reach a rate of {} within {:?}",
+                target_rate.rate(),
+                consts::RAMP_TIMEOUT
+            ),
+        }
         self.state = MotorState::Moving(target_rate);
         Ok(())
     }
 
+    /// Turns the motor through any slack before it starts moving the scope the other way. Must be stopped
+    pub(in crate::telescope_control::connection::transport) async fn take_up_backlash(
+        &mut self,
+        direction: Direction,
+    ) -> MotorResult<()> {
+        let slack = self.backlash.turning(direction);
+        if slack == 0. {
+            return Ok(());
+        }
+        let motor_pos = self.mc.inquire_pos().await?;
+        self.mc.set_goto_mode().await?;
+        self.mc.set_goto_target(motor_pos + slack).await?;
+        self.mc.start_motion().await?;
+        GotoEndWaiter.wait_sync(self).await?;
+        StopWaiter.wait_sync(self).await?;
+        self.backlash.taken_up(slack);
+        Ok(())
+    }
+
+    /// Goes the way the goto will, taking up any slack in the goto itself. Returns the motor's target
+    pub(in crate::telescope_control::connection::transport) async fn goto_target_through_backlash(
+        &mut self,
+        deg: Degrees,
+    ) -> MotorResult<Degrees> {
+        let direction = if self.get_pos().await? < deg {
+            Direction::Clockwise
+        } else {
+            Direction::CounterClockwise
+        };
+        let slack = self.backlash.turning(direction);
+        self.backlash.taken_up(slack);
+        Ok(self.backlash.to_motor_pos(deg))
+    }
+
     /// Moving -> Stopped
     async fn stop_open(&mut self) -> MotorResult<()> {
         match self.state {
@@ -51,6 +98,7 @@ impl Motor {
             motion_rate.set_rate(self.get_max_speed());
         }
 
+        self.take_up_backlash(motion_rate.direction()).await?;
         self.mc.set_tracking_mode(motion_rate.direction()).await?;
         self.mc.set_motion_rate(motion_rate.rate().abs()).await?;
         self.mc.start_motion().await?;
@@ -109,8 +157,9 @@ impl Motor {
         if !matches!(self.state, MotorState::Stationary) {
             panic!("goto called on motor not stopped")
         }
+        let motor_target = self.goto_target_through_backlash(deg).await?;
         self.mc.set_goto_mode().await?;
-        self.mc.set_goto_target(deg).await?;
+        self.mc.set_goto_target(motor_target).await?;
         self.mc.start_motion().await?;
         self.state = MotorState::Gotoing(deg);
         self.wait_for_goto_end_open().await?;
@@ -9,6 +9,15 @@ pub enum MotorState {
 }
 
 impl MotorState {
+    /// None while the rate is changing or a goto is running
+    pub fn try_get_rate(&self) -> Option<MotionRate> {
+        match self {
+            Self::Stationary => Some(MotionRate::ZERO),
+            Self::Moving(rate) => Some(*rate),
+            Self::Gotoing(_) | Self::Changing => None,
+        }
+    }
+
     pub fn get_rate(&self) -> MotionRate {
         match self {
             Self::Stationary => MotionRate::ZERO,
@@ -0,0 +1,53 @@
+//! A JSON lines record of every command sent to the motor controller and what came back,
+//! for working out afterwards what the mount was actually told to do.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub time: DateTime<Utc>, // When the command was sent
+    pub port: String,
+    pub command: String,
+    pub try_no: u32,
+    pub response: String, // Debug formatted result
+    pub ok: bool,
+    pub millis: f64, // Round trip
+}
+
+#[derive(Clone)]
+pub struct MotorTrace {
+    port: String,
+    file: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl MotorTrace {
+    /// Appends to the file so traces from reconnects and other mounts end up together
+    pub fn open(path: &str, port: impl Into<String>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            port: port.into(),
+            file: Arc::new(Mutex::new(BufWriter::new(file))),
+        })
+    }
+
+    pub fn port(&self) -> &str {
+        &self.port
+    }
+
+    pub fn record(&self, entry: &TraceEntry) {
+        let mut file = self.file.lock().unwrap();
+        // Tracing must never get in the way of driving the motor
+        let written = serde_json::to_writer(&mut *file, entry)
+            .map_err(io::Error::from)
+            .and_then(|_| file.write_all(b"\n"))
+            .and_then(|_| file.flush());
+        if let Err(e) = written {
+            tracing::warn!("Couldn't write the motor trace: {}", e);
+        }
+    }
+}
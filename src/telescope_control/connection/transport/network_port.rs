@@ -0,0 +1,381 @@
+//! Lets the motor controller be reached over the network, e.g. through a SynScan WiFi dongle or an ESP-01 bridge.
+//! The socket is presented as a serial port so the controller can't tell the difference.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use super::consts::*;
+
+/// Where the motor controller is found, parsed from the configured com path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComPath {
+    Serial(String),
+    Tcp(String),
+    Udp(String),
+    Simulated(Option<u8>), // Mount code to pose as, if not the default
+}
+
+impl ComPath {
+    pub fn parse(path: &str) -> Self {
+        if let Some(addr) = path.strip_prefix("tcp://") {
+            ComPath::Tcp(Self::with_default_port(addr))
+        } else if let Some(addr) = path.strip_prefix("udp://") {
+            ComPath::Udp(Self::with_default_port(addr))
+        } else if let Some(code) = path.strip_prefix("sim://") {
+            ComPath::Simulated(u8::from_str_radix(code.trim_start_matches("0x"), 16).ok())
+        } else {
+            ComPath::Serial(path.to_string())
+        }
+    }
+
+    fn with_default_port(addr: &str) -> String {
+        if addr.contains(':') {
+            addr.to_string()
+        } else {
+            format!("{}:{}", addr, SYNSCAN_WIFI_PORT)
+        }
+    }
+}
+
+enum Socket {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+pub struct NetworkPort {
+    name: String,
+    socket: Socket,
+    timeout: Duration,
+    received: Mutex<Vec<u8>>, // Rest of the last datagram, not yet read. Emptied by clear, which only gets &self
+    unsent: Vec<u8>,          // A datagram is only sent once the command is complete
+}
+
+impl NetworkPort {
+    pub fn open(path: &ComPath, timeout: Duration) -> io::Result<Self> {
+        let (name, socket) = match path {
+            ComPath::Serial(path) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} isn't a network address", path),
+                ))
+            }
+            ComPath::Simulated(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The simulator isn't a network address",
+                ))
+            }
+            ComPath::Tcp(addr) => {
+                let stream = TcpStream::connect_timeout(&Self::resolve(addr)?, CONNECT_TIMEOUT)?;
+                stream.set_nodelay(true)?;
+                (format!("tcp://{}", addr), Socket::Tcp(stream))
+            }
+            ComPath::Udp(addr) => {
+                let socket = UdpSocket::bind("0.0.0.0:0")?;
+                socket.connect(Self::resolve(addr)?)?;
+                (format!("udp://{}", addr), Socket::Udp(socket))
+            }
+        };
+
+        let mut port = NetworkPort {
+            name,
+            socket,
+            timeout,
+            received: Mutex::new(Vec::new()),
+            unsent: Vec::new(),
+        };
+        port.set_timeout(timeout)?;
+        Ok(port)
+    }
+
+    fn resolve(addr: &str) -> io::Result<SocketAddr> {
+        addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Couldn't resolve {}", addr),
+            )
+        })
+    }
+
+    /// Throws away whatever has arrived but not been read, such as a late reply to a command that timed out.
+    /// The framing can't tell one reply from another, so it would otherwise be taken for the next command's.
+    fn discard_pending(&self) -> io::Result<()> {
+        self.received.lock().unwrap().clear();
+        let mut buf = [0; 512];
+        match &self.socket {
+            Socket::Tcp(stream) => {
+                stream.set_nonblocking(true)?;
+                let result = Self::drain(|buf| (&*stream).read(buf), &mut buf);
+                stream.set_nonblocking(false)?;
+                result
+            }
+            Socket::Udp(socket) => {
+                socket.set_nonblocking(true)?;
+                let result = Self::drain(|buf| socket.recv(buf), &mut buf);
+                socket.set_nonblocking(false)?;
+                result
+            }
+        }
+    }
+
+    /// Reads until nothing more is waiting
+    fn drain(
+        mut read: impl FnMut(&mut [u8]) -> io::Result<usize>,
+        buf: &mut [u8],
+    ) -> io::Result<()> {
+        loop {
+            match read(buf) {
+                Ok(0) => return Ok(()), // Closed, which the next read reports
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn unsupported<T>() -> serialport::Result<T> {
+        Err(serialport::Error::new(
+            serialport::ErrorKind::InvalidInput,
+            "Not supported by a network connection",
+        ))
+    }
+}
+
+impl Read for NetworkPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.socket {
+            Socket::Tcp(stream) => stream.read(buf),
+            Socket::Udp(socket) => {
+                let received = self.received.get_mut().unwrap();
+                if received.is_empty() {
+                    let mut datagram = [0; 512];
+                    let len = socket.recv(&mut datagram)?;
+                    received.extend_from_slice(&datagram[..len]);
+                }
+                let len = buf.len().min(received.len());
+                buf[..len].copy_from_slice(&received[..len]);
+                received.drain(..len);
+                Ok(len)
+            }
+        }
+    }
+}
+
+impl Write for NetworkPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.socket {
+            Socket::Tcp(stream) => stream.write(buf),
+            Socket::Udp(_) => {
+                self.unsent.extend_from_slice(buf);
+                if self.unsent.ends_with(b"\r") {
+                    self.flush()?;
+                }
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.socket {
+            Socket::Tcp(stream) => stream.flush(),
+            Socket::Udp(socket) => {
+                if !self.unsent.is_empty() {
+                    socket.send(&self.unsent)?;
+                    self.unsent.clear();
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl SerialPort for NetworkPort {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(BAUD_RATE)
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(DataBits::Eight)
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(FlowControl::None)
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(Parity::None)
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(StopBits::One)
+    }
+
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn set_baud_rate(&mut self, _baud_rate: u32) -> serialport::Result<()> {
+        Ok(()) // Fixed by the adapter
+    }
+
+    fn set_data_bits(&mut self, _data_bits: DataBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, _flow_control: FlowControl) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_parity(&mut self, _parity: Parity) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, _stop_bits: StopBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        match &self.socket {
+            Socket::Tcp(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+            }
+            Socket::Udp(socket) => {
+                socket.set_read_timeout(Some(timeout))?;
+                socket.set_write_timeout(Some(timeout))?;
+            }
+        }
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, _level: bool) -> serialport::Result<()> {
+        Self::unsupported()
+    }
+
+    fn write_data_terminal_ready(&mut self, _level: bool) -> serialport::Result<()> {
+        Self::unsupported()
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Self::unsupported()
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Self::unsupported()
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Self::unsupported()
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Self::unsupported()
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self.received.lock().unwrap().len() as u32)
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(self.unsent.len() as u32)
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        match buffer_to_clear {
+            ClearBuffer::Input | ClearBuffer::All => Ok(self.discard_pending()?),
+            ClearBuffer::Output => Ok(()), // Everything written has already been sent
+        }
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        let socket = match &self.socket {
+            Socket::Tcp(stream) => Socket::Tcp(stream.try_clone()?),
+            Socket::Udp(socket) => Socket::Udp(socket.try_clone()?),
+        };
+        Ok(Box::new(NetworkPort {
+            name: self.name.clone(),
+            socket,
+            timeout: self.timeout,
+            received: Mutex::new(Vec::new()),
+            unsent: Vec::new(),
+        }))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        Self::unsupported()
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        Self::unsupported()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_com_path() {
+        assert_eq!(
+            ComPath::parse("/dev/ttyUSB0"),
+            ComPath::Serial("/dev/ttyUSB0".to_string())
+        );
+        assert_eq!(
+            ComPath::parse("tcp://192.168.4.1:11880"),
+            ComPath::Tcp("192.168.4.1:11880".to_string())
+        );
+        assert_eq!(
+            ComPath::parse("udp://192.168.4.1"),
+            ComPath::Udp("192.168.4.1:11880".to_string())
+        );
+        assert_eq!(ComPath::parse("sim://"), ComPath::Simulated(None));
+        assert_eq!(ComPath::parse("sim://a5"), ComPath::Simulated(Some(0xA5)));
+    }
+
+    #[test]
+    fn test_clear_drops_late_replies() {
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let path = ComPath::Udp(peer.local_addr().unwrap().to_string());
+        let mut port = NetworkPort::open(&path, Duration::from_millis(200)).unwrap();
+        port.write_all(b":j1\r").unwrap();
+        let mut buf = [0; 16];
+        let (_, from) = peer.recv_from(&mut buf).unwrap();
+
+        // A reply arriving after the command gave up, then the one to the next command
+        peer.send_to(b"=000080\r", from).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        port.clear(ClearBuffer::Input).unwrap();
+        peer.send_to(b"=010080\r", from).unwrap();
+
+        let len = port.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"=010080\r");
+    }
+
+    #[test]
+    fn test_clear_drains_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let path = ComPath::Tcp(listener.local_addr().unwrap().to_string());
+        let mut port = NetworkPort::open(&path, Duration::from_millis(200)).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        peer.write_all(b"=000080\r").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        port.clear(ClearBuffer::All).unwrap();
+        peer.write_all(b"=010080\r").unwrap();
+
+        let mut buf = [0; 16];
+        let len = port.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"=010080\r");
+    }
+}
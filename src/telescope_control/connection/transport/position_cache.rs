@@ -0,0 +1,108 @@
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::util::*;
+
+#[derive(Debug, Copy, Clone)]
+struct PosReading {
+    pos: Degrees,
+    rate: MotionRate, // The motor's rate when read
+    time: Instant,
+}
+
+/// Lets position reads close together share one inquiry, moving the shared reading on by the motor's rate
+#[derive(Debug)]
+pub struct PositionCache {
+    freshness: Duration, // Zero to always inquire
+    last: Mutex<Option<PosReading>>,
+}
+
+impl PositionCache {
+    pub fn new(freshness: Duration) -> Self {
+        Self {
+            freshness,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// rate is the motor's current rate, None while it's changing or gotoing
+    pub async fn get_or_inquire<F, E>(
+        &self,
+        rate: Option<MotionRate>,
+        inquire: F,
+    ) -> Result<Degrees, E>
+    where
+        F: std::future::Future<Output = Result<Degrees, E>>,
+    {
+        if self.freshness.is_zero() {
+            return inquire.await;
+        }
+        // Readers arriving during an inquiry wait for it and share its reading
+        let mut last = self.last.lock().await;
+        if let Some(pos) =
+            last.and_then(|reading| reading.extrapolate(rate, Instant::now(), self.freshness))
+        {
+            return Ok(pos);
+        }
+
+        let sent = Instant::now();
+        let result = inquire.await;
+        *last = match (&result, rate) {
+            (Ok(pos), Some(rate)) => Some(PosReading {
+                pos: *pos,
+                rate,
+                time: sent + sent.elapsed() / 2,
+            }),
+            _ => None,
+        };
+        result
+    }
+}
+
+impl PosReading {
+    /// Where the motor is now, if the reading is fresh and the motor hasn't changed rate since
+    fn extrapolate(
+        &self,
+        rate: Option<MotionRate>,
+        now: Instant,
+        freshness: Duration,
+    ) -> Option<Degrees> {
+        let age = now.saturating_duration_since(self.time);
+        if freshness < age || rate != Some(self.rate) {
+            return None;
+        }
+        Some(self.pos + self.rate.clockwise_rate() * age.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extrapolate() {
+        let time = Instant::now();
+        let rate = MotionRate::from_clockwise_rate(0.5);
+        let reading = PosReading {
+            pos: 10.,
+            rate,
+            time,
+        };
+        let freshness = Duration::from_millis(100);
+
+        let pos = reading
+            .extrapolate(Some(rate), time + Duration::from_millis(50), freshness)
+            .unwrap();
+        assert!((pos - 10.025).abs() < 1e-9);
+        // Stale
+        assert!(reading
+            .extrapolate(Some(rate), time + Duration::from_millis(150), freshness)
+            .is_none());
+        // Changed rate since
+        assert!(reading
+            .extrapolate(Some(MotionRate::ZERO), time, freshness)
+            .is_none());
+        assert!(reading.extrapolate(None, time, freshness).is_none());
+    }
+}
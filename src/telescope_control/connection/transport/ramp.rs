@@ -0,0 +1,118 @@
+use super::MotorResult;
+use crate::util::*;
+
+/// A rate change in progress, which can be cut short between its steps
+#[derive(Clone)]
+pub(super) struct Ramp {
+    task: WaitableTask<MotorResult<()>>,
+    stepping: AbortableTask<(), ()>, // Aborted to take no more steps
+    target: MotionRate,
+}
+
+impl Ramp {
+    pub(super) fn new(
+        task: WaitableTask<MotorResult<()>>,
+        stepping: AbortableTask<(), ()>,
+        target: MotionRate,
+    ) -> Self {
+        Self {
+            task,
+            stepping,
+            target,
+        }
+    }
+
+    /// A change made in one step, so there's nothing to cut short
+    pub(super) fn single(task: WaitableTask<MotorResult<()>>, target: MotionRate) -> Self {
+        Self::new(task, AbortableTask::new_completed(()), target)
+    }
+
+    pub(super) fn target(&self) -> MotionRate {
+        self.target
+    }
+
+    pub(super) fn task(&self) -> WaitableTask<MotorResult<()>> {
+        self.task.clone()
+    }
+
+    pub(super) fn is_ready(&self) -> bool {
+        self.task.is_ready()
+    }
+
+    /// Leaves the motor at whatever step it's reached, for the caller to stop
+    pub(super) fn abort(&self) {
+        if !self.stepping.get_abort_waiter().is_ready() {
+            let _ = self.stepping.abort();
+        }
+    }
+}
+
+/// The rates to step through from one rate to another, ending with the target.
+/// A reversal stops on the way, and steps too slow for the motor to turn at are skipped.
+pub(super) fn ramp_steps(
+    from: MotionRate,
+    to: MotionRate,
+    max_step: Degrees,
+    min_speed: Degrees,
+) -> Vec<MotionRate> {
+    let (from, to) = (from.clockwise_rate(), to.clockwise_rate());
+    if max_step <= 0. {
+        return vec![MotionRate::from_clockwise_rate(to)];
+    }
+
+    let num_steps = ((to - from).abs() / max_step).ceil().max(1.) as usize;
+    let step = (to - from) / num_steps as f64;
+    let mut steps: Vec<MotionRate> = Vec::with_capacity(num_steps + 1);
+    let mut last = from;
+    for i in 1..=num_steps {
+        let rate = if i == num_steps {
+            to
+        } else {
+            from + step * i as f64
+        };
+        if last != 0. && last * rate <= 0. {
+            steps.push(MotionRate::ZERO);
+        }
+        if rate != 0. && (rate == to || min_speed <= rate.abs()) {
+            steps.push(MotionRate::from_clockwise_rate(rate));
+        }
+        last = rate;
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ramp_steps() {
+        let rates = |steps: Vec<MotionRate>| -> Vec<Degrees> {
+            steps.iter().map(|r| r.clockwise_rate()).collect()
+        };
+        let (slow, fast) = (
+            MotionRate::from_clockwise_rate(0.125),
+            MotionRate::from_clockwise_rate(0.5),
+        );
+
+        assert_eq!(
+            rates(ramp_steps(slow, fast, 0.125, 0.01)),
+            vec![0.25, 0.375, 0.5]
+        );
+        assert_eq!(rates(ramp_steps(slow, fast, 0., 0.01)), vec![0.5]);
+        assert_eq!(rates(ramp_steps(fast, slow, 1., 0.01)), vec![0.125]);
+
+        // Reversing stops first
+        let reverse = rates(ramp_steps(
+            fast,
+            MotionRate::from_clockwise_rate(-0.2),
+            0.25,
+            0.01,
+        ));
+        assert!(reverse.contains(&0.));
+        assert_eq!(reverse.last(), Some(&-0.2));
+        assert!(reverse
+            .windows(2)
+            .all(|w| (w[1] - w[0]).abs() <= 0.25 + 1E-9));
+    }
+}
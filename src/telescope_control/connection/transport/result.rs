@@ -6,6 +6,8 @@ use std::io::Error;
 pub enum MotorError {
     IOError(String),
     Disconnected,
+    Rejected(String), // The controller refused a command we sent, which is our bug rather than a lost link
+    UnexpectedState(String), // The motor is in a state the driver didn't put it in, so can't be planned from
 }
 
 pub type MotorResult<T> = Result<T, MotorError>;
@@ -33,6 +35,8 @@ impl Display for MotorError {
         match self {
             Self::IOError(s) => Display::fmt(&s, f),
             Self::Disconnected => Display::fmt("Disconnected", f),
+            Self::Rejected(s) => write!(f, "Command rejected by the controller: {}", s),
+            Self::UnexpectedState(s) => write!(f, "Unexpected motor state: {}", s),
         }
     }
 }
@@ -0,0 +1,66 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+use synscan::util::SynScanError;
+
+/// Counts of how the serial link has behaved since the driver started, to pick out a flaky adapter
+#[derive(Debug, Default)]
+pub struct SerialHealth {
+    commands: AtomicU64,
+    retries: AtomicU64,
+    failures: AtomicU64,
+    timeouts: AtomicU64,
+    checksum_errors: AtomicU64,
+    other_errors: AtomicU64,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SerialHealthCounts {
+    pub commands: u64,
+    pub retries: u64,
+    pub failures: u64, // Commands that failed on every try
+    pub timeouts: u64,
+    pub checksum_errors: u64, // Garbled responses, which are all the protocol can tell apart from a good one
+    pub other_errors: u64,
+}
+
+impl SerialHealth {
+    pub fn counts(&self) -> SerialHealthCounts {
+        let get = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        SerialHealthCounts {
+            commands: get(&self.commands),
+            retries: get(&self.retries),
+            failures: get(&self.failures),
+            timeouts: get(&self.timeouts),
+            checksum_errors: get(&self.checksum_errors),
+            other_errors: get(&self.other_errors),
+        }
+    }
+
+    pub(super) fn record_command(&self) {
+        self.commands.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Every failed try, including those retried
+    pub(super) fn record_error(&self, e: &SynScanError) {
+        let counter = match e {
+            SynScanError::CommunicationError(e) if e.kind() == io::ErrorKind::TimedOut => {
+                &self.timeouts
+            }
+            SynScanError::CommunicationError(e) if e.kind() == io::ErrorKind::InvalidData => {
+                &self.checksum_errors
+            }
+            _ => &self.other_errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
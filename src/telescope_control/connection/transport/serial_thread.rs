@@ -0,0 +1,343 @@
+//! The serial port is owned by one dedicated thread so blocking I/O and retry delays never stall the async runtime.
+//! Commands arrive over a channel and are answered through a oneshot.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use synscan::serialport::SPSerialPort;
+use synscan::util::SynScanResult;
+use synscan::MotorController;
+use tokio::sync::oneshot;
+
+use super::consts::*;
+use super::motor_trace::{MotorTrace, TraceEntry};
+use super::serial_health::SerialHealth;
+
+type Port = MotorController<SPSerialPort>;
+
+/// Ordered lowest first
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Only reads motor state
+    Inquiry,
+    /// Changes what the motor is doing. Run before any queued inquiries.
+    Motion,
+    /// A guide pulse's rate change and the inquiries confirming it. Run before any queued motion.
+    Guide,
+    /// An emergency stop. Run before anything else queued, dropping the queued motion and guides that would restart the motor.
+    Stop,
+}
+
+struct Request {
+    priority: Priority,
+    run: Box<dyn FnOnce(&Port) + Send>,
+}
+
+/// First in first out within a priority
+#[derive(Default)]
+struct RequestQueue {
+    stops: VecDeque<Request>,
+    guides: VecDeque<Request>,
+    motion: VecDeque<Request>,
+    inquiries: VecDeque<Request>,
+}
+
+impl RequestQueue {
+    fn is_empty(&self) -> bool {
+        self.stops.is_empty()
+            && self.guides.is_empty()
+            && self.motion.is_empty()
+            && self.inquiries.is_empty()
+    }
+
+    fn push(&mut self, request: Request) {
+        match request.priority {
+            Priority::Stop => {
+                // Their callers see the motor as disconnected
+                self.guides.clear();
+                self.motion.clear();
+                self.stops.push_back(request)
+            }
+            Priority::Guide => self.guides.push_back(request),
+            Priority::Motion => self.motion.push_back(request),
+            Priority::Inquiry => self.inquiries.push_back(request),
+        }
+    }
+
+    fn pop(&mut self) -> Option<Request> {
+        self.stops
+            .pop_front()
+            .or_else(|| self.guides.pop_front())
+            .or_else(|| self.motion.pop_front())
+            .or_else(|| self.inquiries.pop_front())
+    }
+}
+
+/// How a failed command is retried
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub tries: u32,      // Including the first
+    pub delay: Duration, // Before the first retry
+    pub backoff: f64,    // Each later retry waits this many times longer than the one before
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            tries: NUM_TRIES,
+            delay: Duration::from_millis(RETRY_MILLIS),
+            backoff: RETRY_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The wait after try number try_no failed, counting from 1. Never more than MAX_RETRY_DELAY.
+    fn delay_after(&self, try_no: u32) -> Duration {
+        let factor = self.backoff.max(1.).powi(try_no as i32 - 1);
+        let secs = self.delay.as_secs_f64() * factor;
+        // min doesn't pass on NaN, and an infinite factor caps like any other
+        Duration::from_secs_f64(secs.min(MAX_RETRY_DELAY.as_secs_f64()).max(0.))
+    }
+}
+
+/// Sleeps out a retry delay, returning early with false if a stop is queued meanwhile
+fn sleep_unless_stopping(delay: Duration, stops_queued: &AtomicUsize) -> bool {
+    let end = Instant::now() + delay;
+    loop {
+        if 0 < stops_queued.load(Ordering::Acquire) {
+            return false;
+        }
+        let now = Instant::now();
+        if end <= now {
+            return true;
+        }
+        thread::sleep((end - now).min(RETRY_SLEEP_SLICE));
+    }
+}
+
+#[derive(Clone)]
+pub struct SerialThread {
+    requests: mpsc::Sender<Request>,
+    trace: Option<MotorTrace>,
+    retry: RetryPolicy,
+    health: Arc<SerialHealth>,
+    stops_queued: Arc<AtomicUsize>, // Sent but not yet run, so a command retrying gives way to them
+}
+
+impl SerialThread {
+    /// Takes ownership of the port. The thread exits once every handle is dropped.
+    pub fn spawn(
+        port: Port,
+        trace: Option<MotorTrace>,
+        retry: RetryPolicy,
+        health: Arc<SerialHealth>,
+    ) -> Self {
+        let (requests, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("serial".to_string())
+            .spawn(move || Self::run(port, receiver))
+            .expect("Couldn't start serial thread");
+        SerialThread {
+            requests,
+            trace,
+            retry,
+            health,
+            stops_queued: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn run(port: Port, receiver: mpsc::Receiver<Request>) {
+        let mut queue = RequestQueue::default();
+        loop {
+            // Block only when there's nothing queued
+            if queue.is_empty() {
+                match receiver.recv() {
+                    Ok(request) => queue.push(request),
+                    Err(_) => return,
+                }
+            }
+            while let Ok(request) = receiver.try_recv() {
+                queue.push(request);
+            }
+
+            if let Some(request) = queue.pop() {
+                (request.run)(&port);
+            }
+        }
+    }
+
+    /// Runs the command on the serial thread, retrying as the retry policy says.
+    /// As such, the command should be idempotent.
+    /// Dropping the returned future cancels the command if it hasn't been sent yet.
+    /// The command's name is only used for the trace.
+    pub async fn command<F, T>(
+        &self,
+        priority: Priority,
+        name: String,
+        f: F,
+    ) -> Option<SynScanResult<T>>
+    where
+        F: Fn(&Port) -> SynScanResult<T> + Send + 'static,
+        T: 'static + Send + Debug,
+    {
+        let (respond, response) = oneshot::channel();
+        let (trace, retry, health) = (self.trace.clone(), self.retry, self.health.clone());
+        let stops_queued = self.stops_queued.clone();
+        let is_stop = priority == Priority::Stop;
+        let run = Box::new(move |port: &Port| {
+            if is_stop {
+                stops_queued.fetch_sub(1, Ordering::AcqRel);
+            }
+            health.record_command();
+            let mut try_no = 1;
+            let result = loop {
+                // The caller gave up, so don't touch the motor
+                if respond.is_closed() {
+                    return;
+                }
+                let (time, sent) = (Utc::now(), Instant::now());
+                let result = f(port);
+                if let Some(trace) = &trace {
+                    trace.record(&TraceEntry {
+                        time,
+                        port: trace.port().to_string(),
+                        command: name.clone(),
+                        try_no,
+                        response: format!("{:?}", result),
+                        ok: result.is_ok(),
+                        millis: sent.elapsed().as_secs_f64() * 1000.,
+                    });
+                }
+                if let Err(e) = &result {
+                    health.record_error(e);
+                }
+                match &result {
+                    Err(e) if try_no < retry.tries => {
+                        tracing::warn!("Error sending command to driver: {} -- Retrying", e);
+                        health.record_retry();
+                        if !sleep_unless_stopping(retry.delay_after(try_no), &stops_queued) {
+                            tracing::warn!("Giving up retrying for a queued stop");
+                            health.record_failure();
+                            break result;
+                        }
+                        try_no += 1;
+                    }
+                    Err(_) => {
+                        health.record_failure();
+                        break result;
+                    }
+                    Ok(_) => break result,
+                }
+            };
+            let _ = respond.send(result);
+        });
+
+        if is_stop {
+            self.stops_queued.fetch_add(1, Ordering::AcqRel);
+        }
+        if self.requests.send(Request { priority, run }).is_err() {
+            if is_stop {
+                self.stops_queued.fetch_sub(1, Ordering::AcqRel);
+            }
+            return None;
+        }
+        response.await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guides_jump_the_queue() {
+        let request = |priority| Request {
+            priority,
+            run: Box::new(|_: &Port| {}),
+        };
+        let mut queue = RequestQueue::default();
+        for priority in [
+            Priority::Inquiry,
+            Priority::Motion,
+            Priority::Inquiry,
+            Priority::Guide,
+        ] {
+            queue.push(request(priority));
+        }
+
+        let order: Vec<Priority> = std::iter::from_fn(|| queue.pop())
+            .map(|request| request.priority)
+            .collect();
+        assert_eq!(
+            order,
+            [
+                Priority::Guide,
+                Priority::Motion,
+                Priority::Inquiry,
+                Priority::Inquiry
+            ]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_stop_drops_queued_motion() {
+        let request = |priority| Request {
+            priority,
+            run: Box::new(|_: &Port| {}),
+        };
+        let mut queue = RequestQueue::default();
+        for priority in [
+            Priority::Motion,
+            Priority::Inquiry,
+            Priority::Guide,
+            Priority::Stop,
+        ] {
+            queue.push(request(priority));
+        }
+
+        let order: Vec<Priority> = std::iter::from_fn(|| queue.pop())
+            .map(|request| request.priority)
+            .collect();
+        assert_eq!(order, [Priority::Stop, Priority::Inquiry]);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = RetryPolicy::default();
+        assert_eq!(retry.delay_after(1), Duration::from_millis(10));
+        assert_eq!(retry.delay_after(2), Duration::from_millis(100));
+
+        // Capped rather than overflowing
+        let retry = RetryPolicy {
+            tries: u32::MAX,
+            delay: Duration::from_secs(1),
+            backoff: 1E300,
+        };
+        assert_eq!(retry.delay_after(1000), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_retry_gives_way_to_stops() {
+        let stops_queued = AtomicUsize::new(1);
+        let start = Instant::now();
+        assert!(!sleep_unless_stopping(
+            Duration::from_secs(1),
+            &stops_queued
+        ));
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        stops_queued.store(0, Ordering::Release);
+        assert!(sleep_unless_stopping(
+            Duration::from_millis(1),
+            &stops_queued
+        ));
+    }
+}
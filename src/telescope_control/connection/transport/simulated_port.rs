@@ -0,0 +1,489 @@
+//! A SynScan motor controller simulated in memory, for running the driver and its tests without a mount.
+//! Chosen with the com path "sim://", or "sim://<mount code in hex>" to pose as another mount.
+//! It's presented as a serial port, so everything above the port runs as it would on the real thing.
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use super::consts::*;
+
+/// The protocol's positions are offset so that power on is halfway through the 24 bit range
+const POSITION_OFFSET: i64 = 0x800000;
+/// The mount code of a Star Adventurer, which is simulated when none is given
+pub const DEFAULT_MOUNT_CODE: u8 = 0x0A;
+const FIRMWARE_VERSION: (u8, u8) = (3, 6);
+
+// The controller's error codes
+const UNKNOWN_COMMAND: u8 = 0;
+const COMMAND_LENGTH: u8 = 1;
+const MOTOR_NOT_STOPPED: u8 = 2;
+const INVALID_CHARACTER: u8 = 3;
+
+/// One motor channel
+#[derive(Debug)]
+struct Axis {
+    position: f64, // Counts from power on
+    step_period: u32,
+    goto_target: f64,
+    tracking: bool, // Moves at the step period until stopped. Otherwise goes to the target
+    fast: bool,     // Moves the high speed ratio in counts per step
+    ccw: bool,
+    running: bool,
+}
+
+impl Axis {
+    fn new() -> Self {
+        Self {
+            position: 0.,
+            step_period: SIDEREAL_PERIOD,
+            goto_target: 0.,
+            tracking: true,
+            fast: false,
+            ccw: false,
+            running: false,
+        }
+    }
+}
+
+/// The state of the simulated controller
+struct Controller {
+    axes: [Axis; 2],
+    counts_per_revolution: u32,
+    timer_frequency: u32,
+    high_speed_ratio: u32,
+    mount_code: u8,
+    updated: Instant, // Motion is worked out up to here
+}
+
+impl Controller {
+    fn new(mount_code: u8) -> Self {
+        let counts_per_revolution = match mount_code {
+            0xA5 => 3_628_800, // AZ-GTi
+            _ => 2_764_800,    // Star Adventurer
+        };
+        Self {
+            axes: [Axis::new(), Axis::new()],
+            counts_per_revolution,
+            // Sidereal at the Star Adventurer's sidereal step period
+            timer_frequency: (SIDEREAL_PERIOD as f64 * counts_per_revolution as f64 / 86_164.0905)
+                .round() as u32,
+            high_speed_ratio: 1,
+            mount_code,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Counts per second while tracking
+    fn tracking_speed(&self, axis: &Axis) -> f64 {
+        let ratio = if axis.fast { self.high_speed_ratio } else { 1 };
+        self.timer_frequency as f64 / axis.step_period.max(1) as f64 * ratio as f64
+    }
+
+    /// Counts per second while going to a target
+    fn goto_speed(&self) -> f64 {
+        SLEW_SPEED_AGAINST_TRACKING * self.counts_per_revolution as f64 / 360.
+    }
+
+    /// Moves the motors on to now
+    fn advance(&mut self) {
+        let now = Instant::now();
+        let elapsed = (now - self.updated).as_secs_f64();
+        self.updated = now;
+        let goto_step = self.goto_speed() * elapsed;
+        for i in 0..self.axes.len() {
+            let tracking_step = self.tracking_speed(&self.axes[i]) * elapsed;
+            let axis = &mut self.axes[i];
+            if !axis.running {
+                continue;
+            }
+            if axis.tracking {
+                axis.position += if axis.ccw {
+                    -tracking_step
+                } else {
+                    tracking_step
+                };
+            } else if (axis.goto_target - axis.position).abs() <= goto_step {
+                axis.position = axis.goto_target;
+                axis.running = false;
+            } else {
+                axis.position += goto_step.copysign(axis.goto_target - axis.position);
+            }
+        }
+    }
+
+    /// Carries out one command, e.g. "j1", returning the response without its framing
+    fn respond(&mut self, command: &str) -> Result<String, u8> {
+        self.advance();
+        let mut chars = command.chars();
+        let (code, channel) = match (chars.next(), chars.next()) {
+            (Some(code), Some(channel)) => (code, channel),
+            _ => return Err(COMMAND_LENGTH),
+        };
+        let data = chars.as_str();
+        let axes = match channel {
+            '1' => 0..1,
+            '2' => 1..2,
+            '3' => 0..2,
+            _ => return Err(INVALID_CHARACTER),
+        };
+        let first = axes.start;
+
+        let response = match code {
+            'a' => encode_24(self.counts_per_revolution),
+            'b' => encode_24(self.timer_frequency),
+            'c' => encode_24(0),
+            'd' | 'i' => encode_24(self.axes[first].step_period),
+            'e' => format!(
+                "{:02X}{:02X}{:02X}",
+                FIRMWARE_VERSION.0, FIRMWARE_VERSION.1, self.mount_code
+            ),
+            'f' => {
+                let axis = &self.axes[first];
+                let mode = axis.tracking as u8 | ((axis.ccw as u8) << 1) | ((axis.fast as u8) << 2);
+                format!("{:X}{:X}1", mode, axis.running as u8)
+            }
+            'g' => format!("{:02X}", self.high_speed_ratio),
+            'h' => encode_24(axis_counts(self.axes[first].goto_target) as u32),
+            'j' => encode_24(axis_counts(self.axes[first].position) as u32),
+            's' => encode_24(0),
+            'q' => encode_24(0),
+            _ => {
+                for i in axes {
+                    self.set(i, code, data)?;
+                }
+                String::new()
+            }
+        };
+        Ok(response)
+    }
+
+    fn set(&mut self, i: usize, code: char, data: &str) -> Result<(), u8> {
+        let axis = &mut self.axes[i];
+        match code {
+            'E' => {
+                if axis.running {
+                    return Err(MOTOR_NOT_STOPPED);
+                }
+                axis.position = (decode_24(data)? as i64 - POSITION_OFFSET) as f64;
+            }
+            'G' => {
+                if axis.running {
+                    return Err(MOTOR_NOT_STOPPED);
+                }
+                let mut digits = data.chars().map(|c| c.to_digit(16));
+                let (mode, direction) = match (digits.next(), digits.next()) {
+                    (Some(Some(mode)), Some(Some(direction))) => (mode, direction),
+                    _ => return Err(INVALID_CHARACTER),
+                };
+                axis.tracking = mode & 1 != 0;
+                // The second bit means fast for tracking, but slow for gotos
+                axis.fast = (mode & 2 != 0) == axis.tracking;
+                axis.ccw = direction & 1 != 0;
+            }
+            'H' => {
+                let increment = decode_24(data)? as f64;
+                axis.goto_target = axis.position + if axis.ccw { -increment } else { increment };
+            }
+            'I' => {
+                axis.step_period = decode_24(data)?;
+            }
+            'J' => {
+                axis.running = true;
+            }
+            'K' | 'L' => {
+                axis.running = false;
+            }
+            'S' => {
+                axis.goto_target = (decode_24(data)? as i64 - POSITION_OFFSET) as f64;
+            }
+            // Initialization done, brake steps and point, autoguide speed, switch, LED and extended settings
+            'F' | 'M' | 'U' | 'P' | 'O' | 'V' | 'W' => {}
+            _ => return Err(UNKNOWN_COMMAND),
+        }
+        Ok(())
+    }
+}
+
+/// The position as sent by the protocol
+fn axis_counts(position: f64) -> i64 {
+    (position.round() as i64 + POSITION_OFFSET) & 0xFFFFFF
+}
+
+/// Least significant byte first, as the protocol sends 24 bit values
+fn encode_24(value: u32) -> String {
+    format!(
+        "{:02X}{:02X}{:02X}",
+        value & 0xFF,
+        (value >> 8) & 0xFF,
+        (value >> 16) & 0xFF
+    )
+}
+
+fn decode_24(data: &str) -> Result<u32, u8> {
+    if data.len() != 6 {
+        return Err(COMMAND_LENGTH);
+    }
+    let bytes = u32::from_str_radix(data, 16).map_err(|_| INVALID_CHARACTER)?;
+    Ok((bytes >> 16) | (bytes & 0xFF00) | ((bytes & 0xFF) << 16))
+}
+
+/// A simulated controller, shared by the ports opened on it.
+/// Kept by the connection builder, so its motors carry on across reconnects like a real controller's.
+#[derive(Clone, Default)]
+pub struct SimulatedMount(Arc<Mutex<Option<Controller>>>);
+
+impl Debug for SimulatedMount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimulatedMount").finish_non_exhaustive()
+    }
+}
+
+impl SimulatedMount {
+    /// Powers the controller on the first time
+    pub fn open(&self, mount_code: Option<u8>, timeout: Duration) -> SimulatedPort {
+        self.0
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| Controller::new(mount_code.unwrap_or(DEFAULT_MOUNT_CODE)));
+        SimulatedPort {
+            mount: self.clone(),
+            timeout,
+            command: Vec::new(),
+            response: Vec::new(),
+        }
+    }
+}
+
+pub struct SimulatedPort {
+    mount: SimulatedMount,
+    timeout: Duration,
+    command: Vec<u8>,  // Written, but not yet ended with a carriage return
+    response: Vec<u8>, // To the last command, not yet read
+}
+
+impl SimulatedPort {
+    fn unsupported<T>() -> serialport::Result<T> {
+        Err(serialport::Error::new(
+            serialport::ErrorKind::InvalidInput,
+            "Not supported by the simulator",
+        ))
+    }
+}
+
+impl Read for SimulatedPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.response.is_empty() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        let len = buf.len().min(self.response.len());
+        buf[..len].copy_from_slice(&self.response[..len]);
+        self.response.drain(..len);
+        Ok(len)
+    }
+}
+
+impl Write for SimulatedPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            if byte != b'\r' {
+                self.command.push(byte);
+                continue;
+            }
+            let command = String::from_utf8_lossy(&self.command).into_owned();
+            self.command.clear();
+            // Anything before the start of a command is noise
+            let command = match command.rfind(':') {
+                Some(start) => &command[start + 1..],
+                None => continue,
+            };
+            let response = self
+                .mount
+                .0
+                .lock()
+                .unwrap()
+                .as_mut()
+                .expect("Simulated port used before it was opened")
+                .respond(command);
+            let response = match response {
+                Ok(data) => format!("={}\r", data),
+                Err(code) => format!("!{}\r", code),
+            };
+            self.response.extend_from_slice(response.as_bytes());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SerialPort for SimulatedPort {
+    fn name(&self) -> Option<String> {
+        Some("sim://".to_string())
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(BAUD_RATE)
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(DataBits::Eight)
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(FlowControl::None)
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(Parity::None)
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(StopBits::One)
+    }
+
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn set_baud_rate(&mut self, _baud_rate: u32) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_data_bits(&mut self, _data_bits: DataBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, _flow_control: FlowControl) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_parity(&mut self, _parity: Parity) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, _stop_bits: StopBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, _level: bool) -> serialport::Result<()> {
+        Self::unsupported()
+    }
+
+    fn write_data_terminal_ready(&mut self, _level: bool) -> serialport::Result<()> {
+        Self::unsupported()
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Self::unsupported()
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Self::unsupported()
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Self::unsupported()
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Self::unsupported()
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self.response.len() as u32)
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+
+    fn clear(&self, _buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        // Responses are only ever to the last command, so there's nothing late to drop
+        Ok(())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(SimulatedPort {
+            mount: self.mount.clone(),
+            timeout: self.timeout,
+            command: Vec::new(),
+            response: Vec::new(),
+        }))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        Self::unsupported()
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        Self::unsupported()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(port: &mut SimulatedPort, command: &str) -> String {
+        port.write_all(format!(":{}\r", command).as_bytes())
+            .unwrap();
+        let mut buf = [0; 16];
+        let len = port.read(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(encode_24(0x123456), "563412");
+        assert_eq!(decode_24("563412"), Ok(0x123456));
+        assert_eq!(decode_24("5634"), Err(COMMAND_LENGTH));
+    }
+
+    #[test]
+    fn test_inquiries() {
+        let mut port = SimulatedMount::default().open(None, Duration::from_millis(50));
+        assert_eq!(command(&mut port, "a1"), "=00302A\r"); // 2_764_800
+        assert_eq!(command(&mut port, "j1"), "=000080\r");
+        assert_eq!(command(&mut port, "e1"), "=03060A\r");
+        assert_eq!(command(&mut port, "f1"), "=101\r");
+        assert_eq!(command(&mut port, "z1"), "!0\r");
+    }
+
+    #[test]
+    fn test_motion() {
+        let mount = SimulatedMount::default();
+        let mut port = mount.open(None, Duration::from_millis(50));
+        // Fast tracking backwards
+        assert_eq!(command(&mut port, "G131"), "=\r");
+        assert_eq!(command(&mut port, "I1000100"), "=\r");
+        assert_eq!(command(&mut port, "J1"), "=\r");
+        assert_eq!(command(&mut port, "f1"), "=711\r");
+        // Mode can't change while moving
+        assert_eq!(command(&mut port, "G100"), "!2\r");
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(command(&mut port, "K1"), "=\r");
+        let moved = command(&mut port, "j1");
+        assert!(decode_24(&moved[1..7]).unwrap() < 0x800000);
+
+        // A goto to 0x800010 finishes there, and the state carries over to a new port
+        assert_eq!(command(&mut port, "E1000080"), "=\r");
+        assert_eq!(command(&mut port, "G100"), "=\r");
+        assert_eq!(command(&mut port, "S1100080"), "=\r");
+        assert_eq!(command(&mut port, "J1"), "=\r");
+        std::thread::sleep(Duration::from_millis(50));
+        let mut port = mount.open(None, Duration::from_millis(50));
+        assert_eq!(command(&mut port, "f1"), "=401\r");
+        assert_eq!(command(&mut port, "j1"), "=100080\r");
+    }
+}
@@ -0,0 +1,118 @@
+use serde::Serialize;
+
+use super::consts::*;
+use crate::util::*;
+
+/// The stepping parameters the motor controller reports
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct ControllerParameters {
+    pub counts_per_revolution: u32,
+    pub timer_frequency: u32, // Hz. A step is taken every step period ticks of this
+    pub high_speed_ratio: u32, // Counts per step in high speed mode
+}
+
+/// The speeds the RA axis can be moved at continuously, in deg/sec
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct SpeedCapabilities {
+    pub parameters: Option<ControllerParameters>, // None if the controller didn't report them
+    pub min_speed: Degrees,
+    pub max_speed: Degrees,
+}
+
+impl SpeedCapabilities {
+    pub fn from_parameters(parameters: ControllerParameters) -> Self {
+        let speed_at_period = |period: u32| {
+            parameters.timer_frequency as f64 / period as f64 * 360.
+                / parameters.counts_per_revolution as f64
+        };
+        Self {
+            parameters: Some(parameters),
+            min_speed: speed_at_period(MAX_STEP_PERIOD),
+            max_speed: speed_at_period(MIN_STEP_PERIOD),
+        }
+    }
+}
+
+impl SpeedCapabilities {
+    /// How much faster this controller goes than a Star Adventurer
+    fn scale(&self) -> f64 {
+        self.max_speed / FALLBACK_MAX_SPEED
+    }
+
+    /// Goto speeds with and against tracking in deg/sec.
+    /// Those measured on a Star Adventurer, scaled by how much faster this controller can go.
+    pub fn goto_speeds(&self) -> (Degrees, Degrees) {
+        (
+            SLEW_SPEED_WITH_TRACKING * self.scale(),
+            SLEW_SPEED_AGAINST_TRACKING * self.scale(),
+        )
+    }
+
+    /// The ranges of speeds the controller can hold, slowest first:
+    /// the continuous slow speeds, then each of the high speeds on its own.
+    /// Speeds in between can't be honored.
+    pub fn rate_ranges(&self) -> Vec<(Degrees, Degrees)> {
+        let (with_tracking, against_tracking) = self.goto_speeds();
+        let mut ranges = vec![(self.min_speed, SLOW_GOTO_SPEED * self.scale())];
+        for high_speed in [
+            with_tracking.min(against_tracking),
+            with_tracking.max(against_tracking),
+        ] {
+            ranges.push((high_speed, high_speed));
+        }
+        ranges
+    }
+}
+
+impl Default for SpeedCapabilities {
+    /// The speeds measured on a Star Adventurer
+    fn default() -> Self {
+        Self {
+            parameters: None,
+            min_speed: FALLBACK_MIN_SPEED,
+            max_speed: FALLBACK_MAX_SPEED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn test_speed_capabilities() {
+        // Sidereal at the Star Adventurer's sidereal period
+        let speed = SpeedCapabilities::from_parameters(ControllerParameters {
+            counts_per_revolution: 2_764_800,
+            timer_frequency: (SIDEREAL_PERIOD as f64 * 2_764_800. / 86_164.0905).round() as u32,
+            high_speed_ratio: 1,
+        });
+        assert_float_relative_eq!(speed.min_speed, FALLBACK_MIN_SPEED, 0.01);
+        assert_float_relative_eq!(speed.max_speed, FALLBACK_MAX_SPEED, 0.01);
+    }
+
+    #[test]
+    fn test_rate_ranges() {
+        let ranges = SpeedCapabilities::default().rate_ranges();
+        assert_eq!(
+            ranges,
+            vec![
+                (FALLBACK_MIN_SPEED, SLOW_GOTO_SPEED),
+                (SLEW_SPEED_WITH_TRACKING, SLEW_SPEED_WITH_TRACKING),
+                (SLEW_SPEED_AGAINST_TRACKING, SLEW_SPEED_AGAINST_TRACKING),
+            ]
+        );
+
+        // A controller twice as fast
+        let ranges = SpeedCapabilities {
+            parameters: None,
+            min_speed: FALLBACK_MIN_SPEED,
+            max_speed: FALLBACK_MAX_SPEED * 2.,
+        }
+        .rate_ranges();
+        assert_eq!(ranges.len(), 3);
+        assert_float_relative_eq!(ranges[0].1, SLOW_GOTO_SPEED * 2.);
+        assert_float_relative_eq!(ranges[2].0, SLEW_SPEED_AGAINST_TRACKING * 2.);
+    }
+}
@@ -0,0 +1,218 @@
+//! Feeds a motor trace back into the simulated controller, to see what the commands do to a mount
+//! that behaves as the driver expects, and where its answers part from the ones recorded.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use synscan::serialport::SPSerialPort;
+use synscan::util::{SynScanError, SynScanResult};
+use synscan::{AutoGuideSpeed, Direction, MotorController};
+
+use super::consts::*;
+use super::motor_trace::TraceEntry;
+use super::simulated_port::SimulatedMount;
+use super::{FirmwareInfo, MotorAxis, MC};
+use crate::util::AutoGuideSpeedExt;
+
+/// A command the simulator answered differently than the mount did
+#[derive(Debug)]
+pub struct Divergence {
+    pub line: usize, // Of the trace file, from 1
+    pub command: String,
+    pub recorded: String,
+    pub replayed: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub port: String, // Whose commands were replayed
+    pub replayed: usize,
+    pub skipped: Vec<(usize, String)>, // Lines with commands the replay doesn't know
+    pub divergences: Vec<Divergence>,
+}
+
+/// Replays the commands sent to `port`, or to the first port in the trace, in order.
+/// They're spaced as recorded, `speed` times faster, or sent back to back if `speed` is 0.
+/// Only first tries are sent, as retries were the same command again.
+pub fn replay(
+    path: &str,
+    port: Option<&str>,
+    mount_code: Option<u8>,
+    speed: f64,
+) -> io::Result<ReplayReport> {
+    let entries = read_trace(path)?;
+    let port = match port
+        .map(str::to_string)
+        .or_else(|| entries.first().map(|(_, entry)| entry.port.clone()))
+    {
+        Some(port) => port,
+        None => return Ok(ReplayReport::default()),
+    };
+
+    let timeout = Duration::from_millis(DEFAULT_TIMEOUT_MILLIS);
+    let simulated = SimulatedMount::default().open(mount_code, timeout);
+    let mc = MotorController::new(SPSerialPort::new(Box::new(simulated)))
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+
+    let mut report = ReplayReport {
+        port: port.clone(),
+        ..Default::default()
+    };
+    let (start, mut first_sent) = (Instant::now(), None);
+    for (line, entry) in entries {
+        if entry.port != port || entry.try_no != 1 {
+            continue;
+        }
+        let first = *first_sent.get_or_insert(entry.time);
+        if 0. < speed {
+            let offset = (entry.time - first).to_std().unwrap_or_default();
+            thread::sleep(offset.div_f64(speed).saturating_sub(start.elapsed()));
+        }
+
+        let replayed = match run(&mc, &entry.command) {
+            Some(replayed) => replayed,
+            None => {
+                report.skipped.push((line, entry.command));
+                continue;
+            }
+        };
+        report.replayed += 1;
+        if replayed != entry.response {
+            report.divergences.push(Divergence {
+                line,
+                command: entry.command,
+                recorded: entry.response,
+                replayed,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// The entries with their line numbers
+fn read_trace(path: &str) -> io::Result<Vec<(usize, TraceEntry)>> {
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Line {}: {}", i + 1, e))
+        })?;
+        entries.push((i + 1, entry));
+    }
+    Ok(entries)
+}
+
+/// Makes the call the command was traced as by MC, with its result formatted as the trace records it.
+/// None for a command it doesn't know.
+fn run(mc: &MotorController<SPSerialPort>, command: &str) -> Option<String> {
+    let (axis, command) = match command.strip_prefix("dec ") {
+        Some(command) => (MotorAxis::Dec, command),
+        None => (MotorAxis::Ra, command),
+    };
+    let channel = axis.channel();
+    let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+
+    let response = match name {
+        "set_tracking_motion_mode" => {
+            let direction = match arg {
+                "Clockwise" => Direction::Clockwise,
+                "CounterClockwise" => Direction::CounterClockwise,
+                _ => return None,
+            };
+            format!(
+                "{:?}",
+                mc.set_tracking_motion_mode(channel, false, direction)
+            )
+        }
+        "set_motion_rate_degrees" => format!(
+            "{:?}",
+            mc.set_motion_rate_degrees(channel, arg.parse().ok()?)
+        ),
+        "start_motion" => format!("{:?}", mc.start_motion(channel)),
+        "stop_motion" => format!("{:?}", mc.stop_motion(channel)),
+        "inquire_pos_degrees" => format!("{:?}", mc.inquire_pos_degrees(channel)),
+        "set_autoguide_speed" => {
+            let speed = AutoGuideSpeed::ALL
+                .into_iter()
+                .find(|speed| format!("{:?}", speed) == arg)?;
+            format!("{:?}", mc.set_autoguide_speed(channel, speed))
+        }
+        "set_goto_motion_mode" => format!("{:?}", mc.set_goto_motion_mode(channel, true)),
+        "set_goto_target_degrees" => format!(
+            "{:?}",
+            mc.set_goto_target_degrees(channel, arg.parse().ok()?)
+        ),
+        "inquire_motion_rate_degrees" => {
+            format!("{:?}", mc.inquire_motion_rate_degrees(channel))
+        }
+        "inquire_status" => format!("{:?}", mc.inquire_status(channel)),
+        "inquire_parameters" => format!("{:?}", optional(MC::read_parameters(mc, axis))),
+        "inquire_motor_board_version" => format!(
+            "{:?}",
+            optional(
+                mc.inquire_motor_board_version(channel)
+                    .map(FirmwareInfo::from_board_version)
+            )
+        ),
+        "inquire_goto_target_degrees" => {
+            format!("{:?}", mc.inquire_goto_target_degrees(channel))
+        }
+        _ => return None,
+    };
+    Some(response)
+}
+
+/// As MC traces the inquiries some boards don't answer: None unless the link failed
+fn optional<T>(result: SynScanResult<T>) -> SynScanResult<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(SynScanError::CommunicationError(e)) => Err(SynScanError::CommunicationError(e)),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::motor_trace::MotorTrace;
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_replay() {
+        let path = std::env::temp_dir().join(format!("sa-trace-{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let trace = MotorTrace::open(&path, "/dev/ttyUSB0").unwrap();
+        let other = MotorTrace::open(&path, "/dev/ttyUSB1").unwrap();
+        let record = |trace: &MotorTrace, command: &str, try_no: u32, response: &str| {
+            trace.record(&TraceEntry {
+                time: Utc::now(),
+                port: trace.port().to_string(),
+                command: command.to_string(),
+                try_no,
+                response: response.to_string(),
+                ok: response.starts_with("Ok"),
+                millis: 10.,
+            })
+        };
+        record(&trace, "set_tracking_motion_mode Clockwise", 1, "Ok(())");
+        record(&trace, "set_motion_rate_degrees 0.1", 1, "Ok(())");
+        record(&trace, "start_motion", 2, "Ok(())"); // A retry
+        record(&other, "start_motion", 1, "Ok(())");
+        record(&trace, "dec stop_motion", 1, "Err(Timeout)");
+        record(&trace, "reset", 1, "Ok(())");
+
+        let report = replay(&path, None, None, 0.).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.port, "/dev/ttyUSB0");
+        assert_eq!(report.replayed, 3);
+        assert_eq!(report.skipped, vec![(6, "reset".to_string())]);
+        assert_eq!(report.divergences.len(), 1);
+        assert_eq!(report.divergences[0].line, 5);
+        assert_eq!(report.divergences[0].replayed, "Ok(())");
+    }
+}
@@ -1,5 +1,5 @@
 use super::*;
-use crate::telescope_control::connection::motor::locked::HasMotor;
+use crate::telescope_control::connection::transport::locked::HasMotor;
 use async_trait::async_trait;
 use std::time::Duration;
 use synscan::DriveMode;
@@ -31,7 +31,8 @@ pub trait Waiter {
         }
     }
 
-    async fn wait<L, T>(&self, locker: L) -> MotorResult<()>
+    /// Checks at no less than `priority`, so a guide pulse's confirmation isn't stuck behind routine inquiries
+    async fn wait<L, T>(&self, locker: L, priority: Priority) -> MotorResult<()>
     where
         L: 'static + RWLockable<T> + Clone + Send + Sync,
         T: HasMotor + Sync,
@@ -42,7 +43,7 @@ pub trait Waiter {
             check_interval.tick().await;
             let ml = locker.read().await;
             let motor = ml.get()?;
-            let result = self.check(&motor.mc).await;
+            let result = self.check(&motor.mc.at(priority)).await;
             match result {
                 Ok(true) => {
                     return Ok(());
@@ -90,6 +91,8 @@ impl Waiter for RateWaiter {
     }
 
     async fn check(&self, mc: &MC) -> MotorResult<bool> {
-        Ok((mc.inquire_rate().await? - self.0).abs() < consts::ALLOWABLE_RATE_DIFFERENCE)
+        let allowable =
+            consts::ALLOWABLE_RATE_DIFFERENCE.max(self.0 * consts::ALLOWABLE_RATE_FRACTION);
+        Ok((mc.inquire_rate().await? - self.0).abs() < allowable)
     }
 }
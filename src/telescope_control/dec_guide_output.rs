@@ -0,0 +1,111 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::{DecGuideOutputKind, DecGuideOutputSettings};
+use crate::util::*;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+
+/// Issues North/South guide pulses through an external guide input on the dec motor
+/// rather than the serial protocol, which only drives RA
+pub struct DecGuideOutput {
+    north: GpioPin,
+    south: GpioPin,
+    hardware_rate: Degrees,     // What the dec motor's controller moves at
+    guide_rate: Mutex<Degrees>, // Slower rates are made by shortening pulses
+    min_pulse: Duration,
+    relay_delay: Duration,
+    guiding: AtomicBool,
+}
+
+impl DecGuideOutput {
+    /// None if no output is configured
+    pub fn new(settings: &DecGuideOutputSettings) -> Option<Self> {
+        settings.output.as_ref().map(|kind| match kind {
+            DecGuideOutputKind::GpioSt4 {
+                north_pin,
+                south_pin,
+                active_low,
+                min_pulse_millis,
+                relay_delay_millis,
+            } => Self {
+                north: GpioPin::new(*north_pin, *active_low),
+                south: GpioPin::new(*south_pin, *active_low),
+                hardware_rate: settings.guide_rate,
+                guide_rate: Mutex::new(settings.guide_rate),
+                min_pulse: Duration::from_millis(*min_pulse_millis),
+                relay_delay: Duration::from_millis(*relay_delay_millis),
+                guiding: AtomicBool::new(false),
+            },
+        })
+    }
+
+    pub fn guide_rate(&self) -> Degrees {
+        *self.guide_rate.lock().unwrap()
+    }
+
+    /// Rates up to the hardware rate can be made exactly. Returns the rate applied.
+    pub fn set_guide_rate(&self, rate: Degrees) -> ASCOMResult<Degrees> {
+        if !(0. < rate && rate <= self.hardware_rate * 1.1) {
+            return Err(ASCOMError::invalid_value(format_args!(
+                "Declination guide rate must be above 0 and at most {}",
+                self.hardware_rate
+            )));
+        }
+        let rate = rate.min(self.hardware_rate);
+        *self.guide_rate.lock().unwrap() = rate;
+        Ok(rate)
+    }
+
+    pub fn is_guiding(&self) -> bool {
+        self.guiding.load(Ordering::SeqCst)
+    }
+
+    /// Holds the north or south output active for long enough to move as far as the duration at the guide rate would
+    /// Only one pulse runs at a time so both relays are never closed together
+    pub async fn pulse(&self, north: bool, duration: Duration) -> ASCOMResult<()> {
+        let duration = duration.mul_f64(self.guide_rate() / self.hardware_rate);
+        if duration < self.min_pulse {
+            tracing::debug!(
+                "Dropping a {:?} dec pulse, shorter than the relays allow",
+                duration
+            );
+            return Ok(());
+        }
+        if self
+            .guiding
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(ASCOMError::invalid_operation(
+                "Already pulse guiding in declination",
+            ));
+        }
+        let (active, inactive) = if north {
+            (&self.north, &self.south)
+        } else {
+            (&self.south, &self.north)
+        };
+
+        let result = async {
+            active.export().await?;
+            inactive.export().await?;
+            inactive.set_active(false).await?;
+            active.set_active(true).await?;
+            tokio::time::sleep(duration + self.relay_delay).await;
+            Ok::<_, io::Error>(())
+        }
+        .await;
+        // Always try to release the relay, even if something failed partway
+        let release = active.set_active(false).await;
+        self.guiding.store(false, Ordering::SeqCst);
+
+        result.and(release).map_err(|e| {
+            ASCOMError::new(
+                ASCOMErrorCode::new_for_driver(6),
+                format_args!("Error driving the declination guide output: {}", e),
+            )
+        })
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt::{Display, Formatter};
+
+use ascom_alpaca::api::SideOfPier;
+use serde::Serialize;
+
+use crate::config::DeclinationKnob;
+use crate::util::*;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KnobDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl KnobDirection {
+    fn reverse(self) -> Self {
+        match self {
+            Self::Clockwise => Self::CounterClockwise,
+            Self::CounterClockwise => Self::Clockwise,
+        }
+    }
+}
+
+/// What the user has to do with the declination knob to complete a slew
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct DeclinationSlewPlan {
+    pub dec_change: Degrees, // Positive toward the north in the frame of the starting pier side
+    pub turns: f64,          // Still to turn
+    pub turns_done: f64,     // Reported turned so far
+    pub knob_direction: Option<KnobDirection>, // None if there's nothing to do or the pier side is unknown
+    pub toward_pole: bool,
+    pub meridian_flip: bool,
+}
+
+impl DeclinationSlewPlan {
+    pub fn new(
+        dec_change: Degrees,
+        meridian_flip: bool,
+        pier_side: SideOfPier,
+        in_north: bool,
+        knob: &DeclinationKnob,
+    ) -> Self {
+        // Moving north goes toward the visible pole only in the northern hemisphere
+        let toward_pole = (0. < dec_change) == in_north;
+
+        let knob_direction = if dec_change == 0. {
+            None
+        } else {
+            let east_direction = if toward_pole == knob.clockwise_toward_pole {
+                KnobDirection::Clockwise
+            } else {
+                KnobDirection::CounterClockwise
+            };
+
+            // The dec axis is turned end over end after a meridian flip
+            match pier_side {
+                SideOfPier::East => Some(east_direction),
+                SideOfPier::West => Some(east_direction.reverse()),
+                SideOfPier::Unknown => None,
+            }
+        };
+
+        Self {
+            dec_change,
+            turns: dec_change.abs() / knob.degrees_per_turn,
+            turns_done: 0.,
+            knob_direction,
+            toward_pole,
+            meridian_flip,
+        }
+    }
+}
+
+/// How far a dec motor turns for a change in dec, taking positive as toward the pole with the scope east of the pier.
+/// None if the pier side is unknown.
+pub fn dec_motor_change(
+    dec_change: Degrees,
+    pier_side: SideOfPier,
+    in_north: bool,
+) -> Option<Degrees> {
+    let toward_pole = if in_north { dec_change } else { -dec_change };
+    // The dec axis is turned end over end after a meridian flip
+    match pier_side {
+        SideOfPier::East => Some(toward_pole),
+        SideOfPier::West => Some(-toward_pole),
+        SideOfPier::Unknown => None,
+    }
+}
+
+impl Display for DeclinationSlewPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let compass = if self.dec_change < 0. {
+            "SOUTH"
+        } else {
+            "NORTH"
+        };
+        match self.knob_direction {
+            Some(KnobDirection::Clockwise) => write!(
+                f,
+                "TURN DECLINATION KNOB {:.2} TURNS CLOCKWISE ({})",
+                self.turns, compass
+            ),
+            Some(KnobDirection::CounterClockwise) => write!(
+                f,
+                "TURN DECLINATION KNOB {:.2} TURNS COUNTERCLOCKWISE ({})",
+                self.turns, compass
+            ),
+            None => write!(
+                f,
+                "TURN DECLINATION KNOB {:.2} TURNS TO THE {}",
+                self.turns, compass
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_knob_direction() {
+        let knob = DeclinationKnob::default();
+
+        let plan = DeclinationSlewPlan::new(5.914, false, SideOfPier::East, true, &knob);
+        assert_eq!(plan.knob_direction, Some(KnobDirection::Clockwise));
+        assert!((plan.turns - 2.).abs() < 1E-6);
+
+        // Same dec change from the other side of the pier turns the other way
+        let plan = DeclinationSlewPlan::new(5.914, false, SideOfPier::West, true, &knob);
+        assert_eq!(plan.knob_direction, Some(KnobDirection::CounterClockwise));
+
+        // North is away from the pole in the south
+        let plan = DeclinationSlewPlan::new(5.914, false, SideOfPier::East, false, &knob);
+        assert_eq!(plan.knob_direction, Some(KnobDirection::CounterClockwise));
+
+        let plan = DeclinationSlewPlan::new(-1., true, SideOfPier::Unknown, true, &knob);
+        assert_eq!(plan.knob_direction, None);
+
+        let plan = DeclinationSlewPlan::new(0., false, SideOfPier::East, true, &knob);
+        assert_eq!(plan.knob_direction, None);
+    }
+
+    #[test]
+    fn test_dec_motor_change() {
+        assert_eq!(dec_motor_change(5., SideOfPier::East, true), Some(5.));
+        assert_eq!(dec_motor_change(5., SideOfPier::West, true), Some(-5.));
+        assert_eq!(dec_motor_change(5., SideOfPier::East, false), Some(-5.));
+        assert_eq!(dec_motor_change(5., SideOfPier::Unknown, true), None);
+    }
+}
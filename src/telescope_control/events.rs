@@ -0,0 +1,198 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::SafeAction;
+use crate::telescope_control::commands::slew::SlewTarget;
+use crate::telescope_control::dec_knob::DeclinationSlewPlan;
+use crate::telescope_control::settle_timer::SettleSource;
+use crate::util::*;
+
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Something clients may want to react to without polling
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Guider calibrations are invalid after this
+    GuideRateChanged {
+        guide_speed_fraction: f64,
+        guide_rate_right_ascension: Degrees,
+        guide_rate_declination: Degrees,
+    },
+    /// Something else started a goto on the controller, so the driver stopped it. Tracking is off.
+    UnexpectedGotoStopped,
+    /// The controller kept moving at a rate other than the one commanded, so the driver stopped it. Tracking is off.
+    RunawayMotionStopped {
+        commanded_rate: Degrees, // Clockwise degrees/sec
+        reported_rate: Degrees,
+    },
+    /// The motor was still moving when the driver connected, e.g. from a pulse that never finished, so it was stopped
+    LeftoverMotionStopped {
+        rate: Degrees, // Clockwise degrees/sec
+    },
+    /// Tracking has carried the mount within `flip-before-limit` of the west limit
+    TrackingLimitReached {
+        hour_angle_past: Hours, // How far past that point, in hours of hour angle
+        stopped_tracking: bool,
+    },
+    /// No Alpaca client was heard from for the idle-safety timeout, so the idle action was applied
+    ClientsIdle {
+        idle_secs: f64,
+        action: SafeAction,
+    },
+    /// MoveAxis motion or a guide pulse was about to carry the mount past the east or west limit, so the driver stopped it
+    MountLimitReached {
+        mech_hour_angle: Hours,
+        stopped: String, // "move_axis" or "pulse_guide"
+    },
+    SlewStarted {
+        target: SlewTarget,
+        estimated_secs: f64, // RA motion only
+    },
+    /// The RA motion of a slew ended. Settling and the declination knob may still be to come.
+    SlewFinished {
+        completed: bool, // False if aborted or failed
+    },
+    /// Settling started or got longer, including the vibration guard. Hold the shutter until `settling_until`.
+    Settling {
+        source: SettleSource,
+        settling_until: DateTime<Utc>,
+    },
+    /// The user has to turn the declination knob to finish a slew
+    DeclinationSlewRequired {
+        plan: DeclinationSlewPlan,
+    },
+    /// Some of the knob turns were reported done. The plan has what's left.
+    DeclinationSlewProgress {
+        plan: DeclinationSlewPlan,
+    },
+    GuidePulseStarted {
+        direction: String,
+        duration_millis: u32,
+    },
+    GuidePulseEnded {
+        direction: String,
+        succeeded: bool,
+    },
+    ParkCompleted,
+    /// A motor error disconnected the driver
+    ConnectionLost {
+        reason: String,
+    },
+    /// The tracked position has set below the horizon profile or min-altitude
+    BelowHorizon {
+        altitude: Degrees,
+        azimuth: Degrees,
+        min_altitude: Degrees,
+        stopped_tracking: bool,
+    },
+    /// A scheduled target was slewed to and is being tracked
+    ScheduledTargetReached {
+        name: String,
+        right_ascension: Hours,
+        declination: Degrees,
+    },
+    ScheduledTargetFailed {
+        name: String,
+        reason: String,
+    },
+    /// A scheduled target wasn't started by its latest start, so it was dropped
+    ScheduledTargetExpired {
+        name: String,
+    },
+    /// The config file was edited. Only the applied settings have taken effect.
+    ConfigReloaded {
+        applied: Vec<String>,  // As "section.key"
+        rejected: Vec<String>, // Need a restart
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventMessage {
+    pub time: DateTime<Utc>,
+    pub device_number: usize, // Of the mount it's from. Only set on the bus from fan_in
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Fans events out to the SSE stream and webhooks
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<EventMessage>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+
+    pub fn publish(&self, event: Event) {
+        tracing::debug!("Publishing {:?}", event);
+        // No receivers just means nobody is listening
+        let _ = self.sender.send(EventMessage {
+            time: Utc::now(),
+            device_number: 0,
+            event,
+        });
+    }
+
+    /// One bus with the events of every mount, each tagged with the device number of its mount
+    pub fn fan_in(buses: Vec<EventBus>) -> EventBus {
+        let combined = EventBus::new();
+        for (device_number, bus) in buses.into_iter().enumerate() {
+            let mut receiver = bus.subscribe();
+            let sender = combined.sender.clone();
+            tokio::spawn(async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(message) => {
+                            let _ = sender.send(EventMessage {
+                                device_number,
+                                ..message
+                            });
+                        }
+                        Err(RecvError::Lagged(missed)) => {
+                            tracing::warn!("Missed {} events from device {}", missed, device_number)
+                        }
+                        Err(RecvError::Closed) => return,
+                    }
+                }
+            });
+        }
+        combined
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<EventMessage> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fan_in() {
+        let buses = vec![EventBus::new(), EventBus::new()];
+        let combined = EventBus::fan_in(buses.clone());
+        let mut receiver = combined.subscribe();
+
+        buses[1].publish(Event::ParkCompleted);
+        let message = receiver.recv().await.unwrap();
+        assert_eq!(message.device_number, 1);
+        assert!(matches!(message.event, Event::ParkCompleted));
+
+        buses[0].publish(Event::UnexpectedGotoStopped);
+        assert_eq!(receiver.recv().await.unwrap().device_number, 0);
+    }
+}
@@ -0,0 +1,243 @@
+//! Reads the site's location and the time from a GPS, either NMEA sentences from a USB receiver
+//! or a gpsd server, so the mount doesn't need reconfiguring at every new site.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::Serialize;
+
+use crate::config::{GpsSettings, GpsSource};
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+
+const DEFAULT_NMEA_BAUD_RATE: u32 = 4800;
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct GpsFix {
+    pub latitude: Degrees,
+    pub longitude: Degrees,
+    pub elevation: Option<f64>, // Metres. Not in every fix
+    pub time: Option<DateTime<Utc>>,
+}
+
+pub struct GpsReceiver {
+    source: GpsSource,
+    timeout: Duration, // For a fix
+    pub set_time: bool,
+}
+
+impl GpsReceiver {
+    /// None if no GPS is configured
+    pub fn new(settings: &GpsSettings) -> Option<Self> {
+        settings.source.clone().map(|source| Self {
+            source,
+            timeout: Duration::from_secs(settings.fix_timeout_secs),
+            set_time: settings.set_time,
+        })
+    }
+
+    pub async fn read_fix(&self) -> ASCOMResult<GpsFix> {
+        let source = self.source.clone();
+        let timeout = self.timeout;
+        let result = tokio::task::spawn_blocking(move || match source {
+            GpsSource::Nmea { path, baud_rate } => {
+                Self::read_nmea(&path, baud_rate.unwrap_or(DEFAULT_NMEA_BAUD_RATE), timeout)
+            }
+            GpsSource::Gpsd { address } => Self::read_gpsd(&address, timeout),
+        })
+        .await
+        .unwrap();
+
+        result.map_err(|e| {
+            ASCOMError::new(
+                ASCOMErrorCode::new_for_driver(8),
+                format_args!("Couldn't get a GPS fix: {}", e),
+            )
+        })
+    }
+
+    fn read_nmea(path: &str, baud_rate: u32, timeout: Duration) -> io::Result<GpsFix> {
+        let port = serialport::new(path, baud_rate)
+            .timeout(READ_TIMEOUT)
+            .open()?;
+        let mut fix: Option<GpsFix> = None;
+        let mut elevation = None;
+        Self::read_lines(BufReader::new(port), timeout, |line| {
+            match parse_nmea(line) {
+                Some(Sentence::Rmc(rmc)) => fix = Some(rmc),
+                Some(Sentence::Gga(altitude)) => elevation = Some(altitude),
+                None => {}
+            }
+            // A receiver sends a GGA each second alongside the RMC
+            fix.filter(|_| elevation.is_some())
+                .map(|fix| GpsFix { elevation, ..fix })
+        })
+    }
+
+    fn read_gpsd(address: &str, timeout: Duration) -> io::Result<GpsFix> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        stream.write_all(b"?WATCH={\"enable\":true,\"json\":true};\n")?;
+        Self::read_lines(BufReader::new(stream), timeout, parse_gpsd)
+    }
+
+    /// Reads lines until one completes a fix or the timeout runs out
+    fn read_lines(
+        reader: impl BufRead,
+        timeout: Duration,
+        mut on_line: impl FnMut(&str) -> Option<GpsFix>,
+    ) -> io::Result<GpsFix> {
+        let deadline = Instant::now() + timeout;
+        for line in reader.lines() {
+            match line {
+                Ok(line) => {
+                    if let Some(fix) = on_line(line.trim()) {
+                        return Ok(fix);
+                    }
+                }
+                // Read timeouts are only the receiver being quiet
+                Err(e) if e.kind() != io::ErrorKind::TimedOut => return Err(e),
+                Err(_) => {}
+            }
+            if deadline < Instant::now() {
+                break;
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("No fix within {}s", timeout.as_secs()),
+        ))
+    }
+}
+
+enum Sentence {
+    Rmc(GpsFix),
+    Gga(f64), // Altitude above mean sea level
+}
+
+/// Only RMC and GGA sentences with a valid fix are of use
+fn parse_nmea(line: &str) -> Option<Sentence> {
+    let (body, checksum) = line.strip_prefix('$')?.split_once('*')?;
+    let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+    if body.bytes().fold(0, |sum, b| sum ^ b) != expected {
+        return None;
+    }
+
+    let fields: Vec<&str> = body.split(',').collect();
+    match fields.first()?.get(2..)? {
+        "RMC" if 10 <= fields.len() && fields[2] == "A" => {
+            let time = NaiveTime::parse_from_str(fields[1], "%H%M%S%.f").ok()?;
+            let date = NaiveDate::parse_from_str(fields[9], "%d%m%y").ok()?;
+            Some(Sentence::Rmc(GpsFix {
+                latitude: parse_nmea_angle(fields[3], fields[4])?,
+                longitude: parse_nmea_angle(fields[5], fields[6])?,
+                elevation: None,
+                time: Some(DateTime::from_utc(date.and_time(time), Utc)),
+            }))
+        }
+        "GGA" if 10 <= fields.len() && !matches!(fields[6], "" | "0") => {
+            Some(Sentence::Gga(fields[9].parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// NMEA angles are (d)ddmm.mmmm with the hemisphere separate
+fn parse_nmea_angle(value: &str, hemisphere: &str) -> Option<Degrees> {
+    let value: f64 = value.parse().ok()?;
+    let degrees = (value / 100.).trunc();
+    let angle = degrees + (value - degrees * 100.) / 60.;
+    match hemisphere {
+        "N" | "E" => Some(angle),
+        "S" | "W" => Some(-angle),
+        _ => None,
+    }
+}
+
+/// A gpsd TPV report with at least a 2D fix
+fn parse_gpsd(line: &str) -> Option<GpsFix> {
+    let report: serde_json::Value = serde_json::from_str(line).ok()?;
+    if report["class"] != "TPV" || report["mode"].as_u64()? < 2 {
+        return None;
+    }
+    Some(GpsFix {
+        latitude: report["lat"].as_f64()?,
+        longitude: report["lon"].as_f64()?,
+        // Only a 3D fix has an altitude
+        elevation: report["altMSL"].as_f64().or_else(|| report["alt"].as_f64()),
+        time: report["time"]
+            .as_str()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc)),
+    })
+}
+
+/// Takes the location from the GPS at startup
+pub fn start(sa: Arc<StarAdventurer>) {
+    if sa.gps.is_none() {
+        return;
+    }
+    tokio::spawn(async move {
+        match sa.update_location_from_gps().await {
+            Ok(fix) => tracing::info!(
+                "Location from GPS: {:.4}, {:.4}",
+                fix.latitude,
+                fix.longitude
+            ),
+            Err(e) => tracing::warn!("Keeping the configured location: {}", e),
+        }
+    });
+}
+
+impl StarAdventurer {
+    /// Sets the observing location, and the time if configured to, from a new GPS fix
+    pub async fn update_location_from_gps(&self) -> ASCOMResult<GpsFix> {
+        let gps = self
+            .gps
+            .as_ref()
+            .ok_or_else(|| ASCOMError::invalid_operation("No GPS is configured"))?;
+        let fix = gps.read_fix().await?;
+
+        self.set_latitude(fix.latitude).await?;
+        self.set_longitude(fix.longitude).await?;
+        if let Some(elevation) = fix.elevation {
+            self.set_elevation(elevation).await?;
+        }
+        if let (true, Some(time)) = (gps.set_time, fix.time) {
+            self.set_utc_date(time).await?;
+        }
+        Ok(fix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nmea() {
+        let rmc = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+        let fix = match parse_nmea(rmc) {
+            Some(Sentence::Rmc(fix)) => fix,
+            _ => panic!("RMC not parsed"),
+        };
+        assert!((fix.latitude - 48.1173).abs() < 1E-4);
+        assert!((fix.longitude - 11.516_667).abs() < 1E-4);
+        assert_eq!(fix.time.unwrap().to_rfc3339(), "1994-03-23T12:35:19+00:00");
+
+        let gga = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+        assert!(matches!(parse_nmea(gga), Some(Sentence::Gga(altitude)) if altitude == 545.4));
+
+        // Bad checksum
+        assert!(parse_nmea(&rmc.replace("*6A", "*6B")).is_none());
+        assert_eq!(
+            parse_nmea_angle("3352.128", "S"),
+            Some(-(33. + 52.128 / 60.))
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::config::GuideBatchSettings;
+use crate::telescope_control::connection::Connection;
+use crate::util::*;
+
+/// Holds back guide pulses that arrive faster than the motor should be commanded,
+/// e.g. a storm of pulses while the guider recovers from a cloud.
+/// Their net correction is applied as a single pulse once the interval has passed.
+#[derive(Clone)]
+pub struct GuideBatcher {
+    min_interval: Duration, // Zero disables batching
+    state: Arc<Mutex<BatchState>>,
+}
+
+#[derive(Default)]
+struct BatchState {
+    last_pulse_end: Option<Instant>,
+    pending_clockwise_millis: i64, // Net correction still to apply
+    guide_speed: Degrees,
+    in_flight: bool, // A pulse is being carried out by the motor
+    flushing: bool,  // A task is waiting to apply the pending correction
+}
+
+impl BatchState {
+    fn too_soon(&self, min_interval: Duration) -> bool {
+        self.in_flight
+            || self
+                .last_pulse_end
+                .map_or(false, |end| Instant::now() < end + min_interval)
+    }
+}
+
+impl GuideBatcher {
+    pub fn new(settings: GuideBatchSettings) -> Self {
+        Self {
+            min_interval: Duration::from_millis(settings.min_pulse_interval_millis),
+            state: Arc::new(Mutex::new(BatchState::default())),
+        }
+    }
+
+    /// Returns true if the pulse was batched, in which case the caller mustn't carry it out.
+    /// Otherwise the caller pulses the motor itself and calls pulse_ended when done.
+    pub fn submit(
+        &self,
+        guide_rate: MotionRate,
+        duration: Duration,
+        connection: &Connection,
+    ) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if self.min_interval.is_zero() || !state.too_soon(self.min_interval) {
+            state.in_flight = true;
+            return false;
+        }
+
+        let millis = duration.as_millis() as i64;
+        state.pending_clockwise_millis += match guide_rate.direction() {
+            synscan::Direction::Clockwise => millis,
+            synscan::Direction::CounterClockwise => -millis,
+        };
+        state.guide_speed = guide_rate.rate();
+
+        if !state.flushing {
+            state.flushing = true;
+            tokio::spawn(self.clone().flush(connection.clone()));
+        }
+        true
+    }
+
+    pub fn pulse_ended(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight = false;
+        state.last_pulse_end = Some(Instant::now());
+    }
+
+    /// True while a pulse is running or a correction is being held
+    pub fn is_busy(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.in_flight || state.flushing
+    }
+
+    async fn flush(self, connection: Connection) {
+        loop {
+            let wake = {
+                let state = self.state.lock().unwrap();
+                state.last_pulse_end.unwrap_or_else(Instant::now) + self.min_interval
+            };
+            tokio::time::sleep_until(wake).await;
+
+            let (millis, speed) = {
+                let mut state = self.state.lock().unwrap();
+                if state.too_soon(self.min_interval) {
+                    continue;
+                }
+                let millis = std::mem::take(&mut state.pending_clockwise_millis);
+                if millis == 0 {
+                    state.flushing = false;
+                    return;
+                }
+                state.in_flight = true;
+                (millis, state.guide_speed)
+            };
+
+            let direction = if 0 < millis {
+                synscan::Direction::Clockwise
+            } else {
+                synscan::Direction::CounterClockwise
+            };
+            let result = match connection
+                .pulse_guide(
+                    MotionRate::new(speed, direction),
+                    Duration::from_millis(millis.unsigned_abs()),
+                    None, // Batched pulses would skew the latency of the guide path
+                )
+                .await
+            {
+                Ok(task) => task.await.unwrap(),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                tracing::warn!("Dropped batched guide correction of {}ms: {}", millis, e);
+            }
+            self.pulse_ended();
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::time::Instant;
+
+const SAMPLES_KEPT: usize = 500;
+
+/// Steps of an RA guide pulse, each timed from the one before
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuideStage {
+    /// Request received until the connection state is locked
+    StateLock,
+    /// Until the rate change is written to the motor
+    SerialWrite,
+    /// Until the motor reports the new rate
+    RateConfirmed,
+    /// Request received until the rate is confirmed
+    Total,
+}
+
+const STAGES: [GuideStage; 4] = [
+    GuideStage::StateLock,
+    GuideStage::SerialWrite,
+    GuideStage::RateConfirmed,
+    GuideStage::Total,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct StageLatency {
+    pub stage: GuideStage,
+    pub samples: usize,
+    pub p50_millis: f64,
+    pub p90_millis: f64,
+    pub p99_millis: f64,
+    pub max_millis: f64,
+}
+
+impl StageLatency {
+    fn from_samples(stage: GuideStage, samples: &VecDeque<Duration>) -> Option<Self> {
+        let mut sorted: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.).collect();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        Some(StageLatency {
+            stage,
+            samples: sorted.len(),
+            p50_millis: percentile(0.5),
+            p90_millis: percentile(0.9),
+            p99_millis: percentile(0.99),
+            max_millis: *sorted.last()?,
+        })
+    }
+}
+
+/// Recent latencies of each stage of RA guide pulses, for checking pulse timing
+#[derive(Clone, Default)]
+pub struct GuideLatency {
+    samples: Arc<Mutex<HashMap<GuideStage, VecDeque<Duration>>>>,
+}
+
+impl GuideLatency {
+    /// Call on receipt of the pulse
+    pub fn start(&self) -> PulseTimer {
+        let now = Instant::now();
+        PulseTimer {
+            received: now,
+            last: now,
+            latency: self.clone(),
+        }
+    }
+
+    fn record(&self, stage: GuideStage, latency: Duration) {
+        tracing::trace!(?stage, ?latency, "Guide pulse stage");
+        let mut samples = self.samples.lock().unwrap();
+        let stage_samples = samples.entry(stage).or_default();
+        if stage_samples.len() == SAMPLES_KEPT {
+            stage_samples.pop_front();
+        }
+        stage_samples.push_back(latency);
+    }
+
+    /// Percentiles over the recent pulses. Stages without samples are left out.
+    pub fn get_stats(&self) -> Vec<StageLatency> {
+        let samples = self.samples.lock().unwrap();
+        STAGES
+            .iter()
+            .filter_map(|stage| StageLatency::from_samples(*stage, samples.get(stage)?))
+            .collect()
+    }
+}
+
+/// Times one pulse through the guide path. Pulses that fail part way only record the stages they reached.
+pub struct PulseTimer {
+    received: Instant,
+    last: Instant,
+    latency: GuideLatency,
+}
+
+impl PulseTimer {
+    pub fn mark(&mut self, stage: GuideStage) {
+        let now = Instant::now();
+        self.latency.record(stage, now - self.last);
+        self.last = now;
+    }
+
+    /// Marks the final stage and records the total
+    pub fn finish(mut self, stage: GuideStage) {
+        self.mark(stage);
+        self.latency
+            .record(GuideStage::Total, self.last - self.received);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let latency = GuideLatency::default();
+        assert!(latency.get_stats().is_empty());
+        for millis in (1..=100).rev() {
+            latency.record(GuideStage::SerialWrite, Duration::from_millis(millis));
+        }
+        let stats = latency.get_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].stage, GuideStage::SerialWrite);
+        assert_eq!(stats[0].samples, 100);
+        assert_eq!(stats[0].p50_millis, 51.);
+        assert_eq!(stats[0].p90_millis, 90.);
+        assert_eq!(stats[0].p99_millis, 99.);
+        assert_eq!(stats[0].max_millis, 100.);
+
+        for _ in 0..SAMPLES_KEPT {
+            latency.record(GuideStage::SerialWrite, Duration::from_millis(1));
+        }
+        assert_eq!(latency.get_stats()[0].max_millis, 1.);
+    }
+}
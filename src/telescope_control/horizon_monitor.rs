@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::TrackingLimitAction;
+use crate::telescope_control::events::Event;
+use crate::telescope_control::StarAdventurer;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Watches for a tracked target setting below the horizon.
+/// Acts once per setting; rising back above re-arms it.
+pub fn start(sa: Arc<StarAdventurer>) {
+    if sa.settings.alt_az {
+        return; // Nothing tracks, and the hour angle is an azimuth
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut below = false;
+        loop {
+            interval.tick().await;
+            // Errors are mostly just being disconnected
+            let (altitude, azimuth) = match (sa.get_altitude().await, sa.get_azimuth().await) {
+                (Ok(altitude), Ok(azimuth)) => (altitude, azimuth),
+                _ => continue,
+            };
+            let (min_altitude, action) = {
+                let safety = sa.settings.slew_safety.read().await;
+                match safety.min_altitude_at(azimuth) {
+                    Some(min_altitude) => (min_altitude, safety.below_horizon_action),
+                    None => continue, // Nothing to watch for until a horizon is configured
+                }
+            };
+            if min_altitude <= altitude {
+                below = false;
+                continue;
+            }
+            if below || !sa.is_tracking().await.unwrap_or(false) {
+                continue;
+            }
+            below = true;
+
+            let stop = action == TrackingLimitAction::StopTracking;
+            tracing::warn!(
+                "Tracked position at altitude {:.1}° has set below the horizon of {:.1}° at azimuth {:.1}°",
+                altitude,
+                min_altitude,
+                azimuth
+            );
+            let stopped_tracking = stop
+                && match sa.set_is_tracking(false).await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::error!("Couldn't stop tracking below the horizon: {}", e);
+                        false
+                    }
+                };
+            sa.events.publish(Event::BelowHorizon {
+                altitude,
+                azimuth,
+                min_altitude,
+                stopped_tracking,
+            });
+        }
+    });
+}
@@ -0,0 +1,83 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::config::{IdleSafetySettings, SafeAction};
+use crate::telescope_control::events::Event;
+use crate::telescope_control::StarAdventurer;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When an Alpaca client was last heard from, to notice the imaging PC has gone away
+#[derive(Debug)]
+pub struct IdleWatchdog {
+    timeout: Option<Duration>,
+    action: SafeAction,
+    last_activity: Mutex<Instant>,
+}
+
+impl IdleWatchdog {
+    pub fn new(settings: &IdleSafetySettings) -> Self {
+        Self {
+            timeout: settings
+                .after_mins
+                .map(|mins| Duration::from_secs_f64(mins * 60.)),
+            action: settings.action,
+            last_activity: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.timeout.is_some()
+    }
+
+    pub fn note_activity(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_activity.lock().unwrap().elapsed()
+    }
+}
+
+/// Stops tracking or parks, as configured, once no client has been heard from for the timeout.
+/// Acts once per idle spell; any client request re-arms it.
+pub fn start(sa: Arc<StarAdventurer>) {
+    let timeout = match sa.idle_watchdog.timeout {
+        Some(timeout) => timeout,
+        None => return,
+    };
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut acted = false;
+        loop {
+            interval.tick().await;
+            let idle_for = sa.idle_watchdog.idle_for();
+            if idle_for < timeout {
+                acted = false;
+                continue;
+            }
+            if acted || !sa.is_connected().await || sa.is_parked().await.unwrap_or(true) {
+                continue;
+            }
+            acted = true;
+
+            let action = sa.idle_watchdog.action;
+            tracing::warn!(
+                "No client for {} minutes, applying the idle action {:?}",
+                idle_for.as_secs() / 60,
+                action
+            );
+            sa.session
+                .log(format!("No client for {} minutes", idle_for.as_secs() / 60));
+            if let Err(e) = sa.apply_safe_action(action).await {
+                tracing::error!("Couldn't make the mount safe while idle: {}", e);
+            }
+            sa.events.publish(Event::ClientsIdle {
+                idle_secs: idle_for.as_secs_f64(),
+                action,
+            });
+        }
+    });
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::TrackingLimitAction;
+use crate::telescope_control::events::Event;
+use crate::telescope_control::StarAdventurer;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Watches for tracking carrying the mount into the west limit during a long session.
+/// Acts once per crossing; a flip or slew back re-arms it.
+pub fn start(sa: Arc<StarAdventurer>) {
+    if sa.settings.alt_az {
+        return; // Nothing tracks, and the hour angle is an azimuth
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut reached = false;
+        loop {
+            interval.tick().await;
+            // Errors are mostly just being disconnected
+            let limit = match sa.get_tracking_limit().await {
+                Ok(limit) => limit,
+                Err(_) => continue,
+            };
+            if !limit.is_reached() {
+                reached = false;
+                continue;
+            }
+            if reached || limit.secs_left.is_none() {
+                continue;
+            }
+            reached = true;
+
+            let stop = sa.settings.tracking_limit_action == TrackingLimitAction::StopTracking;
+            tracing::warn!(
+                "Tracking is {}h past the flip point before the west limit",
+                -limit.hour_angle_left
+            );
+            let stopped_tracking = stop
+                && match sa.set_is_tracking(false).await {
+                    Ok(()) => {
+                        sa.session.log("Tracking stopped at the west limit");
+                        true
+                    }
+                    Err(e) => {
+                        tracing::error!("Couldn't stop tracking at the limit: {}", e);
+                        false
+                    }
+                };
+            sa.events.publish(Event::TrackingLimitReached {
+                hour_angle_past: -limit.hour_angle_left,
+                stopped_tracking,
+            });
+        }
+    });
+}
@@ -1,29 +1,74 @@
+pub use connection::trace_replay;
 pub use star_adventurer::StarAdventurer;
 
 use ascom_alpaca::api::{AlignmentMode, EquatorialSystem, SideOfPier};
 use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
 
+pub mod clock_monitor;
+pub mod config_watcher;
 mod connection;
+mod dec_guide_output;
+pub mod dec_knob;
+pub mod events;
+pub mod gps;
+mod guide_batcher;
+pub mod guide_latency;
+pub mod horizon_monitor;
+pub mod idle_watchdog;
 mod commands {
+    pub mod capabilities;
+    pub mod centering;
+    pub mod dither;
+    pub mod driver_info;
     pub mod guide;
+    pub mod jog;
+    pub mod move_presets;
     pub mod observing_pos;
     pub mod parking;
+    pub mod pointing_conditions;
+    pub mod pointing_model;
     pub mod pointing_pos;
+    pub mod schedule;
+    pub mod session;
+    pub mod setup;
+    pub mod shutdown;
     pub mod slew;
+    pub mod slew_check;
+    pub mod slew_progress;
+    pub mod snapshot;
     pub mod sync;
     pub mod target;
     pub mod tracking;
+    pub mod tracking_limit;
+    pub mod worm;
 }
+pub mod limit_monitor;
+pub mod motion_limit_monitor;
+pub mod motor_watchdog;
 pub mod mount_limits;
+pub mod mount_profile;
+pub mod pointing_model;
+mod power_switch;
+pub mod scheduler;
+mod secondary_axis;
+pub mod session;
+pub mod settle_timer;
 mod slew_def;
+pub mod stability_webhook;
 mod star_adventurer;
 #[cfg(test)]
-pub(in crate::telescope_control) mod test_util;
+pub(crate) mod test_util;
+pub mod tracking_rate_monitor;
+pub mod vibration_guard;
 
 impl StarAdventurer {
     /// Returns the alignment mode of the mount (Alt/Az, Polar, German Polar)
     pub async fn get_alignment_mode(&self) -> ASCOMResult<AlignmentMode> {
-        Ok(AlignmentMode::GermanPolar)
+        Ok(if self.settings.alt_az {
+            AlignmentMode::AltAz
+        } else {
+            AlignmentMode::GermanPolar
+        })
     }
 
     /// Returns the current equatorial coordinate system used by this telescope (e.g. Topocentric or J2000).
@@ -83,16 +128,27 @@ impl StarAdventurer {
 
     /// Indicates the pointing state of the mount
     pub async fn get_side_of_pier(&self) -> ASCOMResult<SideOfPier> {
-        Ok(*self.settings.pier_side.read().await)
+        Ok(self.settings.pier_side.get())
     }
 
     /// True if the SideOfPier property can be set, meaning that the mount can be forced to flip.
     pub async fn can_set_side_of_pier(&self) -> ASCOMResult<bool> {
-        Ok(false)
+        Ok(!self.settings.alt_az)
     }
 
-    /// Sets the pointing state of the mount
-    pub async fn set_side_of_pier(&self, _side: SideOfPier) -> ASCOMResult<()> {
-        Err(ASCOMError::NOT_IMPLEMENTED)
+    /// Sets the pointing state of the mount, flipping to point at the same place from the other side.
+    /// Returns once the flip has started, like the async slews.
+    pub async fn set_side_of_pier(&self, side: SideOfPier) -> ASCOMResult<()> {
+        if self.settings.alt_az {
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        }
+        match side {
+            SideOfPier::Unknown => Err(ASCOMError::invalid_value("Pier side must be east or west")),
+            side if side == self.settings.pier_side.get() => Ok(()),
+            _ => {
+                let _finish = self.flip_pier_side().await?;
+                Ok(())
+            }
+        }
     }
 }
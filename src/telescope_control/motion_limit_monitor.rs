@@ -0,0 +1,86 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::telescope_control::events::Event;
+use crate::telescope_control::StarAdventurer;
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+/// Far enough ahead that motion is stopped before the next check would find it past the limit
+pub const LOOKAHEAD: Duration = Duration::from_millis(500);
+
+/// Watches MoveAxis motion and guide pulses, which unlike gotos aren't checked against the mount limits up front,
+/// and stops them before they carry the mount past the east or west limit.
+/// MoveAxis on a dec motor is stopped before a pole or the horizon.
+pub fn start(sa: Arc<StarAdventurer>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            check_dec(&sa).await;
+            // Errors are mostly just being disconnected
+            match sa.connection.is_moving_unchecked().await {
+                Ok(true) => {}
+                _ => continue,
+            }
+            // Where a ramp is heading, so a MoveAxis is caught while still speeding up
+            let rate = match sa.connection.get_heading_rate().await {
+                Ok(Some(rate)) => rate,
+                _ => continue,
+            };
+            match sa.is_heading_past_limit(rate, LOOKAHEAD).await {
+                Ok(true) => {}
+                _ => continue,
+            }
+            let slewing = match sa.connection.is_slewing().await {
+                Ok(slewing) => slewing,
+                Err(_) => continue,
+            };
+            // MoveAxis is a slew to the state machine, anything else here is a pulse
+            if !slewing {
+                sa.settings.stopped_at_limit.store(true, Ordering::SeqCst);
+            }
+            if let Err(e) = sa.connection.abort_slew().await {
+                tracing::error!("Couldn't stop motion at the mount limit: {}", e);
+                continue;
+            }
+            let stopped = if slewing { "move_axis" } else { "pulse_guide" };
+            tracing::warn!("Stopped {} at the mount limit", stopped);
+            sa.session
+                .log(format!("Stopped {} at the mount limit", stopped));
+            if let Ok(mech_ha) = sa.get_mech_ha().await {
+                sa.events.publish(Event::MountLimitReached {
+                    mech_hour_angle: mech_ha,
+                    stopped: stopped.to_string(),
+                });
+            }
+        }
+    });
+}
+
+async fn check_dec(sa: &StarAdventurer) {
+    // Only MoveAxis. Dec gotos go where they were checked to be allowed.
+    if sa.settings.dec_move_start.read().await.is_none() {
+        return;
+    }
+    let rate = match sa.connection.get_dec_heading_rate().await {
+        Ok(Some(rate)) => rate,
+        _ => return,
+    };
+    match sa.is_dec_heading_past_limit(rate, LOOKAHEAD).await {
+        Ok(true) => {}
+        _ => return,
+    }
+    if let Err(e) = sa.stop_dec_move().await {
+        tracing::error!("Couldn't stop dec at its limit: {}", e);
+        return;
+    }
+    tracing::warn!("Stopped move_axis at the dec limit");
+    sa.session.log("Stopped move_axis at the dec limit");
+    if let Ok(mech_ha) = sa.get_mech_ha().await {
+        sa.events.publish(Event::MountLimitReached {
+            mech_hour_angle: mech_ha,
+            stopped: "move_axis".to_string(),
+        });
+    }
+}
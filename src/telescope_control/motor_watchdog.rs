@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::telescope_control::connection::Connection;
+use crate::telescope_control::events::{Event, EventBus};
+use crate::util::*;
+
+const GOTO_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const RATE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// How long the rates may disagree, e.g. while the controller ramps after a rate change
+const RATE_MISMATCH_GRACE: Duration = Duration::from_secs(3);
+const RATE_TOLERANCE_FRACTION: f64 = 0.05;
+const RATE_TOLERANCE_FLOOR: Degrees = 0.0005; // About an eighth of sidereal
+const MAX_INCIDENTS: usize = 100; // The oldest are forgotten first
+
+/// A hardware quirk the watchdog had to step in for
+#[derive(Debug, Copy, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IncidentKind {
+    UnexpectedGoto,
+    RunawayMotion {
+        commanded_rate: Degrees, // Clockwise degrees/sec
+        reported_rate: Degrees,
+    },
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct HardwareIncident {
+    pub time: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: IncidentKind,
+}
+
+/// Polls the motor controller for motion we didn't command and stops it so the driver's state matches the motor again.
+/// This covers gotos started elsewhere, e.g. from the vendor hand app used in parallel,
+/// and the motor running away from the commanded rate, e.g. the endless motion after a goto overshoots.
+#[derive(Clone, Default)]
+pub struct MotorWatchdog {
+    incidents: Arc<Mutex<VecDeque<HardwareIncident>>>,
+}
+
+impl MotorWatchdog {
+    pub fn start(connection: Connection, events: EventBus) -> Self {
+        let watchdog = Self::default();
+
+        let (goto_watchdog, goto_connection, goto_events) =
+            (watchdog.clone(), connection.clone(), events.clone());
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(GOTO_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                // Errors are mostly just being disconnected
+                if let Ok(true) = goto_connection.recover_from_foreign_goto().await {
+                    goto_watchdog.record(IncidentKind::UnexpectedGoto);
+                    goto_events.publish(Event::UnexpectedGotoStopped);
+                }
+            }
+        });
+
+        let rate_watchdog = watchdog.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RATE_CHECK_INTERVAL);
+            let mut mismatch = RateMismatch::default();
+            loop {
+                interval.tick().await;
+                let rates = connection.read_motor_rates().await.ok().flatten();
+                if !mismatch.observe(rates, Instant::now()) {
+                    continue;
+                }
+                mismatch = RateMismatch::default();
+
+                let (commanded, reported) = rates.unwrap();
+                tracing::error!(
+                    "Motor is moving at {}°/s instead of the commanded {}°/s",
+                    reported.clockwise_rate(),
+                    commanded.clockwise_rate()
+                );
+                match connection.stop_runaway_motor().await {
+                    Ok(true) => tracing::warn!("Stopped the runaway motor"),
+                    Ok(false) => {
+                        tracing::warn!("Left the motor to the slew or park that took it over");
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::error!("Couldn't stop the runaway motor: {}", e);
+                        continue;
+                    }
+                }
+                rate_watchdog.record(IncidentKind::RunawayMotion {
+                    commanded_rate: commanded.clockwise_rate(),
+                    reported_rate: reported.clockwise_rate(),
+                });
+                events.publish(Event::RunawayMotionStopped {
+                    commanded_rate: commanded.clockwise_rate(),
+                    reported_rate: reported.clockwise_rate(),
+                });
+            }
+        });
+
+        watchdog
+    }
+
+    fn record(&self, kind: IncidentKind) {
+        let mut incidents = self.incidents.lock().unwrap();
+        if incidents.len() == MAX_INCIDENTS {
+            incidents.pop_front();
+        }
+        incidents.push_back(HardwareIncident {
+            time: Utc::now(),
+            kind,
+        });
+    }
+
+    /// The most recent, oldest first
+    pub fn incidents(&self) -> Vec<HardwareIncident> {
+        self.incidents.lock().unwrap().iter().copied().collect()
+    }
+
+    pub fn last_incident(&self) -> Option<HardwareIncident> {
+        self.incidents.lock().unwrap().back().copied()
+    }
+}
+
+/// Tracks how long the commanded and reported rates have disagreed
+#[derive(Debug, Default)]
+struct RateMismatch {
+    since: Option<Instant>,
+}
+
+impl RateMismatch {
+    fn rates_match(commanded: MotionRate, reported: MotionRate) -> bool {
+        let tolerance = (RATE_TOLERANCE_FRACTION * commanded.rate().max(reported.rate()))
+            .max(RATE_TOLERANCE_FLOOR);
+        (commanded - reported).rate() <= tolerance
+    }
+
+    /// True once the rates have disagreed for longer than the grace period.
+    /// Rates of None, i.e. unknown or legitimately changing, reset the timer.
+    fn observe(&mut self, rates: Option<(MotionRate, MotionRate)>, now: Instant) -> bool {
+        match rates {
+            Some((commanded, reported)) if !Self::rates_match(commanded, reported) => {
+                let since = *self.since.get_or_insert(now);
+                RATE_MISMATCH_GRACE < now - since
+            }
+            _ => {
+                self.since = None;
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use synscan::Direction;
+
+    #[test]
+    fn mismatch_needs_to_outlast_grace_period() {
+        let tracking = MotionRate::new(0.004178, Direction::Clockwise);
+        let slewing = MotionRate::new(0.5, Direction::Clockwise);
+        let start = Instant::now();
+        let mut mismatch = RateMismatch::default();
+
+        // Small differences from the controller's rate resolution are fine
+        let close = MotionRate::new(0.00418, Direction::Clockwise);
+        assert!(!mismatch.observe(Some((tracking, close)), start + RATE_MISMATCH_GRACE * 2));
+        assert!(!mismatch.observe(Some((tracking, close)), start + RATE_MISMATCH_GRACE * 3));
+
+        assert!(!mismatch.observe(Some((tracking, slewing)), start));
+        assert!(!mismatch.observe(Some((tracking, slewing)), start + RATE_MISMATCH_GRACE / 2));
+        assert!(mismatch.observe(Some((tracking, slewing)), start + RATE_MISMATCH_GRACE * 2));
+
+        // A rate change restarts the grace period
+        let mut mismatch = RateMismatch::default();
+        assert!(!mismatch.observe(Some((tracking, slewing)), start));
+        assert!(!mismatch.observe(None, start + RATE_MISMATCH_GRACE / 2));
+        assert!(!mismatch.observe(Some((tracking, slewing)), start + RATE_MISMATCH_GRACE));
+
+        // Moving in the wrong direction at the right speed is a mismatch too
+        let mut mismatch = RateMismatch::default();
+        let backwards = MotionRate::new(0.004178, Direction::CounterClockwise);
+        assert!(!mismatch.observe(Some((tracking, backwards)), start));
+        assert!(mismatch.observe(Some((tracking, backwards)), start + RATE_MISMATCH_GRACE * 2));
+    }
+
+    #[test]
+    fn incidents_are_capped() {
+        let watchdog = MotorWatchdog::default();
+        for _ in 0..MAX_INCIDENTS {
+            watchdog.record(IncidentKind::UnexpectedGoto);
+        }
+        let runaway = IncidentKind::RunawayMotion {
+            commanded_rate: 0.004178,
+            reported_rate: 0.5,
+        };
+        watchdog.record(runaway);
+        assert_eq!(watchdog.incidents().len(), MAX_INCIDENTS);
+        assert!(matches!(
+            watchdog.last_incident().unwrap().kind,
+            IncidentKind::RunawayMotion { .. }
+        ));
+    }
+}
@@ -8,21 +8,40 @@ use crate::util::*;
 /// Valid mechanical hour angles are in the range east-west, usually crossing zero
 #[derive(Debug, Clone, Copy)]
 pub struct MountLimits {
-    east: Hours, // Lower end of the valid ha range
-    west: Hours, // Higher end -- may be above 24; always greater than east
+    east: Hours,        // Lower end of the valid ha range
+    west: Hours,        // Higher end -- may be above 24; always greater than east
+    flip_before: Hours, // Margin before the west limit that tracking shouldn't run into
 }
 
 impl MountLimits {
     /// east: 0, west: 24 for no limit
-    pub fn new(east: Hours, west: Hours) -> Self {
+    pub fn new(east: Hours, west: Hours, flip_before: Hours) -> Self {
         let east = astro_math::modulo(east, 24.);
         let west = astro_math::modulo(west, 24.);
         Self {
             east,
             west: east + astro_math::modulo(west - east, 24.),
+            flip_before: flip_before.max(0.),
         }
     }
 
+    /// Hours of hour angle that tracking can still cover before it comes within the flip margin of the west limit.
+    /// Negative once past that point.
+    pub fn tracking_left(&self, mech_ha: Hours) -> Hours {
+        self.west - self.flip_before - self.niceify_ha(mech_ha)
+    }
+
+    /// Hours of hour angle past the nearer limit. Zero in range.
+    pub fn distance_outside(&self, ha: Hours) -> Hours {
+        let ha = self.niceify_ha(ha);
+        (self.east - ha).max(ha - self.west).max(0.)
+    }
+
+    /// True if moving from one ha to the other ends up further out of range than it started
+    pub fn is_moving_out(&self, from: Hours, to: Hours) -> bool {
+        self.distance_outside(from) < self.distance_outside(to)
+    }
+
     pub fn is_valid_ha(&self, ha: Hours) -> bool {
         let ha = self.niceify_ha(ha);
         (self.east..=self.west).contains(&ha)
@@ -73,3 +92,30 @@ impl MountLimits {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracking_left() {
+        let limits = MountLimits::new(18., 6., 0.5);
+        assert_eq!(limits.tracking_left(0.), 5.5);
+        assert_eq!(limits.tracking_left(20.), 9.5);
+        assert_eq!(limits.tracking_left(5.75), -0.25);
+        assert_eq!(limits.tracking_left(7.), -1.5);
+    }
+
+    #[test]
+    fn test_is_moving_out() {
+        let limits = MountLimits::new(18., 6., 0.);
+        assert_eq!(limits.distance_outside(0.), 0.);
+        assert_eq!(limits.distance_outside(7.), 1.);
+        assert_eq!(limits.distance_outside(16.5), 1.5);
+        assert!(limits.is_moving_out(5.9, 6.1));
+        assert!(limits.is_moving_out(17.9, 17.8));
+        assert!(!limits.is_moving_out(5., 5.5));
+        // Heading back in from past a limit
+        assert!(!limits.is_moving_out(7., 6.5));
+    }
+}
@@ -0,0 +1,89 @@
+use serde::Serialize;
+
+use crate::config::MountModel;
+
+/// What differs between the SynScan mounts the driver runs. Speeds are read from the controller itself.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MountProfile {
+    pub model: MountModel,
+    pub name: &'static str,
+    pub dec_motor: bool, // Dec is driven on the controller's second channel
+    pub st4_port: bool,  // Has an autoguide port, whose speed the controller is told
+    pub counts_per_revolution: Option<u32>, // Expected from the controller, to catch the wrong profile. None if it varies
+    #[serde(skip)]
+    mount_codes: &'static [u8], // Reported in the firmware version, for auto detection
+}
+
+static PROFILES: [MountProfile; 4] = [
+    MountProfile {
+        model: MountModel::StarAdventurer,
+        name: "Star Adventurer",
+        dec_motor: false,
+        st4_port: true,
+        counts_per_revolution: Some(2_764_800),
+        mount_codes: &[0x0A],
+    },
+    MountProfile {
+        model: MountModel::Gti,
+        name: "Star Adventurer GTi",
+        dec_motor: true,
+        st4_port: true,
+        counts_per_revolution: None,
+        mount_codes: &[0x0C],
+    },
+    MountProfile {
+        model: MountModel::AzGti,
+        name: "AZ-GTi",
+        dec_motor: true, // In equatorial mode
+        st4_port: false,
+        counts_per_revolution: Some(3_628_800),
+        mount_codes: &[0xA5],
+    },
+    MountProfile {
+        model: MountModel::EqM35,
+        name: "EQ-M35",
+        dec_motor: true,
+        st4_port: true,
+        counts_per_revolution: None,
+        mount_codes: &[0x26],
+    },
+];
+
+impl MountProfile {
+    /// Auto runs as a Star Adventurer until the controller is detected
+    pub fn for_model(model: MountModel) -> &'static MountProfile {
+        PROFILES
+            .iter()
+            .find(|profile| profile.model == model)
+            .unwrap_or(&PROFILES[0])
+    }
+
+    pub fn detect(mount_code: u8) -> Option<&'static MountProfile> {
+        PROFILES
+            .iter()
+            .find(|profile| profile.mount_codes.contains(&mount_code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles() {
+        assert_eq!(
+            MountProfile::for_model(MountModel::Auto).model,
+            MountModel::StarAdventurer
+        );
+        assert!(MountProfile::for_model(MountModel::Gti).dec_motor);
+        assert_eq!(MountProfile::detect(0xA5).unwrap().model, MountModel::AzGti);
+        assert_eq!(MountProfile::detect(0x00), None);
+        // Every model can be detected, by its own code
+        for profile in &PROFILES {
+            assert!(!profile.mount_codes.is_empty(), "{}", profile.name);
+            for &code in profile.mount_codes {
+                assert_eq!(MountProfile::detect(code), Some(profile));
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use serde::Serialize;
+
+use crate::astro_math;
+use crate::util::*;
+
+/// Where the mount thought it was pointing when synced, and where it really was
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct SyncPoint {
+    pub mount_ha: Hours,
+    pub mount_dec: Degrees,
+    pub true_ha: Hours,
+    pub true_dec: Degrees,
+}
+
+/// Fitted corrections, all in degrees.
+/// The polar terms are how far the RA axis is tilted from the pole, in elevation and azimuth.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize)]
+pub struct ModelTerms {
+    pub ha_offset: Degrees,
+    pub dec_offset: Degrees,
+    pub polar_elevation: Degrees,
+    pub polar_azimuth: Degrees,
+}
+
+impl ModelTerms {
+    /// Difference between the true and mount coordinates at a mount position: (hours, degrees)
+    fn correction(&self, ha: Hours, dec: Degrees) -> (Hours, Degrees) {
+        let (sin_h, cos_h) = astro_math::hours_to_rad(ha).sin_cos();
+        let tan_d = astro_math::deg_to_rad(dec.clamp(-89., 89.)).tan();
+        let ha_correction =
+            self.ha_offset + tan_d * (self.polar_elevation * sin_h - self.polar_azimuth * cos_h);
+        let dec_correction =
+            self.dec_offset + self.polar_elevation * cos_h + self.polar_azimuth * sin_h;
+        (astro_math::deg_to_hours(ha_correction), dec_correction)
+    }
+}
+
+/// A pointing model fitted to the sync points since the last reset
+#[derive(Debug, Clone, Default)]
+pub struct PointingModel {
+    points: Vec<SyncPoint>,
+    terms: ModelTerms,
+    rms: Degrees, // On sky, of the residuals after fitting
+}
+
+impl PointingModel {
+    pub fn points(&self) -> &[SyncPoint] {
+        &self.points
+    }
+
+    /// Nothing synced since the last reset, so no corrections
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn terms(&self) -> ModelTerms {
+        self.terms
+    }
+
+    pub fn rms(&self) -> Degrees {
+        self.rms
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Drops the oldest point once there are more than max_points
+    pub fn add_point(&mut self, point: SyncPoint, max_points: usize) {
+        self.points.push(point);
+        if max_points < self.points.len() {
+            self.points.remove(0);
+        }
+        self.fit();
+    }
+
+    /// Mount coordinates to true coordinates
+    pub fn to_true(&self, ha: Hours, dec: Degrees) -> (Hours, Degrees) {
+        let (ha_correction, dec_correction) = self.terms.correction(ha, dec);
+        (
+            astro_math::modulo(ha + ha_correction, 24.),
+            dec + dec_correction,
+        )
+    }
+
+    /// True coordinates to mount coordinates.
+    /// The corrections are small so a few rounds of fixed point iteration converge.
+    pub fn to_mount(&self, ha: Hours, dec: Degrees) -> (Hours, Degrees) {
+        let (mut mount_ha, mut mount_dec) = (ha, dec);
+        for _ in 0..4 {
+            let (ha_correction, dec_correction) = self.terms.correction(mount_ha, mount_dec);
+            mount_ha = astro_math::modulo(ha - ha_correction, 24.);
+            mount_dec = dec - dec_correction;
+        }
+        (mount_ha, mount_dec)
+    }
+
+    /// Least squares over both axes. One point only gives the offsets.
+    fn fit(&mut self) {
+        let rows: Vec<([f64; 4], f64)> = self
+            .points
+            .iter()
+            .flat_map(|p| {
+                let (sin_h, cos_h) = astro_math::hours_to_rad(p.mount_ha).sin_cos();
+                let (sin_d, cos_d) = astro_math::deg_to_rad(p.mount_dec).sin_cos();
+                let ha_error = astro_math::hours_to_deg(
+                    astro_math::modulo(p.true_ha - p.mount_ha + 12., 24.) - 12.,
+                );
+                // The HA row is scaled by cos(dec) so it's measured on sky, like the dec row
+                [
+                    ([cos_d, 0., sin_h * sin_d, -cos_h * sin_d], ha_error * cos_d),
+                    ([0., 1., cos_h, sin_h], p.true_dec - p.mount_dec),
+                ]
+            })
+            .collect();
+
+        let num_terms = if 2 <= self.points.len() { 4 } else { 2 };
+        let solution = Self::solve_least_squares(&rows, num_terms)
+            .or_else(|| Self::solve_least_squares(&rows, 2))
+            .unwrap_or([0.; 4]);
+        self.terms = ModelTerms {
+            ha_offset: solution[0],
+            dec_offset: solution[1],
+            polar_elevation: solution[2],
+            polar_azimuth: solution[3],
+        };
+
+        let sum_of_squares: f64 = rows
+            .iter()
+            .map(|(row, value)| {
+                let fitted: f64 = row.iter().zip(solution).map(|(a, x)| a * x).sum();
+                (value - fitted).powi(2)
+            })
+            .sum();
+        self.rms = if self.is_empty() {
+            0.
+        } else {
+            (sum_of_squares / self.points.len() as f64).sqrt()
+        };
+    }
+
+    /// Solves the normal equations for the first num_terms terms. None if they're degenerate.
+    fn solve_least_squares(rows: &[([f64; 4], f64)], num_terms: usize) -> Option<[f64; 4]> {
+        let n = num_terms;
+        let mut a = [[0.; 5]; 4]; // Augmented with the right hand side
+        for (row, value) in rows {
+            for (a_row, row_i) in a.iter_mut().zip(row).take(n) {
+                for (a_ij, row_j) in a_row.iter_mut().zip(row).take(n) {
+                    *a_ij += row_i * row_j;
+                }
+                a_row[4] += row_i * value;
+            }
+        }
+
+        // Gauss-Jordan elimination with partial pivoting
+        for col in 0..n {
+            let pivot = (col..n).max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))?;
+            if a[pivot][col].abs() < 1E-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            let pivot_row = a[col];
+            for (r, a_row) in a.iter_mut().enumerate().take(n) {
+                if r != col {
+                    let factor = a_row[col] / pivot_row[col];
+                    for (x, p) in a_row.iter_mut().zip(pivot_row).skip(col) {
+                        *x -= factor * p;
+                    }
+                }
+            }
+        }
+
+        let mut solution = [0.; 4];
+        for (i, (x, a_row)) in solution.iter_mut().zip(a).enumerate().take(n) {
+            *x = a_row[4] / a_row[i];
+        }
+        Some(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn test_fit_pointing_model() {
+        let actual = ModelTerms {
+            ha_offset: 0.2,
+            dec_offset: -0.1,
+            polar_elevation: 0.05,
+            polar_azimuth: -0.03,
+        };
+        let mut model = PointingModel::default();
+        for (ha, dec) in [(-3., 10.), (1., 40.), (4., -20.), (0.5, 70.), (-1.5, 25.)] {
+            let (ha_correction, dec_correction) = actual.correction(ha, dec);
+            model.add_point(
+                SyncPoint {
+                    mount_ha: astro_math::modulo(ha, 24.),
+                    mount_dec: dec,
+                    true_ha: astro_math::modulo(ha + ha_correction, 24.),
+                    true_dec: dec + dec_correction,
+                },
+                20,
+            );
+        }
+        let terms = model.terms();
+        assert_float_absolute_eq!(terms.ha_offset, actual.ha_offset, 1E-9);
+        assert_float_absolute_eq!(terms.dec_offset, actual.dec_offset, 1E-9);
+        assert_float_absolute_eq!(terms.polar_elevation, actual.polar_elevation, 1E-9);
+        assert_float_absolute_eq!(terms.polar_azimuth, actual.polar_azimuth, 1E-9);
+        assert_float_absolute_eq!(model.rms(), 0., 1E-9);
+
+        let (ha, dec) = model.to_mount(23., 35.);
+        let (true_ha, true_dec) = model.to_true(ha, dec);
+        assert_float_absolute_eq!(true_ha, 23., 1E-6);
+        assert_float_absolute_eq!(true_dec, 35., 1E-6);
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::config::{PowerSettings, PowerSwitchKind};
+use crate::util::*;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum PowerState {
+    Unknown,
+    On,
+    Off,
+    OffAtPark,
+}
+
+/// Switches the mount's 12V feed through the configured switch
+pub struct PowerSwitch {
+    kind: PowerSwitchKind,
+    power_off_on_park: bool,
+    power_on_delay: Duration,
+    state: Mutex<PowerState>,
+}
+
+impl PowerSwitch {
+    /// None if no switch is configured
+    pub fn new(settings: &PowerSettings) -> Option<Self> {
+        settings.switch.clone().map(|kind| Self {
+            kind,
+            power_off_on_park: settings.power_off_on_park,
+            power_on_delay: Duration::from_millis(settings.power_on_delay_millis),
+            state: Mutex::new(PowerState::Unknown),
+        })
+    }
+
+    pub fn powers_off_on_park(&self) -> bool {
+        self.power_off_on_park
+    }
+
+    /// True if the mount was powered down after a park and hasn't been powered up since
+    pub async fn is_off_at_park(&self) -> bool {
+        *self.state.lock().await == PowerState::OffAtPark
+    }
+
+    async fn switch(&self, on: bool) -> ASCOMResult<()> {
+        let result = match &self.kind {
+            PowerSwitchKind::Gpio { pin, active_low } => {
+                let pin = GpioPin::new(*pin, *active_low);
+                match pin.export().await {
+                    Ok(_) => pin.set_active(on).await,
+                    Err(e) => Err(e),
+                }
+            }
+            PowerSwitchKind::Command {
+                on: on_cmd,
+                off: off_cmd,
+            } => {
+                let cmd = if on { on_cmd } else { off_cmd };
+                match Command::new("sh").arg("-c").arg(cmd).status().await {
+                    Ok(status) if status.success() => Ok(()),
+                    Ok(status) => Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("\"{}\" exited with {}", cmd, status),
+                    )),
+                    Err(e) => Err(e),
+                }
+            }
+        };
+
+        result.map_err(|e| {
+            ASCOMError::new(
+                ASCOMErrorCode::new_for_driver(3),
+                format_args!("Error switching mount power: {}", e),
+            )
+        })
+    }
+
+    /// Powers the mount on, waiting for the motor controller to boot unless it was known to be on.
+    /// Returns true if the mount had been powered down by a park
+    pub async fn power_on(&self) -> ASCOMResult<bool> {
+        let mut state = self.state.lock().await;
+        if *state == PowerState::On {
+            return Ok(false);
+        }
+
+        tracing::info!("Powering mount on");
+        self.switch(true).await?;
+        // The switch may have been off before the driver started, so Unknown waits too
+        tokio::time::sleep(self.power_on_delay).await;
+
+        let was_parked = *state == PowerState::OffAtPark;
+        *state = PowerState::On;
+        Ok(was_parked)
+    }
+
+    /// Cuts power to the mount. The caller must ensure the motor is stopped.
+    pub async fn power_off(&self, parked: bool) -> ASCOMResult<()> {
+        let mut state = self.state.lock().await;
+        tracing::info!("Powering mount off");
+        self.switch(false).await?;
+        *state = if parked {
+            PowerState::OffAtPark
+        } else {
+            PowerState::Off
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Instant;
+
+    #[tokio::test]
+    async fn test_power_on_waits_unless_known_on() {
+        let settings = PowerSettings {
+            switch: Some(PowerSwitchKind::Command {
+                on: "true".to_string(),
+                off: "true".to_string(),
+            }),
+            power_off_on_park: true,
+            power_on_delay_millis: 100,
+        };
+        let delay = Duration::from_millis(settings.power_on_delay_millis);
+        let power_switch = PowerSwitch::new(&settings).unwrap();
+
+        // Unknown at startup
+        let start = Instant::now();
+        assert!(!power_switch.power_on().await.unwrap());
+        assert!(delay <= start.elapsed());
+
+        // Already on
+        let start = Instant::now();
+        assert!(!power_switch.power_on().await.unwrap());
+        assert!(start.elapsed() < delay);
+
+        power_switch.power_off(true).await.unwrap();
+        assert!(power_switch.is_off_at_park().await);
+        let start = Instant::now();
+        assert!(power_switch.power_on().await.unwrap());
+        assert!(delay <= start.elapsed());
+    }
+}
@@ -0,0 +1,206 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::telescope_control::events::Event;
+use crate::telescope_control::StarAdventurer;
+use crate::util::*;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A target to slew to once its start window opens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledTarget {
+    #[serde(default)]
+    pub id: u32, // Assigned when scheduled
+    pub name: String,
+    pub right_ascension: Hours,
+    pub declination: Degrees,
+    #[serde(default)]
+    pub priority: i32, // Higher goes first when several are due
+    #[serde(default)]
+    pub earliest_start: Option<DateTime<Utc>>, // None to start as soon as possible
+    #[serde(default)]
+    pub latest_start: Option<DateTime<Utc>>, // Dropped if not started by then
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Schedule {
+    pub queue: Vec<ScheduledTarget>,
+    pub current: Option<ScheduledTarget>, // The last one started
+    pub on_target: bool,
+    #[serde(skip)]
+    next_id: u32,
+}
+
+/// Targets queued by clients, slewed to in turn by the task from `start`
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    schedule: Mutex<Schedule>,
+    changed: Notify,
+}
+
+impl Scheduler {
+    pub fn get(&self) -> Schedule {
+        self.schedule.lock().unwrap().clone()
+    }
+
+    pub fn add(&self, mut target: ScheduledTarget) -> ScheduledTarget {
+        let mut schedule = self.schedule.lock().unwrap();
+        schedule.next_id += 1;
+        target.id = schedule.next_id;
+        schedule.queue.push(target.clone());
+        drop(schedule);
+        self.changed.notify_one();
+        target
+    }
+
+    /// False if no queued target has the id
+    pub fn remove(&self, id: u32) -> bool {
+        let mut schedule = self.schedule.lock().unwrap();
+        let len = schedule.queue.len();
+        schedule.queue.retain(|target| target.id != id);
+        len != schedule.queue.len()
+    }
+
+    pub fn clear(&self) {
+        self.schedule.lock().unwrap().queue.clear();
+    }
+
+    fn begin(&self, target: ScheduledTarget) {
+        let mut schedule = self.schedule.lock().unwrap();
+        schedule.current = Some(target);
+        schedule.on_target = false;
+    }
+
+    fn finish(&self, reached: bool) {
+        let mut schedule = self.schedule.lock().unwrap();
+        schedule.on_target = reached;
+        if !reached {
+            schedule.current = None;
+        }
+    }
+}
+
+/// Takes the highest priority target whose window is open, oldest first among equals.
+/// Returns it with any whose window has closed, which are dropped.
+fn take_due(
+    queue: &mut Vec<ScheduledTarget>,
+    now: DateTime<Utc>,
+) -> (Option<ScheduledTarget>, Vec<ScheduledTarget>) {
+    let (expired, waiting) = std::mem::take(queue)
+        .into_iter()
+        .partition(|target| target.latest_start.map_or(false, |latest| latest < now));
+    *queue = waiting;
+    let due = queue
+        .iter()
+        .enumerate()
+        .filter(|(_, target)| {
+            target
+                .earliest_start
+                .map_or(true, |earliest| earliest <= now)
+        })
+        .max_by_key(|(i, target)| (target.priority, std::cmp::Reverse(*i)))
+        .map(|(i, _)| i);
+    (due.map(|i| queue.remove(i)), expired)
+}
+
+/// Slews to each scheduled target as it comes due, waiting for the declination knob, and tracks it
+pub fn start(sa: Arc<StarAdventurer>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {},
+                _ = sa.scheduler.changed.notified() => {},
+            }
+            // Targets wait while parked or disconnected
+            if sa.is_parked().await.unwrap_or(true) {
+                continue;
+            }
+            let (due, expired) =
+                take_due(&mut sa.scheduler.schedule.lock().unwrap().queue, Utc::now());
+            for target in expired {
+                sa.session.log(format!(
+                    "Scheduled target {} dropped, not started by its latest start",
+                    target.name
+                ));
+                sa.events
+                    .publish(Event::ScheduledTargetExpired { name: target.name });
+            }
+            let target = match due {
+                Some(target) => target,
+                None => continue,
+            };
+
+            sa.scheduler.begin(target.clone());
+            sa.session
+                .log(format!("Slewing to scheduled target {}", target.name));
+            let result = go_to(&sa, &target).await;
+            sa.scheduler.finish(result.is_ok());
+            match result {
+                Ok(()) => sa.events.publish(Event::ScheduledTargetReached {
+                    name: target.name,
+                    right_ascension: target.right_ascension,
+                    declination: target.declination,
+                }),
+                Err(e) => {
+                    tracing::warn!("Couldn't go to scheduled target {}: {}", target.name, e);
+                    sa.events.publish(Event::ScheduledTargetFailed {
+                        name: target.name,
+                        reason: e.to_string(),
+                    });
+                }
+            }
+        }
+    });
+}
+
+async fn go_to(sa: &StarAdventurer, target: &ScheduledTarget) -> ascom_alpaca::ASCOMResult<()> {
+    // Finishes once the declination knob is confirmed and the mount has settled
+    sa.slew_to_coordinates(target.right_ascension, target.declination)
+        .await?;
+    if !sa.is_tracking().await? {
+        sa.set_is_tracking(true).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_due() {
+        let now = Utc::now();
+        let minute = chrono::Duration::minutes(1);
+        let target = |id, priority, earliest_start, latest_start| ScheduledTarget {
+            id,
+            name: id.to_string(),
+            right_ascension: 0.,
+            declination: 0.,
+            priority,
+            earliest_start,
+            latest_start,
+        };
+        let mut queue = vec![
+            target(1, 0, None, Some(now - minute)),
+            target(2, 0, None, None),
+            target(3, 5, Some(now + minute), None),
+            target(4, 1, Some(now - minute), Some(now + minute)),
+            target(5, 1, None, None),
+        ];
+
+        let (due, expired) = take_due(&mut queue, now);
+        assert_eq!(due.unwrap().id, 4);
+        assert_eq!(expired.iter().map(|t| t.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(take_due(&mut queue, now).0.unwrap().id, 5);
+        assert_eq!(take_due(&mut queue, now).0.unwrap().id, 2);
+        // Not due yet
+        assert_eq!(take_due(&mut queue, now).0, None);
+        assert_eq!(take_due(&mut queue, now + minute * 2).0.unwrap().id, 3);
+    }
+}
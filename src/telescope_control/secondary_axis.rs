@@ -0,0 +1,18 @@
+use super::dec_guide_output::DecGuideOutput;
+
+/// What moves the scope in declination when guiding, jogging or dithering
+pub enum SecondaryAxis<'a> {
+    GpioSt4(&'a DecGuideOutput), // Relays on the dec motor's ST-4 guide port
+    Motor,                       // The mount's own dec motor, driven through the connection
+}
+
+impl<'a> SecondaryAxis<'a> {
+    /// A configured guide output is used even on a mount with a dec motor. None if dec can't be moved.
+    pub fn new(output: Option<&'a DecGuideOutput>, dec_motor: bool) -> Option<Self> {
+        match output {
+            Some(output) => Some(Self::GpioSt4(output)),
+            None if dec_motor => Some(Self::Motor),
+            None => None,
+        }
+    }
+}
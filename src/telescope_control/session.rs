@@ -0,0 +1,404 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::config::SessionSettings;
+use crate::util::*;
+use ascom_alpaca::api::PutPulseGuideDirection;
+use ascom_alpaca::{ASCOMError, ASCOMErrorCode, ASCOMResult};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetRecord {
+    pub time: DateTime<Utc>,
+    pub right_ascension: Hours,
+    pub declination: Degrees,
+}
+
+/// Guide pulses in one direction
+#[derive(Debug, Copy, Clone, Default, Serialize)]
+pub struct PulseTotals {
+    pub count: u32,
+    pub total_millis: u64,
+    pub correction_arcsec: f64, // On the axis, at the guide rate
+}
+
+#[derive(Debug, Copy, Clone, Default, Serialize)]
+pub struct GuideTotals {
+    pub north: PulseTotals,
+    pub south: PulseTotals,
+    pub east: PulseTotals,
+    pub west: PulseTotals,
+}
+
+impl GuideTotals {
+    fn get_mut(&mut self, direction: PutPulseGuideDirection) -> &mut PulseTotals {
+        match direction {
+            PutPulseGuideDirection::North => &mut self.north,
+            PutPulseGuideDirection::South => &mut self.south,
+            PutPulseGuideDirection::East => &mut self.east,
+            PutPulseGuideDirection::West => &mut self.west,
+        }
+    }
+}
+
+/// A stretch of the session with tracking off
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackingInterruption {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>, // None while still stopped
+    pub reason: String,
+}
+
+/// The log keeps the latest this many entries, so a long night doesn't grow it without bound
+const MAX_LOG_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub time: DateTime<Utc>,
+    pub message: String,
+}
+
+/// Everything that happened between the start and end of one observing session
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    pub night: NaiveDate,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub slews: u32,
+    pub pulse_guides: u32,
+    pub guiding: GuideTotals,
+    pub parks: u32,
+    pub meridian_flips: u32,
+    pub tracking_interruptions: Vec<TrackingInterruption>,
+    pub targets: Vec<TargetRecord>,
+    pub log: VecDeque<LogEntry>, // The latest MAX_LOG_ENTRIES
+}
+
+impl Session {
+    fn new(night: NaiveDate) -> Self {
+        Self {
+            night,
+            started_at: Utc::now(),
+            ended_at: None,
+            slews: 0,
+            pulse_guides: 0,
+            guiding: GuideTotals::default(),
+            parks: 0,
+            meridian_flips: 0,
+            tracking_interruptions: Vec::new(),
+            targets: Vec::new(),
+            log: VecDeque::new(),
+        }
+    }
+
+    /// Time with tracking off, up to now for an interruption still going on
+    pub fn tracking_interrupted_secs(&self) -> f64 {
+        let now = self.ended_at.unwrap_or_else(Utc::now);
+        self.tracking_interruptions
+            .iter()
+            .map(|i| (i.ended_at.unwrap_or(now) - i.started_at).num_milliseconds() as f64 / 1000.)
+            .sum()
+    }
+
+    /// One metric per row, for a spreadsheet next to the image log
+    pub fn summary_csv(&self) -> String {
+        let mut rows = vec![
+            ("night".to_string(), self.night.to_string()),
+            ("started_at".to_string(), self.started_at.to_rfc3339()),
+            (
+                "ended_at".to_string(),
+                self.ended_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            ),
+            ("slews".to_string(), self.slews.to_string()),
+            (
+                "meridian_flips".to_string(),
+                self.meridian_flips.to_string(),
+            ),
+            ("parks".to_string(), self.parks.to_string()),
+            (
+                "tracking_interruptions".to_string(),
+                self.tracking_interruptions.len().to_string(),
+            ),
+            (
+                "tracking_interrupted_secs".to_string(),
+                format!("{:.1}", self.tracking_interrupted_secs()),
+            ),
+            ("pulse_guides".to_string(), self.pulse_guides.to_string()),
+        ];
+        let g = &self.guiding;
+        for (name, totals) in [
+            ("north", g.north),
+            ("south", g.south),
+            ("east", g.east),
+            ("west", g.west),
+        ] {
+            rows.push((format!("guide_{}_pulses", name), totals.count.to_string()));
+            rows.push((
+                format!("guide_{}_millis", name),
+                totals.total_millis.to_string(),
+            ));
+            rows.push((
+                format!("guide_{}_arcsec", name),
+                format!("{:.2}", totals.correction_arcsec),
+            ));
+        }
+
+        let mut csv = "metric,value\n".to_string();
+        for (metric, value) in rows {
+            csv += &format!("{},{}\n", metric, value);
+        }
+        csv
+    }
+}
+
+/// The night a time belongs to, named by the local date on which it started
+/// Sessions roll over at local solar noon so a night isn't split at midnight
+pub fn night_of(time: DateTime<Utc>, longitude: Degrees) -> NaiveDate {
+    let local_solar_offset = chrono::Duration::seconds((longitude * 240.) as i64);
+    (time + local_solar_offset - chrono::Duration::hours(12)).date_naive()
+}
+
+/// Brackets statistics, logs and target history into observing sessions
+pub struct SessionLog {
+    settings: SessionSettings,
+    current: Mutex<Option<Session>>,
+    last_summary: Mutex<Option<Session>>,
+}
+
+impl SessionLog {
+    pub fn new(settings: SessionSettings) -> Self {
+        Self {
+            settings,
+            current: Mutex::new(None),
+            last_summary: Mutex::new(None),
+        }
+    }
+
+    /// Starts a new session if this is the first connection of the night.
+    /// The connection goes ahead even if last night's summary can't be written.
+    pub async fn on_connect(&self, longitude: Degrees) {
+        if !self.settings.auto_start {
+            return;
+        }
+
+        let tonight = night_of(Utc::now(), longitude);
+        let current_night = self.current.lock().unwrap().as_ref().map(|s| s.night);
+        if current_night != Some(tonight) {
+            // Ended here so a failed summary write is logged rather than stopping the new session
+            if let Err(e) = self.end().await {
+                tracing::error!("{}", e.message);
+            }
+            if let Err(e) = self.start(longitude).await {
+                tracing::error!("{}", e.message);
+            }
+        }
+        self.log("Connected");
+    }
+
+    /// Ends any running session and starts a new one
+    pub async fn start(&self, longitude: Degrees) -> ASCOMResult<Session> {
+        self.end().await?;
+        let session = Session::new(night_of(Utc::now(), longitude));
+        tracing::info!(
+            "Starting observing session for the night of {}",
+            session.night
+        );
+        *self.current.lock().unwrap() = Some(session.clone());
+        Ok(session)
+    }
+
+    /// Ends the running session, writing its summary to disk
+    /// Returns None if no session was running
+    pub async fn end(&self) -> ASCOMResult<Option<Session>> {
+        let session = self.current.lock().unwrap().take();
+        let mut session = match session {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+        session.ended_at = Some(Utc::now());
+        tracing::info!(
+            "Ending observing session for the night of {}",
+            session.night
+        );
+
+        *self.last_summary.lock().unwrap() = Some(session.clone());
+        self.write_summary(&session).await?;
+        Ok(Some(session))
+    }
+
+    async fn write_summary(&self, session: &Session) -> ASCOMResult<()> {
+        let dir = PathBuf::from(&self.settings.summary_dir);
+        let path = dir.join(format!(
+            "session-{}.json",
+            session.started_at.format("%Y%m%dT%H%M%SZ")
+        ));
+
+        let result = async {
+            let contents = serde_json::to_string_pretty(session)?;
+            tokio::fs::create_dir_all(&dir).await?;
+            tokio::fs::write(&path, contents).await
+        }
+        .await;
+
+        result.map_err(|e: std::io::Error| {
+            ASCOMError::new(
+                ASCOMErrorCode::new_for_driver(5),
+                format_args!(
+                    "Couldn't write session summary to {}: {}",
+                    path.display(),
+                    e
+                ),
+            )
+        })
+    }
+
+    pub fn current(&self) -> Option<Session> {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// The summary of the most recently ended session
+    pub fn last_summary(&self) -> Option<Session> {
+        self.last_summary.lock().unwrap().clone()
+    }
+
+    fn with_current(&self, f: impl FnOnce(&mut Session)) {
+        if let Some(session) = self.current.lock().unwrap().as_mut() {
+            f(session)
+        }
+    }
+
+    pub fn log(&self, message: impl Into<String>) {
+        let message = message.into();
+        self.with_current(|s| {
+            if s.log.len() == MAX_LOG_ENTRIES {
+                s.log.pop_front();
+            }
+            s.log.push_back(LogEntry {
+                time: Utc::now(),
+                message,
+            })
+        });
+    }
+
+    pub fn record_slew(&self, right_ascension: Hours, declination: Degrees) {
+        self.with_current(|s| {
+            s.slews += 1;
+            s.targets.push(TargetRecord {
+                time: Utc::now(),
+                right_ascension,
+                declination,
+            });
+        });
+    }
+
+    /// rate is the guide rate on the pulse's axis, in degrees/sec
+    pub fn record_pulse_guide(
+        &self,
+        direction: PutPulseGuideDirection,
+        duration_millis: u32,
+        rate: Degrees,
+    ) {
+        self.with_current(|s| {
+            s.pulse_guides += 1;
+            let totals = s.guiding.get_mut(direction);
+            totals.count += 1;
+            totals.total_millis += duration_millis as u64;
+            totals.correction_arcsec += rate * 3600. * duration_millis as f64 / 1000.;
+        });
+    }
+
+    pub fn record_meridian_flip(&self) {
+        self.with_current(|s| s.meridian_flips += 1);
+        self.log("Meridian flip");
+    }
+
+    pub fn record_tracking_stopped(&self, reason: impl Into<String>) {
+        let reason = reason.into();
+        self.with_current(|s| {
+            if s.tracking_interruptions
+                .last()
+                .map_or(true, |i| i.ended_at.is_some())
+            {
+                s.tracking_interruptions.push(TrackingInterruption {
+                    started_at: Utc::now(),
+                    ended_at: None,
+                    reason,
+                });
+            }
+        });
+    }
+
+    pub fn record_tracking_resumed(&self) {
+        self.with_current(|s| {
+            if let Some(interruption) = s.tracking_interruptions.last_mut() {
+                interruption.ended_at.get_or_insert_with(Utc::now);
+            }
+        });
+    }
+
+    pub fn record_park(&self) {
+        self.with_current(|s| s.parks += 1);
+        self.log("Parked");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_night_of() {
+        let evening = Utc.with_ymd_and_hms(2023, 5, 1, 21, 0, 0).unwrap();
+        let early_morning = Utc.with_ymd_and_hms(2023, 5, 2, 3, 0, 0).unwrap();
+        let next_evening = Utc.with_ymd_and_hms(2023, 5, 2, 21, 0, 0).unwrap();
+        let night = NaiveDate::from_ymd_opt(2023, 5, 1).unwrap();
+
+        assert_eq!(night_of(evening, 0.), night);
+        assert_eq!(night_of(early_morning, 0.), night);
+        assert_ne!(night_of(next_evening, 0.), night);
+
+        // 02:00 UTC is still the evening before in the western US
+        let utc_morning = Utc.with_ymd_and_hms(2023, 5, 2, 2, 0, 0).unwrap();
+        assert_eq!(night_of(utc_morning, -120.), night);
+    }
+
+    #[test]
+    fn test_guide_totals() {
+        let log = SessionLog::new(SessionSettings::default());
+        *log.current.lock().unwrap() = Some(Session::new(NaiveDate::default()));
+        let sidereal_half = 0.5 * 360. / 86164.0905;
+        log.record_pulse_guide(PutPulseGuideDirection::East, 1000, sidereal_half);
+        log.record_pulse_guide(PutPulseGuideDirection::East, 500, sidereal_half);
+        log.record_tracking_stopped("Tracking turned off");
+        log.record_tracking_resumed();
+
+        let session = log.current().unwrap();
+        assert_eq!(session.guiding.east.count, 2);
+        assert_eq!(session.guiding.east.total_millis, 1500);
+        assert!((session.guiding.east.correction_arcsec - 11.28).abs() < 0.01);
+        assert_eq!(session.guiding.north.count, 0);
+        assert!(session.tracking_interruptions[0].ended_at.is_some());
+        assert!(session.summary_csv().contains("guide_east_pulses,2\n"));
+    }
+
+    #[test]
+    fn test_log_keeps_latest() {
+        let log = SessionLog::new(SessionSettings::default());
+        *log.current.lock().unwrap() = Some(Session::new(NaiveDate::default()));
+        for i in 0..MAX_LOG_ENTRIES + 10 {
+            log.log(i.to_string());
+        }
+
+        let session = log.current().unwrap();
+        assert_eq!(session.log.len(), MAX_LOG_ENTRIES);
+        assert_eq!(session.log.front().unwrap().message, "10");
+        assert_eq!(
+            session.log.back().unwrap().message,
+            (MAX_LOG_ENTRIES + 9).to_string()
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::Notify;
+use tokio::time::{self, Instant};
+
+use crate::telescope_control::events::{Event, EventBus};
+
+/// What the mount is settling after
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettleSource {
+    Slew,
+    Park,
+    MoveAxis,
+    /// Started by a client after dithering through guide pulses
+    Dither,
+    /// The vibration guard after any rate change. Not reported as slewing.
+    Vibration,
+}
+
+#[derive(Default)]
+struct SettleState {
+    source: Option<SettleSource>,
+    deadline: Option<Instant>,
+    paused_remaining: Option<Duration>, // Set instead of the deadline while paused
+    stability_wait: Option<Duration>, // Still waiting this long past the deadline for stability to be confirmed
+    generation: u64,                  // Counts settles, so a confirmation can't end a later one
+}
+
+impl SettleState {
+    fn timer_remaining(&self) -> Option<Duration> {
+        if let Some(remaining) = self.paused_remaining {
+            return Some(remaining);
+        }
+        let remaining = self.deadline?.checked_duration_since(Instant::now())?;
+        (!remaining.is_zero()).then_some(remaining)
+    }
+
+    /// Time left to wait for stability once the timer has run out
+    fn stability_remaining(&self) -> Option<Duration> {
+        if self.timer_remaining().is_some() {
+            return None;
+        }
+        let confirm_by = self.deadline? + self.stability_wait?;
+        let remaining = confirm_by.checked_duration_since(Instant::now())?;
+        (!remaining.is_zero()).then_some(remaining)
+    }
+
+    fn remaining(&self) -> Option<Duration> {
+        self.timer_remaining()
+            .or_else(|| self.stability_remaining())
+    }
+}
+
+/// The post-motion settle time (SlewSettleTime) shared by every motion source.
+/// Waiting on it is cancellation safe, and it can be paused, extended or cancelled while running.
+/// With a stability wait, it keeps settling after the time runs out until `confirm_stable` or the wait is up.
+#[derive(Clone, Default)]
+pub struct SettleTimer {
+    state: Arc<Mutex<SettleState>>,
+    changed: Arc<Notify>,
+    stability_wait: Option<Duration>,
+    events: Option<EventBus>, // Told when a settle starts or gets longer
+}
+
+impl SettleTimer {
+    pub fn new(stability_wait: Option<Duration>) -> Self {
+        Self {
+            stability_wait,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn waits_for_stability(&self) -> bool {
+        self.stability_wait.is_some()
+    }
+
+    /// Starts settling, never shortening a settle time that's already running.
+    /// Stability has to be confirmed again after any new motion.
+    pub fn start(&self, source: SettleSource, duration: Duration) {
+        if duration.is_zero() && self.stability_wait.is_none() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state
+            .timer_remaining()
+            .map_or(true, |remaining| remaining < duration)
+        {
+            *state = SettleState {
+                source: Some(source),
+                deadline: Some(Instant::now() + duration),
+                paused_remaining: None,
+                stability_wait: self.stability_wait,
+                generation: state.generation + 1,
+            };
+            drop(state);
+            self.publish_settling();
+        } else {
+            state.stability_wait = self.stability_wait;
+        }
+        self.changed.notify_waiters();
+    }
+
+    /// Ends the wait for stability of the current settle. The settle time still has to run out.
+    pub fn confirm_stable(&self) {
+        self.state.lock().unwrap().stability_wait = None;
+        self.changed.notify_waiters();
+    }
+
+    /// True once the settle time has run out and stability hasn't been confirmed yet
+    pub fn is_awaiting_stability(&self) -> bool {
+        self.state.lock().unwrap().stability_remaining().is_some()
+    }
+
+    pub fn extend(&self, by: Duration) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(remaining) = state.paused_remaining.as_mut() {
+            *remaining += by;
+        } else if state.remaining().is_some() {
+            state.deadline = state.deadline.map(|deadline| deadline + by);
+        }
+        drop(state);
+        self.publish_settling();
+        self.changed.notify_waiters();
+    }
+
+    /// Holds the remaining time until resumed. Waiting for stability can't be paused.
+    pub fn pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.paused_remaining.is_none() {
+            if let Some(remaining) = state.timer_remaining() {
+                state.paused_remaining = Some(remaining);
+                state.deadline = None;
+            }
+        }
+        self.changed.notify_waiters();
+    }
+
+    pub fn resume(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(remaining) = state.paused_remaining.take() {
+            state.deadline = Some(Instant::now() + remaining);
+        }
+        self.changed.notify_waiters();
+    }
+
+    /// Ends the settle now, releasing anything waiting on it
+    pub fn cancel(&self) {
+        let mut state = self.state.lock().unwrap();
+        *state = SettleState {
+            generation: state.generation,
+            ..Default::default()
+        };
+        drop(state);
+        self.changed.notify_waiters();
+    }
+
+    /// None once settled
+    pub fn remaining(&self) -> Option<Duration> {
+        self.state.lock().unwrap().remaining()
+    }
+
+    /// When settling will be done if nothing changes. None once settled.
+    pub fn settling_until(&self) -> Option<DateTime<Utc>> {
+        let remaining = chrono::Duration::from_std(self.remaining()?).ok()?;
+        Some(Utc::now() + remaining)
+    }
+
+    fn publish_settling(&self) {
+        if let (Some(events), Some(source), Some(settling_until)) =
+            (&self.events, self.source(), self.settling_until())
+        {
+            events.publish(Event::Settling {
+                source,
+                settling_until,
+            });
+        }
+    }
+
+    pub fn source(&self) -> Option<SettleSource> {
+        let state = self.state.lock().unwrap();
+        state.remaining().and(state.source)
+    }
+
+    pub fn is_settling(&self) -> bool {
+        self.remaining().is_some()
+    }
+
+    /// Returns once settled. Safe to drop at any point.
+    pub async fn wait(&self) {
+        self.wait_while(|state| state.remaining().is_some()).await
+    }
+
+    /// Returns the generation of the next settle, after `last`, to start waiting for stability.
+    /// Safe to drop at any point.
+    pub async fn wait_for_stability_check(&self, last: u64) -> u64 {
+        self.wait_while(|state| {
+            !(state.generation != last && state.stability_remaining().is_some())
+        })
+        .await;
+        self.state.lock().unwrap().generation
+    }
+
+    /// Confirms stability only if the settle it was checked for is still running
+    pub fn confirm_stable_for(&self, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation == generation {
+            state.stability_wait = None;
+        }
+        drop(state);
+        self.changed.notify_waiters();
+    }
+
+    /// Rechecks on every change and whenever the time or the wait for stability runs out
+    async fn wait_while(&self, waiting: impl Fn(&SettleState) -> bool) {
+        loop {
+            // Registered before checking so a change in between isn't missed
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            let wake_in = {
+                let state = self.state.lock().unwrap();
+                if !waiting(&state) {
+                    return;
+                }
+                if state.paused_remaining.is_some() {
+                    None
+                } else {
+                    state.remaining()
+                }
+            };
+
+            match wake_in {
+                Some(wake_in) => {
+                    tokio::select! {
+                        _ = time::sleep(wake_in) => {}
+                        _ = changed => {}
+                    }
+                }
+                None => changed.await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_settle_timer() {
+        let timer = SettleTimer::default();
+        timer.wait().await; // Nothing to wait for
+
+        timer.start(SettleSource::Slew, Duration::from_millis(100));
+        timer.start(SettleSource::Park, Duration::from_millis(10)); // Doesn't shorten
+        assert_eq!(timer.source(), Some(SettleSource::Slew));
+
+        timer.pause();
+        let paused_remaining = timer.remaining().unwrap();
+        time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(timer.remaining(), Some(paused_remaining));
+        timer.resume();
+        timer.extend(Duration::from_millis(50));
+
+        let start = Instant::now();
+        timer.wait().await;
+        assert!(paused_remaining + Duration::from_millis(50) <= Instant::now() - start);
+        assert!(!timer.is_settling());
+
+        timer.start(SettleSource::MoveAxis, Duration::from_secs(60));
+        let waiter = tokio::spawn({
+            let timer = timer.clone();
+            async move { timer.wait().await }
+        });
+        time::sleep(Duration::from_millis(10)).await;
+        timer.cancel();
+        time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("cancel didn't release the waiter")
+            .unwrap();
+        assert_eq!(timer.source(), None);
+    }
+
+    #[tokio::test]
+    async fn test_settle_waits_for_stability() {
+        let timer = SettleTimer::new(Some(Duration::from_secs(60)));
+        timer.start(SettleSource::Slew, Duration::from_millis(20));
+        assert!(!timer.is_awaiting_stability());
+
+        let generation = time::timeout(Duration::from_secs(1), timer.wait_for_stability_check(0))
+            .await
+            .expect("the settle time didn't run out");
+        assert!(timer.is_awaiting_stability());
+        assert!(time::timeout(Duration::from_millis(50), timer.wait())
+            .await
+            .is_err());
+
+        // A confirmation for an earlier settle doesn't count
+        timer.confirm_stable_for(generation - 1);
+        assert!(timer.is_settling());
+        timer.confirm_stable_for(generation);
+        time::timeout(Duration::from_secs(1), timer.wait())
+            .await
+            .expect("confirming didn't end the settle");
+
+        // Gives up waiting after the stability wait
+        let timer = SettleTimer::new(Some(Duration::from_millis(30)));
+        timer.start(SettleSource::MoveAxis, Duration::ZERO);
+        assert!(timer.is_awaiting_stability());
+        time::timeout(Duration::from_secs(1), timer.wait())
+            .await
+            .expect("the stability wait didn't run out");
+    }
+}
@@ -2,6 +2,7 @@ use crate::astro_math::{deg_to_hours, hours_to_deg, modulo};
 use crate::telescope_control::connection::consts::{
     SLEW_SPEED_AGAINST_TRACKING, SLEW_SPEED_WITH_TRACKING,
 };
+use crate::telescope_control::connection::SpeedCapabilities;
 use crate::tracking_direction::TrackingDirection;
 use crate::util::*;
 use crate::StarAdventurer;
@@ -16,12 +17,15 @@ pub struct Slew {
 }
 
 impl Slew {
-    fn find_best(start_mech_ha: Hours, mount_limits: MountLimits, options: Vec<Self>) -> Self {
+    fn find_best(
+        start_mech_ha: Hours,
+        mount_limits: MountLimits,
+        options: Vec<Self>,
+    ) -> Option<Self> {
         options
             .into_iter()
             .filter(|s| mount_limits.is_valid_slew(start_mech_ha, s))
             .min_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap())
-            .expect("No valid slew -- this shouldn't happen")
     }
 
     pub fn to_mech_ha(
@@ -46,14 +50,16 @@ impl Slew {
                 },
             ],
         )
+        .expect("No valid slew -- this shouldn't happen")
     }
 
+    /// None if the mount limits block every path to the target
     pub fn to_ha(
         current_mech_ha: Hours,
         target_ha: Hours,
         current_pier_side: SideOfPier,
         mount_limits: MountLimits,
-    ) -> Self {
+    ) -> Option<Self> {
         let east_ha = StarAdventurer::calc_ha_from_mech_ha(current_mech_ha, SideOfPier::East);
         let west_ha = StarAdventurer::calc_ha_from_mech_ha(current_mech_ha, SideOfPier::West);
 
@@ -85,7 +91,12 @@ impl Slew {
         )
     }
 
-    pub fn change_ra(current_mech_ha: Hours, ra_change: Hours, mount_limits: MountLimits) -> Self {
+    /// None if the mount limits block every path to the target
+    pub fn change_ra(
+        current_mech_ha: Hours,
+        ra_change: Hours,
+        mount_limits: MountLimits,
+    ) -> Option<Self> {
         let ra_change = modulo(ra_change, 24.);
         let ra_change_with_flip = modulo(ra_change + 12., 24.);
         let pos_ha_change = Self::ha_change_from_ra_change(ra_change - 24.);
@@ -121,6 +132,27 @@ impl Slew {
         )
     }
 
+    /// Half a turn to point at the same place from the other side of the pier.
+    /// None if the mount limits block both ways round.
+    pub fn flip(current_mech_ha: Hours, mount_limits: MountLimits) -> Option<Self> {
+        Self::find_best(
+            current_mech_ha,
+            mount_limits,
+            vec![
+                Self {
+                    distance: 12.,
+                    direction: TrackingDirection::WithTracking,
+                    meridian_flip: true,
+                },
+                Self {
+                    distance: 12.,
+                    direction: TrackingDirection::AgainstTracking,
+                    meridian_flip: true,
+                },
+            ],
+        )
+    }
+
     pub fn distance(&self) -> Hours {
         self.distance
     }
@@ -133,14 +165,12 @@ impl Slew {
         self.meridian_flip
     }
 
-    pub fn estimate_slew_time(&self) -> Duration {
+    /// At the goto speeds of the controller
+    pub fn estimate_slew_time(&self, speed: &SpeedCapabilities) -> Duration {
+        let (with_tracking, against_tracking) = speed.goto_speeds();
         Duration::from_secs_f64(match self.direction {
-            TrackingDirection::WithTracking => {
-                hours_to_deg(self.distance) / SLEW_SPEED_WITH_TRACKING
-            }
-            TrackingDirection::AgainstTracking => {
-                hours_to_deg(self.distance) / SLEW_SPEED_AGAINST_TRACKING
-            }
+            TrackingDirection::WithTracking => hours_to_deg(self.distance) / with_tracking,
+            TrackingDirection::AgainstTracking => hours_to_deg(self.distance) / against_tracking,
         })
     }
 
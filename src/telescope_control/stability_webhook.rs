@@ -0,0 +1,46 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::Client;
+use serde_json::json;
+
+use crate::config::SettleSettings;
+use crate::telescope_control::StarAdventurer;
+use crate::util::{parse_webhook_uri, post_json};
+
+/// Asks the stability webhook about each settle once its time runs out, ending the settle when it says the mount is still
+pub fn start(settings: &SettleSettings, sa: Arc<StarAdventurer>) {
+    let url = match &settings.stability_webhook {
+        Some(url) => url.clone(),
+        None => return,
+    };
+    let uri = match parse_webhook_uri(&url) {
+        Ok(uri) => uri,
+        Err(e) => {
+            tracing::error!("Not using the stability webhook: {}", e);
+            return;
+        }
+    };
+    if !settings.wait_for_stability {
+        tracing::warn!("The stability webhook is only called with wait-for-stability on");
+        return;
+    }
+    let max_wait = Duration::from_secs(settings.max_stability_wait_secs);
+
+    tokio::spawn(async move {
+        let client = Client::new();
+        let settle_timer = sa.settle_timer();
+        let mut generation = 0;
+        loop {
+            generation = settle_timer.wait_for_stability_check(generation).await;
+            let json = json!({ "source": settle_timer.source() }).to_string();
+
+            // The settle ends on its own when the wait runs out, so the check can too
+            match tokio::time::timeout(max_wait, post_json(&client, &uri, json)).await {
+                Ok(Ok(())) => settle_timer.confirm_stable_for(generation),
+                Ok(Err(e)) => tracing::warn!("Stability webhook {} failed: {}", url, e),
+                Err(_) => tracing::warn!("Stability webhook {} didn't confirm in time", url),
+            }
+        }
+    });
+}
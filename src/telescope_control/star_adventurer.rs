@@ -1,23 +1,52 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::Duration;
 
 use synscan::AutoGuideSpeed;
-use tokio::join;
 use tokio::sync::RwLock;
 
-use crate::config::TelescopeDetails;
+use crate::config::{
+    CenteringSettings, DeclinationKnob, JogSettings, MovePresets, ParkApproach, ParkStore,
+    PointingModelSettings, SafeAction, SlewSafety, TelescopeDetails, TrackingLimitAction,
+};
 use crate::rotation_direction::{RotationDirection, RotationDirectionKey};
 use crate::telescope_control::connection::*;
 use crate::tracking_direction::TrackingDirection;
 use crate::util::*;
 use crate::{astro_math, config, Config};
 
+use super::clock_monitor::ClockMonitor;
+use super::commands::centering::CenteringRun;
+use super::commands::guide::GuideHistory;
+use super::commands::parking::PositionProblem;
+use super::commands::pointing_pos::FrozenCoordinates;
+use super::commands::slew::InterruptedSlew;
+use super::commands::slew_progress::ActiveSlew;
 use super::commands::target::Target;
+use super::commands::tracking::CustomTrackingRate;
+use super::dec_guide_output::DecGuideOutput;
+use super::events::EventBus;
+use super::gps::GpsReceiver;
+use super::guide_batcher::GuideBatcher;
+use super::guide_latency::GuideLatency;
+use super::idle_watchdog::IdleWatchdog;
+use super::motor_watchdog::MotorWatchdog;
+use super::mount_profile::MountProfile;
+use super::pointing_model::PointingModel;
+use super::power_switch::PowerSwitch;
+use super::scheduler::Scheduler;
+use super::secondary_axis::SecondaryAxis;
+use super::session::SessionLog;
+use super::settle_timer::SettleTimer;
+use super::vibration_guard::VibrationGuard;
 use ascom_alpaca::api::{DriveRate, SideOfPier};
 use ascom_alpaca::ASCOMResult;
 
 pub enum DeclinationSlew {
     Waiting {
-        dec_change: Degrees,
+        dec_change: Degrees, // Still to turn
+        turned: Degrees,     // Reported turned so far, already added to the declination
         meridian_flip: bool,
         finisher: WaitableTaskFinisher<AbortResult<(), ()>>,
     },
@@ -34,6 +63,19 @@ pub struct StarAdventurer {
     pub(in crate::telescope_control) settings: Settings,
     pub(in crate::telescope_control) connection: Connection,
     pub(in crate::telescope_control) dec_slew: RwLock<DeclinationSlew>,
+    pub(in crate::telescope_control) power_switch: Option<PowerSwitch>,
+    pub(in crate::telescope_control) dec_guide_output: Option<DecGuideOutput>, // Relays for guiding dec
+    pub(in crate::telescope_control) gps: Option<GpsReceiver>,
+    pub(in crate::telescope_control) guide_batcher: GuideBatcher,
+    pub(in crate::telescope_control) guide_latency: GuideLatency,
+    pub(in crate::telescope_control) idle_watchdog: IdleWatchdog,
+    pub(in crate::telescope_control) events: EventBus,
+    pub(in crate::telescope_control) vibration_guard: VibrationGuard,
+    pub(in crate::telescope_control) settle_timer: SettleTimer,
+    pub(in crate::telescope_control) session: SessionLog,
+    pub(in crate::telescope_control) clock_monitor: ClockMonitor,
+    pub(in crate::telescope_control) motor_watchdog: MotorWatchdog,
+    pub(in crate::telescope_control) scheduler: Scheduler,
 }
 
 impl std::fmt::Debug for StarAdventurer {
@@ -45,19 +87,79 @@ impl std::fmt::Debug for StarAdventurer {
 impl StarAdventurer {
     pub async fn new(config: &Config) -> Self {
         let mut cb = ConnectionBuilder::new()
-            .with_timeout(Duration::from_millis(config.com.timeout_millis as u64));
+            .with_timeout(Duration::from_millis(config.com.timeout_millis as u64))
+            .with_max_acceleration(config.ramp.max_acceleration)
+            .with_position_freshness(Duration::from_millis(config.com.position_cache_millis))
+            .with_backlash(config.telescope_details.ra_backlash_arcsec / 3600.)
+            .with_retry_policy(RetryPolicy {
+                tries: config.com.tries,
+                delay: Duration::from_millis(config.com.retry_delay_millis),
+                backoff: config.com.retry_backoff,
+            });
 
         if config.com.path.is_some() {
             cb = cb.with_path(config.com.path.clone().unwrap());
         }
+        if let Some(trace_file) = &config.com.trace_file {
+            cb = cb.with_trace(trace_file.clone());
+        }
 
         let settings = Settings::new(config);
+        let clock_monitor = ClockMonitor::start(&settings.date_offset);
+        let events = EventBus::new();
+        let connection = Connection::new(cb, events.clone());
+        let motor_watchdog = MotorWatchdog::start(connection.clone(), events.clone());
+        let settle_timer = SettleTimer::new(
+            config
+                .settle
+                .wait_for_stability
+                .then(|| Duration::from_secs(config.settle.max_stability_wait_secs)),
+        )
+        .with_events(events.clone());
 
         StarAdventurer {
             settings,
-            connection: Connection::new(cb),
+            connection,
             dec_slew: RwLock::new(DeclinationSlew::Idle),
+            power_switch: PowerSwitch::new(&config.power),
+            dec_guide_output: DecGuideOutput::new(&config.dec_guide_output),
+            gps: GpsReceiver::new(&config.gps),
+            guide_batcher: GuideBatcher::new(config.guide_batch),
+            guide_latency: GuideLatency::default(),
+            idle_watchdog: IdleWatchdog::new(&config.idle_safety),
+            events,
+            vibration_guard: VibrationGuard::new(config.vibration_guard, settle_timer.clone()),
+            settle_timer,
+            session: SessionLog::new(config.session.clone()),
+            clock_monitor,
+            motor_watchdog,
+            scheduler: Scheduler::default(),
+        }
+    }
+
+    /// Saves positions set by SetPark and parks to the state file, and takes back a park from the last run
+    pub fn with_park_store(mut self, park_store: ParkStore) -> Self {
+        match park_store.load_state() {
+            Ok(state) => self.settings.parked_motor_pos = RwLock::new(state.parked_motor_pos),
+            Err(e) => tracing::warn!("Couldn't load the parked motor position: {}", e),
         }
+        self.settings.park_store = Some(park_store);
+        self
+    }
+
+    /// For delivering events outside the driver
+    pub fn events(&self) -> EventBus {
+        self.events.clone()
+    }
+
+    /// The post-motion settle shared by slews, parks and MoveAxis
+    pub fn settle_timer(&self) -> SettleTimer {
+        self.settle_timer.clone()
+    }
+
+    /// A client was heard from, resetting the idle-safety timeout
+    pub fn note_client_activity(&self) {
+        self.idle_watchdog.note_activity();
     }
 
     pub async fn is_connected(&self) -> bool {
@@ -65,9 +167,45 @@ impl StarAdventurer {
     }
 
     pub async fn connect(&self) -> ASCOMResult<()> {
-        self.connection
-            .connect(*self.settings.autoguide_speed.read().await)
-            .await
+        let powered_off_at_park = match &self.power_switch {
+            Some(power_switch) => power_switch.power_on().await?,
+            None => false,
+        };
+
+        // Without the pointing, which isn't known yet, for smart tracking
+        let tracking_rate = self
+            .calc_tracking_motion_rate(*self.settings.tracking_rate.read().await, None)
+            .await;
+        self.connection.connect(tracking_rate).await?;
+        if self.resolve_profile().await.dec_motor {
+            if let Err(e) = self.connection.connect_dec_axis().await {
+                self.connection.disconnect().await;
+                return Err(e);
+            }
+        }
+        if self.settings.alt_az && self.connection.is_tracking().await? {
+            // Carried on from before, but tracking the RA axis doesn't follow the sky in alt-az
+            tracing::warn!("Stopping tracking, which the mount can't do in alt-az mode");
+            self.connection.stop_tracking().await?;
+        }
+        // Mounts without an autoguide port may not take the command
+        if self.settings.profile.get().st4_port {
+            let speed = *self.settings.autoguide_speed.read().await;
+            if let Err(e) = self.connection.set_autoguide_speed(speed).await {
+                self.connection.disconnect().await;
+                return Err(e);
+            }
+        }
+
+        if powered_off_at_park {
+            self.restore_park_after_power_cycle().await?;
+        } else if let Some(parked_pos) = *self.settings.parked_motor_pos.read().await {
+            self.restore_saved_park(parked_pos).await?;
+        }
+
+        let longitude = self.settings.observation_location.get().longitude;
+        self.session.on_connect(longitude).await;
+        Ok(())
     }
 
     pub async fn disconnect(&self) -> ASCOMResult<()> {
@@ -90,65 +228,158 @@ impl StarAdventurer {
 
     pub(in crate::telescope_control) async fn get_mech_ha(&self) -> ASCOMResult<Hours> {
         let pos = self.connection.get_pos().await?;
-        let (mech_ha_offset, obs_loc) = join!(
-            async { *self.settings.mech_ha_offset.read().await },
-            async { *self.settings.observation_location.read().await },
-        );
+        Ok(self.calc_mech_ha_at(pos))
+    }
 
-        Ok(Self::calc_mech_ha(
+    /// Two-axis mounts slew and guide dec on their own motor. Known once connected for model = "auto".
+    pub(in crate::telescope_control) fn has_dec_motor(&self) -> bool {
+        self.settings.profile.get().dec_motor
+    }
+
+    /// What moves the scope in dec, if anything
+    pub(in crate::telescope_control) fn secondary_axis(&self) -> Option<SecondaryAxis<'_>> {
+        SecondaryAxis::new(self.dec_guide_output.as_ref(), self.has_dec_motor())
+    }
+
+    /// The mechanical HA with the motor at pos
+    pub(in crate::telescope_control) fn calc_mech_ha_at(&self, pos: Degrees) -> Hours {
+        Self::calc_mech_ha(
             pos,
-            mech_ha_offset,
-            obs_loc.get_rotation_direction_key(),
-        ))
+            self.settings.mech_ha_offset.get(),
+            self.settings
+                .observation_location
+                .get()
+                .get_rotation_direction_key(),
+        )
     }
 }
 
 pub(in crate::telescope_control) struct Settings {
     // Not affected by motor state, Only changed by specific requests
-    pub observation_location: RwLock<config::ObservingLocation>,
-    pub date_offset: RwLock<chrono::Duration>,
+    // Polled constantly, so published rather than locked. See `Published`
+    pub observation_location: Published<config::ObservingLocation>,
+    pub date_offset: Arc<RwLock<chrono::Duration>>, // Shared with the clock monitor
     pub instant_dec_slew: RwLock<bool>,
 
     pub park_ha: RwLock<Hours>, // Mechanical HA, 0..24
+    pub park_fixed: bool,       // Set by park-alt-az, so SetPark isn't allowed
+    pub park_store: Option<ParkStore>,
+    pub park_approach: Option<ParkApproach>,
+    pub goto_approach: Option<Degrees>, // Length of the final leg of gotos, moving with tracking
+    pub resume_tracking_on_unpark: bool,
+    pub tracking_before_park: RwLock<Option<DriveRate>>, // The tracking rate when last parked, if it was tracking
     pub mount_limits: RwLock<MountLimits>,
+    pub tracking_limit_action: TrackingLimitAction,
+    pub on_shutdown: SafeAction,
+    pub stopped_at_limit: AtomicBool, // Set when the motion limit monitor stops a pulse, for the pulse to report
     pub target: RwLock<Target>,
+    pub stopped_target: RwLock<Option<Target>>, // What was being tracked when tracking was last stopped
 
     pub post_slew_settle_time: RwLock<u32>,
     pub autoguide_speed: RwLock<AutoGuideSpeed>, // Set to motor on connection
 
     pub tracking_rate: RwLock<DriveRate>, // Read from motor on connection
+    pub ra_rate_offset: RwLock<f64>,      // RightAscensionRate, seconds of RA per sidereal second
+    pub custom_tracking_rates: BTreeMap<String, Degrees>,
+    pub custom_tracking_rate: RwLock<Option<CustomTrackingRate>>, // Used in place of tracking_rate if set
 
     // Pos
-    pub mech_ha_offset: RwLock<Hours>, // Mechanical HA, 0..24
+    pub mech_ha_offset: Published<Hours>, // Mechanical HA, 0..24
     pub declination: RwLock<Degrees>,
-    pub pier_side: RwLock<SideOfPier>,
+    /// Dec motor position a MoveAxis(Secondary) started from, until it's stopped
+    pub dec_move_start: RwLock<Option<Degrees>>,
+    pub pointing_model: RwLock<PointingModel>, // Maps the above to where the scope really points
+    pub pier_side: Published<SideOfPier>,
+    pub parked_motor_pos: RwLock<Option<Degrees>>, // Where the motor stopped, while parked. Saved by the park store
+    pub position_problem: RwLock<Option<PositionProblem>>, // Set on unpark until re-synced
+    pub unpark_position_tolerance: Degrees,
+
+    pub sky_clock_follows_utc_date: bool,
+    pub freeze_coordinates_while_stopped: bool,
+    pub smart_tracking: bool,
+    pub ephemeris_lunar_tracking: bool, // The lunar rate follows the Moon's motion
+    pub refined_king_rate: bool,        // The King rate is computed for the current pointing
+    pub alt_az: bool, // The RA axis turns in azimuth and the declination knob sets the altitude
+    pub frozen_coordinates: RwLock<Option<FrozenCoordinates>>,
+    pub reckoned_pos: PositionCache, // Dead reckoned between motor reads so readouts move smoothly
+    pub interrupted_slew: Arc<std::sync::Mutex<Option<InterruptedSlew>>>, // Shared with the slew task
+    pub active_slew: Arc<std::sync::Mutex<Option<ActiveSlew>>>, // Shared with the slew task
+    pub centering_run: RwLock<Option<CenteringRun>>,
+    pub guide_history: std::sync::Mutex<GuideHistory>,
 
     pub telescope_details: TelescopeDetails,
+    pub profile: Published<&'static MountProfile>, // Of the configured model, or the one detected on connecting
+    pub dec_knob: DeclinationKnob,
+    pub slew_safety: RwLock<SlewSafety>, // Live, so the horizon can be edited mid session
+    pub move_presets: MovePresets,
+    pub centering: CenteringSettings,
+    pub jog: JogSettings,
+    pub pointing_model_settings: PointingModelSettings,
 }
 
 impl Settings {
     pub fn new(config: &Config) -> Self {
         Settings {
-            observation_location: RwLock::new(config.observation_location),
-            park_ha: RwLock::new(astro_math::modulo(config.other.park_hour_angle, 24.)), // Mechanical hour angle
+            observation_location: Published::new(config.observation_location),
+            park_ha: RwLock::new(StarAdventurer::calc_park_ha(config)), // Mechanical hour angle
+            park_fixed: config.other.park_alt_az.is_some(),
+            park_store: None, // Only the server saves to its config
+            park_approach: config.other.park_approach,
+            goto_approach: config.other.goto_approach,
+            resume_tracking_on_unpark: config.other.resume_tracking_on_unpark,
+            tracking_before_park: RwLock::new(None),
             mount_limits: RwLock::new(MountLimits::new(
                 config.other.mount_limit_east,
                 config.other.mount_limit_west,
+                config.other.flip_before_limit,
             )),
-            declination: RwLock::new(config.initialization.declination), // Set only by sync or goto
+            tracking_limit_action: config.other.tracking_limit_action,
+            on_shutdown: config.other.on_shutdown,
+            stopped_at_limit: AtomicBool::new(false),
+            declination: RwLock::new(config.initialization.declination), // Set by sync, goto and moving the dec motor
+            dec_move_start: RwLock::new(None),
             // hour_angle_offset: RwLock::new(StarAdventurer::calc_ha_from_mech_ha(
             //     config.initialization.hour_angle,
             //     config.initialization.pier_side,
             // )),
-            mech_ha_offset: RwLock::new(config.initialization.hour_angle),
+            mech_ha_offset: Published::new(config.initialization.hour_angle),
+            pointing_model: RwLock::new(PointingModel::default()), // Built up by syncs
             autoguide_speed: RwLock::new(config.other.auto_guide_speed), // Write only
-            pier_side: RwLock::new(config.initialization.pier_side),
-            date_offset: RwLock::new(chrono::Duration::zero()), // Assume using computer time
+            pier_side: Published::new(config.initialization.pier_side),
+            parked_motor_pos: RwLock::new(None),
+            position_problem: RwLock::new(None),
+            unpark_position_tolerance: config.other.unpark_position_tolerance,
+            date_offset: Arc::new(RwLock::new(chrono::Duration::zero())), // Assume using computer time
             post_slew_settle_time: RwLock::new(config.other.slew_settle_time),
             target: RwLock::new(Target::default()), // No target initially
+            stopped_target: RwLock::new(None),
             tracking_rate: RwLock::new(DriveRate::Sidereal),
+            ra_rate_offset: RwLock::new(0.),
+            custom_tracking_rates: config.custom_tracking_rates.clone(),
+            custom_tracking_rate: RwLock::new(None),
             instant_dec_slew: RwLock::new(config.other.instant_dec_slew),
+            sky_clock_follows_utc_date: config.other.sky_clock_follows_utc_date,
+            freeze_coordinates_while_stopped: config.other.freeze_coordinates_while_stopped,
+            smart_tracking: config.other.smart_tracking,
+            ephemeris_lunar_tracking: config.other.ephemeris_lunar_tracking,
+            refined_king_rate: config.other.refined_king_rate,
+            alt_az: config.other.alt_az,
+            frozen_coordinates: RwLock::new(None),
+            reckoned_pos: PositionCache::new(Duration::from_millis(
+                config.other.dead_reckoning_millis,
+            )),
+            interrupted_slew: Arc::new(std::sync::Mutex::new(None)),
+            active_slew: Arc::new(std::sync::Mutex::new(None)),
+            centering_run: RwLock::new(None),
+            guide_history: std::sync::Mutex::new(GuideHistory::default()),
             telescope_details: config.telescope_details,
+            profile: Published::new(MountProfile::for_model(config.telescope_details.model)),
+            dec_knob: config.declination_knob,
+            slew_safety: RwLock::new(config.slew_safety.clone()),
+            move_presets: config.move_presets,
+            centering: config.centering,
+            jog: config.jog.clone(),
+            pointing_model_settings: config.pointing_model,
         }
     }
 }
@@ -1,8 +1,93 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::{self, JoinHandle};
+use tokio::time;
+
 use crate::config::Config;
 
+use super::dec_knob::DeclinationSlewPlan;
 use super::StarAdventurer;
 
-pub(in crate::telescope_control) async fn create_sa(config: Option<Config>) -> StarAdventurer {
+pub(crate) async fn create_sa(config: Option<Config>) -> StarAdventurer {
     let config = config.unwrap_or_else(|| confy::load_path("test_config.toml").unwrap());
     StarAdventurer::new(&config).await
 }
+
+/// Test config where declination slews wait for the user instead of completing instantly
+pub(crate) fn manual_dec_config() -> Config {
+    let mut config: Config = confy::load_path("test_config.toml").unwrap();
+    config.other.instant_dec_slew = false;
+    config
+}
+
+async fn next_dec_request(sa: &StarAdventurer) -> DeclinationSlewPlan {
+    loop {
+        if let Some(plan) = sa.get_pending_dec_slew_plan().await {
+            return plan;
+        }
+        time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+/// Waits until a declination slew is waiting on the user
+pub(crate) async fn wait_for_dec_request(
+    sa: &StarAdventurer,
+    timeout: Duration,
+) -> Option<DeclinationSlewPlan> {
+    time::timeout(timeout, next_dec_request(sa)).await.ok()
+}
+
+/// How the simulated user answers a declination slew request
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum DecResponse {
+    /// Turns the knob and acknowledges after the delay
+    Acknowledge(Duration),
+    /// Gives up and aborts the slew after the delay
+    Abort(Duration),
+    /// Never answers
+    Ignore,
+}
+
+/// Stands in for the person turning the declination knob.
+/// Answers each declination slew request in turn with the next scripted response.
+pub(crate) struct DecOperator {
+    requests: Arc<Mutex<Vec<DeclinationSlewPlan>>>,
+    handle: JoinHandle<()>,
+}
+
+impl DecOperator {
+    pub fn start(sa: Arc<StarAdventurer>, responses: Vec<DecResponse>) -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let handle = task::spawn(async move {
+            for response in responses {
+                let plan = next_dec_request(&sa).await;
+                seen.lock().unwrap().push(plan);
+                match response {
+                    DecResponse::Acknowledge(delay) => {
+                        time::sleep(delay).await;
+                        sa.complete_dec_slew().await;
+                    }
+                    DecResponse::Abort(delay) => {
+                        time::sleep(delay).await;
+                        sa.abort_dec_slew().await;
+                    }
+                    DecResponse::Ignore => return,
+                }
+            }
+        });
+        DecOperator { requests, handle }
+    }
+
+    /// The requests the operator has seen so far
+    pub fn requests(&self) -> Vec<DeclinationSlewPlan> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for DecOperator {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
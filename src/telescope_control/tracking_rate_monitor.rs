@@ -0,0 +1,27 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::telescope_control::StarAdventurer;
+
+// The Moon's rate and the refraction away from the horizon change by well under 0.1% in this time
+const UPDATE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Keeps the ephemeris lunar rate following the Moon, and the refined King rate the pointing, while tracking
+pub fn start(sa: Arc<StarAdventurer>) {
+    if !sa.settings.ephemeris_lunar_tracking && !sa.settings.refined_king_rate {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPDATE_INTERVAL);
+        loop {
+            interval.tick().await;
+            // Errors are mostly just being disconnected
+            if !sa.is_tracking().await.unwrap_or(false) {
+                continue;
+            }
+            if let Err(e) = sa.refresh_computed_tracking_rate().await {
+                tracing::warn!("Couldn't update the tracking rate: {}", e);
+            }
+        }
+    });
+}
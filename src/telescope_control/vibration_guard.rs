@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use crate::config::VibrationGuardSettings;
+use crate::telescope_control::settle_timer::{SettleSource, SettleTimer};
+
+/// Motor rate changes that shake the mount. Guide pulses are too small to matter.
+#[derive(Debug, Copy, Clone)]
+pub enum GuardedOperation {
+    Slew,
+    MoveAxis,
+    TrackingChange,
+    Park,
+}
+
+/// Settles for as long as the mount keeps vibrating after a rate change.
+/// It shares the settle timer, so anything waiting on the settle waits for the vibrations too.
+#[derive(Clone)]
+pub struct VibrationGuard {
+    settings: VibrationGuardSettings,
+    settle_timer: SettleTimer,
+}
+
+impl VibrationGuard {
+    pub fn new(settings: VibrationGuardSettings, settle_timer: SettleTimer) -> Self {
+        Self {
+            settings,
+            settle_timer,
+        }
+    }
+
+    fn guard_millis(&self, operation: GuardedOperation) -> u64 {
+        match operation {
+            GuardedOperation::Slew => self.settings.after_slew_millis,
+            GuardedOperation::MoveAxis => self.settings.after_move_axis_millis,
+            GuardedOperation::TrackingChange => self.settings.after_tracking_change_millis,
+            GuardedOperation::Park => self.settings.after_park_millis,
+        }
+    }
+
+    /// Call once the motor has reached its new rate. Never shortens a settle that's already running.
+    pub fn note_rate_change(&self, operation: GuardedOperation) {
+        if !self.settings.enabled {
+            return;
+        }
+        let duration = Duration::from_millis(self.guard_millis(operation));
+        if !duration.is_zero() {
+            self.settle_timer.start(SettleSource::Vibration, duration);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telescope_control::events::{Event, EventBus};
+    use chrono::Utc;
+    use tokio::time::{self, Instant};
+
+    #[tokio::test]
+    async fn test_guard_gates_settle() {
+        let settings = VibrationGuardSettings {
+            enabled: true,
+            after_slew_millis: 100,
+            after_tracking_change_millis: 20,
+            ..Default::default()
+        };
+        let timer = SettleTimer::default();
+        let guard = VibrationGuard::new(settings, timer.clone());
+
+        let start = Instant::now();
+        guard.note_rate_change(GuardedOperation::Slew);
+        guard.note_rate_change(GuardedOperation::TrackingChange); // Doesn't shorten
+        assert_eq!(timer.source(), Some(SettleSource::Vibration));
+        time::timeout(Duration::from_secs(1), timer.wait())
+            .await
+            .expect("the guard didn't run out");
+        assert!(Duration::from_millis(100) <= start.elapsed());
+
+        // A longer settle after a slew carries on past the guard
+        timer.start(SettleSource::Slew, Duration::from_millis(200));
+        guard.note_rate_change(GuardedOperation::Slew);
+        assert_eq!(timer.source(), Some(SettleSource::Slew));
+        timer.cancel();
+
+        let disabled = VibrationGuard::new(VibrationGuardSettings::default(), timer.clone());
+        disabled.note_rate_change(GuardedOperation::Slew);
+        assert!(!timer.is_settling());
+    }
+
+    #[tokio::test]
+    async fn test_guard_publishes_settling() {
+        let settings = VibrationGuardSettings {
+            enabled: true,
+            after_move_axis_millis: 500,
+            ..Default::default()
+        };
+        let events = EventBus::new();
+        let mut receiver = events.subscribe();
+        let timer = SettleTimer::default().with_events(events);
+        let guard = VibrationGuard::new(settings, timer.clone());
+
+        guard.note_rate_change(GuardedOperation::MoveAxis);
+        match receiver.try_recv().unwrap().event {
+            Event::Settling {
+                source,
+                settling_until,
+            } => {
+                assert_eq!(source, SettleSource::Vibration);
+                let now = Utc::now();
+                assert!(now < settling_until);
+                assert!(settling_until <= now + chrono::Duration::milliseconds(500));
+            }
+            event => panic!("Unexpected {:?}", event),
+        }
+    }
+}
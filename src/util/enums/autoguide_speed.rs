@@ -0,0 +1,44 @@
+use synscan::AutoGuideSpeed;
+
+pub trait AutoGuideSpeedExt {
+    const ALL: [AutoGuideSpeed; 5];
+    const MIN_FRACTION: f64 = 0.125;
+    const MAX_FRACTION: f64 = 1.;
+
+    /// The supported speed closest to the given fraction of the tracking rate
+    fn closest_to(fraction: f64) -> Self;
+}
+
+impl AutoGuideSpeedExt for AutoGuideSpeed {
+    const ALL: [AutoGuideSpeed; 5] = [
+        AutoGuideSpeed::Eighth,
+        AutoGuideSpeed::Quarter,
+        AutoGuideSpeed::Half,
+        AutoGuideSpeed::ThreeQuarters,
+        AutoGuideSpeed::One,
+    ];
+
+    fn closest_to(fraction: f64) -> Self {
+        Self::ALL
+            .into_iter()
+            .min_by(|a, b| {
+                (a.multiplier() - fraction)
+                    .abs()
+                    .total_cmp(&(b.multiplier() - fraction).abs())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closest_to() {
+        assert_eq!(AutoGuideSpeed::closest_to(0.3).multiplier(), 0.25);
+        assert_eq!(AutoGuideSpeed::closest_to(5.).multiplier(), 1.);
+        // Doesn't panic on a rate that isn't a number
+        AutoGuideSpeed::closest_to(f64::NAN);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::rotation_direction::*;
 use ascom_alpaca::api::PutPulseGuideDirection;
 use std::fmt::Formatter;
+use synscan::Direction;
 
 impl From<ResolvedDirection> for PutPulseGuideDirection {
     fn from(dir: ResolvedDirection) -> PutPulseGuideDirection {
@@ -29,3 +30,66 @@ impl RotationDirection for PutPulseGuideDirection {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracking_direction::TrackingDirection;
+
+    /// Guiding West must speed tracking up and East slow it down, in either hemisphere.
+    /// A mismatch reverses guide pulses.
+    fn check_guide_directions(key: RotationDirectionKey) -> Result<(), String> {
+        for (guide_direction, tracking_direction) in [
+            (
+                PutPulseGuideDirection::West,
+                TrackingDirection::WithTracking,
+            ),
+            (
+                PutPulseGuideDirection::East,
+                TrackingDirection::AgainstTracking,
+            ),
+        ] {
+            let guide_motor: Direction = guide_direction.using(key).into();
+            let tracking_motor: Direction = tracking_direction.using(key).into();
+            if guide_motor != tracking_motor {
+                return Err(format!(
+                    "Guiding {:?} turns the motor {:?} but {:?} turns it {:?}",
+                    guide_direction, guide_motor, tracking_direction, tracking_motor
+                ));
+            }
+            if PutPulseGuideDirection::from(guide_direction.using(key)) != guide_direction
+                || TrackingDirection::from(tracking_direction.using(key)) != tracking_direction
+            {
+                return Err(format!(
+                    "{:?} or {:?} doesn't map back to itself",
+                    guide_direction, tracking_direction
+                ));
+            }
+            let reversed: Direction = tracking_direction.using(key.reverse()).into();
+            if reversed == tracking_motor {
+                return Err(format!(
+                    "{:?} turns the motor the same way in both hemispheres",
+                    tracking_direction
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_guide_directions() {
+        for in_north in [true, false] {
+            assert_eq!(
+                check_guide_directions(RotationDirectionKey::from_hemisphere(in_north)),
+                Ok(())
+            );
+        }
+
+        // Tracking runs counterclockwise in the south, so guiding West does too
+        let south = RotationDirectionKey::from_hemisphere(false);
+        let west: Direction = PutPulseGuideDirection::West.using(south).into();
+        let east: Direction = PutPulseGuideDirection::East.using(south).into();
+        assert_eq!(west, CounterClockwise);
+        assert_eq!(east, Clockwise);
+    }
+}
@@ -1,17 +1,17 @@
 pub mod alternate;
+pub mod autoguide_speed;
 pub mod guide_direction;
 pub mod motion_rate;
 pub mod motor_encoder_direction;
-// pub mod motor_state;
 pub mod pier_side;
 pub mod rotation_direction;
 pub mod tracking_direction;
 pub mod tracking_rate;
 
+pub use autoguide_speed::*;
 pub use guide_direction::*;
 pub use motion_rate::*;
 pub use motor_encoder_direction::*;
-// pub use motor_state::*;
 pub use pier_side::*;
 pub use tracking_rate::*;
 
@@ -18,6 +18,11 @@ impl MotionRate {
         }
     }
 
+    /// Negative for counterclockwise
+    pub fn from_clockwise_rate(clockwise_rate: Degrees) -> Self {
+        MotionRate { clockwise_rate }
+    }
+
     pub fn is_zero(&self) -> bool {
         self.clockwise_rate == 0.
     }
@@ -26,6 +31,11 @@ impl MotionRate {
         self.clockwise_rate.abs()
     }
 
+    /// Negative when counterclockwise
+    pub fn clockwise_rate(&self) -> Degrees {
+        self.clockwise_rate
+    }
+
     /// If negative, will flip direction
     pub fn set_rate(&mut self, rate: Degrees) {
         if self.clockwise_rate < 0. {
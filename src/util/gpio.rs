@@ -0,0 +1,35 @@
+use std::io;
+use std::path::PathBuf;
+
+const SYSFS_GPIO: &str = "/sys/class/gpio";
+
+/// An output pin driven through the sysfs GPIO interface
+#[derive(Debug, Clone)]
+pub struct GpioPin {
+    pin: u32,
+    active_low: bool,
+}
+
+impl GpioPin {
+    pub fn new(pin: u32, active_low: bool) -> Self {
+        Self { pin, active_low }
+    }
+
+    fn pin_dir(&self) -> PathBuf {
+        PathBuf::from(format!("{}/gpio{}", SYSFS_GPIO, self.pin))
+    }
+
+    /// Exports the pin and configures it as an output
+    pub async fn export(&self) -> io::Result<()> {
+        if tokio::fs::metadata(self.pin_dir()).await.is_err() {
+            tokio::fs::write(format!("{}/export", SYSFS_GPIO), self.pin.to_string()).await?;
+        }
+        tokio::fs::write(self.pin_dir().join("direction"), "out").await
+    }
+
+    /// Drives the pin to its active (or inactive) level, respecting active_low
+    pub async fn set_active(&self, active: bool) -> io::Result<()> {
+        let high = active != self.active_low;
+        tokio::fs::write(self.pin_dir().join("value"), if high { "1" } else { "0" }).await
+    }
+}
@@ -5,10 +5,14 @@ use serde::{Deserialize, Serialize};
 pub use crate::telescope_control::mount_limits::*;
 pub use abort_result::*;
 pub use enums::*;
+pub use gpio::*;
 pub use infinite_future::*;
 pub use lockable::*;
+pub use number_parsing::*;
+pub use published::*;
 pub use result::*;
 pub use tasks::*;
+pub use webhook::*;
 
 mod abort_result;
 mod tasks {
@@ -20,9 +24,13 @@ mod tasks {
 }
 
 mod enums;
+mod gpio;
 mod infinite_future;
 mod lockable;
+mod number_parsing;
+mod published;
 mod result;
+mod webhook;
 
 #[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct AxisRate {
@@ -0,0 +1,125 @@
+/// Parses a number however a client in any locale may have written it.
+/// Accepts "12.5", "12,5" and sexagesimal like "12:30:00", "12h30m00s", "-5°30'15\"" or "+12 30 00".
+/// None rather than a partial value if any of it doesn't make sense.
+pub fn parse_number(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let (negative, body) = match s.chars().next()? {
+        '-' | '−' => (true, s[s.chars().next()?.len_utf8()..].trim_start()),
+        '+' => (false, s[1..].trim_start()),
+        _ => (false, s),
+    };
+
+    // A lone comma is a decimal separator. Anything else with commas is ambiguous.
+    let body = match (body.matches(',').count(), body.contains('.')) {
+        (0, _) => body.to_string(),
+        (1, false) => body.replace(',', "."),
+        _ => return None,
+    };
+
+    let value = if body.contains(is_sexagesimal_separator) {
+        parse_sexagesimal(&body)?
+    } else {
+        body.parse::<f64>().ok()?
+    };
+    if !value.is_finite() || value.is_sign_negative() {
+        return None;
+    }
+    Some(if negative { -value } else { value })
+}
+
+fn is_sexagesimal_separator(c: char) -> bool {
+    matches!(
+        c,
+        ':' | ' ' | 'h' | 'H' | 'd' | 'D' | '°' | '\'' | '′' | 'm' | '"' | '″' | 's'
+    )
+}
+
+/// Degrees or hours, minutes and seconds. Only the last part may have a fraction.
+fn parse_sexagesimal(s: &str) -> Option<f64> {
+    let parts = s
+        .split(is_sexagesimal_separator)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>();
+    if parts.is_empty() || 3 < parts.len() {
+        return None;
+    }
+
+    let mut value = 0.;
+    for (i, part) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        if !part
+            .chars()
+            .all(|c| c.is_ascii_digit() || (last && c == '.'))
+        {
+            return None;
+        }
+        let part = part.parse::<f64>().ok()?;
+        if 0 < i && 60. <= part {
+            return None;
+        }
+        value += part / 60f64.powi(i as i32);
+    }
+    Some(value)
+}
+
+/// Parses action parameters made of `N` numbers, each in any format `parse_number` accepts.
+/// Values are separated by commas, or by semicolons when the values themselves use decimal commas.
+/// A single number is never split, so its comma is a decimal comma.
+pub fn parse_numbers<const N: usize>(s: &str) -> Option<[f64; N]> {
+    if N == 1 {
+        return Some([parse_number(s)?; N]);
+    }
+    let separator = if s.contains(';') { ';' } else { ',' };
+    let values = s
+        .split(separator)
+        .map(parse_number)
+        .collect::<Option<Vec<_>>>()?;
+    values.try_into().ok()
+}
+
+/// How parsed parameters are echoed back, so clients can see what was understood
+pub fn format_numbers(values: &[f64]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("12.5"), Some(12.5));
+        assert_eq!(parse_number(" -12,5 "), Some(-12.5));
+        assert_eq!(parse_number("1e-3"), Some(0.001));
+        assert_eq!(parse_number("12:30:00"), Some(12.5));
+        assert!((parse_number("12h30m36s").unwrap() - 12.51).abs() < 1e-12);
+        assert_eq!(parse_number("-5°30'00\""), Some(-5.5));
+        assert_eq!(parse_number("−5° 30′"), Some(-5.5));
+        assert_eq!(parse_number("+12 30 00,0"), Some(12.5));
+        assert_eq!(parse_number("5:59:60"), None);
+        assert_eq!(parse_number("5:1.5:0"), None);
+        assert_eq!(parse_number("1,234.5"), None);
+        assert_eq!(parse_number("1.2.3"), None);
+        assert_eq!(parse_number("--5"), None);
+        assert_eq!(parse_number("inf"), None);
+        assert_eq!(parse_number(""), None);
+        assert_eq!(parse_number("12abc"), None);
+    }
+
+    #[test]
+    fn test_parse_numbers() {
+        assert_eq!(parse_numbers("5.5,-20"), Some([5.5, -20.]));
+        assert_eq!(parse_numbers("5,5; -20,25"), Some([5.5, -20.25]));
+        assert_eq!(parse_numbers("05:30:00,-20:15:00"), Some([5.5, -20.25]));
+        assert_eq!(parse_numbers::<2>("5,5,-20,25"), None);
+        assert_eq!(parse_numbers::<2>("5.5"), None);
+        assert_eq!(parse_numbers("3"), Some([3.]));
+        assert_eq!(parse_numbers("12,5"), Some([12.5]));
+        assert_eq!(parse_numbers::<1>("5.5,-20"), None);
+        assert_eq!(format_numbers(&[5.5, -20.25]), "5.5,-20.25");
+    }
+}
@@ -0,0 +1,62 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use tokio::sync::{Mutex, MutexGuard};
+
+/// Copy-on-write value for state read on every poll.
+/// Reads never block; writers are serialized and publish a new snapshot when their guard drops.
+pub struct Published<T: Copy> {
+    current: ArcSwap<T>,
+    writer: Mutex<()>,
+}
+
+impl<T: Copy> Published<T> {
+    pub fn new(value: T) -> Self {
+        Published {
+            current: ArcSwap::from_pointee(value),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// The last published value
+    pub fn get(&self) -> T {
+        **self.current.load()
+    }
+
+    /// Waits for other writers. Readers keep seeing the old value until the guard drops.
+    pub async fn write(&self) -> PublishGuard<'_, T> {
+        let lock = self.writer.lock().await;
+        PublishGuard {
+            value: self.get(),
+            current: &self.current,
+            _lock: lock,
+        }
+    }
+}
+
+pub struct PublishGuard<'a, T: Copy> {
+    value: T,
+    current: &'a ArcSwap<T>,
+    _lock: MutexGuard<'a, ()>,
+}
+
+impl<T: Copy> Deref for PublishGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Copy> DerefMut for PublishGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Copy> Drop for PublishGuard<'_, T> {
+    fn drop(&mut self) {
+        self.current.store(Arc::new(self.value));
+    }
+}
@@ -0,0 +1,50 @@
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, Uri};
+
+/// Reads a webhook URL once, up front, rather than on every call.
+/// The client has no TLS, so only http:// URLs can be reached.
+pub fn parse_webhook_uri(url: &str) -> Result<Uri, String> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|e| format!("{} isn't a valid URL: {}", url, e))?;
+    match uri.scheme_str() {
+        Some("http") => Ok(uri),
+        Some("https") => Err(format!(
+            "{} can't be used since https isn't supported. Use http, e.g. through a local relay.",
+            url
+        )),
+        _ => Err(format!("{} isn't an http:// URL", url)),
+    }
+}
+
+/// POSTs a JSON body, failing unless the reply has a success status
+pub async fn post_json(
+    client: &Client<HttpConnector>,
+    uri: &Uri,
+    json: String,
+) -> Result<(), String> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(uri.clone())
+        .header("content-type", "application/json")
+        .body(Body::from(json))
+        .map_err(|e| e.to_string())?;
+    let response = client.request(request).await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("returned {}", response.status()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_webhook_uri() {
+        assert!(parse_webhook_uri("http://localhost:8080/settled").is_ok());
+        assert!(parse_webhook_uri("not a url").is_err());
+        assert!(parse_webhook_uri("https://ntfy.sh/mount").is_err());
+        assert!(parse_webhook_uri("localhost:8080").is_err());
+    }
+}